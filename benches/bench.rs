@@ -29,7 +29,7 @@ fn bench_add_removes(b: &mut Bencher) {
 #[cfg(feature = "nightly")]
 #[bench]
 fn bench_add_removes_unsafe(b: &mut Bencher) {
-    let mut rng = rand::StdRng::from_seed(Default::default());
+    let mut rng = rand::rngs::StdRng::from_seed(Default::default());
     let params = generator::Params {
         ..Default::default()
     };
@@ -39,7 +39,7 @@ fn bench_add_removes_unsafe(b: &mut Bencher) {
     b.iter(|| {
         let mut doc = doc.clone();
         let mut result = Ok(());
-        for ref p in &patches {
+        for p in &patches {
             // Patch mutable
             result = result.and_then(|_| patch_unsafe(&mut doc, p));
         }
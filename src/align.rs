@@ -0,0 +1,412 @@
+//! Alignment of the elements of two arrays, see [`ArrayDiffAlgorithm`].
+use crate::diff::ArrayDiffAlgorithm;
+use crate::hash::HashedValue;
+use alloc::{vec, vec::Vec};
+use hashbrown::HashMap;
+use serde_json::Value;
+
+/// Step of transforming the left array into the right one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Edit {
+    /// Left element at the first index corresponds to the right element at the second one. They
+    /// are equal, unless the element changed in place.
+    Pair(usize, usize),
+    /// Left element is removed.
+    Remove(usize),
+    /// Right element is added.
+    Add(usize),
+}
+
+/// Align the elements of the arrays with the algorithm. Elements matched by the algorithm are
+/// equal, the elements between them are paired by position, and the remaining ones are removed
+/// or added.
+pub(crate) fn align(left: &[Value], right: &[Value], algorithm: ArrayDiffAlgorithm) -> Vec<Edit> {
+    // Equal elements get equal identifiers, so they are compared as integers
+    let mut ids: HashMap<HashedValue, usize> = HashMap::new();
+    let mut identify = |item| {
+        let next = ids.len();
+        *ids.entry(HashedValue(item)).or_insert(next)
+    };
+    let a: Vec<usize> = left.iter().map(&mut identify).collect();
+    let b: Vec<usize> = right.iter().map(&mut identify).collect();
+
+    let mut matches = Vec::new();
+    match algorithm {
+        ArrayDiffAlgorithm::Positional => {}
+        ArrayDiffAlgorithm::Myers => myers(&a, &b, 0, 0, &mut matches),
+        ArrayDiffAlgorithm::Patience => patience(&a, &b, 0, 0, &mut matches),
+        ArrayDiffAlgorithm::Anchored => anchored(&a, &b, &mut matches),
+        ArrayDiffAlgorithm::Chunked => chunked(&a, &b, &mut matches),
+    }
+
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    let (mut l, mut r) = (0, 0);
+    for &(ml, mr) in &matches {
+        gap(l..ml, r..mr, &mut edits);
+        edits.push(Edit::Pair(ml, mr));
+        (l, r) = (ml + 1, mr + 1);
+    }
+    gap(l..a.len(), r..b.len(), &mut edits);
+    edits
+}
+
+/// Elements between two matches are paired by position, so elements changed in place are diffed
+/// rather than removed and added.
+fn gap(left: core::ops::Range<usize>, right: core::ops::Range<usize>, edits: &mut Vec<Edit>) {
+    let paired = left.len().min(right.len());
+    edits.extend((0..paired).map(|i| Edit::Pair(left.start + i, right.start + i)));
+    edits.extend((left.start + paired..left.end).map(Edit::Remove));
+    edits.extend((right.start + paired..right.end).map(Edit::Add));
+}
+
+/// Lengths of the common prefix and of the common suffix (not overlapping the prefix).
+fn trim(a: &[usize], b: &[usize]) -> (usize, usize) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    (prefix, suffix)
+}
+
+/// Longest common subsequence, found by the linear space variant of the Myers' algorithm.
+fn myers(a: &[usize], b: &[usize], a_off: usize, b_off: usize, matches: &mut Vec<(usize, usize)>) {
+    let (prefix, suffix) = trim(a, b);
+    matches.extend((0..prefix).map(|i| (a_off + i, b_off + i)));
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (a_off_mid, b_off_mid) = (a_off + prefix, b_off + prefix);
+    if !a_mid.is_empty() && !b_mid.is_empty() {
+        let (x, y) = middle_snake(a_mid, b_mid);
+        myers(&a_mid[..x], &b_mid[..y], a_off_mid, b_off_mid, matches);
+        myers(
+            &a_mid[x..],
+            &b_mid[y..],
+            a_off_mid + x,
+            b_off_mid + y,
+            matches,
+        );
+    }
+    let (a_end, b_end) = (a_off_mid + a_mid.len(), b_off_mid + b_mid.len());
+    matches.extend((0..suffix).map(|i| (a_end + i, b_end + i)));
+}
+
+/// Start of the middle snake of the shortest edit script of non-empty sequences, which splits
+/// the script into two halves.
+fn middle_snake(a: &[usize], b: &[usize]) -> (usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    let d_max = (n + m + 1) / 2;
+    // Furthest reaching paths by diagonal, forward from the start and backward from the end
+    let offset = d_max + 1;
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = vec![0isize; 2 * offset as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+
+    for d in 0..=d_max {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            if odd && (k - delta).abs() < d && x + backward[at(delta - k)] >= n {
+                return (x0 as usize, y0 as usize);
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            if !odd && (k - delta).abs() <= d && x + forward[at(delta - k)] >= n {
+                return ((n - x) as usize, (m - y) as usize);
+            }
+        }
+    }
+    unreachable!("paths of non-empty sequences overlap")
+}
+
+/// Patience diff: elements occurring once in both sequences anchor the alignment, and the
+/// sequences between the anchors are aligned recursively, with the Myers' algorithm if they have
+/// no such elements.
+fn patience(
+    a: &[usize],
+    b: &[usize],
+    a_off: usize,
+    b_off: usize,
+    matches: &mut Vec<(usize, usize)>,
+) {
+    let (prefix, suffix) = trim(a, b);
+    matches.extend((0..prefix).map(|i| (a_off + i, b_off + i)));
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (a_off_mid, b_off_mid) = (a_off + prefix, b_off + prefix);
+
+    let anchors = unique_anchors(a_mid, b_mid);
+    if anchors.is_empty() {
+        myers(a_mid, b_mid, a_off_mid, b_off_mid, matches);
+    } else {
+        let (mut l, mut r) = (0, 0);
+        for (al, ar) in anchors {
+            patience(
+                &a_mid[l..al],
+                &b_mid[r..ar],
+                a_off_mid + l,
+                b_off_mid + r,
+                matches,
+            );
+            matches.push((a_off_mid + al, b_off_mid + ar));
+            (l, r) = (al + 1, ar + 1);
+        }
+        patience(
+            &a_mid[l..],
+            &b_mid[r..],
+            a_off_mid + l,
+            b_off_mid + r,
+            matches,
+        );
+    }
+
+    let (a_end, b_end) = (a_off_mid + a_mid.len(), b_off_mid + b_mid.len());
+    matches.extend((0..suffix).map(|i| (a_end + i, b_end + i)));
+}
+
+/// Alignment anchored on the elements occurring once in both sequences only, leaving everything
+/// between them to be paired by position.
+fn anchored(a: &[usize], b: &[usize], matches: &mut Vec<(usize, usize)>) {
+    let (prefix, suffix) = trim(a, b);
+    matches.extend((0..prefix).map(|i| (i, i)));
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    matches.extend(
+        unique_anchors(a_mid, b_mid)
+            .into_iter()
+            .map(|(l, r)| (prefix + l, prefix + r)),
+    );
+    let (a_end, b_end) = (prefix + a_mid.len(), prefix + b_mid.len());
+    matches.extend((0..suffix).map(|i| (a_end + i, b_end + i)));
+}
+
+/// Number of consecutive elements hashed together by [`chunked`].
+const CHUNK: usize = 4;
+
+/// Alignment of runs of at least [`CHUNK`] equal elements, found like rsync does: chunks of the
+/// left sequence are indexed by their hashes, and a rolling hash of the right sequence finds the
+/// chunks it shares with the left one. Matched runs are extended as far as possible, and the
+/// first run after the previous one in both sequences is taken.
+fn chunked(a: &[usize], b: &[usize], matches: &mut Vec<(usize, usize)>) {
+    let (prefix, suffix) = trim(a, b);
+    matches.extend((0..prefix).map(|i| (i, i)));
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    if a_mid.len() >= CHUNK && b_mid.len() >= CHUNK {
+        // Positions of the chunks of the left sequence by their hashes, in increasing order
+        let mut chunks: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut hash = chunk_hash(&a_mid[..CHUNK]);
+        for pos in 0..=a_mid.len() - CHUNK {
+            if pos > 0 {
+                hash = roll(hash, a_mid[pos - 1], a_mid[pos + CHUNK - 1]);
+            }
+            chunks.entry(hash).or_default().push(pos);
+        }
+
+        let (mut next, mut j) = (0, 0);
+        let mut hash = chunk_hash(&b_mid[..CHUNK]);
+        while j + CHUNK <= b_mid.len() {
+            let window = &b_mid[j..j + CHUNK];
+            let found = chunks.get(&hash).and_then(|positions| {
+                let first = positions.partition_point(|pos| *pos < next);
+                positions[first..]
+                    .iter()
+                    .copied()
+                    .find(|pos| &a_mid[*pos..*pos + CHUNK] == window)
+            });
+            match found {
+                Some(pos) => {
+                    let len = a_mid[pos..]
+                        .iter()
+                        .zip(&b_mid[j..])
+                        .take_while(|(x, y)| x == y)
+                        .count();
+                    matches.extend((0..len).map(|i| (prefix + pos + i, prefix + j + i)));
+                    next = pos + len;
+                    j += len;
+                    if j + CHUNK <= b_mid.len() {
+                        hash = chunk_hash(&b_mid[j..j + CHUNK]);
+                    }
+                }
+                None => {
+                    if j + CHUNK < b_mid.len() {
+                        hash = roll(hash, b_mid[j], b_mid[j + CHUNK]);
+                    }
+                    j += 1;
+                }
+            }
+        }
+    }
+
+    let (a_end, b_end) = (prefix + a_mid.len(), prefix + b_mid.len());
+    matches.extend((0..suffix).map(|i| (a_end + i, b_end + i)));
+}
+
+/// Multiplier of the polynomial rolling hash.
+const BASE: u64 = 0x100000001b3;
+
+fn chunk_hash(chunk: &[usize]) -> u64 {
+    chunk.iter().fold(0u64, |hash, id| {
+        hash.wrapping_mul(BASE).wrapping_add(*id as u64 + 1)
+    })
+}
+
+/// Hash of the chunk shifted by one element, from `out` to `new`.
+fn roll(hash: u64, out: usize, new: usize) -> u64 {
+    let weight = (1..CHUNK).fold(1u64, |weight, _| weight.wrapping_mul(BASE));
+    hash.wrapping_sub((out as u64 + 1).wrapping_mul(weight))
+        .wrapping_mul(BASE)
+        .wrapping_add(new as u64 + 1)
+}
+
+/// Positions of the elements occurring once in both sequences, keeping the largest set of them
+/// which are in the same order in both.
+fn unique_anchors(a: &[usize], b: &[usize]) -> Vec<(usize, usize)> {
+    // Occurrences in both sequences, and the positions of the last ones
+    let mut occurrences: HashMap<usize, (usize, usize, usize, usize)> = HashMap::new();
+    for (pos, id) in a.iter().enumerate() {
+        let entry = occurrences.entry(*id).or_default();
+        entry.0 += 1;
+        entry.2 = pos;
+    }
+    for (pos, id) in b.iter().enumerate() {
+        if let Some(entry) = occurrences.get_mut(id) {
+            entry.1 += 1;
+            entry.3 = pos;
+        }
+    }
+    let mut unique: Vec<(usize, usize)> = occurrences
+        .into_values()
+        .filter(|(in_a, in_b, _, _)| *in_a == 1 && *in_b == 1)
+        .map(|(_, _, l, r)| (l, r))
+        .collect();
+    unique.sort_unstable();
+
+    let right: Vec<usize> = unique.iter().map(|(_, r)| *r).collect();
+    let kept = increasing_run(&right);
+    unique
+        .into_iter()
+        .zip(kept)
+        .filter_map(|(pair, kept)| kept.then_some(pair))
+        .collect()
+}
+
+/// Flags of the elements of the longest strictly increasing subsequence of the indices.
+pub(crate) fn increasing_run(indices: &[usize]) -> Vec<bool> {
+    // Positions of the smallest tails of the subsequences of each length, and the predecessors
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![usize::MAX; indices.len()];
+    for (pos, idx) in indices.iter().enumerate() {
+        let len = tails.partition_point(|tail| indices[*tail] < *idx);
+        if len > 0 {
+            prev[pos] = tails[len - 1];
+        }
+        if len == tails.len() {
+            tails.push(pos);
+        } else {
+            tails[len] = pos;
+        }
+    }
+    let mut kept = vec![false; indices.len()];
+    let mut pos = tails.last().copied().unwrap_or(usize::MAX);
+    while pos != usize::MAX {
+        kept[pos] = true;
+        pos = prev[pos];
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{align, Edit};
+    use crate::diff::ArrayDiffAlgorithm;
+    use alloc::vec::Vec;
+    use serde_json::{json, Value};
+
+    /// Length of the longest common subsequence, by dynamic programming.
+    fn lcs(a: &[Value], b: &[Value]) -> usize {
+        let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                table[i][j] = if a[i] == b[j] {
+                    table[i + 1][j + 1] + 1
+                } else {
+                    table[i + 1][j].max(table[i][j + 1])
+                };
+            }
+        }
+        table[0][0]
+    }
+
+    #[test]
+    fn alignments() {
+        let algorithms = [
+            ArrayDiffAlgorithm::Positional,
+            ArrayDiffAlgorithm::Myers,
+            ArrayDiffAlgorithm::Patience,
+            ArrayDiffAlgorithm::Anchored,
+            ArrayDiffAlgorithm::Chunked,
+        ];
+        // Pseudo-random arrays over a small alphabet, so they have plenty of duplicates
+        let mut seed = 7u64;
+        let mut random = |len: usize| -> Vec<Value> {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    json!((seed >> 60) % 5)
+                })
+                .collect()
+        };
+        for round in 0..200 {
+            let a = random(round % 13);
+            let b = random(round % 17);
+            for algorithm in algorithms {
+                let edits = align(&a, &b, algorithm);
+                let (mut lefts, mut rights, mut equal) = (Vec::new(), Vec::new(), 0);
+                for edit in edits {
+                    match edit {
+                        Edit::Pair(l, r) => {
+                            lefts.push(l);
+                            rights.push(r);
+                            equal += usize::from(a[l] == b[r]);
+                        }
+                        Edit::Remove(l) => lefts.push(l),
+                        Edit::Add(r) => rights.push(r),
+                    }
+                }
+                lefts.sort_unstable();
+                assert!(lefts.into_iter().eq(0..a.len()), "{algorithm:?}");
+                assert!(rights.into_iter().eq(0..b.len()), "{algorithm:?}");
+                if algorithm == ArrayDiffAlgorithm::Myers {
+                    assert!(equal >= lcs(&a, &b), "{a:?} {b:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Generation of arbitrary patches for fuzzing and property testing, see [`Arbitrary`].
+//!
+//! Generated pointers are always valid and generated patches can always be serialized and
+//! deserialized back, but they are not necessarily applicable to any particular document. To
+//! make that more likely, reference tokens of the pointers are often array indices, `-` or short
+//! keys from a tiny alphabet, and values are nested at most a few levels deep.
+use crate::{
+    AddOperation, CopyOperation, MoveOperation, Patch, PatchOperation, Pointer, RemoveOperation,
+    ReplaceOperation, TestOperation,
+};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use serde_json::{Map, Number, Value};
+
+/// Maximum nesting of the generated values.
+const MAX_DEPTH: usize = 4;
+
+fn token(u: &mut Unstructured<'_>) -> Result<String> {
+    Ok(match u.int_in_range(0..=7)? {
+        0..=2 => u.int_in_range(0..=4u8)?.to_string(),
+        3 => "-".to_string(),
+        4..=6 => {
+            let len = u.int_in_range(0..=2)?;
+            (0..len)
+                .map(|_| u.choose(&['a', 'b', '/', '~']).copied())
+                .collect::<Result<_>>()?
+        }
+        _ => u.arbitrary()?,
+    })
+}
+
+fn pointer(u: &mut Unstructured<'_>) -> Result<String> {
+    Ok(Pointer::arbitrary(u)?.into())
+}
+
+fn value(u: &mut Unstructured<'_>, depth: usize) -> Result<Value> {
+    let max = if depth < MAX_DEPTH { 6 } else { 4 };
+    Ok(match u.int_in_range(0..=max)? {
+        0 => Value::Null,
+        1 => Value::Bool(u.arbitrary()?),
+        2 => match u.int_in_range(0..=2)? {
+            0 => Value::from(u.arbitrary::<u64>()?),
+            1 => Value::from(u.arbitrary::<i64>()?),
+            _ => Number::from_f64(u.arbitrary()?).map_or(Value::Null, Value::Number),
+        },
+        3 | 4 => Value::String(u.arbitrary()?),
+        5 => {
+            let len = u.int_in_range(0..=4)?;
+            Value::Array(
+                (0..len)
+                    .map(|_| value(u, depth + 1))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        _ => {
+            let len = u.int_in_range(0..=4)?;
+            let mut obj = Map::new();
+            for _ in 0..len {
+                obj.insert(token(u)?, value(u, depth + 1)?);
+            }
+            Value::Object(obj)
+        }
+    })
+}
+
+impl<'a> Arbitrary<'a> for Pointer {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(0..=4)?;
+        let tokens = (0..len).map(|_| token(u)).collect::<Result<Vec<_>>>()?;
+        Ok(Pointer::from_tokens(tokens))
+    }
+}
+
+impl<'a> Arbitrary<'a> for AddOperation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(AddOperation {
+            path: pointer(u)?,
+            value: value(u, 0)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for RemoveOperation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(RemoveOperation { path: pointer(u)? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ReplaceOperation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ReplaceOperation {
+            path: pointer(u)?,
+            value: value(u, 0)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for MoveOperation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(MoveOperation {
+            from: pointer(u)?,
+            path: pointer(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CopyOperation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CopyOperation {
+            from: pointer(u)?,
+            path: pointer(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for TestOperation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(TestOperation {
+            path: pointer(u)?,
+            value: value(u, 0)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for PatchOperation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=5)? {
+            0 => PatchOperation::Add(u.arbitrary()?),
+            1 => PatchOperation::Remove(u.arbitrary()?),
+            2 => PatchOperation::Replace(u.arbitrary()?),
+            3 => PatchOperation::Move(u.arbitrary()?),
+            4 => PatchOperation::Copy(u.arbitrary()?),
+            _ => PatchOperation::Test(u.arbitrary()?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Patch {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Patch(u.arbitrary()?))
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        Ok(Patch(Arbitrary::arbitrary_take_rest(u)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Patch, Pointer};
+    use arbitrary::{Arbitrary, Unstructured};
+    use rand::{Rng, SeedableRng};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn valid_patches() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut kinds = BTreeSet::new();
+        for _ in 0..1_000 {
+            let bytes: Vec<u8> = (0..rng.gen_range(0..256)).map(|_| rng.gen()).collect();
+            let p = Patch::arbitrary_take_rest(Unstructured::new(&bytes)).unwrap();
+            for op in &p {
+                kinds.insert(op.op_kind().as_str());
+                assert!(Pointer::parse(op.path()).is_ok());
+                if let Some(from) = op.from() {
+                    assert!(Pointer::parse(from).is_ok());
+                }
+            }
+            let json = serde_json::to_string(&p).unwrap();
+            let parsed: Patch = serde_json::from_str(&json).unwrap();
+            assert!(parsed
+                .iter()
+                .map(|op| op.path())
+                .eq(p.iter().map(|op| op.path())));
+        }
+        assert_eq!(kinds.len(), 6);
+    }
+}
@@ -0,0 +1,109 @@
+//! Command line tool for diffing documents and applying, inverting and validating patches.
+//!
+//! Requires the `cli` feature.
+use clap::{Parser, Subcommand};
+use json_patch::Patch;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Diff JSON documents and apply, invert or validate JSON Patches (RFC 6902).
+///
+/// Files are read from the given paths, or from the standard input for `-`.
+#[derive(Parser)]
+#[command(name = "json-patch", version)]
+struct Cli {
+    /// Print indented, multi-line JSON.
+    #[arg(long, global = true)]
+    pretty: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the patch transforming the left document into the right one.
+    Diff { left: PathBuf, right: PathBuf },
+    /// Print the document with the patch applied.
+    Apply { doc: PathBuf, patch: PathBuf },
+    /// Print the patch reverting the changes the patch makes to the document.
+    Invert { doc: PathBuf, patch: PathBuf },
+    /// Check if the patch applies to the document, printing the failed operations. Exits with
+    /// status 1 if any of the operations fails.
+    Validate { doc: PathBuf, patch: PathBuf },
+}
+
+fn read<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let result = if path == Path::new("-") {
+        serde_json::from_reader(io::stdin().lock())
+    } else {
+        let file = File::open(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        serde_json::from_reader(BufReader::new(file))
+    };
+    result.map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn write<T: Serialize>(value: &T, pretty: bool) -> Result<(), String> {
+    let mut stdout = io::stdout().lock();
+    let result = if pretty {
+        serde_json::to_writer_pretty(&mut stdout, value)
+    } else {
+        serde_json::to_writer(&mut stdout, value)
+    };
+    result.map_err(|err| err.to_string())?;
+    writeln!(stdout).map_err(|err| err.to_string())
+}
+
+fn run(cli: Cli) -> Result<ExitCode, String> {
+    let (Command::Diff { left: a, right: b }
+    | Command::Apply { doc: a, patch: b }
+    | Command::Invert { doc: a, patch: b }
+    | Command::Validate { doc: a, patch: b }) = &cli.command;
+    if a == Path::new("-") && b == Path::new("-") {
+        return Err("only one of the inputs can be read from the standard input".into());
+    }
+
+    match cli.command {
+        Command::Diff { left, right } => {
+            let left: Value = read(&left)?;
+            let right: Value = read(&right)?;
+            write(&json_patch::diff(&left, &right), cli.pretty)?;
+        }
+        Command::Apply { doc, patch } => {
+            let mut doc: Value = read(&doc)?;
+            let patch: Patch = read(&patch)?;
+            json_patch::patch(&mut doc, &patch).map_err(|err| err.to_string())?;
+            write(&doc, cli.pretty)?;
+        }
+        Command::Invert { doc, patch } => {
+            let doc: Value = read(&doc)?;
+            let patch: Patch = read(&patch)?;
+            let inverse = json_patch::invert(&patch, &doc).map_err(|err| err.to_string())?;
+            write(&inverse, cli.pretty)?;
+        }
+        Command::Validate { doc, patch } => {
+            let doc: Value = read(&doc)?;
+            let patch: Patch = read(&patch)?;
+            if let Err(errors) = json_patch::validate(&doc, &patch) {
+                for err in errors {
+                    println!("{}", err);
+                }
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(err) => {
+            let _ = writeln!(io::stderr(), "error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,529 @@
+//! Compact binary encoding of patches.
+//!
+//! The encoding starts with the format version, followed by the table of reference tokens of all
+//! the JSON Pointers of the patch and by the operations. Every operation is encoded as its tag,
+//! its pointers (as indices into the table of reference tokens) and its value, if any. All lengths
+//! and indices are LEB128 varints.
+use crate::{
+    AddOperation, CopyOperation, MoveOperation, Patch, PatchOperation, RemoveOperation,
+    ReplaceOperation, TestOperation,
+};
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::String, vec, vec::Vec};
+use serde_json::{Map, Number, Value};
+use thiserror::Error;
+
+const VERSION: u8 = 1;
+
+const ADD: u8 = 0;
+const REMOVE: u8 = 1;
+const REPLACE: u8 = 2;
+const MOVE: u8 = 3;
+const COPY: u8 = 4;
+const TEST: u8 = 5;
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const UNSIGNED: u8 = 3;
+const NEGATIVE: u8 = 4;
+const FLOAT: u8 = 5;
+const STRING: u8 = 6;
+const ARRAY: u8 = 7;
+const OBJECT: u8 = 8;
+
+/// Error of decoding a patch from its binary encoding, see [`Patch::from_bytes`].
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BinaryDecodeError {
+    /// The encoding is of an unsupported version.
+    #[error("unsupported encoding version {0}")]
+    UnsupportedVersion(u8),
+    /// The encoding ends prematurely.
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+    /// There are bytes left after the last operation.
+    #[error("unexpected bytes after the patch")]
+    TrailingBytes,
+    /// An operation or a value has an unknown tag.
+    #[error("invalid tag {0}")]
+    InvalidTag(u8),
+    /// A varint does not fit into 64 bits.
+    #[error("invalid varint")]
+    InvalidVarint,
+    /// A string is not valid UTF-8.
+    #[error("invalid UTF-8 string")]
+    InvalidUtf8,
+    /// A pointer refers to a reference token not present in the table.
+    #[error("invalid reference token index {0}")]
+    InvalidToken(u64),
+}
+
+impl Patch {
+    /// Encodes the patch into the compact binary format. Reference tokens of the pointers are
+    /// stored once and referred to by their indices, values are stored without any separators.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "replace", "path": "/sensors/temperature/value", "value": 21.5 },
+    ///   { "op": "replace", "path": "/sensors/humidity/value", "value": 40 },
+    /// ])).unwrap();
+    ///
+    /// let bytes = p.to_bytes();
+    /// assert!(bytes.len() < p.to_string().len());
+    /// assert_eq!(Patch::from_bytes(&bytes).unwrap(), p);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        for op in &self.0 {
+            encoder.operation(op);
+        }
+
+        let mut out = vec![VERSION];
+        write_varint(&mut out, encoder.tokens.len() as u64);
+        for token in &encoder.tokens {
+            write_str(&mut out, token);
+        }
+        write_varint(&mut out, self.0.len() as u64);
+        out.extend(encoder.out);
+        out
+    }
+
+    /// Decodes the patch encoded by [`Patch::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Patch, BinaryDecodeError> {
+        let mut decoder = Decoder {
+            bytes,
+            tokens: Vec::new(),
+        };
+        let version = decoder.byte()?;
+        if version != VERSION {
+            return Err(BinaryDecodeError::UnsupportedVersion(version));
+        }
+        let count = decoder.len()?;
+        decoder.tokens = Vec::with_capacity(count);
+        for _ in 0..count {
+            let token = decoder.string()?;
+            decoder.tokens.push(token);
+        }
+        let count = decoder.len()?;
+        let mut ops = Vec::with_capacity(count);
+        for _ in 0..count {
+            ops.push(decoder.operation()?);
+        }
+        if !decoder.bytes.is_empty() {
+            return Err(BinaryDecodeError::TrailingBytes);
+        }
+        Ok(Patch(ops))
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+#[derive(Default)]
+struct Encoder<'a> {
+    out: Vec<u8>,
+    tokens: Vec<&'a str>,
+    indices: BTreeMap<&'a str, usize>,
+}
+
+impl<'a> Encoder<'a> {
+    fn operation(&mut self, op: &'a PatchOperation) {
+        match op {
+            PatchOperation::Add(op) => {
+                self.out.push(ADD);
+                self.pointer(&op.path);
+                self.value(&op.value);
+            }
+            PatchOperation::Remove(op) => {
+                self.out.push(REMOVE);
+                self.pointer(&op.path);
+            }
+            PatchOperation::Replace(op) => {
+                self.out.push(REPLACE);
+                self.pointer(&op.path);
+                self.value(&op.value);
+            }
+            PatchOperation::Move(op) => {
+                self.out.push(MOVE);
+                self.pointer(&op.from);
+                self.pointer(&op.path);
+            }
+            PatchOperation::Copy(op) => {
+                self.out.push(COPY);
+                self.pointer(&op.from);
+                self.pointer(&op.path);
+            }
+            PatchOperation::Test(op) => {
+                self.out.push(TEST);
+                self.pointer(&op.path);
+                self.value(&op.value);
+            }
+        }
+    }
+
+    /// Encodes the number of reference tokens (shifted left by one bit), followed by their
+    /// indices. Strings which are not valid pointers are encoded as a single token, with the
+    /// lowest bit of the count set.
+    fn pointer(&mut self, pointer: &'a str) {
+        match pointer.strip_prefix('/') {
+            Some(tokens) => {
+                write_varint(&mut self.out, (tokens.split('/').count() as u64) << 1);
+                for token in tokens.split('/') {
+                    self.token(token);
+                }
+            }
+            None if pointer.is_empty() => write_varint(&mut self.out, 0),
+            None => {
+                write_varint(&mut self.out, 1);
+                self.token(pointer);
+            }
+        }
+    }
+
+    fn token(&mut self, token: &'a str) {
+        let next = self.tokens.len();
+        let idx = *self.indices.entry(token).or_insert(next);
+        if idx == next {
+            self.tokens.push(token);
+        }
+        write_varint(&mut self.out, idx as u64);
+    }
+
+    /// Encodes the value without recursion, so deeply nested values cannot overflow the stack.
+    fn value(&mut self, value: &Value) {
+        enum Item<'v> {
+            Value(&'v Value),
+            Key(&'v str),
+        }
+
+        let mut stack = vec![Item::Value(value)];
+        while let Some(item) = stack.pop() {
+            let value = match item {
+                Item::Value(value) => value,
+                Item::Key(key) => {
+                    write_str(&mut self.out, key);
+                    continue;
+                }
+            };
+            match value {
+                Value::Null => self.out.push(NULL),
+                Value::Bool(false) => self.out.push(FALSE),
+                Value::Bool(true) => self.out.push(TRUE),
+                Value::Number(n) => {
+                    if let Some(n) = n.as_u64() {
+                        self.out.push(UNSIGNED);
+                        write_varint(&mut self.out, n);
+                    } else if let Some(n) = n.as_i64() {
+                        // Negative numbers are encoded as `-1 - n`, so -1 is 0
+                        self.out.push(NEGATIVE);
+                        write_varint(&mut self.out, !(n as u64));
+                    } else {
+                        self.out.push(FLOAT);
+                        let n = n.as_f64().unwrap_or_default();
+                        self.out.extend_from_slice(&n.to_le_bytes());
+                    }
+                }
+                Value::String(s) => {
+                    self.out.push(STRING);
+                    write_str(&mut self.out, s);
+                }
+                Value::Array(arr) => {
+                    self.out.push(ARRAY);
+                    write_varint(&mut self.out, arr.len() as u64);
+                    stack.extend(arr.iter().rev().map(Item::Value));
+                }
+                Value::Object(obj) => {
+                    self.out.push(OBJECT);
+                    write_varint(&mut self.out, obj.len() as u64);
+                    for (key, value) in obj.iter().rev() {
+                        stack.push(Item::Value(value));
+                        stack.push(Item::Key(key));
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    tokens: Vec<String>,
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> Result<u8, BinaryDecodeError> {
+        let (&byte, rest) = self
+            .bytes
+            .split_first()
+            .ok_or(BinaryDecodeError::UnexpectedEnd)?;
+        self.bytes = rest;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], BinaryDecodeError> {
+        if self.bytes.len() < len {
+            return Err(BinaryDecodeError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, BinaryDecodeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = u64::from(byte & 0x7f);
+            if bits << shift >> shift != bits {
+                return Err(BinaryDecodeError::InvalidVarint);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BinaryDecodeError::InvalidVarint)
+    }
+
+    /// Reads the length of a sequence of items taking at least a byte each, which cannot be
+    /// longer than the rest of the input.
+    fn len(&mut self) -> Result<usize, BinaryDecodeError> {
+        match usize::try_from(self.varint()?) {
+            Ok(len) if len <= self.bytes.len() => Ok(len),
+            _ => Err(BinaryDecodeError::UnexpectedEnd),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, BinaryDecodeError> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| BinaryDecodeError::InvalidUtf8)
+    }
+
+    fn token(&mut self) -> Result<&str, BinaryDecodeError> {
+        let idx = self.varint()?;
+        usize::try_from(idx)
+            .ok()
+            .and_then(|idx| self.tokens.get(idx))
+            .map(String::as_str)
+            .ok_or(BinaryDecodeError::InvalidToken(idx))
+    }
+
+    fn pointer(&mut self) -> Result<String, BinaryDecodeError> {
+        let header = self.varint()?;
+        if header & 1 == 1 {
+            return Ok(self.token()?.to_owned());
+        }
+        let mut pointer = String::new();
+        for _ in 0..header >> 1 {
+            let token = self.token()?;
+            pointer.push('/');
+            pointer.push_str(token);
+        }
+        Ok(pointer)
+    }
+
+    fn operation(&mut self) -> Result<PatchOperation, BinaryDecodeError> {
+        Ok(match self.byte()? {
+            ADD => PatchOperation::Add(AddOperation {
+                path: self.pointer()?,
+                value: self.value()?,
+            }),
+            REMOVE => PatchOperation::Remove(RemoveOperation {
+                path: self.pointer()?,
+            }),
+            REPLACE => PatchOperation::Replace(ReplaceOperation {
+                path: self.pointer()?,
+                value: self.value()?,
+            }),
+            MOVE => PatchOperation::Move(MoveOperation {
+                from: self.pointer()?,
+                path: self.pointer()?,
+            }),
+            COPY => PatchOperation::Copy(CopyOperation {
+                from: self.pointer()?,
+                path: self.pointer()?,
+            }),
+            TEST => PatchOperation::Test(TestOperation {
+                path: self.pointer()?,
+                value: self.value()?,
+            }),
+            tag => return Err(BinaryDecodeError::InvalidTag(tag)),
+        })
+    }
+
+    /// Decodes the value without recursion, so deeply nested values cannot overflow the stack.
+    fn value(&mut self) -> Result<Value, BinaryDecodeError> {
+        /// Array or object being decoded, with the number of the remaining elements or members.
+        enum Frame {
+            Array(Vec<Value>, usize),
+            Object(Map<String, Value>, usize, String),
+        }
+
+        let mut stack = Vec::new();
+        loop {
+            if let Some(Frame::Object(_, _, key)) = stack.last_mut() {
+                *key = self.string()?;
+            }
+            let mut value = match self.byte()? {
+                NULL => Value::Null,
+                FALSE => Value::Bool(false),
+                TRUE => Value::Bool(true),
+                UNSIGNED => Value::from(self.varint()?),
+                NEGATIVE => match i64::try_from(self.varint()?) {
+                    Ok(n) => Value::from(!n),
+                    Err(_) => return Err(BinaryDecodeError::InvalidVarint),
+                },
+                FLOAT => {
+                    let bytes = self.take(8)?.try_into().unwrap();
+                    Number::from_f64(f64::from_le_bytes(bytes)).map_or(Value::Null, Value::Number)
+                }
+                STRING => Value::String(self.string()?),
+                ARRAY => match self.len()? {
+                    0 => Value::Array(Vec::new()),
+                    len => {
+                        stack.push(Frame::Array(Vec::with_capacity(len), len));
+                        continue;
+                    }
+                },
+                OBJECT => match self.len()? {
+                    0 => Value::Object(Map::new()),
+                    len => {
+                        stack.push(Frame::Object(Map::new(), len, String::new()));
+                        continue;
+                    }
+                },
+                tag => return Err(BinaryDecodeError::InvalidTag(tag)),
+            };
+            // Put the value into its parent, completing the parents with all their children
+            loop {
+                let remaining = match stack.last_mut() {
+                    None => return Ok(value),
+                    Some(Frame::Array(arr, remaining)) => {
+                        arr.push(value);
+                        remaining
+                    }
+                    Some(Frame::Object(obj, remaining, key)) => {
+                        obj.insert(core::mem::take(key), value);
+                        remaining
+                    }
+                };
+                *remaining -= 1;
+                if *remaining > 0 {
+                    break;
+                }
+                value = match stack.pop() {
+                    Some(Frame::Array(arr, _)) => Value::Array(arr),
+                    Some(Frame::Object(obj, _, _)) => Value::Object(obj),
+                    None => unreachable!(),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BinaryDecodeError;
+    use crate::{AddOperation, Patch, PatchOperation};
+    use serde_json::{from_value, json, Value};
+
+    fn patch() -> Patch {
+        from_value(json!([
+            { "op": "add", "path": "", "value": { "b": [[], {}, [null]], "a": { "": "" } } },
+            { "op": "remove", "path": "/a~1b/~0c" },
+            { "op": "replace", "path": "/ünïcode", "value": "☕ \u{0} \u{1F600}" },
+            { "op": "move", "from": "/x/0", "path": "/y/-" },
+            { "op": "copy", "from": "/", "path": "//x//" },
+            { "op": "remove", "path": "invalid/pointer" },
+            { "op": "test", "path": "/numbers", "value": [
+                0, -1, 127, 128, -128, -129, 65536,
+                u64::MAX, i64::MIN, 0.5, -0.0, -1e300, f64::MIN_POSITIVE,
+            ] },
+            { "op": "test", "path": "/scalars", "value": [true, false, null, "", "x"] },
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let p = patch();
+        let bytes = p.to_bytes();
+        let decoded = Patch::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, p);
+        // Numbers and the order of object members are preserved
+        assert_eq!(decoded.to_string(), p.to_string());
+        assert_eq!(
+            Patch::from_bytes(&Patch::default().to_bytes()).unwrap(),
+            Patch::default()
+        );
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let mut value = Value::Null;
+        for _ in 0..100_000 {
+            value = Value::Array(vec![value]);
+        }
+        let p = Patch(vec![PatchOperation::Add(AddOperation {
+            path: "/a".to_string(),
+            value,
+        })]);
+        let decoded = Patch::from_bytes(&p.to_bytes()).unwrap();
+        assert!(crate::deep::values_equal(
+            decoded[0].value().unwrap(),
+            p[0].value().unwrap()
+        ));
+        // Values are too deep to be dropped recursively
+        core::mem::forget((p, decoded));
+    }
+
+    #[test]
+    fn invalid() {
+        let bytes = patch().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(Patch::from_bytes(&bytes[..len]).is_err());
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Patch::from_bytes(&trailing),
+            Err(BinaryDecodeError::TrailingBytes)
+        );
+        assert_eq!(
+            Patch::from_bytes(&[2, 0, 0]),
+            Err(BinaryDecodeError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            Patch::from_bytes(&[1, 0, 1, 9]),
+            Err(BinaryDecodeError::InvalidTag(9))
+        );
+        assert_eq!(
+            Patch::from_bytes(&[1, 0, 1, 1, 2, 0]),
+            Err(BinaryDecodeError::InvalidToken(0))
+        );
+        assert_eq!(
+            Patch::from_bytes(&[1, 1, 1, 0xff, 0, 1, 1, 2, 0]),
+            Err(BinaryDecodeError::InvalidUtf8)
+        );
+        assert_eq!(
+            Patch::from_bytes(&[1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]),
+            Err(BinaryDecodeError::InvalidVarint)
+        );
+    }
+}
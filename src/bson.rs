@@ -0,0 +1,184 @@
+//! Patching and diffing BSON documents, see [`JsonLike`].
+//!
+//! Values of the patch operations are represented in the relaxed Extended JSON format, so BSON
+//! specific values like `ObjectId`, `DateTime` or `Binary` are kept intact when the patch is
+//! applied. These values are compared as opaque scalars. Relaxed Extended JSON represents all
+//! integers as plain numbers though, so the added or replaced integers become `Int32` if they fit
+//! and `Int64` otherwise.
+use crate::generic::{self, JsonLike};
+use crate::{Patch, PatchError, PatchOperation};
+use bson::{Bson, Document};
+use serde_json::Value;
+
+impl JsonLike for Bson {
+    fn from_json(value: &Value) -> Self {
+        Bson::try_from(value.clone()).unwrap_or_else(|_| plain(value))
+    }
+
+    fn to_json(&self) -> Value {
+        self.clone().into_relaxed_extjson()
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        self.as_array().map(Vec::as_slice)
+    }
+
+    fn as_array_mut(&mut self) -> Option<&mut [Self]> {
+        self.as_array_mut().map(Vec::as_mut_slice)
+    }
+
+    fn insert_element(&mut self, index: usize, value: Self) {
+        match self {
+            Bson::Array(arr) => arr.insert(index, value),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn remove_element(&mut self, index: usize) -> Self {
+        match self {
+            Bson::Array(arr) => arr.remove(index),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn is_object(&self) -> bool {
+        matches!(self, Bson::Document(_))
+    }
+
+    fn members(&self) -> Option<Vec<(&str, &Self)>> {
+        let doc = self.as_document()?;
+        Some(doc.iter().map(|(k, v)| (k.as_str(), v)).collect())
+    }
+
+    fn member(&self, key: &str) -> Option<&Self> {
+        self.as_document()?.get(key)
+    }
+
+    fn member_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_document_mut()?.get_mut(key)
+    }
+
+    fn insert_member(&mut self, key: String, value: Self) -> Option<Self> {
+        self.as_document_mut()?.insert(key, value)
+    }
+
+    fn remove_member(&mut self, key: &str) -> Option<Self> {
+        self.as_document_mut()?.remove(key)
+    }
+}
+
+/// Converts the JSON value which is not valid Extended JSON, taking the value itself literally.
+fn plain(value: &Value) -> Bson {
+    match value {
+        Value::Null => Bson::Null,
+        Value::Bool(b) => Bson::Boolean(*b),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(n), _) => i32::try_from(n).map_or(Bson::Int64(n), Bson::Int32),
+            (None, n) => Bson::Double(n.unwrap_or_default()),
+        },
+        Value::String(s) => Bson::String(s.clone()),
+        Value::Array(arr) => Bson::Array(arr.iter().map(Bson::from_json).collect()),
+        Value::Object(obj) => Bson::Document(
+            obj.iter()
+                .map(|(k, v)| (k.clone(), Bson::from_json(v)))
+                .collect::<Document>(),
+        ),
+    }
+}
+
+/// Diff two BSON values and generate a JSON Patch (RFC 6902), as [`diff`](crate::diff) does for
+/// JSON documents.
+///
+/// Requires the `bson` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use bson::{doc, oid::ObjectId, Bson};
+/// use json_patch::diff_bson;
+/// use serde_json::json;
+///
+/// let owner = ObjectId::new();
+/// let left = Bson::Document(doc! { "name": "report", "owner": null });
+/// let right = Bson::Document(doc! { "name": "report", "owner": owner });
+///
+/// let p = diff_bson(&left, &right);
+/// assert_eq!(p[0].path(), "/owner");
+/// assert_eq!(
+///     serde_json::to_value(&p).unwrap()[0]["value"],
+///     json!({ "$oid": owner.to_hex() })
+/// );
+/// ```
+pub fn diff_bson(left: &Bson, right: &Bson) -> Patch {
+    generic::diff(left, right)
+}
+
+/// Patch the BSON value in place, as [`patch`](crate::patch) does for JSON documents. If any of
+/// the operations fails, all previous operations are reverted.
+///
+/// Requires the `bson` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use bson::{doc, oid::ObjectId, Bson};
+/// use json_patch::{patch_bson, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let owner = ObjectId::new();
+/// let mut doc = Bson::Document(doc! { "name": "report" });
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/owner", "value": { "$oid": owner.to_hex() } },
+/// ])).unwrap();
+///
+/// patch_bson(&mut doc, &p).unwrap();
+/// assert_eq!(doc, Bson::Document(doc! { "name": "report", "owner": owner }));
+/// ```
+pub fn patch_bson(doc: &mut Bson, patch: &[PatchOperation]) -> Result<(), PatchError> {
+    generic::patch(doc, patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_bson, patch_bson};
+    use bson::{doc, oid::ObjectId, spec::BinarySubtype, Binary, Bson, DateTime};
+
+    #[test]
+    fn round_trip() {
+        let id = ObjectId::new();
+        let left = Bson::Document(doc! {
+            "_id": id,
+            "created": DateTime::from_millis(1_000),
+            "payload": Binary { subtype: BinarySubtype::Generic, bytes: vec![1, 2, 3] },
+            "count": 1_i64 << 40,
+            "tags": ["a", "b"],
+        });
+        let right = Bson::Document(doc! {
+            "_id": id,
+            "created": DateTime::from_millis(2_000),
+            "payload": Binary { subtype: BinarySubtype::Generic, bytes: vec![1, 2] },
+            "count": 1_i64 << 41,
+            "tags": ["a", { "parent": ObjectId::new() }],
+            "meta": { "$oid": "not an object id" },
+        });
+
+        let p = diff_bson(&left, &right);
+        assert_eq!(p.len(), 5);
+        let mut doc = left.clone();
+        patch_bson(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+    }
+
+    #[test]
+    fn failed_patch() {
+        let mut doc = Bson::Document(doc! { "a": [1, 2] });
+        let p: crate::Patch = serde_json::from_value(serde_json::json!([
+            { "op": "remove", "path": "/a/0" },
+            { "op": "add", "path": "/b/c", "value": 1 },
+        ]))
+        .unwrap();
+        let err = patch_bson(&mut doc, &p).unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert_eq!(doc, Bson::Document(doc! { "a": [1, 2] }));
+    }
+}
@@ -0,0 +1,260 @@
+//! Patching and diffing CBOR documents, see [`JsonLike`].
+//!
+//! Maps are addressed by their text keys, like JSON objects; entries with other keys are never
+//! added, removed or compared by the patch operations. Byte strings and tagged values are compared
+//! as opaque scalars. In the values of the patch operations, they are represented by the objects
+//! `{ "$bytes": "<hex>" }` and `{ "$tag": <tag>, "$value": <value> }`.
+use crate::generic::{self, JsonLike};
+use crate::{Patch, PatchError, PatchOperation};
+use ciborium::value::Value as Cbor;
+use serde_json::{Map, Value};
+use std::fmt::Write;
+
+impl JsonLike for Cbor {
+    fn from_json(value: &Value) -> Self {
+        match value {
+            Value::Null => Cbor::Null,
+            Value::Bool(b) => Cbor::Bool(*b),
+            Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(n), _) => Cbor::Integer(n.into()),
+                (None, Some(n)) => Cbor::Integer(n.into()),
+                (None, None) => Cbor::Float(n.as_f64().unwrap_or_default()),
+            },
+            Value::String(s) => Cbor::Text(s.clone()),
+            Value::Array(arr) => Cbor::Array(arr.iter().map(Cbor::from_json).collect()),
+            Value::Object(obj) => from_special(obj).unwrap_or_else(|| {
+                Cbor::Map(
+                    obj.iter()
+                        .map(|(k, v)| (Cbor::Text(k.clone()), Cbor::from_json(v)))
+                        .collect(),
+                )
+            }),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Cbor::Null => Value::Null,
+            Cbor::Bool(b) => Value::Bool(*b),
+            Cbor::Integer(n) => {
+                let n = i128::from(*n);
+                if let Ok(n) = u64::try_from(n) {
+                    Value::from(n)
+                } else if let Ok(n) = i64::try_from(n) {
+                    Value::from(n)
+                } else {
+                    Value::from(n as f64)
+                }
+            }
+            // Infinities and NaN have no JSON representation
+            Cbor::Float(n) => Value::from(*n),
+            Cbor::Text(s) => Value::String(s.clone()),
+            Cbor::Bytes(bytes) => {
+                let mut hex = String::with_capacity(bytes.len() * 2);
+                for byte in bytes {
+                    write!(hex, "{:02x}", byte).unwrap();
+                }
+                let mut obj = Map::new();
+                obj.insert("$bytes".to_string(), Value::String(hex));
+                Value::Object(obj)
+            }
+            Cbor::Tag(tag, value) => {
+                let mut obj = Map::new();
+                obj.insert("$tag".to_string(), Value::from(*tag));
+                obj.insert("$value".to_string(), value.to_json());
+                Value::Object(obj)
+            }
+            Cbor::Array(arr) => Value::Array(arr.iter().map(Cbor::to_json).collect()),
+            Cbor::Map(entries) => Value::Object(
+                entries
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.as_text()?.to_string(), v.to_json())))
+                    .collect(),
+            ),
+            _ => Value::Null,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        self.as_array().map(Vec::as_slice)
+    }
+
+    fn as_array_mut(&mut self) -> Option<&mut [Self]> {
+        self.as_array_mut().map(Vec::as_mut_slice)
+    }
+
+    fn insert_element(&mut self, index: usize, value: Self) {
+        match self {
+            Cbor::Array(arr) => arr.insert(index, value),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn remove_element(&mut self, index: usize) -> Self {
+        match self {
+            Cbor::Array(arr) => arr.remove(index),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn is_object(&self) -> bool {
+        self.is_map()
+    }
+
+    fn members(&self) -> Option<Vec<(&str, &Self)>> {
+        let entries = self.as_map()?;
+        Some(
+            entries
+                .iter()
+                .filter_map(|(k, v)| Some((k.as_text()?, v)))
+                .collect(),
+        )
+    }
+
+    fn member(&self, key: &str) -> Option<&Self> {
+        let entries = self.as_map()?;
+        entries
+            .iter()
+            .find(|(k, _)| k.as_text() == Some(key))
+            .map(|(_, v)| v)
+    }
+
+    fn member_mut(&mut self, key: &str) -> Option<&mut Self> {
+        let entries = self.as_map_mut()?;
+        entries
+            .iter_mut()
+            .find(|(k, _)| k.as_text() == Some(key))
+            .map(|(_, v)| v)
+    }
+
+    fn insert_member(&mut self, key: String, value: Self) -> Option<Self> {
+        match self.member_mut(&key) {
+            Some(prev) => Some(std::mem::replace(prev, value)),
+            None => {
+                self.as_map_mut()?.push((Cbor::Text(key), value));
+                None
+            }
+        }
+    }
+
+    fn remove_member(&mut self, key: &str) -> Option<Self> {
+        let entries = self.as_map_mut()?;
+        let idx = entries.iter().position(|(k, _)| k.as_text() == Some(key))?;
+        Some(entries.remove(idx).1)
+    }
+}
+
+/// Converts the object representing a byte string or a tagged value, if it is one.
+fn from_special(obj: &Map<String, Value>) -> Option<Cbor> {
+    match (
+        obj.len(),
+        obj.get("$bytes"),
+        obj.get("$tag"),
+        obj.get("$value"),
+    ) {
+        (1, Some(Value::String(hex)), _, _) if hex.len() % 2 == 0 => {
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+                .collect::<Option<_>>()?;
+            Some(Cbor::Bytes(bytes))
+        }
+        (2, None, Some(tag), Some(value)) => {
+            Some(Cbor::Tag(tag.as_u64()?, Box::new(Cbor::from_json(value))))
+        }
+        _ => None,
+    }
+}
+
+/// Diff two CBOR values and generate a JSON Patch (RFC 6902), as [`diff`](crate::diff) does for
+/// JSON documents.
+///
+/// Requires the `cbor` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use ciborium::Value;
+/// use json_patch::{diff_cbor, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let left = Value::Map(vec![("key".into(), Value::Bytes(vec![1, 2]))]);
+/// let right = Value::Map(vec![("key".into(), Value::Bytes(vec![1, 255]))]);
+///
+/// let p = diff_cbor(&left, &right);
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/key", "value": { "$bytes": "01ff" } },
+/// ])).unwrap());
+/// ```
+pub fn diff_cbor(left: &Cbor, right: &Cbor) -> Patch {
+    generic::diff(left, right)
+}
+
+/// Patch the CBOR value in place, as [`patch`](crate::patch) does for JSON documents. If any of
+/// the operations fails, all previous operations are reverted.
+///
+/// Requires the `cbor` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use ciborium::Value;
+/// use json_patch::{patch_cbor, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = Value::Map(vec![("readings".into(), Value::Array(vec![]))]);
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/readings/-", "value": { "$bytes": "0a0b" } },
+/// ])).unwrap();
+///
+/// patch_cbor(&mut doc, &p).unwrap();
+/// assert_eq!(
+///     doc,
+///     Value::Map(vec![("readings".into(), Value::Array(vec![Value::Bytes(vec![10, 11])]))])
+/// );
+/// ```
+pub fn patch_cbor(doc: &mut Cbor, patch: &[PatchOperation]) -> Result<(), PatchError> {
+    generic::patch(doc, patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_cbor, patch_cbor};
+    use ciborium::value::{Integer, Value as Cbor};
+
+    fn map(entries: Vec<(&str, Cbor)>) -> Cbor {
+        Cbor::Map(entries.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut left = map(vec![
+            ("id", Cbor::Bytes(vec![0xde, 0xad])),
+            ("time", Cbor::Tag(1, Box::new(Cbor::Integer(1_000.into())))),
+            ("big", Cbor::Integer(Integer::from(u64::MAX))),
+            ("values", Cbor::Array(vec![Cbor::Float(1.5), Cbor::Null])),
+        ]);
+        let mut right = map(vec![
+            ("id", Cbor::Bytes(vec![0xbe, 0xef])),
+            ("time", Cbor::Tag(1, Box::new(Cbor::Integer(2_000.into())))),
+            ("big", Cbor::Integer(Integer::from(i64::MIN))),
+            ("values", Cbor::Array(vec![Cbor::Float(1.5)])),
+        ]);
+        // Entries with keys other than text are left alone
+        for value in [&mut left, &mut right] {
+            if let Cbor::Map(entries) = value {
+                entries.push((Cbor::Integer(1.into()), Cbor::Bool(true)));
+            }
+        }
+        if let Cbor::Map(entries) = &mut right {
+            let bytes = map(vec![("$bytes", Cbor::Text("xyz".into()))]);
+            entries.push(("bytes".into(), bytes));
+        }
+
+        let p = diff_cbor(&left, &right);
+        assert_eq!(p.len(), 5);
+        let mut doc = left.clone();
+        patch_cbor(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+    }
+}
@@ -0,0 +1,368 @@
+use crate::deep::{clone_value, drop_operations, drop_value};
+use crate::{
+    add, copy, member_position, mov, move_positions, remove, replace, revert, test,
+    translate_error, AddOperation, MoveOperation, Patch, PatchError, PatchErrorKind,
+    PatchOperation, RemoveOperation, ReplaceOperation, Token, Undo,
+};
+use alloc::{string::String, vec::Vec};
+use serde_json::Value;
+
+/// JSON pointer split into reference tokens.
+#[derive(Debug, Clone)]
+struct CompiledPointer {
+    pointer: String,
+    tokens: Vec<Token<'static>>,
+}
+
+impl CompiledPointer {
+    fn new(pointer: &str) -> Option<CompiledPointer> {
+        let tokens = match pointer.strip_prefix('/') {
+            Some(rest) => rest
+                .split('/')
+                .map(|token| Token::new(token).into_owned())
+                .collect(),
+            None if pointer.is_empty() => Vec::new(),
+            None => return None,
+        };
+        Some(CompiledPointer {
+            pointer: pointer.into(),
+            tokens,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum CompiledOperation {
+    Add(CompiledPointer, Value),
+    Remove(CompiledPointer),
+    Replace(CompiledPointer, Value),
+    Move(CompiledPointer, CompiledPointer),
+    Copy(CompiledPointer, CompiledPointer),
+    Test(CompiledPointer, Value),
+}
+
+impl CompiledOperation {
+    fn path(&self) -> &str {
+        match self {
+            CompiledOperation::Add(path, _)
+            | CompiledOperation::Remove(path)
+            | CompiledOperation::Replace(path, _)
+            | CompiledOperation::Move(_, path)
+            | CompiledOperation::Copy(_, path)
+            | CompiledOperation::Test(path, _) => &path.pointer,
+        }
+    }
+}
+
+/// JSON patch with its pointers parsed in advance, for applying the same patch to many
+/// documents. Created by [`Patch::compile`].
+///
+/// Applying a compiled patch has the same effect, and fails with the same errors, as applying
+/// the patch it was compiled from with [`crate::patch`], but the pointers are not split and
+/// unescaped again for every document.
+#[derive(Debug, Clone)]
+pub struct CompiledPatch {
+    ops: Vec<CompiledOperation>,
+}
+
+impl Patch {
+    /// Compiles the patch for repeated application, splitting every pointer into its reference
+    /// tokens once.
+    ///
+    /// Fails if a `path` or a `from` pointer is malformed (neither empty nor starting with `/`),
+    /// with the error applying the patch would fail with once it reaches that operation.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "test", "path": "/kind", "value": "user" },
+    ///   { "op": "add", "path": "/tags/-", "value": "archived" }
+    /// ])).unwrap();
+    /// let compiled = p.compile().unwrap();
+    ///
+    /// let mut docs = vec![
+    ///     json!({ "kind": "user", "tags": [] }),
+    ///     json!({ "kind": "user", "tags": ["admin"] }),
+    /// ];
+    /// for doc in &mut docs {
+    ///     compiled.apply(doc).unwrap();
+    /// }
+    /// assert_eq!(docs[1], json!({ "kind": "user", "tags": ["admin", "archived"] }));
+    ///
+    /// let mut group = json!({ "kind": "group", "tags": [] });
+    /// assert!(compiled.apply(&mut group).is_err());
+    /// assert_eq!(group, json!({ "kind": "group", "tags": [] }));
+    /// ```
+    pub fn compile(&self) -> Result<CompiledPatch, PatchError> {
+        let ops = self
+            .iter()
+            .enumerate()
+            .map(|(operation, op)| {
+                let path = |pointer: &str| {
+                    CompiledPointer::new(pointer).ok_or_else(|| {
+                        translate_error(PatchErrorKind::InvalidPointer, operation, op.path())
+                    })
+                };
+                let from = |pointer: &str| {
+                    CompiledPointer::new(pointer).ok_or_else(|| {
+                        translate_error(PatchErrorKind::InvalidFromPointer, operation, op.path())
+                    })
+                };
+                Ok(match op {
+                    PatchOperation::Add(op) => {
+                        CompiledOperation::Add(path(&op.path)?, clone_value(&op.value))
+                    }
+                    PatchOperation::Remove(op) => CompiledOperation::Remove(path(&op.path)?),
+                    PatchOperation::Replace(op) => {
+                        CompiledOperation::Replace(path(&op.path)?, clone_value(&op.value))
+                    }
+                    PatchOperation::Move(op) => {
+                        CompiledOperation::Move(from(&op.from)?, path(&op.path)?)
+                    }
+                    PatchOperation::Copy(op) => {
+                        CompiledOperation::Copy(from(&op.from)?, path(&op.path)?)
+                    }
+                    PatchOperation::Test(op) => {
+                        CompiledOperation::Test(path(&op.path)?, clone_value(&op.value))
+                    }
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(CompiledPatch { ops })
+    }
+}
+
+impl CompiledPatch {
+    /// Returns the number of operations in the patch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if the patch has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Patch provided JSON document in-place, the same as [`crate::patch`]. If any of the
+    /// operations fails, all previous operations are reverted.
+    pub fn apply(&self, doc: &mut Value) -> Result<(), PatchError> {
+        let mut undo_stack = Vec::with_capacity(self.ops.len());
+        if let Err(e) = self.apply_ops(doc, Some(&mut undo_stack)) {
+            revert(doc, undo_stack);
+            return Err(e);
+        }
+        drop_operations(undo_stack);
+        Ok(())
+    }
+
+    /// Apply the patch to a copy of the provided JSON document, the same as [`crate::patched`].
+    pub fn patched(&self, doc: &Value) -> Result<Value, PatchError> {
+        let mut doc = clone_value(doc);
+        if let Err(e) = self.apply_ops(&mut doc, None) {
+            drop_value(doc);
+            return Err(e);
+        }
+        Ok(doc)
+    }
+
+    /// Applies the operations, recording the changes in the undo stack for [`crate::revert`],
+    /// the same as [`crate::apply_patches`].
+    fn apply_ops(
+        &self,
+        doc: &mut Value,
+        mut undo_stack: Option<&mut Vec<Undo>>,
+    ) -> Result<(), PatchError> {
+        for (operation, op) in self.ops.iter().enumerate() {
+            let error = |e| translate_error(e, operation, op.path());
+            match op {
+                CompiledOperation::Add(path, value) => {
+                    let prev =
+                        add(doc, path.tokens.as_slice(), clone_value(value)).map_err(error)?;
+                    record_added(undo_stack.as_deref_mut(), path, prev);
+                }
+                CompiledOperation::Remove(path) => {
+                    let tokens = path.tokens.as_slice();
+                    let position = undo_stack
+                        .as_ref()
+                        .and_then(|_| member_position(doc, tokens));
+                    let prev = remove(doc, tokens, false).map_err(error)?;
+                    record_removed(undo_stack.as_deref_mut(), path, prev, position);
+                }
+                CompiledOperation::Replace(path, value) => {
+                    let prev =
+                        replace(doc, path.tokens.as_slice(), clone_value(value)).map_err(error)?;
+                    match undo_stack.as_deref_mut() {
+                        Some(undo_stack) => {
+                            undo_stack.push(Undo::from(PatchOperation::Replace(ReplaceOperation {
+                                path: path.pointer.clone(),
+                                value: prev,
+                            })))
+                        }
+                        None => drop_value(prev),
+                    }
+                }
+                CompiledOperation::Move(from, path) => {
+                    let (from_tokens, path_tokens) =
+                        (from.tokens.as_slice(), path.tokens.as_slice());
+                    let (from_position, path_position) = match undo_stack {
+                        Some(_) => move_positions(doc, from_tokens, path_tokens),
+                        None => (None, None),
+                    };
+                    let prev = mov(doc, from_tokens, path_tokens, false).map_err(error)?;
+                    if let Some(prev) = prev {
+                        record_removed(undo_stack.as_deref_mut(), path, prev, path_position);
+                    }
+                    if let Some(undo_stack) = undo_stack.as_deref_mut() {
+                        undo_stack.push(Undo {
+                            op: PatchOperation::Move(MoveOperation {
+                                from: path.pointer.clone(),
+                                path: from.pointer.clone(),
+                            }),
+                            position: from_position,
+                        });
+                    }
+                }
+                CompiledOperation::Copy(from, path) => {
+                    let prev =
+                        copy(doc, from.tokens.as_slice(), path.tokens.as_slice()).map_err(error)?;
+                    record_added(undo_stack.as_deref_mut(), path, prev);
+                }
+                CompiledOperation::Test(path, value) => {
+                    test(doc, path.tokens.as_slice(), value).map_err(error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records the inverse of adding a value at the location, which replaced `prev` if any.
+fn record_added(undo_stack: Option<&mut Vec<Undo>>, path: &CompiledPointer, prev: Option<Value>) {
+    match (prev, undo_stack) {
+        (None, Some(undo_stack)) => {
+            undo_stack.push(Undo::from(PatchOperation::Remove(RemoveOperation {
+                path: path.pointer.clone(),
+            })))
+        }
+        (Some(prev), undo_stack) => record_removed(undo_stack, path, prev, None),
+        (None, None) => {}
+    }
+}
+
+/// Records the inverse of taking the value out of the location, putting the object member back
+/// at its position (see [`crate::member_position`]).
+fn record_removed(
+    undo_stack: Option<&mut Vec<Undo>>,
+    path: &CompiledPointer,
+    prev: Value,
+    position: Option<usize>,
+) {
+    match undo_stack {
+        Some(undo_stack) => undo_stack.push(Undo {
+            op: PatchOperation::Add(AddOperation {
+                path: path.pointer.clone(),
+                value: prev,
+            }),
+            position,
+        }),
+        None => drop_value(prev),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{patch, Patch};
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn same_as_patch() {
+        let doc = json!({
+            "a": { "b": [1, 2, 3], "c~/d": "x" },
+            "e": [{ "f": null }],
+        });
+        let patches = [
+            json!([{ "op": "add", "path": "/a/b/-", "value": 4 }]),
+            json!([{ "op": "add", "path": "/a/b/01", "value": 4 }]),
+            json!([{ "op": "add", "path": "/a/b/4", "value": 4 }]),
+            json!([{ "op": "add", "path": "", "value": 4 }]),
+            json!([{ "op": "add", "path": "/x/y", "value": 4 }]),
+            json!([{ "op": "remove", "path": "/a/c~0~1d" }, { "op": "remove", "path": "/a/b/0" }]),
+            json!([{ "op": "remove", "path": "/a/b/-" }]),
+            json!([{ "op": "remove", "path": "" }]),
+            json!([{ "op": "replace", "path": "/e/0/f", "value": 1 }]),
+            json!([{ "op": "replace", "path": "/e/1", "value": 1 }]),
+            json!([{ "op": "move", "from": "/a/b", "path": "/e/0/b" }]),
+            json!([{ "op": "move", "from": "/a", "path": "/a/g" }]),
+            json!([{ "op": "move", "from": "/a/b/0", "path": "/a/b/-" }]),
+            json!([{ "op": "move", "from": "/a/z", "path": "/b" }]),
+            json!([{ "op": "move", "from": "/a/b/1", "path": "/z/0" }]),
+            json!([{ "op": "copy", "from": "/e", "path": "/a/b/0" }]),
+            json!([{ "op": "copy", "from": "/e/1", "path": "/a/b/0" }]),
+            json!([{ "op": "test", "path": "/a/c~0~1d", "value": "x" }]),
+            json!([{ "op": "test", "path": "/e/0/f", "value": 0 }]),
+            json!([{ "op": "test", "path": "/e/0/g", "value": 0 }]),
+            json!([
+                { "op": "add", "path": "/a/b/0", "value": 0 },
+                { "op": "replace", "path": "/a/c~0~1d", "value": "y" },
+                { "op": "move", "from": "/a/b/1", "path": "/a/b/-" },
+                { "op": "copy", "from": "/a", "path": "/e/0/f" },
+                { "op": "remove", "path": "/e/0/f/b/0" },
+                { "op": "test", "path": "/e/0/f/b", "value": [2, 3, 1] }
+            ]),
+            json!([
+                { "op": "add", "path": "/a/b/0", "value": 0 },
+                { "op": "add", "path": "/a/c~0~1d", "value": "y" },
+                { "op": "move", "from": "/a/b/1", "path": "/a/b/-" },
+                { "op": "copy", "from": "/a", "path": "/e/0/f" },
+                { "op": "remove", "path": "/e/0/f/b/0" },
+                { "op": "test", "path": "/e/0/f/b", "value": [] }
+            ]),
+        ];
+
+        for p in patches {
+            let p: Patch = from_value(p).unwrap();
+            let compiled = p.compile().unwrap();
+
+            let mut expected = doc.clone();
+            let expected_result = patch(&mut expected, &p).map_err(|e| e.to_string());
+            let mut actual = doc.clone();
+            let actual_result = compiled.apply(&mut actual).map_err(|e| e.to_string());
+            assert_eq!(actual_result, expected_result, "{p}");
+            assert_eq!(actual, expected, "{p}");
+            assert_eq!(
+                compiled.patched(&doc).map_err(|e| e.to_string()),
+                expected_result.map(|()| expected),
+                "{p}"
+            );
+        }
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn member_positions_reverted() {
+        let original = r#"{"a/b":0,"x":null}"#;
+        let p: Patch = from_value(json!([
+            { "op": "remove", "path": "/a~1b" },
+            { "op": "copy", "from": "/new", "path": "/x" }
+        ]))
+        .unwrap();
+        let mut doc: serde_json::Value = serde_json::from_str(original).unwrap();
+        assert!(p.compile().unwrap().apply(&mut doc).is_err());
+        assert_eq!(doc.to_string(), original);
+    }
+
+    #[test]
+    fn invalid_pointers() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a", "value": 1 },
+            { "op": "copy", "from": "a", "path": "b" }
+        ]))
+        .unwrap();
+        let expected = patch(&mut json!({}), &p).unwrap_err().to_string();
+        assert_eq!(p.compile().unwrap_err().to_string(), expected);
+    }
+}
@@ -0,0 +1,169 @@
+#[cfg(feature = "diff")]
+use crate::Patch;
+use crate::{PatchError, PatchOperation};
+use serde_json::Value;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// JSON document shared between threads, patched atomically: readers see either the whole
+/// result of a patch or none of it, and failed patches leave the document unchanged.
+///
+/// Readers take [snapshots](SharedDocument::snapshot) of the document, which are not affected by
+/// the patches applied afterwards. Clones of a `SharedDocument` refer to the same document.
+///
+/// Patches are applied in place while the document is locked for writing, copying it first only
+/// if snapshots of the current version are still alive.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{Patch, SharedDocument};
+/// use serde_json::{from_value, json};
+/// use std::thread;
+///
+/// let doc = SharedDocument::new(json!({ "visits": [] }));
+/// let before = doc.snapshot();
+///
+/// let threads: Vec<_> = (0..4)
+///     .map(|n| {
+///         let doc = doc.clone();
+///         thread::spawn(move || {
+///             let p: Patch = from_value(json!([
+///               { "op": "add", "path": "/visits/-", "value": n }
+///             ])).unwrap();
+///             doc.apply(&p).unwrap();
+///         })
+///     })
+///     .collect();
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+///
+/// assert_eq!(doc.snapshot()["visits"].as_array().unwrap().len(), 4);
+/// assert_eq!(*before, json!({ "visits": [] }));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SharedDocument(Arc<RwLock<Arc<Value>>>);
+
+impl SharedDocument {
+    /// Share the document.
+    pub fn new(doc: Value) -> SharedDocument {
+        SharedDocument(Arc::new(RwLock::new(Arc::new(doc))))
+    }
+
+    /// Returns the current version of the document.
+    pub fn snapshot(&self) -> Arc<Value> {
+        // Only internal errors of patching poison the lock, failed patches being reverted
+        let doc = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(&doc)
+    }
+
+    /// Patch the document the same way [`patch`](crate::patch) does, returning the patched
+    /// version. If any of the operations fails, the document is left unchanged.
+    pub fn apply(&self, patch: &[PatchOperation]) -> Result<Arc<Value>, PatchError> {
+        let mut doc = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        match Arc::get_mut(&mut doc) {
+            Some(doc) => crate::patch(doc, patch)?,
+            None => {
+                // Patch a copy, the current version being still in use
+                let mut patched = Value::clone(&doc);
+                crate::patch_unsafe(&mut patched, patch)?;
+                *doc = Arc::new(patched);
+            }
+        }
+        Ok(Arc::clone(&doc))
+    }
+
+    /// Replace the document, returning the previous version.
+    pub fn replace(&self, doc: Value) -> Arc<Value> {
+        let mut current = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        core::mem::replace(&mut *current, Arc::new(doc))
+    }
+
+    /// Diff the current version of the document against `other`, returning the patch
+    /// transforming the document into `other`. Requires the `diff` feature.
+    #[cfg(feature = "diff")]
+    pub fn diff_against(&self, other: &Value) -> Patch {
+        crate::diff(&self.snapshot(), other)
+    }
+}
+
+impl From<Value> for SharedDocument {
+    fn from(doc: Value) -> SharedDocument {
+        SharedDocument::new(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedDocument;
+    use crate::{Patch, PatchErrorKind};
+    use serde_json::{from_value, json};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn concurrent_patches() {
+        let doc = SharedDocument::new(json!({ "n": 0, "log": [] }));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let doc = doc.clone();
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        // Test-and-set, retried until no other thread interferes
+                        loop {
+                            let n = doc.snapshot()["n"].as_u64().unwrap();
+                            let p: Patch = from_value(json!([
+                                { "op": "test", "path": "/n", "value": n },
+                                { "op": "replace", "path": "/n", "value": n + 1 },
+                                { "op": "add", "path": "/log/-", "value": n },
+                            ]))
+                            .unwrap();
+                            if doc.apply(&p).is_ok() {
+                                break;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let snapshot = doc.snapshot();
+        assert_eq!(snapshot["n"], 400);
+        let log: Vec<_> = (0..400).collect();
+        assert_eq!(snapshot["log"], json!(log));
+    }
+
+    #[test]
+    fn failed_patches() {
+        let doc = SharedDocument::from(json!({ "a": 1 }));
+        let p: Patch = from_value(json!([
+            { "op": "replace", "path": "/a", "value": 2 },
+            { "op": "remove", "path": "/b" },
+        ]))
+        .unwrap();
+
+        // Patched in place, and with a snapshot alive
+        let err = doc.apply(&p).unwrap_err();
+        assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+        let snapshot = doc.snapshot();
+        let err = doc.apply(&p).unwrap_err();
+        assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+        assert!(Arc::ptr_eq(&snapshot, &doc.snapshot()));
+        assert_eq!(*snapshot, json!({ "a": 1 }));
+
+        let p: Patch = from_value(json!([{ "op": "add", "path": "/b", "value": 2 }])).unwrap();
+        let patched = doc.apply(&p).unwrap();
+        assert_eq!(*patched, json!({ "a": 1, "b": 2 }));
+        assert_eq!(*snapshot, json!({ "a": 1 }));
+
+        let previous = doc.replace(json!(null));
+        assert!(Arc::ptr_eq(&previous, &patched));
+        #[cfg(feature = "diff")]
+        assert_eq!(
+            doc.diff_against(&json!([])),
+            from_value::<Patch>(json!([{ "op": "replace", "path": "", "value": [] }])).unwrap()
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use crate::deep::drop_operations;
+use crate::{apply_patches, is_prefix, revert, Patch, PatchError, PatchOperation};
+use alloc::vec::Vec;
+use serde_json::Value;
+
+/// Values of the document the operations of the patch depend on: the values they remove,
+/// replace, move or copy, with their pointers. Values nested in other ones are left out, since
+/// testing the outer value covers them.
+pub(crate) fn targets<'a, 'd>(
+    patch: &'a [PatchOperation],
+    doc: &'d Value,
+) -> Vec<(&'a str, &'d Value)> {
+    let mut targets: Vec<(&str, &Value)> = Vec::new();
+    let pointers = patch.iter().flat_map(|op| match op {
+        PatchOperation::Test(_) => [None, None],
+        _ => [op.from(), Some(op.path())],
+    });
+    for pointer in pointers.flatten() {
+        // New members and elements appended with `-` do not exist in the document
+        let Some(value) = doc.pointer(pointer) else {
+            continue;
+        };
+        if targets.iter().any(|(outer, _)| is_prefix(outer, pointer)) {
+            continue;
+        }
+        targets.retain(|(inner, _)| !is_prefix(pointer, inner));
+        targets.push((pointer, value));
+    }
+    targets
+}
+
+/// Make the patch conditional on the document it was created against: prepend `test`
+/// operations asserting the current values the patch removes, replaces, moves or copies, so
+/// that it fails rather than overwriting concurrent changes of these values (optimistic
+/// concurrency).
+///
+/// The absence of the members the patch adds cannot be expressed with `test` operations, so
+/// these are not checked. Values nested in other tested values are not tested separately.
+///
+/// With the `extended-ops` feature,
+/// [`make_conditional_digest`](crate::extended::make_conditional_digest) tests the digests of
+/// the values instead, which keeps the patch small when the targeted values are large.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{make_conditional, patch, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "title": "Hello", "tags": ["a", "b"] });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/title", "value": "Hello!" },
+///   { "op": "remove", "path": "/tags/0" },
+///   { "op": "add", "path": "/author", "value": "ann" }
+/// ])).unwrap();
+///
+/// let conditional = make_conditional(&p, &doc);
+/// assert_eq!(conditional, from_value(json!([
+///   { "op": "test", "path": "/title", "value": "Hello" },
+///   { "op": "test", "path": "/tags/0", "value": "a" },
+///   { "op": "replace", "path": "/title", "value": "Hello!" },
+///   { "op": "remove", "path": "/tags/0" },
+///   { "op": "add", "path": "/author", "value": "ann" }
+/// ])).unwrap());
+///
+/// // The title was changed concurrently
+/// let mut current = json!({ "title": "Goodbye", "tags": ["a", "b"] });
+/// assert!(patch(&mut current, &conditional).is_err());
+/// ```
+pub fn make_conditional(patch: &[PatchOperation], doc: &Value) -> Patch {
+    targets(patch, doc)
+        .into_iter()
+        .map(|(path, value)| PatchOperation::test(path, value.clone()))
+        .chain(patch.iter().cloned())
+        .collect()
+}
+
+/// Patch provided JSON document (given as `serde_json::Value`) in-place, evaluating all the
+/// `test` operations of the patch against the document as it is before the patch, and only then
+/// applying the other operations, in their order. Preconditions of the patch thus refer to the
+/// original document wherever they are in the patch, rather than to the document partially
+/// patched by the operations preceding them.
+///
+/// If any of the `test` operations fails, the document is not modified at all (the first failed
+/// one is reported). If any of the other operations fails, all the previous operations are
+/// reverted. Errors report the indices of the operations in the whole patch.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch, patch_preconditioned, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/status", "value": "published" },
+///   { "op": "test", "path": "/status", "value": "draft" }
+/// ])).unwrap();
+///
+/// let mut doc = json!({ "status": "draft" });
+/// patch_preconditioned(&mut doc, &p).unwrap();
+/// assert_eq!(doc, json!({ "status": "published" }));
+///
+/// // Tested against the partially patched document otherwise
+/// let mut doc = json!({ "status": "draft" });
+/// assert!(patch(&mut doc, &p).is_err());
+/// ```
+pub fn patch_preconditioned(doc: &mut Value, patch: &[PatchOperation]) -> Result<(), PatchError> {
+    for (operation, op) in patch.iter().enumerate() {
+        if let PatchOperation::Test(_) = op {
+            apply_patches(doc, core::slice::from_ref(op), None).map_err(|mut e| {
+                e.operation = operation;
+                e
+            })?;
+        }
+    }
+    let mut undo_stack = Vec::with_capacity(patch.len());
+    for (operation, op) in patch.iter().enumerate() {
+        if let PatchOperation::Test(_) = op {
+            continue;
+        }
+        if let Err(mut e) = apply_patches(doc, core::slice::from_ref(op), Some(&mut undo_stack)) {
+            revert(doc, undo_stack);
+            e.operation = operation;
+            return Err(e);
+        }
+    }
+    drop_operations(undo_stack);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{make_conditional, patch_preconditioned};
+    use crate::{patch, Patch, PatchErrorKind};
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn nested_targets() {
+        let doc = json!({ "a": { "b": 1, "c": [1, 2] }, "d": 2 });
+        let p: Patch = from_value(json!([
+            { "op": "remove", "path": "/a/c/0" },
+            { "op": "test", "path": "/d", "value": 2 },
+            { "op": "move", "from": "/a/b", "path": "/d" },
+            { "op": "replace", "path": "/a", "value": { "c": [] } },
+            { "op": "copy", "from": "/d", "path": "/a/c/-" },
+            { "op": "add", "path": "/e", "value": 3 },
+        ]))
+        .unwrap();
+        let conditional = make_conditional(&p, &doc);
+        assert_eq!(
+            conditional[..2],
+            from_value::<Patch>(json!([
+                { "op": "test", "path": "/d", "value": 2 },
+                { "op": "test", "path": "/a", "value": { "b": 1, "c": [1, 2] } },
+            ]))
+            .unwrap()[..]
+        );
+        assert_eq!(conditional[2..], p[..]);
+
+        let mut patched = doc.clone();
+        patch(&mut patched, &conditional).unwrap();
+        assert_eq!(patched, json!({ "a": { "c": [1] }, "d": 1, "e": 3 }));
+
+        let mut current = json!({ "a": { "b": 1, "c": [1, 3] }, "d": 2 });
+        let err = patch(&mut current, &conditional).unwrap_err();
+        assert_eq!((err.operation, err.path.as_str()), (1, "/a"));
+    }
+
+    #[test]
+    fn preconditions() {
+        let doc = json!({ "a": [1, 2], "b": "x" });
+        let p: Patch = from_value(json!([
+            { "op": "remove", "path": "/a/0" },
+            { "op": "test", "path": "/a/0", "value": 1 },
+            { "op": "replace", "path": "/b", "value": "y" },
+            { "op": "test", "path": "/b", "value": "x" },
+        ]))
+        .unwrap();
+        let mut patched = doc.clone();
+        patch_preconditioned(&mut patched, &p).unwrap();
+        assert_eq!(patched, json!({ "a": [2], "b": "y" }));
+
+        // Failed preconditions
+        let mut current = json!({ "a": [1, 2], "b": "z" });
+        let err = patch_preconditioned(&mut current, &p).unwrap_err();
+        assert_eq!((err.operation, err.path.as_str()), (3, "/b"));
+        assert!(matches!(err.kind, PatchErrorKind::TestFailed { .. }));
+        assert_eq!(current, json!({ "a": [1, 2], "b": "z" }));
+
+        // Failed operations after the preconditions
+        let mut current = json!({ "a": [1], "b": "x" });
+        let p: Patch = from_value(json!([
+            { "op": "test", "path": "/b", "value": "x" },
+            { "op": "remove", "path": "/a/0" },
+            { "op": "remove", "path": "/a/0" },
+        ]))
+        .unwrap();
+        let err = patch_preconditioned(&mut current, &p).unwrap_err();
+        assert_eq!(err.operation, 2);
+        assert_eq!(current, json!({ "a": [1], "b": "x" }));
+    }
+}
@@ -0,0 +1,524 @@
+use crate::{apply_patches, revert, OpKind, Patch, PatchError, PatchOperation};
+use alloc::{format, string::String, vec::Vec};
+use serde::de::{Deserializer, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Options controlling how patches are deserialized from JSON.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{from_str_with_options, DeserializeOptions};
+///
+/// let options = DeserializeOptions {
+///     strict: true,
+///     ..Default::default()
+/// };
+///
+/// let p = from_str_with_options(r#"[{ "op": "remove", "path": "/a~1b" }]"#, &options);
+/// assert!(p.is_ok());
+/// let err = from_str_with_options(r#"[{ "op": "remove", "path": "/a~2b" }]"#, &options);
+/// assert_eq!(err.unwrap_err().to_string(), "operation '/0': invalid path '/a~2b'");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeserializeOptions {
+    /// Reject the `path` and `from` members which are not valid JSON pointers as defined by
+    /// RFC 6901: pointers must be empty or start with `/`, and `~` must only be used in the `~0`
+    /// and `~1` escape sequences. By default, such pointers are only rejected when applied.
+    pub strict: bool,
+    /// What to do with the members of operations not defined for their operation, like a
+    /// `value` member of a `remove` operation, or annotations added by the producer of the patch.
+    pub unknown_fields: UnknownFields,
+    /// Accept common variants of the operation names: names in any case (like `"Add"`), and
+    /// `"delete"` for `"remove"`.
+    pub aliases: bool,
+}
+
+/// Policy for the members of operations not defined for their operation, see
+/// [`DeserializeOptions::unknown_fields`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownFields {
+    /// Ignore the unknown members, as required by RFC 6902.
+    #[default]
+    Ignore,
+    /// Reject the operations with unknown members.
+    Reject,
+    /// Keep the unknown members in [`AnnotatedOperation::extra`], when deserializing an
+    /// [`AnnotatedPatch`]. Same as `Ignore` when deserializing a [`Patch`].
+    Preserve,
+}
+
+/// Operation of an [`AnnotatedPatch`], with the members not defined for the operation.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct AnnotatedOperation {
+    /// Operation.
+    #[serde(flatten)]
+    pub operation: PatchOperation,
+    /// Members of the operation not defined for it, serialized after the defined ones.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl From<PatchOperation> for AnnotatedOperation {
+    fn from(operation: PatchOperation) -> Self {
+        AnnotatedOperation {
+            operation,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl AnnotatedOperation {
+    /// Name of the member holding the metadata of an operation, see [`AnnotatedOperation::meta`].
+    pub const META: &'static str = "x-meta";
+
+    /// Returns the operation with the given metadata attached.
+    pub fn with_meta(operation: PatchOperation, meta: Value) -> Self {
+        let mut extra = Map::new();
+        extra.insert(Self::META.into(), meta);
+        AnnotatedOperation { operation, extra }
+    }
+
+    /// Returns the metadata of the operation (like the actor or the reason of the change), kept
+    /// in the `x-meta` member. Metadata is ignored when the operation is applied.
+    pub fn meta(&self) -> Option<&Value> {
+        self.extra.get(Self::META)
+    }
+
+    /// Returns the mutable metadata of the operation, see [`AnnotatedOperation::meta`].
+    pub fn meta_mut(&mut self) -> Option<&mut Value> {
+        self.extra.get_mut(Self::META)
+    }
+}
+
+impl<'de> Deserialize<'de> for AnnotatedOperation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let options = DeserializeOptions {
+            unknown_fields: UnknownFields::Preserve,
+            ..Default::default()
+        };
+        options
+            .operation(Value::deserialize(deserializer)?)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Patch keeping the members of its operations which are not defined for them, so they survive a
+/// round trip through deserialization and serialization.
+///
+/// When deserialized directly, the unknown members are preserved; use
+/// [`annotated_from_str_with_options`] (or its variants) for other options.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{AnnotatedPatch, Patch};
+/// use serde_json::{from_value, json, to_value};
+///
+/// let value = json!([
+///   { "op": "add", "path": "/a", "value": 1, "author": "ann", "reason": "init" }
+/// ]);
+/// let p: AnnotatedPatch = from_value(value.clone()).unwrap();
+/// assert_eq!(p.0[0].extra["author"], "ann");
+/// assert_eq!(to_value(&p).unwrap(), value);
+///
+/// let p: Patch = p.into();
+/// assert_eq!(to_value(&p).unwrap(), json!([{ "op": "add", "path": "/a", "value": 1 }]));
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct AnnotatedPatch(pub Vec<AnnotatedOperation>);
+
+impl From<AnnotatedPatch> for Patch {
+    fn from(patch: AnnotatedPatch) -> Self {
+        patch.0.into_iter().map(|op| op.operation).collect()
+    }
+}
+
+impl From<Patch> for AnnotatedPatch {
+    fn from(patch: Patch) -> Self {
+        AnnotatedPatch(patch.into_iter().map(AnnotatedOperation::from).collect())
+    }
+}
+
+/// Patch provided JSON document (given as `serde_json::Value`) in place with an annotated patch,
+/// ignoring the members of its operations not defined for them (including the metadata).
+///
+/// Same as [`patch`](crate::patch): if any operation fails, the document is left unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_annotated, AnnotatedPatch};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "a": 1 });
+/// let p: AnnotatedPatch = from_value(json!([
+///   { "op": "replace", "path": "/a", "value": 2, "x-meta": { "actor": "ann" } },
+///   { "op": "add", "path": "/b", "value": 3, "x-meta": { "actor": "bob", "reason": "init" } }
+/// ])).unwrap();
+/// assert_eq!(p.0[1].meta().unwrap()["reason"], "init");
+///
+/// patch_annotated(&mut doc, &p).unwrap();
+/// assert_eq!(doc, json!({ "a": 2, "b": 3 }));
+/// ```
+pub fn patch_annotated(doc: &mut Value, patch: &AnnotatedPatch) -> Result<(), PatchError> {
+    let mut undo_stack = Vec::with_capacity(patch.0.len());
+    for (operation, op) in patch.0.iter().enumerate() {
+        let ops = core::slice::from_ref(&op.operation);
+        if let Err(mut e) = apply_patches(doc, ops, Some(&mut undo_stack)) {
+            revert(doc, undo_stack);
+            e.operation = operation;
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+impl DeserializeOptions {
+    /// Whether patches can be deserialized directly, without looking at their members.
+    fn is_direct(&self) -> bool {
+        self.unknown_fields == UnknownFields::Ignore && !self.aliases
+    }
+
+    /// Check the deserialized patch against the options.
+    fn check(&self, patch: Patch) -> serde_json::Result<Patch> {
+        for (operation, op) in patch.iter().enumerate() {
+            self.check_operation(operation, op)?;
+        }
+        Ok(patch)
+    }
+
+    /// Check the pointers of the operation at the given index of the patch, in strict mode.
+    fn check_operation(&self, operation: usize, op: &PatchOperation) -> serde_json::Result<()> {
+        if self.strict {
+            check_pointer(operation, "path", op.path())?;
+            if let Some(from) = op.from() {
+                check_pointer(operation, "from", from)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserialize the patch from a JSON value, applying the options to its members.
+    fn annotated(&self, value: Value) -> serde_json::Result<AnnotatedPatch> {
+        let Value::Array(ops) = value else {
+            return Err(serde_json::Error::custom("patch must be an array"));
+        };
+        let ops = ops
+            .into_iter()
+            .enumerate()
+            .map(|(operation, op)| {
+                let op = self.operation(op).map_err(|e| {
+                    serde_json::Error::custom(format!("operation '/{}': {}", operation, e))
+                })?;
+                self.check_operation(operation, &op.operation)?;
+                Ok(op)
+            })
+            .collect::<serde_json::Result<_>>()?;
+        Ok(AnnotatedPatch(ops))
+    }
+
+    /// Deserialize an operation from a JSON value, applying the options to its members.
+    fn operation(&self, value: Value) -> serde_json::Result<AnnotatedOperation> {
+        let Value::Object(mut members) = value else {
+            return Err(serde_json::Error::custom("operation must be an object"));
+        };
+        let kind = match members.get("op") {
+            Some(Value::String(name)) => self.op_kind(name),
+            Some(_) => return Err(serde_json::Error::custom("`op` must be a string")),
+            None => return Err(serde_json::Error::missing_field("op")),
+        };
+        let Some(kind) = kind else {
+            // Let the regular deserialization report the unknown operation
+            return serde_json::from_value::<PatchOperation>(Value::Object(members))
+                .map(AnnotatedOperation::from);
+        };
+        members.insert("op".into(), kind.as_str().into());
+
+        let defined: &[&str] = match kind {
+            OpKind::Add | OpKind::Replace | OpKind::Test => &["op", "path", "value"],
+            OpKind::Remove => &["op", "path"],
+            OpKind::Move | OpKind::Copy => &["op", "from", "path"],
+        };
+        let mut extra = Map::new();
+        members.retain(|name, value| {
+            let keep = defined.contains(&name.as_str());
+            if !keep {
+                extra.insert(name.clone(), core::mem::take(value));
+            }
+            keep
+        });
+        match self.unknown_fields {
+            UnknownFields::Reject if !extra.is_empty() => {
+                let names: Vec<_> = extra.keys().map(String::as_str).collect();
+                return Err(serde_json::Error::custom(format!(
+                    "unknown field(s) `{}` of `{}` operation",
+                    names.join("`, `"),
+                    kind
+                )));
+            }
+            UnknownFields::Preserve => {}
+            _ => extra.clear(),
+        }
+
+        let operation = serde_json::from_value(Value::Object(members))?;
+        Ok(AnnotatedOperation { operation, extra })
+    }
+
+    /// Returns the kind of the operation with the given name, accepting aliases if enabled.
+    fn op_kind(&self, name: &str) -> Option<OpKind> {
+        let kinds = [
+            OpKind::Add,
+            OpKind::Remove,
+            OpKind::Replace,
+            OpKind::Move,
+            OpKind::Copy,
+            OpKind::Test,
+        ];
+        if !self.aliases {
+            return kinds.into_iter().find(|kind| kind.as_str() == name);
+        }
+        if name.eq_ignore_ascii_case("delete") {
+            return Some(OpKind::Remove);
+        }
+        kinds
+            .into_iter()
+            .find(|kind| kind.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+fn check_pointer(operation: usize, member: &str, pointer: &str) -> serde_json::Result<()> {
+    if crate::pointer::check_pointer(pointer).is_ok() {
+        Ok(())
+    } else {
+        Err(serde_json::Error::custom(format!(
+            "operation '/{}': invalid {} '{}'",
+            operation, member, pointer
+        )))
+    }
+}
+
+/// Deserialize a patch from JSON text, using provided options.
+pub fn from_str_with_options(s: &str, options: &DeserializeOptions) -> serde_json::Result<Patch> {
+    if options.is_direct() {
+        options.check(serde_json::from_str(s)?)
+    } else {
+        from_value_with_options(serde_json::from_str(s)?, options)
+    }
+}
+
+/// Deserialize a patch from JSON text given as bytes, using provided options.
+pub fn from_slice_with_options(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> serde_json::Result<Patch> {
+    if options.is_direct() {
+        options.check(serde_json::from_slice(bytes)?)
+    } else {
+        from_value_with_options(serde_json::from_slice(bytes)?, options)
+    }
+}
+
+/// Deserialize a patch from a JSON value, using provided options.
+pub fn from_value_with_options(
+    value: Value,
+    options: &DeserializeOptions,
+) -> serde_json::Result<Patch> {
+    if options.is_direct() {
+        options.check(serde_json::from_value(value)?)
+    } else {
+        options.annotated(value).map(Patch::from)
+    }
+}
+
+/// Deserialize an annotated patch from JSON text, using provided options. The unknown members of
+/// the operations are only kept with [`UnknownFields::Preserve`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{annotated_from_str_with_options, DeserializeOptions, UnknownFields};
+///
+/// let options = DeserializeOptions {
+///     unknown_fields: UnknownFields::Preserve,
+///     aliases: true,
+///     ..Default::default()
+/// };
+///
+/// let p = annotated_from_str_with_options(
+///     r#"[{ "op": "Delete", "path": "/a", "ts": 1 }]"#,
+///     &options,
+/// ).unwrap();
+/// assert_eq!(
+///     serde_json::to_string(&p).unwrap(),
+///     r#"[{"op":"remove","path":"/a","ts":1}]"#
+/// );
+/// ```
+pub fn annotated_from_str_with_options(
+    s: &str,
+    options: &DeserializeOptions,
+) -> serde_json::Result<AnnotatedPatch> {
+    options.annotated(serde_json::from_str(s)?)
+}
+
+/// Deserialize an annotated patch from JSON text given as bytes, using provided options.
+pub fn annotated_from_slice_with_options(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> serde_json::Result<AnnotatedPatch> {
+    options.annotated(serde_json::from_slice(bytes)?)
+}
+
+/// Deserialize an annotated patch from a JSON value, using provided options.
+pub fn annotated_from_value_with_options(
+    value: Value,
+    options: &DeserializeOptions,
+) -> serde_json::Result<AnnotatedPatch> {
+    options.annotated(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn strict() {
+        let strict = DeserializeOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let p = json!([
+            { "op": "add", "path": "/a", "value": 1 },
+            { "op": "copy", "from": "a", "path": "/b" },
+        ]);
+        assert!(from_value_with_options(p.clone(), &Default::default()).is_ok());
+        let err = from_value_with_options(p.clone(), &strict).unwrap_err();
+        assert_eq!(err.to_string(), "operation '/1': invalid from 'a'");
+        let preserve = DeserializeOptions {
+            unknown_fields: UnknownFields::Preserve,
+            ..strict
+        };
+        let err = annotated_from_value_with_options(p, &preserve).unwrap_err();
+        assert_eq!(err.to_string(), "operation '/1': invalid from 'a'");
+
+        let p = json!([{ "op": "test", "path": "/a~", "value": 1 }]);
+        assert!(from_value_with_options(p, &strict).is_err());
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let value = json!([
+            { "op": "remove", "path": "/a", "value": 1 },
+            { "op": "move", "from": "/b", "path": "/c", "meta": { "by": "x" } },
+            { "op": "test", "path": "/c", "value": null },
+        ]);
+        let options = |unknown_fields| DeserializeOptions {
+            unknown_fields,
+            ..Default::default()
+        };
+
+        let p = from_value_with_options(value.clone(), &options(UnknownFields::Preserve)).unwrap();
+        assert_eq!(p, serde_json::from_value::<Patch>(value.clone()).unwrap());
+
+        let p = annotated_from_value_with_options(value.clone(), &options(UnknownFields::Preserve))
+            .unwrap();
+        assert_eq!(
+            p.0[0].extra,
+            json!({ "value": 1 }).as_object().cloned().unwrap()
+        );
+        assert!(p.0[2].extra.is_empty());
+        assert_eq!(serde_json::to_value(&p).unwrap(), value);
+        assert_eq!(
+            serde_json::from_value::<AnnotatedPatch>(value.clone()).unwrap(),
+            p
+        );
+
+        let p = annotated_from_value_with_options(value.clone(), &options(UnknownFields::Ignore))
+            .unwrap();
+        assert!(p.0.iter().all(|op| op.extra.is_empty()));
+
+        let err = from_value_with_options(value.clone(), &options(UnknownFields::Reject));
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "operation '/0': unknown field(s) `value` of `remove` operation"
+        );
+        let err =
+            from_value_with_options(json!([{ "op": "remove" }]), &options(UnknownFields::Reject));
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .starts_with("operation '/0': missing field `path`"));
+    }
+
+    #[test]
+    fn aliases() {
+        let value = json!([
+            { "op": "ADD", "path": "/a", "value": 1 },
+            { "op": "Delete", "path": "/a" },
+            { "op": "replace", "path": "", "value": {} },
+        ]);
+        assert!(from_value_with_options(value.clone(), &Default::default()).is_err());
+
+        let options = DeserializeOptions {
+            aliases: true,
+            ..Default::default()
+        };
+        let p = from_value_with_options(value, &options).unwrap();
+        assert_eq!(
+            p,
+            Patch(vec![
+                PatchOperation::add("/a", json!(1)),
+                PatchOperation::remove("/a"),
+                PatchOperation::replace("", json!({})),
+            ])
+        );
+        let err = from_value_with_options(json!([{ "op": "erase", "path": "/a" }]), &options);
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("unknown variant `erase`"));
+    }
+
+    #[test]
+    fn meta() {
+        let add = |path: &str| {
+            PatchOperation::Add(crate::AddOperation {
+                path: path.into(),
+                value: json!(1),
+            })
+        };
+        let mut p = AnnotatedPatch(vec![
+            AnnotatedOperation::with_meta(add("/a"), json!({ "actor": "ann" })),
+            add("/b/c").into(),
+        ]);
+        assert_eq!(p.0[1].meta(), None);
+        p.0[0].meta_mut().unwrap()["reason"] = json!("init");
+
+        let value = serde_json::to_value(&p).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                { "op": "add", "path": "/a", "value": 1, "x-meta": { "actor": "ann", "reason": "init" } },
+                { "op": "add", "path": "/b/c", "value": 1 },
+            ])
+        );
+        assert_eq!(
+            serde_json::from_value::<AnnotatedPatch>(value.clone()).unwrap(),
+            p
+        );
+        let plain: Patch = serde_json::from_value(value).unwrap();
+        assert_eq!(plain, Patch::from(p.clone()));
+
+        // The document is left unchanged if any operation fails
+        let mut doc = json!({});
+        let err = patch_annotated(&mut doc, &p).unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert_eq!(doc, json!({}));
+
+        p.0.pop();
+        patch_annotated(&mut doc, &p).unwrap();
+        assert_eq!(doc, json!({ "a": 1 }));
+    }
+}
@@ -0,0 +1,162 @@
+//! Stack-safe counterparts of the `Clone`, `Drop` and `PartialEq` implementations of
+//! `serde_json::Value`, which recurse into nested values and can overflow the stack on deeply
+//! nested documents.
+use crate::PatchOperation;
+use alloc::{string::String, vec, vec::Vec};
+use serde_json::{map, Map, Number, Value};
+
+/// Object or array being cloned, with the iterator over its remaining children.
+enum Frame<'a> {
+    Array(core::slice::Iter<'a, Value>, Vec<Value>),
+    Object(map::Iter<'a>, Map<String, Value>, String),
+}
+
+/// Clone the value without recursion.
+pub(crate) fn clone_value(value: &Value) -> Value {
+    let mut stack = Vec::new();
+    let mut current = value;
+    loop {
+        let mut cloned = match current {
+            Value::Array(arr) => {
+                stack.push(Frame::Array(arr.iter(), Vec::with_capacity(arr.len())));
+                None
+            }
+            Value::Object(obj) => {
+                stack.push(Frame::Object(obj.iter(), Map::new(), String::new()));
+                None
+            }
+            scalar => Some(scalar.clone()),
+        };
+        // Put the cloned value into its parent and find the next child to clone, closing the
+        // objects and arrays with all their children cloned.
+        loop {
+            let Some(frame) = stack.last_mut() else {
+                return cloned.unwrap();
+            };
+            match frame {
+                Frame::Array(iter, items) => {
+                    items.extend(cloned.take());
+                    if let Some(next) = iter.next() {
+                        current = next;
+                        break;
+                    }
+                }
+                Frame::Object(iter, obj, key) => {
+                    if let Some(value) = cloned.take() {
+                        obj.insert(core::mem::take(key), value);
+                    }
+                    if let Some((next_key, next)) = iter.next() {
+                        key.clone_from(next_key);
+                        current = next;
+                        break;
+                    }
+                }
+            }
+            cloned = match stack.pop() {
+                Some(Frame::Array(_, items)) => Some(Value::Array(items)),
+                Some(Frame::Object(_, obj, _)) => Some(Value::Object(obj)),
+                None => unreachable!(),
+            };
+        }
+    }
+}
+
+/// Drop the value without recursion.
+pub(crate) fn drop_value(value: Value) {
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Array(arr) => stack.extend(arr),
+            Value::Object(obj) => stack.extend(obj.into_iter().map(|(_, value)| value)),
+            _ => {}
+        }
+    }
+}
+
+/// Drop the values of the operations without recursion.
+pub(crate) fn drop_operations<T: Into<PatchOperation>>(ops: Vec<T>) {
+    for op in ops {
+        match op.into() {
+            PatchOperation::Add(op) => drop_value(op.value),
+            PatchOperation::Replace(op) => drop_value(op.value),
+            PatchOperation::Test(op) => drop_value(op.value),
+            _ => {}
+        }
+    }
+}
+
+/// Compare values without recursion, using the given function to compare numbers.
+pub(crate) fn values_equal_by<F>(left: &Value, right: &Value, mut numbers_equal: F) -> bool
+where
+    F: FnMut(&Number, &Number) -> bool,
+{
+    let mut pairs = vec![(left, right)];
+    while let Some(pair) = pairs.pop() {
+        let equal = match pair {
+            (Value::Number(l), Value::Number(r)) => numbers_equal(l, r),
+            (Value::Array(l), Value::Array(r)) => {
+                pairs.extend(l.iter().zip(r));
+                l.len() == r.len()
+            }
+            (Value::Object(l), Value::Object(r)) => {
+                l.len() == r.len()
+                    && l.iter().all(|(key, l)| match r.get(key) {
+                        Some(r) => {
+                            pairs.push((l, r));
+                            true
+                        }
+                        None => false,
+                    })
+            }
+            // Scalars, or values of different types
+            (left, right) => left == right,
+        };
+        if !equal {
+            return false;
+        }
+    }
+    true
+}
+
+/// Compare values without recursion.
+pub(crate) fn values_equal(left: &Value, right: &Value) -> bool {
+    values_equal_by(left, right, |l, r| l == r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clone_value, drop_value, values_equal};
+    use serde_json::{json, Value};
+
+    fn nested(depth: usize, leaf: Value) -> Value {
+        (0..depth).fold(leaf, |value, idx| {
+            if idx % 2 == 0 {
+                Value::Array(vec![json!(idx), value])
+            } else {
+                Value::Object([("a".to_owned(), value)].into_iter().collect())
+            }
+        })
+    }
+
+    #[test]
+    fn deep_values() {
+        let value = json!({ "a": [1, { "b": null, "c": "d" }, []], "e": {}, "f": 1.5 });
+        assert_eq!(clone_value(&value), value);
+        assert!(values_equal(&value, &value.clone()));
+        assert!(!values_equal(
+            &value,
+            &json!({ "a": [1, {}, []], "e": {}, "f": 1.5 })
+        ));
+        assert!(!values_equal(&json!([1, 2]), &json!([1])));
+        assert!(!values_equal(&json!({ "a": 1 }), &json!({ "b": 1 })));
+
+        let left = nested(100_000, json!(1));
+        let right = clone_value(&left);
+        assert!(values_equal(&left, &right));
+        let other = nested(100_000, json!(2));
+        assert!(!values_equal(&left, &other));
+        drop_value(left);
+        drop_value(right);
+        drop_value(other);
+    }
+}
@@ -0,0 +1,389 @@
+//! Conversion between JSON Patch and the "delta" format of the [jsondiffpatch] JavaScript library.
+//!
+//! [jsondiffpatch]: https://github.com/benjamine/jsondiffpatch
+use crate::{append_path, patched, Patch, PatchError, PatchOperation};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// Magic number of a text diff in a delta.
+const TEXT_DIFF: u64 = 2;
+/// Magic number of an array element move in a delta.
+const ARRAY_MOVE: u64 = 3;
+
+/// Error of converting a jsondiffpatch delta into a patch, see [`from_delta`].
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeltaError {
+    /// Delta is not valid.
+    #[error("invalid delta at path '{0}'")]
+    InvalidDelta(String),
+    /// Delta contains a text diff, which cannot be represented by patch operations.
+    #[error("text diff at path '{0}' is not supported")]
+    TextDiff(String),
+    /// Delta does not match the document, e.g. it changes members of an object which is an array
+    /// in the document or moves an element which does not exist.
+    #[error("delta does not match the document at path '{0}'")]
+    Mismatch(String),
+}
+
+/// Convert the patch into a jsondiffpatch delta between the document and the result of applying
+/// the patch to it. Deltas contain the previous values of the changed locations, so the document
+/// is required. `Value::Null` is returned if the patch does not change the document.
+///
+/// Array elements are compared by their positions, so the delta never contains moves of
+/// elements.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{to_delta, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "title": "Draft", "tags": ["a"], "author": "Ann" });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/title", "value": "Final" },
+///   { "op": "add", "path": "/tags/-", "value": "b" },
+///   { "op": "remove", "path": "/author" },
+/// ])).unwrap();
+///
+/// assert_eq!(to_delta(&doc, &p).unwrap(), json!({
+///   "title": ["Draft", "Final"],
+///   "tags": { "_t": "a", "1": ["b"] },
+///   "author": ["Ann", 0, 0],
+/// }));
+/// ```
+pub fn to_delta(doc: &Value, patch: &[PatchOperation]) -> Result<Value, PatchError> {
+    let right = patched(doc, patch)?;
+    Ok(delta(doc, &right).unwrap_or(Value::Null))
+}
+
+fn delta(left: &Value, right: &Value) -> Option<Value> {
+    match (left, right) {
+        (left, right) if left == right => None,
+        (Value::Object(left), Value::Object(right)) => {
+            let mut delta = Map::new();
+            for (key, left) in left {
+                let value = match right.get(key) {
+                    Some(right) => self::delta(left, right),
+                    None => Some(removed(left)),
+                };
+                if let Some(value) = value {
+                    delta.insert(key.clone(), value);
+                }
+            }
+            for (key, right) in right {
+                if !left.contains_key(key) {
+                    delta.insert(key.clone(), Value::Array(vec![right.clone()]));
+                }
+            }
+            Some(Value::Object(delta))
+        }
+        (Value::Array(left), Value::Array(right)) => {
+            let mut delta = Map::new();
+            delta.insert("_t".to_string(), Value::from("a"));
+            for (idx, left) in left.iter().enumerate() {
+                match right.get(idx) {
+                    Some(right) => {
+                        if let Some(value) = self::delta(left, right) {
+                            delta.insert(idx.to_string(), value);
+                        }
+                    }
+                    None => {
+                        delta.insert(format!("_{}", idx), removed(left));
+                    }
+                }
+            }
+            for (idx, right) in right.iter().enumerate().skip(left.len()) {
+                delta.insert(idx.to_string(), Value::Array(vec![right.clone()]));
+            }
+            Some(Value::Object(delta))
+        }
+        (left, right) => Some(Value::Array(vec![left.clone(), right.clone()])),
+    }
+}
+
+fn removed(value: &Value) -> Value {
+    Value::Array(vec![value.clone(), Value::from(0), Value::from(0)])
+}
+
+/// Convert the jsondiffpatch delta into a patch, which transforms the document the delta was
+/// created for the same way as the delta does. The document is required to resolve array element
+/// moves, which do not contain the moved values; `Value::Null` delta produces an empty patch.
+///
+/// Text diffs (deltas created with the `textDiff` option of jsondiffpatch) are not supported.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{from_delta, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "items": ["x", "y", "z"], "done": false });
+/// let delta = json!({
+///   "items": { "_t": "a", "_0": ["", 1, 3], "_1": ["y", 0, 0] },
+///   "done": [false, true],
+/// });
+///
+/// let p = from_delta(&doc, &delta).unwrap();
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "remove", "path": "/items/1" },
+///   { "op": "remove", "path": "/items/0" },
+///   { "op": "add", "path": "/items/1", "value": "x" },
+///   { "op": "replace", "path": "/done", "value": true },
+/// ])).unwrap());
+///
+/// let mut doc = doc;
+/// json_patch::patch(&mut doc, &p).unwrap();
+/// assert_eq!(doc, json!({ "items": ["z", "x"], "done": true }));
+/// ```
+pub fn from_delta(doc: &Value, delta: &Value) -> Result<Patch, DeltaError> {
+    let mut converter = Converter {
+        path: String::new(),
+        ops: Vec::new(),
+    };
+    if !delta.is_null() {
+        converter.convert(Some(doc), delta)?;
+    }
+    Ok(Patch(converter.ops))
+}
+
+struct Converter {
+    path: String,
+    ops: Vec<PatchOperation>,
+}
+
+impl Converter {
+    fn convert(&mut self, left: Option<&Value>, delta: &Value) -> Result<(), DeltaError> {
+        match delta {
+            Value::Array(delta) => {
+                let op = match delta.as_slice() {
+                    [value] => PatchOperation::add(self.path.clone(), value.clone()),
+                    [_, value] => PatchOperation::replace(self.path.clone(), value.clone()),
+                    [_, zero, magic] if zero == 0 && magic == 0 => {
+                        PatchOperation::remove(self.path.clone())
+                    }
+                    [_, _, magic] if magic == TEXT_DIFF => {
+                        return Err(DeltaError::TextDiff(self.path.clone()))
+                    }
+                    _ => return Err(self.invalid()),
+                };
+                self.ops.push(op);
+                Ok(())
+            }
+            Value::Object(delta) if delta.get("_t").is_some_and(|t| t == "a") => match left {
+                Some(Value::Array(left)) => self.convert_array(left, delta),
+                _ => Err(self.mismatch()),
+            },
+            Value::Object(delta) => {
+                let left = match left {
+                    Some(Value::Object(left)) => left,
+                    _ => return Err(self.mismatch()),
+                };
+                for (key, delta) in delta {
+                    let len = self.path.len();
+                    self.path.push('/');
+                    append_path(&mut self.path, key);
+                    self.convert(left.get(key), delta)?;
+                    self.path.truncate(len);
+                }
+                Ok(())
+            }
+            _ => Err(self.invalid()),
+        }
+    }
+
+    /// Converts the array delta, applying its changes in the same order as jsondiffpatch does:
+    /// removals and moves by their indices in the original array, then insertions and
+    /// modifications by their indices in the resulting array.
+    fn convert_array(
+        &mut self,
+        left: &[Value],
+        delta: &Map<String, Value>,
+    ) -> Result<(), DeltaError> {
+        let mut removals = Vec::new();
+        let mut insertions = Vec::new();
+        let mut modifications = Vec::new();
+        for (key, delta) in delta {
+            if key == "_t" {
+                continue;
+            }
+            let (removal, index) = match key.strip_prefix('_') {
+                Some(index) => (true, index),
+                None => (false, key.as_str()),
+            };
+            let index = index.parse::<usize>().map_err(|_| self.invalid())?;
+            match (removal, delta.as_array().map(Vec::as_slice)) {
+                (true, Some([_, zero, magic])) if zero == 0 && magic == 0 => {
+                    removals.push((index, None));
+                }
+                (true, Some([_, to, magic])) if magic == ARRAY_MOVE => {
+                    let to = to.as_u64().ok_or_else(|| self.invalid())?;
+                    removals.push((index, Some(to as usize)));
+                }
+                (true, _) => return Err(self.invalid()),
+                (false, Some([value])) => insertions.push((index, Some(value), None)),
+                (false, _) => modifications.push((index, delta)),
+            }
+        }
+
+        // Elements of the array after each step, `None` for the inserted ones
+        let mut elements: Vec<Option<&Value>> = left.iter().map(Some).collect();
+        removals.sort_unstable_by_key(|(index, _)| *index);
+        for &(index, to) in removals.iter().rev() {
+            if index >= elements.len() {
+                return Err(self.mismatch());
+            }
+            let element = elements.remove(index);
+            self.ops.push(PatchOperation::remove(self.element(index)));
+            if let Some(to) = to {
+                insertions.push((to, None, element));
+            }
+        }
+
+        insertions.sort_by_key(|(index, _, _)| *index);
+        for (index, value, moved) in insertions {
+            if index > elements.len() {
+                return Err(self.mismatch());
+            }
+            let value = value.or(moved).cloned().unwrap_or_default();
+            self.ops
+                .push(PatchOperation::add(self.element(index), value));
+            elements.insert(index, moved);
+        }
+
+        modifications.sort_unstable_by_key(|(index, _)| *index);
+        for (index, delta) in modifications {
+            let left = match elements.get(index) {
+                Some(left) => *left,
+                None => return Err(self.mismatch()),
+            };
+            let len = self.path.len();
+            self.path = self.element(index);
+            self.convert(left, delta)?;
+            self.path.truncate(len);
+        }
+        Ok(())
+    }
+
+    fn element(&self, index: usize) -> String {
+        format!("{}/{}", self.path, index)
+    }
+
+    fn invalid(&self) -> DeltaError {
+        DeltaError::InvalidDelta(self.path.clone())
+    }
+
+    fn mismatch(&self) -> DeltaError {
+        DeltaError::Mismatch(self.path.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_delta, to_delta, DeltaError};
+    use crate::{patch, Patch, PatchOperation};
+    use serde_json::{from_value, json, Value};
+
+    fn round_trip(left: Value, right: Value) {
+        let p = [PatchOperation::replace("", right.clone())];
+        let delta = to_delta(&left, &p).unwrap();
+        let p = from_delta(&left, &delta).unwrap();
+        let mut doc = left.clone();
+        patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+    }
+
+    #[test]
+    fn conversion() {
+        round_trip(json!({}), json!({}));
+        round_trip(json!(1), json!([1]));
+        round_trip(
+            json!({ "a": [1, 2, { "b": 3 }], "c~/": { "d": null }, "e": 1 }),
+            json!({ "a": [1, { "b": 4 }], "c~/": { "d": false }, "f": [] }),
+        );
+        round_trip(
+            json!([[1, 2], { "a": 1 }, 3]),
+            json!([[1, 2, 3, 4], { "a": 1, "b": 2 }, 3, 4, 5]),
+        );
+        assert_eq!(
+            to_delta(&json!({}), &Patch::default()).unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            from_delta(&json!({}), &Value::Null).unwrap(),
+            Patch::default()
+        );
+    }
+
+    #[test]
+    fn jsondiffpatch() {
+        // Delta created by jsondiffpatch, with moves, modifications of moved elements and
+        // insertions
+        let left = json!({ "list": [{ "id": 1 }, { "id": 2 }, { "id": 3, "v": 0 }, 4] });
+        let right = json!({ "list": [{ "id": 3, "v": 1 }, 5, { "id": 1 }, { "id": 2 }] });
+        let delta = json!({
+            "list": {
+                "_t": "a",
+                "_2": ["", 0, 3],
+                "_3": [4, 0, 0],
+                "0": { "v": [0, 1] },
+                "1": [5],
+            }
+        });
+        let p = from_delta(&left, &delta).unwrap();
+        let mut doc = left;
+        patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+    }
+
+    #[test]
+    fn invalid() {
+        let doc = json!({ "a": "text", "b": [1] });
+        let cases = [
+            (
+                json!({ "a": ["@@ -1 +1 @@", 0, 2] }),
+                DeltaError::TextDiff("/a".into()),
+            ),
+            (
+                json!({ "a": [1, 2, 3, 4] }),
+                DeltaError::InvalidDelta("/a".into()),
+            ),
+            (json!({ "a": 1 }), DeltaError::InvalidDelta("/a".into())),
+            (
+                json!({ "b": { "_t": "a", "x": [1] } }),
+                DeltaError::InvalidDelta("/b".into()),
+            ),
+            (
+                json!({ "b": { "_t": "a", "_0": [1] } }),
+                DeltaError::InvalidDelta("/b".into()),
+            ),
+            (
+                json!({ "b": { "_t": "a", "_1": [1, 0, 0] } }),
+                DeltaError::Mismatch("/b".into()),
+            ),
+            (
+                json!({ "b": { "_t": "a", "5": [1] } }),
+                DeltaError::Mismatch("/b".into()),
+            ),
+            (
+                json!({ "a": { "_t": "a" } }),
+                DeltaError::Mismatch("/a".into()),
+            ),
+            (
+                json!({ "c": { "d": [1] } }),
+                DeltaError::Mismatch("/c".into()),
+            ),
+        ];
+        for (delta, err) in cases {
+            assert_eq!(from_delta(&doc, &delta), Err(err), "{}", delta);
+        }
+        let p: Patch = from_value(json!([{ "op": "remove", "path": "/x" }])).unwrap();
+        assert!(to_delta(&doc, &p).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::normalize::commute;
+use crate::Patch;
+use alloc::{vec, vec::Vec};
+
+impl Patch {
+    /// Returns, for every operation of the patch, the indices of the earlier operations it
+    /// depends on, in ascending order.
+    ///
+    /// An operation depends on an earlier one if applying them in the opposite order could have
+    /// a different effect: one of them reads or modifies a location the other one modifies (such
+    /// as an `add` creating the container a later operation targets, or a `test` of a value
+    /// replaced earlier), or one of them inserts or removes an array element, shifting the
+    /// elements the other one refers to. The analysis only looks at the pointers of the
+    /// operations, so it is conservative: operations reported as dependent may still happen to
+    /// commute on a particular document.
+    ///
+    /// Operations without dependencies between them can be reordered or applied in parallel
+    /// without changing the result (see [`Patch::dependency_levels`]).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "add", "path": "/a", "value": {} },
+    ///   { "op": "add", "path": "/a/x", "value": 1 },
+    ///   { "op": "remove", "path": "/b/0" },
+    ///   { "op": "replace", "path": "/b/1", "value": 2 },
+    ///   { "op": "replace", "path": "/c", "value": 3 }
+    /// ])).unwrap();
+    ///
+    /// assert_eq!(p.dependencies(), [vec![], vec![0], vec![], vec![2], vec![]]);
+    /// ```
+    pub fn dependencies(&self) -> Vec<Vec<usize>> {
+        self.iter()
+            .enumerate()
+            .map(|(idx, op)| {
+                self[..idx]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, prev)| !commute(prev, op))
+                    .map(|(prev, _)| prev)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Groups the operations of the patch into levels, so that operations of the same level do
+    /// not depend on each other (see [`Patch::dependencies`]) and only depend on operations of
+    /// the previous levels. Indices within each level are in ascending order.
+    ///
+    /// Applying the levels one after another, with the operations of each level applied in any
+    /// order (or in parallel, on disjoint parts of the document), has the same effect as applying
+    /// the patch.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "add", "path": "/a", "value": {} },
+    ///   { "op": "add", "path": "/a/x", "value": 1 },
+    ///   { "op": "remove", "path": "/b/0" },
+    ///   { "op": "replace", "path": "/b/1", "value": 2 },
+    ///   { "op": "replace", "path": "/c", "value": 3 }
+    /// ])).unwrap();
+    ///
+    /// assert_eq!(p.dependency_levels(), [vec![0, 2, 4], vec![1, 3]]);
+    /// ```
+    pub fn dependency_levels(&self) -> Vec<Vec<usize>> {
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+        let mut op_levels = vec![0; self.len()];
+        for (idx, deps) in self.dependencies().into_iter().enumerate() {
+            let level = deps
+                .iter()
+                .map(|&dep| op_levels[dep] + 1)
+                .max()
+                .unwrap_or(0);
+            op_levels[idx] = level;
+            if level == levels.len() {
+                levels.push(Vec::new());
+            }
+            levels[level].push(idx);
+        }
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Patch;
+    use alloc::vec::Vec;
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn dependencies() {
+        let p: Patch = from_value(json!([
+            { "op": "test", "path": "/a", "value": { "x": [1, 2, 3] } },
+            { "op": "remove", "path": "/a/x/0" },
+            { "op": "test", "path": "/a/x/1", "value": 3 },
+            { "op": "add", "path": "/a/x/-", "value": 4 },
+            { "op": "copy", "from": "/a/x/0", "path": "/b" },
+            { "op": "move", "from": "/c", "path": "/d" },
+            { "op": "test", "path": "/d", "value": 1 },
+            { "op": "replace", "path": "/e", "value": 1 },
+            { "op": "add", "path": "/ab", "value": 1 },
+        ]))
+        .unwrap();
+        assert_eq!(
+            p.dependencies(),
+            [
+                vec![],
+                vec![0],
+                vec![0, 1],
+                vec![0, 1, 2],
+                vec![0, 1, 3],
+                vec![],
+                vec![5],
+                vec![],
+                vec![],
+            ]
+        );
+        let levels = p.dependency_levels();
+        assert_eq!(
+            levels,
+            [vec![0, 5, 7, 8], vec![1, 6], vec![2], vec![3], vec![4]]
+        );
+
+        // Applying the levels in reverse order within each level gives the same result
+        let doc = json!({ "a": { "x": [1, 2, 3] }, "c": 1, "e": 0 });
+        let reordered: Patch = levels
+            .iter()
+            .flat_map(|level| level.iter().rev().map(|&idx| p[idx].clone()))
+            .collect();
+        let expected = crate::patched(&doc, &p).unwrap();
+        assert_eq!(crate::patched(&doc, &reordered).unwrap(), expected);
+
+        assert_eq!(Patch::default().dependencies(), Vec::<Vec<usize>>::new());
+        assert!(Patch::default().dependency_levels().is_empty());
+    }
+}
@@ -1,230 +1,3769 @@
-use serde_json::Value;
+use crate::align::{align, increasing_run, Edit};
+use crate::deep::values_equal_by;
+use crate::estimate::serialized_len;
+use crate::generic::JsonLike;
+use crate::glob;
+use crate::hash::{HashedValue, SubtreeHashes};
+use crate::options::NumberTolerance;
+use crate::trace;
+use crate::{append_path, is_prefix, resolve, Pointer};
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::{self, Display, Formatter};
+use hashbrown::{HashMap, HashSet};
+use serde::Serialize;
+use serde_json::{Number, Value};
+use thiserror::Error;
 
-struct PatchDiffer {
+/// Summary of the changes made under a single top-level key (or array index) of the document.
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct KeySummary {
+    /// Number of values added under this key.
+    pub added: usize,
+    /// Number of values removed under this key.
+    pub removed: usize,
+    /// Number of values replaced under this key.
+    pub modified: usize,
+    /// First change observed under this key, as a representative example.
+    pub example: Option<ChangeExample>,
+}
+
+/// Single change included into the [`DiffSummary`] as a representative example.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ChangeExample {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) of the changed location.
+    pub path: String,
+    /// Value before the change, if there was one.
+    pub old: Option<Value>,
+    /// Value after the change, if there is one.
+    pub new: Option<Value>,
+}
+
+/// Human-oriented summary of the differences between two JSON documents, grouped by the
+/// top-level key (or array index). Changes replacing the whole document are grouped under an
+/// empty key.
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    /// Summaries of the changes, by top-level key.
+    pub keys: BTreeMap<String, KeySummary>,
+}
+
+impl Display for DiffSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (key, summary) in &self.keys {
+            write!(f, "/{}:", key)?;
+            let counts = [
+                (summary.added, "added"),
+                (summary.removed, "removed"),
+                (summary.modified, "modified"),
+            ];
+            let mut first = true;
+            for (count, label) in counts.into_iter().filter(|(count, _)| *count > 0) {
+                write!(f, "{} {} {}", if first { "" } else { "," }, count, label)?;
+                first = false;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options controlling how [`diff_with_options`] generates patches. Default options produce the
+/// same patches as [`diff`].
+///
+/// New options might be added in the future, so construct options with `..Default::default()`.
+#[derive(Debug, Clone, PartialEq)]
+// Options with the `element_matcher` are equal if it is the same function, as far as its address
+// tells
+#[allow(unpredictable_function_pointer_comparisons)]
+pub struct DiffOptions {
+    /// Emit a single `move` operation when a value is removed from one object member and an equal
+    /// value is added at another location, instead of a `remove` and an `add` pair.
+    pub detect_moves: bool,
+    /// Emit a `copy` operation when an added object or array is equal to a subtree which is left
+    /// unchanged by the patch, instead of embedding the whole value into an `add` operation.
+    pub detect_copies: bool,
+    /// Arrays of objects to match by an identity field rather than by position, as a map from the
+    /// JSON Pointer of the array to the name of the identity field. A pointer also applies to any
+    /// arrays nested under it, the longest matching pointer wins (use `""` to match all arrays).
+    ///
+    /// Elements of keyed arrays are matched by the value of the identity field, so reordering or
+    /// updating an element produces `move` operations and operations targeting that element only.
+    /// If some of the elements are not objects or have missing or duplicate identity values, the
+    /// array is diffed by position.
+    pub array_keys: BTreeMap<String, String>,
+    /// JSON Pointers of arrays to be treated as unordered collections (multisets), which also
+    /// apply to any arrays nested under them. Elements are matched by equality regardless of their
+    /// positions: unmatched elements of the left array are removed and unmatched elements of the
+    /// right array are appended to the end, so differences in the order only produce no operations.
+    /// Patched arrays are therefore equal to the right arrays only up to the order of elements.
+    ///
+    /// Arrays matching the [`DiffOptions::array_keys`] are matched by identity field instead.
+    pub unordered_arrays: BTreeSet<String>,
+    /// JSON Pointers of locations to skip entirely: no operations are emitted for them, nor for
+    /// anything nested under them. Pointers are [`PointerGlob`](crate::PointerGlob)s, like
+    /// `/items/*/updatedAt` or `/**/etag`.
+    ///
+    /// Like all the other pointers in the options, these refer to locations in the right document
+    /// (or in the left document, for removed values), which might be different from the paths in
+    /// the generated patch due to shifting of array indices.
+    pub ignore_paths: Vec<String>,
+    /// Maximum depth the differ descends to. Values at this depth (the whole document is at the
+    /// depth of zero, its members are at the depth of one, and so on) which differ are replaced
+    /// with a single `replace` operation instead of being compared member by member.
+    pub max_depth: Option<usize>,
+    /// Tolerance for comparing numbers: numbers which differ by no more than the tolerance are
+    /// treated as unchanged. By default, numbers are compared exactly.
+    pub number_tolerance: Option<NumberTolerance>,
+    /// How numbers are compared before the [`DiffOptions::number_tolerance`] is applied, see
+    /// [`NumberEquality`].
+    pub number_equality: NumberEquality,
+    /// Precede every `remove` and `replace` operation with a `test` operation asserting the value
+    /// being removed or replaced, so the patch fails instead of overwriting changes if the target
+    /// document differs from the left document.
+    pub test_before_destructive: bool,
+    /// Use the `-` end-of-array reference token in `add` operations appending elements to the end
+    /// of arrays (like in `/log/-`) instead of absolute indices, so that the patch stays valid for
+    /// arrays which have grown independently.
+    pub append_to_end: bool,
+    /// Maximum depth the differ accepts (counted as for [`DiffOptions::max_depth`]), as a guard
+    /// against absurdly nested inputs: diffing fails with [`DiffError::TooDeep`] if it has to go
+    /// deeper. Has no effect beyond the `max_depth`, as the differ never descends past it.
+    pub depth_limit: Option<usize>,
+    /// Order of the emitted operations, see [`OpOrder`].
+    pub op_order: OpOrder,
+    /// Fraction of the children of an object or an array (between `0.0` and `1.0`) above which
+    /// the whole object or array is replaced with a single `replace` operation, instead of being
+    /// patched child by child. Added, removed and changed children count as changed. For example,
+    /// with `0.5`, an object with more than half of its members changed is replaced.
+    ///
+    /// Arrays matched by [`DiffOptions::array_keys`] or [`DiffOptions::unordered_arrays`] are
+    /// never replaced, nor are objects and arrays containing [`DiffOptions::ignore_paths`].
+    pub coalesce_threshold: Option<f32>,
+    /// How array elements replaced as a whole are patched, see [`ElementReplacement`].
+    pub element_replacement: ElementReplacement,
+    /// Maximum number of operations of the generated patch. A patch with more operations is
+    /// replaced with a single `replace` operation of the whole document, or of the value diffed
+    /// by [`diff_at_with_options`] (preceded by a `test` operation, with
+    /// [`DiffOptions::test_before_destructive`]), which adds or removes the value as a whole if it
+    /// is missing in one of the documents. That replaces the [`DiffOptions::ignore_paths`] too.
+    pub max_operations: Option<usize>,
+    /// Maximum length in bytes of the compact JSON serialization of the generated patch. A larger
+    /// patch is replaced with a single `replace` operation, as for [`DiffOptions::max_operations`].
+    pub max_patch_bytes: Option<usize>,
+    /// Emit `move` operations for arrays whose elements were only reordered, like after a
+    /// drag-and-drop, instead of replacing every displaced element. Elements are matched by
+    /// equality, and the elements of the longest run keeping its relative order stay in place.
+    ///
+    /// Only applies to arrays with the same elements, which are not matched by
+    /// [`DiffOptions::array_keys`] or [`DiffOptions::unordered_arrays`] and contain no
+    /// [`DiffOptions::ignore_paths`].
+    pub detect_reorders: bool,
+    /// How the elements of arrays are matched, see [`ArrayDiffAlgorithm`]. Arrays matched by
+    /// [`DiffOptions::array_keys`], [`DiffOptions::unordered_arrays`] or
+    /// [`DiffOptions::detect_reorders`] are diffed as these options say.
+    pub array_diff: ArrayDiffAlgorithm,
+    /// Function pairing up the elements of arrays, which tells how well a left element matches a
+    /// right one (see [`MatchQuality`]), like invoice lines matching by their SKU even when their
+    /// amounts differ. Paired elements are moved into place and diffed, as for
+    /// [`DiffOptions::array_keys`], other elements are removed or added. The function is called
+    /// for every pair of a left and a right element, so diffing takes quadratic time.
+    ///
+    /// Arrays without any matching elements are diffed as if there were no matcher, and arrays
+    /// matching the [`DiffOptions::array_keys`] are matched by identity field instead.
+    pub element_matcher: Option<fn(&Value, &Value) -> MatchQuality>,
+    /// Treat object members set to `null` as missing, for documents written by serializers which
+    /// disagree on emitting `null` members: no operations are emitted for members going from
+    /// `null` to absent or the other way around. Array elements are not affected.
+    pub null_equals_missing: bool,
+    /// With [`DiffOptions::null_equals_missing`], emit `remove` operations for object members
+    /// set to `null`, instead of `replace` operations with a `null` value.
+    pub remove_nulls: bool,
+    /// Emit the `remove` operations of consecutive array elements from the last element to the
+    /// first one, at their original indices (like `/items/2`, then `/items/1`), instead of from
+    /// the first element to the last one, at the indices shifted by the preceding removals (like
+    /// `/items/1` twice). Both forms have the same outcome, this one is for patch appliers which
+    /// do not handle the shifted indices correctly.
+    pub descending_removals: bool,
+}
+
+impl DiffOptions {
+    /// Default options, usable in constant expressions.
+    const fn defaults() -> DiffOptions {
+        DiffOptions {
+            detect_moves: false,
+            detect_copies: false,
+            array_keys: BTreeMap::new(),
+            unordered_arrays: BTreeSet::new(),
+            ignore_paths: Vec::new(),
+            max_depth: None,
+            number_tolerance: None,
+            number_equality: NumberEquality::Exact,
+            test_before_destructive: false,
+            append_to_end: false,
+            depth_limit: None,
+            op_order: OpOrder::Document,
+            coalesce_threshold: None,
+            element_replacement: ElementReplacement::Replace,
+            max_operations: None,
+            max_patch_bytes: None,
+            detect_reorders: false,
+            array_diff: ArrayDiffAlgorithm::Positional,
+            element_matcher: None,
+            null_equals_missing: false,
+            remove_nulls: false,
+            descending_removals: false,
+        }
+    }
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions::defaults()
+    }
+}
+
+/// Order of the operations of generated patches, see [`DiffOptions::op_order`].
+///
+/// Whatever the order, patches only depend on the contents of the documents, not on the order of
+/// their object members. Operations are only reordered where that cannot change the outcome of
+/// the patch: operations on different members of an object are reordered as groups, while the
+/// operations on array elements, and on object members with `move` or `copy` operations between
+/// them, are kept in the document order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OpOrder {
+    /// Order in which the differ walks the documents: for every object or array, operations on
+    /// its changed children come first, followed by the added children and the removed ones, each
+    /// in the order of keys (or indices).
+    #[default]
+    Document,
+    /// Operations on the members of every object ordered by the member names, whether the members
+    /// are changed, added or removed.
+    SortedByPath,
+    /// Same as [`OpOrder::SortedByPath`], but with the removals of members of every object after
+    /// all the other operations on its members.
+    RemovesLast,
+}
+
+/// Algorithm matching the elements of arrays, see [`DiffOptions::array_diff`].
+///
+/// Except for [`ArrayDiffAlgorithm::Positional`], the algorithms align equal elements of both
+/// arrays, so inserting or removing an element in the middle of an array produces a single `add`
+/// or `remove` operation rather than changes of all the subsequent elements. Elements between the
+/// aligned ones are paired by position and diffed, and the remaining ones are removed or added.
+/// Elements are aligned only if they are exactly equal, regardless of the
+/// [`DiffOptions::number_equality`] and the [`DiffOptions::number_tolerance`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ArrayDiffAlgorithm {
+    /// Elements at the same index are diffed, extra elements are appended or removed at the end.
+    /// Fastest, in linear time.
+    #[default]
+    Positional,
+    /// Longest common subsequence of elements, found by the Myers' algorithm, so the fewest
+    /// elements are added and removed. Takes `O((n + m) * d)` time, where `d` is the number of
+    /// elements added and removed, so it gets slow for large arrays which differ a lot.
+    Myers,
+    /// Patience diff, aligning the elements occurring once in both arrays first and the elements
+    /// between them recursively, falling back to the Myers' algorithm where there are no such
+    /// elements. Tends to produce more intuitive patches than the Myers' algorithm for arrays of
+    /// records, and is faster for arrays which differ a lot.
+    Patience,
+    /// Aligns only the elements occurring once in both arrays (and the common prefix and suffix),
+    /// in `O(n log n)` time. Cheapest of the aligning algorithms, but elements between the
+    /// anchors are paired by position, so duplicated elements are not aligned.
+    Anchored,
+    /// Aligns runs of at least four equal elements, found with a rolling hash like rsync does, in
+    /// about linear time. Meant for huge arrays (like telemetry with tens of thousands of
+    /// samples) where the other aligning algorithms are too slow. Runs are taken greedily, so
+    /// shorter runs and runs which moved past others are removed and added rather than aligned.
+    Chunked,
+}
+
+/// How well two array elements match each other, see [`DiffOptions::element_matcher`].
+///
+/// Elements are paired greedily: full matches first, then partial matches with higher scores
+/// first, and among equally good matches, elements closer in position first. Each element is
+/// paired with at most one other element.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchQuality {
+    /// Elements are unrelated, and are never paired.
+    NoMatch,
+    /// Elements are related with a score from `0.0` (barely) to `1.0` (very closely).
+    Partial(f32),
+    /// Elements are the same entity, possibly changed.
+    Full,
+}
+
+/// Operations emitted for array elements replaced as a whole, see
+/// [`DiffOptions::element_replacement`].
+///
+/// Array elements are replaced as a whole when they are scalars which changed, when they change
+/// their type (like from an object to a string), or when [`DiffOptions::max_depth`] or
+/// [`DiffOptions::coalesce_threshold`] stop the differ from comparing them member by member.
+/// Members of objects are always replaced with a `replace` operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ElementReplacement {
+    /// A single `replace` operation, like `{ "op": "replace", "path": "/arr/1", "value": 2 }`.
+    #[default]
+    Replace,
+    /// An `add` operation of the new element followed by a `remove` operation of the old one,
+    /// shifted to the next index by the `add`, for consumers treating replacements differently
+    /// from insertions.
+    RemoveAndAdd,
+}
+
+/// This type represents all possible errors that can occur when diffing JSON documents.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DiffError {
+    /// Documents are nested deeper than the [`DiffOptions::depth_limit`].
+    #[error("documents are nested too deep at path '{path}'")]
+    TooDeep {
+        /// JSON Pointer of the location exceeding the limit.
+        path: String,
+    },
+}
+
+/// Policy for comparing numbers, see [`DiffOptions::number_equality`].
+///
+/// Whatever the policy, array elements are matched by [`DiffOptions::array_keys`],
+/// [`DiffOptions::unordered_arrays`] and [`DiffOptions::detect_moves`] (and the subtrees are
+/// hashed for that) as `PartialEq` compares them, which is the [`NumberEquality::Exact`] policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NumberEquality {
+    /// Numbers are equal if `serde_json` considers them equal. Integers are never equal to
+    /// floating-point numbers, so `1` and `1.0` are different. With the `arbitrary_precision`
+    /// feature of `serde_json`, numbers are equal if they are written the same way.
+    #[default]
+    Exact,
+    /// Numbers are equal if they have the same numeric value, however they are written: `1`,
+    /// `1.0` and `1e0` are all equal. Integers (which fit into `i64` or `u64`) are compared
+    /// exactly, other numbers are compared as `f64`. So numbers which differ beyond the
+    /// precision of `f64` (like large integers or long decimals with the `arbitrary_precision`
+    /// feature of `serde_json`) are equal when they round to the same `f64`.
+    Numeric,
+}
+
+impl NumberEquality {
+    /// Checks if two numbers are equal according to the policy.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::NumberEquality;
+    /// use serde_json::Number;
+    ///
+    /// let one = Number::from(1);
+    /// let float = Number::from_f64(1.0).unwrap();
+    /// assert!(!NumberEquality::Exact.equal(&one, &float));
+    /// assert!(NumberEquality::Numeric.equal(&one, &float));
+    /// assert!(!NumberEquality::Numeric.equal(&Number::from(u64::MAX), &Number::from(-1)));
+    /// ```
+    pub fn equal(&self, left: &Number, right: &Number) -> bool {
+        fn integer(n: &Number) -> Option<i128> {
+            n.as_i64()
+                .map(i128::from)
+                .or_else(|| n.as_u64().map(i128::from))
+        }
+        match self {
+            NumberEquality::Exact => left == right,
+            NumberEquality::Numeric => match (integer(left), integer(right)) {
+                (Some(l), Some(r)) => l == r,
+                _ => match (left.as_f64(), right.as_f64()) {
+                    (Some(l), Some(r)) => l == r,
+                    _ => left == right,
+                },
+            },
+        }
+    }
+}
+
+/// Receives the events of the differ as it walks both documents, see [`diff_with_visitor`]. All
+/// methods do nothing by default.
+///
+/// Paths are the paths of the generated patch operations, so array indices account for the
+/// elements added and removed before.
+pub trait DiffVisitor {
+    /// Called when descending into a child of the current value.
+    fn enter(&mut self, path: &str) {
+        let _ = path;
+    }
+
+    /// Called when returning back from a child to the current value.
+    fn leave(&mut self, path: &str) {
+        let _ = path;
+    }
+
+    /// Called for a value present only in the right document.
+    fn added(&mut self, path: &str, value: &Value) {
+        let _ = (path, value);
+    }
+
+    /// Called for a value present only in the left document.
+    fn removed(&mut self, path: &str, value: &Value) {
+        let _ = (path, value);
+    }
+
+    /// Called for a value replaced in the right document.
+    fn modified(&mut self, path: &str, old: &Value, new: &Value) {
+        let _ = (path, old, new);
+    }
+
+    /// Called for a value equal in both documents. Children of such values are not visited.
+    fn unchanged(&mut self, path: &str, value: &Value) {
+        let _ = (path, value);
+    }
+}
+
+enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+struct PatchDiffer<'a> {
+    options: &'a DiffOptions,
+    /// Path of the current location in the patch.
     path: String,
+    /// Path of the current location in the right document (or in the left one, for removed
+    /// values). Differs from the `path` by array indices.
+    location: String,
+    /// Depth of the current location.
+    depth: usize,
+    /// Whether the current location is an array element. Only kept up to date when descending,
+    /// which is when values at the current location are compared.
+    element: bool,
     patch: super::Patch,
-    shift: usize,
+    summary: Option<DiffSummary>,
+    /// Values removed from object members, with the index of their `remove` operation. Only
+    /// collected when detecting moves.
+    removed: Option<Vec<(usize, &'a Value)>>,
+    /// Paths and values of unchanged subtrees. Only collected when detecting copies.
+    unchanged: Option<Vec<(String, &'a Value)>>,
+    /// Non-zero while diffing inside array elements which changed their position during the patch
+    /// (as paths computed inside those are not valid for the whole patch).
+    unstable: usize,
+    /// Indices of `add` and `replace` operations emitted with a placeholder value, with the
+    /// location of the value in the right document. Only collected when the values are to be
+    /// moved out of the right document instead of being cloned.
+    deferred: Option<Vec<(usize, String)>>,
+    /// Memoized hashes of the compared objects and arrays, so that changed subtrees are told apart
+    /// without comparing them.
+    hashes: SubtreeHashes,
+    visitor: Option<&'a mut dyn DiffVisitor>,
+    /// Inverses of the emitted operations, in the order of the operations. Only collected when
+    /// generating the inverse patch.
+    inverse: Option<Vec<super::PatchOperation>>,
 }
 
-impl PatchDiffer {
-    fn new() -> Self {
+impl<'a> PatchDiffer<'a> {
+    fn new(options: &'a DiffOptions) -> Self {
         Self {
+            options,
             path: "".to_string(),
+            location: "".to_string(),
+            depth: 0,
+            element: false,
             patch: super::Patch(Vec::new()),
-            shift: 0,
+            summary: None,
+            removed: if options.detect_moves {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            unchanged: if options.detect_copies {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            unstable: 0,
+            deferred: None,
+            hashes: SubtreeHashes::default(),
+            visitor: None,
+            inverse: None,
+        }
+    }
+
+    /// Value for the `add` or `replace` operation being emitted at the current location.
+    fn emitted_value(&mut self, value: &Value) -> Value {
+        match self.deferred {
+            Some(ref mut deferred) => {
+                deferred.push((self.patch.0.len(), self.location.clone()));
+                Value::Null
+            }
+            None => value.clone(),
+        }
+    }
+
+    /// Record the inverse of the operation being emitted at the current path, if collected.
+    fn inverted(&mut self, inverse: impl FnOnce(String) -> super::PatchOperation) {
+        if let Some(ref mut ops) = self.inverse {
+            ops.push(inverse(self.path.clone()));
         }
     }
+
+    /// Finish diffing and return the generated patch.
+    fn finish(self) -> super::Patch {
+        let patch = match self.removed {
+            Some(removed) => pair_moves(self.patch, removed),
+            None => self.patch,
+        };
+        match self.unchanged {
+            Some(unchanged) => find_copies(patch, unchanged),
+            None => patch,
+        }
+    }
+
+    /// Report an event at the current path to the visitor, if any.
+    fn visit(&mut self, event: impl FnOnce(&mut dyn DiffVisitor, &str)) {
+        if let Some(visitor) = self.visitor.as_deref_mut() {
+            event(visitor, &self.path);
+        }
+    }
+
+    fn record(&mut self, kind: ChangeKind, old: Option<&Value>, new: Option<&Value>) {
+        let summary = match self.summary {
+            Some(ref mut summary) => summary,
+            None => return,
+        };
+        let key = self
+            .path
+            .split('/')
+            .nth(1)
+            .map(|token| super::unescape(token).into_owned())
+            .unwrap_or_default();
+        let entry = summary.keys.entry(key).or_default();
+        match kind {
+            ChangeKind::Added => entry.added += 1,
+            ChangeKind::Removed => entry.removed += 1,
+            ChangeKind::Modified => entry.modified += 1,
+        }
+        if entry.example.is_none() {
+            entry.example = Some(ChangeExample {
+                path: self.path.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            });
+        }
+    }
+}
+
+/// Key of a child value within an object or an array. Array indices are ordered before object
+/// keys, object keys are ordered lexicographically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Key<'a> {
+    Index(usize),
+    Name(&'a str),
+}
+
+/// Children of an object or an array, ordered by their keys. `None` for scalar values.
+pub(crate) fn children<V: JsonLike>(value: &V) -> Option<Vec<(Key<'_>, &V)>> {
+    if let Some(arr) = value.as_array() {
+        return Some(
+            arr.iter()
+                .enumerate()
+                .map(|(idx, v)| (Key::Index(idx), v))
+                .collect(),
+        );
+    }
+    let mut children: Vec<_> = value
+        .members()?
+        .into_iter()
+        .map(|(k, v)| (Key::Name(k), v))
+        .collect();
+    children.sort_unstable_by_key(|(key, _)| *key);
+    Some(children)
+}
+
+/// Unit of work of the differ, see [`PatchDiffer::diff_values`].
+enum Task<'a> {
+    /// Compare two values at the current path.
+    Diff(&'a Value, &'a Value),
+    /// Descend into the child with the given key.
+    Push(Key<'a>),
+    /// Return back to the parent of the current path.
+    Pop,
+    /// Emit operations for the added and removed children, once the common ones are diffed.
+    Children(Children<'a>),
+    /// Continue diffing keyed arrays.
+    Keyed(Box<KeyedArrays<'a>>),
+    /// Continue diffing aligned arrays.
+    Aligned(Box<AlignedArrays<'a>>),
+    /// Leave an array element which changed its position during the patch.
+    Stable,
+}
+
+/// Children added to and removed from the value at the current path.
+struct Children<'a> {
+    left_is_array: bool,
+    added: Vec<(Key<'a>, &'a Value)>,
+    removed: Vec<(Key<'a>, &'a Value)>,
+}
+
+/// State of diffing two arrays with paired elements, by the identity field or by the
+/// [`DiffOptions::element_matcher`].
+struct KeyedArrays<'a> {
+    left: &'a [Value],
+    right: &'a [Value],
+    /// Indices of the left elements paired with the right elements.
+    pairs: Vec<Option<usize>>,
+    /// Elements in the array as it is being patched: indices of the left elements, or indices of
+    /// the added right elements offset by the length of the left array.
+    current: Vec<usize>,
+    /// Whether elements of the array have changed their positions so far.
+    changed: bool,
+    /// Number of right elements which were not added.
+    skipped: usize,
+    /// Index of the next right element.
+    next: usize,
+}
+
+/// State of diffing two arrays aligned by an [`ArrayDiffAlgorithm`].
+struct AlignedArrays<'a> {
+    left: &'a [Value],
+    right: &'a [Value],
+    edits: Vec<Edit>,
+    /// Index of the next edit.
+    next: usize,
+    /// Index of the next element in the array being patched.
+    idx: usize,
+    /// Length of the array being patched.
+    len: usize,
+    /// Whether elements were added to or removed from the array so far.
+    changed: bool,
+}
+
+/// Compare values, taking the number equality policy and the number tolerance into account.
+fn values_equal(
+    left: &Value,
+    right: &Value,
+    equality: NumberEquality,
+    tolerance: Option<&NumberTolerance>,
+) -> bool {
+    values_equal_by(left, right, |l, r| {
+        equality.equal(l, r)
+            || match (tolerance, l.as_f64(), r.as_f64()) {
+                (Some(tolerance), Some(l), Some(r)) => tolerance.within(l, r),
+                _ => false,
+            }
+    })
 }
 
-impl<'a> treediff::Delegate<'a, treediff::value::Key, Value> for PatchDiffer {
-    fn push(&mut self, key: &treediff::value::Key) {
-        use std::fmt::Write;
-        self.path.push('/');
-        match *key {
-            treediff::value::Key::Index(idx) => write!(self.path, "{}", idx - self.shift).unwrap(),
-            treediff::value::Key::String(ref key) => append_path(&mut self.path, key),
+impl<'a> PatchDiffer<'a> {
+    /// Descend into the child with the given key. Array indices in the patch are shifted by
+    /// `shift` (removed or skipped array elements shift indices of the subsequent ones).
+    fn push(&mut self, key: Key, shift: usize) {
+        match key {
+            Key::Index(idx) => self.push_index(idx, idx - shift),
+            Key::Name(name) => {
+                self.path.push('/');
+                append_path(&mut self.path, name);
+                self.location.push('/');
+                append_path(&mut self.location, name);
+                self.depth += 1;
+                self.element = false;
+                self.visit(|visitor, path| visitor.enter(path));
+            }
+        }
+    }
+
+    /// Descend into the array element with the given index in the document, which has `index`
+    /// in the array being patched.
+    fn push_index(&mut self, location: usize, index: usize) {
+        use core::fmt::Write;
+        write!(self.path, "/{}", index).unwrap();
+        write!(self.location, "/{}", location).unwrap();
+        self.depth += 1;
+        self.element = true;
+        self.visit(|visitor, path| visitor.enter(path));
+    }
+
+    /// Descend into the array element with the given index in the document, which is appended to
+    /// the end of the array being patched at `index`.
+    fn push_appended(&mut self, location: usize, index: usize) {
+        if self.options.append_to_end {
+            use core::fmt::Write;
+            write!(self.location, "/{}", location).unwrap();
+            self.path.push_str("/-");
+            self.depth += 1;
+            self.visit(|visitor, path| visitor.enter(path));
+        } else {
+            self.push_index(location, index);
         }
     }
 
+    /// Return back to the parent of the current location.
     fn pop(&mut self) {
-        let pos = self.path.rfind('/').unwrap_or(0);
-        self.path.truncate(pos);
-        self.shift = 0;
+        self.visit(|visitor, path| visitor.leave(path));
+        // Escaped reference tokens never contain `/`
+        self.path.truncate(self.path.rfind('/').unwrap_or(0));
+        self.location
+            .truncate(self.location.rfind('/').unwrap_or(0));
+        self.depth -= 1;
+    }
+
+    /// Compare two values at the current path.
+    ///
+    /// Values are traversed with an explicit stack of tasks rather than recursively, so deeply
+    /// nested documents cannot overflow the call stack.
+    fn diff_values(&mut self, left: &'a Value, right: &'a Value) -> Result<(), DiffError> {
+        trace::span!("traverse");
+        let result = self.run(vec![Task::Diff(left, right)]);
+        #[cfg(feature = "tracing")]
+        if let Err(e) = &result {
+            trace::event!(debug, path = %self.path, error = %e, "diff failed");
+        }
+        result
+    }
+
+    /// Perform the tasks, popping them from the end of the stack.
+    fn run(&mut self, mut tasks: Vec<Task<'a>>) -> Result<(), DiffError> {
+        while let Some(task) = tasks.pop() {
+            self.perform(task, &mut tasks)?;
+        }
+        Ok(())
+    }
+
+    /// Perform a single task, scheduling the tasks it results in.
+    fn perform(&mut self, task: Task<'a>, tasks: &mut Vec<Task<'a>>) -> Result<(), DiffError> {
+        match task {
+            Task::Diff(left, right) => self.diff_step(left, right, tasks)?,
+            Task::Push(key) => self.push(key, 0),
+            Task::Pop => self.pop(),
+            Task::Children(children) => self.diff_children(children),
+            Task::Keyed(keyed) => self.diff_keyed_step(keyed, tasks),
+            Task::Aligned(aligned) => self.diff_aligned_step(aligned, tasks),
+            Task::Stable => self.unstable -= 1,
+        }
+        Ok(())
+    }
+
+    /// Compare two values at the current path, scheduling comparison of their children.
+    fn diff_step(
+        &mut self,
+        left: &'a Value,
+        right: &'a Value,
+        tasks: &mut Vec<Task<'a>>,
+    ) -> Result<(), DiffError> {
+        if self.is_ignored() {
+            return Ok(());
+        }
+        if matches!(self.options.max_depth, Some(max_depth) if self.depth >= max_depth) {
+            if self.values_equal(left, right) {
+                self.unchanged(left)
+            } else {
+                self.modified(left, right)
+            }
+            return Ok(());
+        }
+        if matches!(self.options.depth_limit, Some(limit) if self.depth > limit) {
+            return Err(DiffError::TooDeep {
+                path: self.location.clone(),
+            });
+        }
+        if let (Value::Array(left_arr), Value::Array(right_arr)) = (left, right) {
+            if !self.identical(left, right) {
+                if let Some(key) = self.array_key() {
+                    if self.diff_keyed_arrays(left_arr, right_arr, key, tasks) {
+                        return Ok(());
+                    }
+                }
+                if let Some(matcher) = self.options.element_matcher {
+                    if self.diff_matched_arrays(left_arr, right_arr, matcher, tasks) {
+                        return Ok(());
+                    }
+                }
+                if self.is_unordered() {
+                    self.diff_unordered_arrays(left_arr, right_arr);
+                    return Ok(());
+                }
+                if self.options.detect_reorders && self.diff_reordered_arrays(left_arr, right_arr) {
+                    return Ok(());
+                }
+                if self.options.array_diff != ArrayDiffAlgorithm::Positional {
+                    let edits = align(left_arr, right_arr, self.options.array_diff);
+                    if self.coalesced_edits(left_arr, right_arr, &edits) {
+                        self.modified(left, right);
+                    } else {
+                        self.diff_aligned_step(
+                            Box::new(AlignedArrays {
+                                left: left_arr,
+                                right: right_arr,
+                                edits,
+                                next: 0,
+                                idx: 0,
+                                len: left_arr.len(),
+                                changed: false,
+                            }),
+                            tasks,
+                        );
+                    }
+                    return Ok(());
+                }
+            }
+        }
+
+        let (left_children, right_children) = match (children(left), children(right)) {
+            (Some(_), Some(_)) if self.identical(left, right) => {
+                self.unchanged(left);
+                return Ok(());
+            }
+            (None, None) if self.values_equal(left, right) => {
+                self.unchanged(left);
+                return Ok(());
+            }
+            // Objects are replaced by arrays and vice versa as a whole, their children being
+            // addressed differently
+            (Some(left_children), Some(right_children)) if left.is_array() == right.is_array() => {
+                (left_children, right_children)
+            }
+            _ => {
+                self.modified(left, right);
+                return Ok(());
+            }
+        };
+
+        let mut common = Vec::new();
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut left_iter = left_children.into_iter().peekable();
+        let mut right_iter = right_children.into_iter().peekable();
+        loop {
+            match (left_iter.peek(), right_iter.peek()) {
+                (Some((lk, _)), Some((rk, _))) if lk == rk => {
+                    let (key, lv) = left_iter.next().unwrap();
+                    let (_, rv) = right_iter.next().unwrap();
+                    common.push((key, lv, rv));
+                }
+                (Some((lk, _)), Some((rk, _))) if lk < rk => {
+                    removed.push(left_iter.next().unwrap())
+                }
+                (Some(_), Some(_)) | (None, Some(_)) => added.push(right_iter.next().unwrap()),
+                (Some(_), None) => removed.push(left_iter.next().unwrap()),
+                (None, None) => break,
+            }
+        }
+
+        if self.options.null_equals_missing && left.is_object() && right.is_object() {
+            added.retain(|(_, value)| !value.is_null());
+            removed.retain(|(_, value)| !value.is_null());
+            if self.options.remove_nulls {
+                common.retain(|&(key, lv, rv)| {
+                    let set_to_null = rv.is_null() && !lv.is_null();
+                    if set_to_null {
+                        removed.push((key, lv));
+                    }
+                    !set_to_null
+                });
+                removed.sort_unstable_by_key(|(key, _)| *key);
+            }
+        }
+
+        if let Some(threshold) = self.options.coalesce_threshold {
+            if self.coalesced(threshold, &common, added.len() + removed.len()) {
+                self.modified(left, right);
+                return Ok(());
+            }
+        }
+
+        // Tasks are popped in reverse: common children are diffed first, in order
+        tasks.push(Task::Children(Children {
+            left_is_array: left.is_array(),
+            added,
+            removed,
+        }));
+        for (key, lv, rv) in common.into_iter().rev() {
+            tasks.push(Task::Pop);
+            tasks.push(Task::Diff(lv, rv));
+            tasks.push(Task::Push(key));
+        }
+        Ok(())
+    }
+
+    /// Emit operations for the children added to and removed from the value at the current path.
+    fn diff_children(&mut self, children: Children<'a>) {
+        // Skipped array elements shift indices of the subsequent ones
+        let mut skipped = 0;
+        for (key, value) in children.added {
+            match key {
+                // Elements added to an array are always appended to its end
+                Key::Index(idx) if children.left_is_array => self.push_appended(idx, idx - skipped),
+                key => self.push(key, skipped),
+            }
+            if !self.added(value) {
+                skipped += 1;
+            }
+            self.pop();
+        }
+        // Removing array elements from the last one keeps the indices of the others
+        let descending = children.left_is_array && self.options.descending_removals;
+        let mut removed = children.removed;
+        if descending {
+            removed.reverse();
+        }
+        let mut shift = 0;
+        for (key, value) in removed {
+            self.push(key, shift);
+            // Shift indices, we are deleting array elements from the first one
+            if self.removed(key, value) && matches!(key, Key::Index(_)) && !descending {
+                shift += 1;
+            }
+            self.pop();
+        }
+    }
+
+    /// Compare values exactly. Values with different hashes are not compared structurally, so
+    /// unchanged subtrees are compared once, when the differ reaches them.
+    fn identical(&mut self, left: &Value, right: &Value) -> bool {
+        self.hashes.get(left) == self.hashes.get(right)
+            && values_equal(left, right, NumberEquality::Exact, None)
     }
 
-    fn removed<'b>(&mut self, k: &'b treediff::value::Key, _v: &'a Value) {
-        let len = self.path.len();
-        self.push(k);
+    /// Compare values, taking the number equality policy and the number tolerance into account.
+    fn values_equal(&self, left: &Value, right: &Value) -> bool {
+        let options = self.options;
+        values_equal(
+            left,
+            right,
+            options.number_equality,
+            options.number_tolerance.as_ref(),
+        )
+    }
+
+    /// Checks if more than the `threshold` fraction of the children of the value at the current
+    /// path changed, so the value is to be replaced as a whole.
+    fn coalesced(
+        &mut self,
+        threshold: f32,
+        common: &[(Key<'a>, &'a Value, &'a Value)],
+        added_or_removed: usize,
+    ) -> bool {
+        let total = common.len() + added_or_removed;
+        if total == 0 || self.contains_ignored() {
+            return false;
+        }
+        let changed = added_or_removed
+            + common
+                .iter()
+                .filter(|(_, left, right)| {
+                    !self.identical(left, right) && !self.values_equal(left, right)
+                })
+                .count();
+        changed as f32 > threshold * total as f32
+    }
+
+    /// Checks if some of the locations nested under the current path should be skipped.
+    fn contains_ignored(&self) -> bool {
+        self.options
+            .ignore_paths
+            .iter()
+            .any(|glob| glob::may_match_under(glob, &self.location))
+    }
+
+    /// Checks if the current path should be skipped.
+    fn is_ignored(&self) -> bool {
+        self.options
+            .ignore_paths
+            .iter()
+            .any(|glob| glob::matches(glob, &self.location))
+    }
+
+    /// Identity field configured for the array at the current path, if any.
+    fn array_key(&self) -> Option<&'a str> {
+        self.options
+            .array_keys
+            .iter()
+            .filter(|(prefix, _)| is_prefix(prefix, &self.location))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, key)| key.as_str())
+    }
+
+    /// Diff two arrays of objects by matching their elements by the identity field. Returns
+    /// `false` if arrays cannot be matched that way.
+    fn diff_keyed_arrays(
+        &mut self,
+        left: &'a [Value],
+        right: &'a [Value],
+        key: &str,
+        tasks: &mut Vec<Task<'a>>,
+    ) -> bool {
+        let (left_ids, right_ids) = match (identities(left, key), identities(right, key)) {
+            (Some(left_ids), Some(right_ids)) => (left_ids, right_ids),
+            _ => return false,
+        };
+        let left_index: HashMap<_, _> = left_ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect();
+        let pairs = right_ids
+            .iter()
+            .map(|id| left_index.get(id).copied())
+            .collect();
+        self.diff_paired_arrays(left, right, pairs, tasks);
+        true
+    }
+
+    /// Diff two arrays by pairing their elements with the [`DiffOptions::element_matcher`].
+    /// Returns `false` if no elements are paired.
+    fn diff_matched_arrays(
+        &mut self,
+        left: &'a [Value],
+        right: &'a [Value],
+        matcher: fn(&Value, &Value) -> MatchQuality,
+        tasks: &mut Vec<Task<'a>>,
+    ) -> bool {
+        match match_elements(left, right, matcher) {
+            Some(pairs) => {
+                self.diff_paired_arrays(left, right, pairs, tasks);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Diff two arrays with paired elements: unpaired left elements are removed, unpaired right
+    /// elements are added, and paired elements are moved into place and diffed.
+    fn diff_paired_arrays(
+        &mut self,
+        left: &'a [Value],
+        right: &'a [Value],
+        pairs: Vec<Option<usize>>,
+        tasks: &mut Vec<Task<'a>>,
+    ) {
+        // Whether the left elements stay in the array
+        let mut kept = vec![false; left.len()];
+        for left_idx in pairs.iter().flatten() {
+            kept[*left_idx] = true;
+        }
+
+        // Unpaired elements are removed before any other operation on the array
+        let unpaired: Vec<(usize, usize)> = (0..left.len())
+            .filter(|idx| !kept[*idx])
+            .map(|idx| (idx, idx))
+            .collect();
+        let removed = self.removed_elements(left, &unpaired);
+        let changed = removed.contains(&true);
+        for ((idx, _), removed) in unpaired.into_iter().zip(removed) {
+            // Element stays, it will end up after all the right elements
+            kept[idx] = !removed;
+        }
+        let current: Vec<usize> = (0..left.len()).filter(|idx| kept[*idx]).collect();
+
+        self.diff_keyed_step(
+            Box::new(KeyedArrays {
+                left,
+                right,
+                pairs,
+                current,
+                changed,
+                skipped: 0,
+                next: 0,
+            }),
+            tasks,
+        );
+    }
+
+    /// Emit removal of left array elements, given in increasing order by their indices in the left
+    /// array and in the array being patched before removing any of them, in the order set by
+    /// [`DiffOptions::descending_removals`]. Returns whether each of the elements was removed,
+    /// rather than ignored.
+    fn removed_elements(&mut self, left: &'a [Value], elements: &[(usize, usize)]) -> Vec<bool> {
+        let mut removed = vec![false; elements.len()];
+        if self.options.descending_removals {
+            for (pos, &(left_idx, idx)) in elements.iter().enumerate().rev() {
+                self.push_index(left_idx, idx);
+                removed[pos] = self.removed(Key::Index(left_idx), &left[left_idx]);
+                self.pop();
+            }
+        } else {
+            let mut shift = 0;
+            for (pos, &(left_idx, idx)) in elements.iter().enumerate() {
+                self.push_index(left_idx, idx - shift);
+                if self.removed(Key::Index(left_idx), &left[left_idx]) {
+                    removed[pos] = true;
+                    shift += 1;
+                }
+                self.pop();
+            }
+        }
+        removed
+    }
+
+    /// Continue diffing arrays with paired elements with the next right element, until an
+    /// element paired with a left one is found. Diffing of the paired elements is scheduled
+    /// before the rest of the arrays.
+    fn diff_keyed_step(&mut self, mut keyed: Box<KeyedArrays<'a>>, tasks: &mut Vec<Task<'a>>) {
+        let (left, right) = (keyed.left, keyed.right);
+        while keyed.next < keyed.pairs.len() {
+            let right_idx = keyed.next;
+            keyed.next += 1;
+            // Right elements which were not added shift indices of the subsequent ones
+            let idx = right_idx - keyed.skipped;
+            match keyed.pairs[right_idx] {
+                None => {
+                    if idx == keyed.current.len() {
+                        self.push_appended(right_idx, idx);
+                    } else {
+                        self.push_index(right_idx, idx);
+                    }
+                    if self.added(&right[right_idx]) {
+                        keyed.current.insert(idx, left.len() + right_idx);
+                        keyed.changed = true;
+                    } else {
+                        keyed.skipped += 1;
+                    }
+                    self.pop();
+                }
+                Some(left_idx) => {
+                    // Elements before `idx` are already in place, so the element is after them
+                    let current_idx = idx
+                        + keyed.current[idx..]
+                            .iter()
+                            .position(|c| *c == left_idx)
+                            .unwrap();
+                    if current_idx != idx {
+                        let from = format!("{}/{}", self.path, current_idx);
+                        self.push_index(right_idx, idx);
+                        self.moved(from);
+                        self.pop();
+                        let id = keyed.current.remove(current_idx);
+                        keyed.current.insert(idx, id);
+                    }
+                    keyed.changed |= current_idx != left_idx;
+
+                    self.push_index(right_idx, idx);
+                    let changed = keyed.changed;
+                    tasks.push(Task::Keyed(keyed));
+                    tasks.push(Task::Pop);
+                    if changed {
+                        self.unstable += 1;
+                        tasks.push(Task::Stable);
+                    }
+                    tasks.push(Task::Diff(&left[left_idx], &right[right_idx]));
+                    return;
+                }
+            }
+        }
+    }
+
+    fn is_unordered(&self) -> bool {
+        self.options
+            .unordered_arrays
+            .iter()
+            .any(|prefix| is_prefix(prefix, &self.location))
+    }
+
+    /// Diff two arrays as multisets, ignoring the order of their elements.
+    fn diff_unordered_arrays(&mut self, left: &'a [Value], right: &'a [Value]) {
+        let mut unmatched: HashMap<HashedValue, usize> = HashMap::new();
+        for item in right {
+            *unmatched.entry(HashedValue(item)).or_default() += 1;
+        }
+
+        let mut removed = Vec::new();
+        for (idx, item) in left.iter().enumerate() {
+            match unmatched.get_mut(&HashedValue(item)) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => removed.push((idx, idx)),
+            }
+        }
+        let shift = self
+            .removed_elements(left, &removed)
+            .into_iter()
+            .filter(|removed| *removed)
+            .count();
+
+        let mut end = left.len() - shift;
+        for (idx, item) in right.iter().enumerate() {
+            let count = unmatched.get_mut(&HashedValue(item)).unwrap();
+            if *count > 0 {
+                *count -= 1;
+                self.push_appended(idx, end);
+                if self.added(item) {
+                    end += 1;
+                }
+                self.pop();
+            }
+        }
+    }
+
+    /// Diff two arrays with the same elements in a different order by moving the elements which
+    /// are out of order. Returns `false` if the right array is not a permutation of the left one.
+    fn diff_reordered_arrays(&mut self, left: &'a [Value], right: &'a [Value]) -> bool {
+        if left.len() != right.len() || self.contains_ignored() {
+            return false;
+        }
+        // Equal elements are matched in the order they appear in
+        let mut positions: HashMap<HashedValue, VecDeque<usize>> = HashMap::new();
+        for (idx, item) in left.iter().enumerate() {
+            positions
+                .entry(HashedValue(item))
+                .or_default()
+                .push_back(idx);
+        }
+        let mut order = Vec::with_capacity(right.len());
+        for item in right {
+            match positions
+                .get_mut(&HashedValue(item))
+                .and_then(VecDeque::pop_front)
+            {
+                Some(idx) => order.push(idx),
+                None => return false,
+            }
+        }
+
+        // Left indices of the elements in the array as it is being patched
+        let kept = increasing_run(&order);
+        let mut current: Vec<usize> = (0..left.len()).collect();
+        for (right_idx, &left_idx) in order.iter().enumerate() {
+            if kept[right_idx] {
+                continue;
+            }
+            let from = current.iter().position(|idx| *idx == left_idx).unwrap();
+            current.remove(from);
+            // Elements before it in the right array are already in their relative order
+            let to = match right_idx {
+                0 => 0,
+                _ => {
+                    let prev = order[right_idx - 1];
+                    current.iter().position(|idx| *idx == prev).unwrap() + 1
+                }
+            };
+            current.insert(to, left_idx);
+            let from = format!("{}/{}", self.path, from);
+            self.push_index(right_idx, to);
+            self.moved(from);
+            self.pop();
+        }
+        true
+    }
+
+    /// Checks if aligned arrays are to be replaced as a whole, according to the
+    /// [`DiffOptions::coalesce_threshold`].
+    fn coalesced_edits(&mut self, left: &'a [Value], right: &'a [Value], edits: &[Edit]) -> bool {
+        let threshold = match self.options.coalesce_threshold {
+            Some(threshold) => threshold,
+            None => return false,
+        };
+        let mut common = Vec::new();
+        for edit in edits {
+            if let Edit::Pair(left_idx, right_idx) = *edit {
+                common.push((Key::Index(right_idx), &left[left_idx], &right[right_idx]));
+            }
+        }
+        let added_or_removed = edits.len() - common.len();
+        self.coalesced(threshold, &common, added_or_removed)
+    }
+
+    /// Continue diffing aligned arrays with the next edit, until a pair of elements is found.
+    /// Diffing of the paired elements is scheduled before the rest of the arrays.
+    fn diff_aligned_step(
+        &mut self,
+        mut aligned: Box<AlignedArrays<'a>>,
+        tasks: &mut Vec<Task<'a>>,
+    ) {
+        let (left, right) = (aligned.left, aligned.right);
+        while let Some(&edit) = aligned.edits.get(aligned.next) {
+            aligned.next += 1;
+            match edit {
+                Edit::Remove(_) => {
+                    // Removed elements following each other are removed together
+                    let mut elements = Vec::new();
+                    while let Some(&Edit::Remove(left_idx)) = aligned.edits.get(aligned.next - 1) {
+                        elements.push((left_idx, aligned.idx + elements.len()));
+                        aligned.next += 1;
+                    }
+                    aligned.next -= 1;
+                    for removed in self.removed_elements(left, &elements) {
+                        if removed {
+                            aligned.len -= 1;
+                            aligned.changed = true;
+                        } else {
+                            // Element stays in place
+                            aligned.idx += 1;
+                        }
+                    }
+                }
+                Edit::Add(right_idx) => {
+                    if aligned.idx == aligned.len {
+                        self.push_appended(right_idx, aligned.idx);
+                    } else {
+                        self.push_index(right_idx, aligned.idx);
+                    }
+                    if self.added(&right[right_idx]) {
+                        aligned.idx += 1;
+                        aligned.len += 1;
+                        aligned.changed = true;
+                    }
+                    self.pop();
+                }
+                Edit::Pair(left_idx, right_idx) => {
+                    self.push_index(right_idx, aligned.idx);
+                    aligned.idx += 1;
+                    let changed = aligned.changed;
+                    tasks.push(Task::Aligned(aligned));
+                    tasks.push(Task::Pop);
+                    if changed {
+                        self.unstable += 1;
+                        tasks.push(Task::Stable);
+                    }
+                    tasks.push(Task::Diff(&left[left_idx], &right[right_idx]));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Emit a `test` operation for the value at the current path, if configured.
+    fn tested(&mut self, value: &Value) {
+        if self.options.test_before_destructive {
+            self.patch
+                .0
+                .push(super::PatchOperation::Test(super::TestOperation {
+                    path: self.path.clone(),
+                    value: value.clone(),
+                }));
+        }
+    }
+
+    fn moved(&mut self, from: String) {
         self.patch
             .0
-            .push(super::PatchOperation::Remove(super::RemoveOperation {
+            .push(super::PatchOperation::Move(super::MoveOperation {
+                from,
                 path: self.path.clone(),
             }));
-        // Shift indices, we are deleting array elements
-        if let treediff::value::Key::Index(_) = k {
-            self.shift += 1;
+    }
+
+    /// Emit removal of the value at the current path, unless it is ignored. Returns `true` if the
+    /// operation was emitted.
+    fn removed(&mut self, key: Key, value: &'a Value) -> bool {
+        if self.is_ignored() {
+            return false;
+        }
+        self.record(ChangeKind::Removed, Some(value), None);
+        self.visit(|visitor, path| visitor.removed(path, value));
+        self.tested(value);
+        if let (Key::Name(_), 0, Some(removed)) = (key, self.unstable, &mut self.removed) {
+            removed.push((self.patch.0.len(), value));
         }
-        self.path.truncate(len);
+        self.inverted(|path| {
+            super::PatchOperation::Add(super::AddOperation {
+                path,
+                value: value.clone(),
+            })
+        });
+        self.patch
+            .0
+            .push(super::PatchOperation::Remove(super::RemoveOperation {
+                path: self.path.clone(),
+            }));
+        true
     }
 
-    fn added(&mut self, k: &treediff::value::Key, v: &Value) {
-        let len = self.path.len();
-        self.push(k);
+    /// Emit addition of the value at the current path, unless it is ignored. Returns `true` if the
+    /// operation was emitted.
+    fn added(&mut self, value: &'a Value) -> bool {
+        if self.is_ignored() {
+            return false;
+        }
+        self.record(ChangeKind::Added, None, Some(value));
+        self.visit(|visitor, path| visitor.added(path, value));
+        self.inverted(|path| super::PatchOperation::Remove(super::RemoveOperation { path }));
+        let value = self.emitted_value(value);
         self.patch
             .0
             .push(super::PatchOperation::Add(super::AddOperation {
                 path: self.path.clone(),
-                value: v.clone(),
+                value,
+            }));
+        true
+    }
+
+    fn unchanged(&mut self, value: &'a Value) {
+        self.visit(|visitor, path| visitor.unchanged(path, value));
+        if let (0, Some(unchanged)) = (self.unstable, &mut self.unchanged) {
+            unchanged.push((self.path.clone(), value));
+        }
+    }
+
+    fn modified(&mut self, old: &'a Value, new: &'a Value) {
+        self.record(ChangeKind::Modified, Some(old), Some(new));
+        self.visit(|visitor, path| visitor.modified(path, old, new));
+        self.tested(old);
+        if self.element && self.options.element_replacement == ElementReplacement::RemoveAndAdd {
+            // The old element is removed after the new one is added, so that a `copy` or a `move`
+            // taking place of the `add` reads the siblings at their original indices
+            let (parent, index) = self.path.rsplit_once('/').unwrap();
+            let shifted = format!("{}/{}", parent, index.parse::<usize>().unwrap() + 1);
+            self.inverted(|path| super::PatchOperation::Remove(super::RemoveOperation { path }));
+            self.inverted(|_| {
+                super::PatchOperation::Add(super::AddOperation {
+                    path: shifted.clone(),
+                    value: old.clone(),
+                })
+            });
+            let value = self.emitted_value(new);
+            self.patch
+                .0
+                .push(super::PatchOperation::Add(super::AddOperation {
+                    path: self.path.clone(),
+                    value,
+                }));
+            self.patch
+                .0
+                .push(super::PatchOperation::Remove(super::RemoveOperation {
+                    path: shifted,
+                }));
+            return;
+        }
+        self.inverted(|path| {
+            super::PatchOperation::Replace(super::ReplaceOperation {
+                path,
+                value: old.clone(),
+            })
+        });
+        let value = self.emitted_value(new);
+        self.patch
+            .0
+            .push(super::PatchOperation::Replace(super::ReplaceOperation {
+                path: self.path.clone(),
+                value,
             }));
-        self.path.truncate(len);
+    }
+}
+
+/// Operations generated by the differ of a subtree, with the values collected for detecting moves
+/// and copies.
+#[cfg(feature = "rayon")]
+struct Subtree<'a> {
+    patch: super::Patch,
+    removed: Option<Vec<(usize, &'a Value)>>,
+    unchanged: Option<Vec<(String, &'a Value)>>,
+}
+
+#[cfg(feature = "rayon")]
+impl<'a> PatchDiffer<'a> {
+    /// Append the operations generated by the differ of a subtree, which follow the operations
+    /// generated so far.
+    fn append(&mut self, subtree: Subtree<'a>) {
+        let offset = self.patch.0.len();
+        self.patch.0.extend(subtree.patch.0);
+        if let (Some(removed), Some(subtree)) = (&mut self.removed, subtree.removed) {
+            removed.extend(
+                subtree
+                    .into_iter()
+                    .map(|(idx, value)| (idx + offset, value)),
+            );
+        }
+        if let (Some(unchanged), Some(subtree)) = (&mut self.unchanged, subtree.unchanged) {
+            unchanged.extend(subtree);
+        }
+    }
+}
+
+/// Replace pairs of `remove` and `add` operations of equal values with `move` operations.
+///
+/// Only removals of object members are considered: removing those does not shift any other
+/// location. Since the differ never emits operations inside removed or added values and never
+/// shifts array indices of locations it descends into, the removed value stays reachable at its
+/// original path for the whole patch, so a `move` can take place of the `add` operation.
+fn pair_moves(patch: super::Patch, removed: Vec<(usize, &Value)>) -> super::Patch {
+    // Index of the paired `remove` operation for every `add` operation.
+    let pairs = {
+        let mut candidates: HashMap<HashedValue, Vec<usize>> = HashMap::new();
+        for &(idx, value) in removed.iter().rev() {
+            candidates.entry(HashedValue(value)).or_default().push(idx);
+        }
+
+        let mut pairs = HashMap::new();
+        for (idx, op) in patch.iter().enumerate() {
+            if let super::PatchOperation::Add(add) = op {
+                let remove_idx = candidates
+                    .get_mut(&HashedValue(&add.value))
+                    .and_then(|candidates| candidates.pop());
+                if let Some(remove_idx) = remove_idx {
+                    pairs.insert(idx, remove_idx);
+                }
+            }
+        }
+        pairs
+    };
+    if pairs.is_empty() {
+        return patch;
+    }
+
+    let mut slots: Vec<Vec<super::PatchOperation>> =
+        patch.0.into_iter().map(|op| vec![op]).collect();
+    for (&add_idx, &remove_idx) in &pairs {
+        let from = match slots[remove_idx].pop() {
+            Some(super::PatchOperation::Remove(remove)) => remove.path,
+            _ => unreachable!("paired operation must be a remove"),
+        };
+        let path = match slots[add_idx].pop() {
+            Some(super::PatchOperation::Add(add)) => add.path,
+            _ => unreachable!("paired operation must be an add"),
+        };
+        // `test` operation guarding the removal now guards the move
+        if remove_idx > 0 {
+            if let [super::PatchOperation::Test(test)] = slots[remove_idx - 1].as_slice() {
+                if test.path == from {
+                    let test = slots[remove_idx - 1].pop().unwrap();
+                    slots[add_idx].push(test);
+                }
+            }
+        }
+        slots[add_idx].push(super::PatchOperation::Move(super::MoveOperation {
+            from,
+            path,
+        }));
+    }
+    super::Patch(slots.into_iter().flatten().collect())
+}
+
+/// Checks if a patch with the given number of operations exceeds the limits of the options.
+/// The length of its serialization is only computed if needed.
+fn over_budget(options: &DiffOptions, operations: usize, bytes: impl FnOnce() -> usize) -> bool {
+    options.max_operations.is_some_and(|max| operations > max)
+        || options.max_patch_bytes.is_some_and(|max| bytes() > max)
+}
+
+/// Replace the patch generated for the values at the `path` with a single `replace` operation,
+/// if it exceeds the limits of the options.
+fn budgeted(
+    patch: super::Patch,
+    path: &str,
+    left: &Value,
+    right: &Value,
+    options: &DiffOptions,
+) -> super::Patch {
+    budgeted_by(patch, path, options, |path| {
+        replace_whole(path, left, right.clone(), options)
+    })
+}
+
+/// Same as `budgeted`, replacing the patch with the one `whole` generates for the `path` if it
+/// exceeds the limits of the options.
+fn budgeted_by(
+    patch: super::Patch,
+    path: &str,
+    options: &DiffOptions,
+    whole: impl FnOnce(&str) -> super::Patch,
+) -> super::Patch {
+    trace::span!("budget", operations = patch.len());
+    if over_budget(options, patch.len(), || serialized_len(&patch)) {
+        trace::event!(
+            debug,
+            path,
+            "patch over budget, replacing the value as a whole"
+        );
+        whole(path)
+    } else {
+        patch
+    }
+}
+
+/// Patch replacing the value at the `path` as a whole.
+fn replace_whole(path: &str, left: &Value, right: Value, options: &DiffOptions) -> super::Patch {
+    let mut patch = Vec::new();
+    if options.test_before_destructive {
+        patch.push(super::PatchOperation::test(path, left.clone()));
+    }
+    patch.push(super::PatchOperation::replace(path, right));
+    super::Patch(patch)
+}
+
+/// Reorder the operations of the patch generated in the document order.
+fn reorder(patch: super::Patch, order: OpOrder) -> super::Patch {
+    if order == OpOrder::Document {
+        return patch;
+    }
+    trace::span!("reorder", operations = patch.len());
+    let indices = operation_order(&patch, order);
+    let mut ops: Vec<_> = patch.0.into_iter().map(Some).collect();
+    indices
+        .into_iter()
+        .filter_map(|idx| ops[idx].take())
+        .collect()
+}
+
+/// Indices of the operations of the patch generated in the document order, in the given order.
+///
+/// Operations are grouped by the path of the location they apply to, level by level: at every
+/// location, consecutive operations under the same child form a group. Groups are reordered if
+/// every child has a single group, children are object members (not array indices) and all the
+/// `move` and `copy` operations have their source in the same group. Groups are then reordered
+/// the same way within themselves.
+pub(crate) fn operation_order(patch: &[super::PatchOperation], order: OpOrder) -> Vec<usize> {
+    fn tokens(pointer: &str) -> Vec<&str> {
+        pointer.split('/').skip(1).collect()
+    }
+    let is_index = |token: &str| token == "-" || token.bytes().all(|b| b.is_ascii_digit());
+    let is_removal = |ops: &mut dyn Iterator<Item = &super::PatchOperation>| {
+        let mut removes = false;
+        for op in ops {
+            match op {
+                super::PatchOperation::Remove(_) => removes = true,
+                super::PatchOperation::Test(_) => {}
+                _ => return false,
+            }
+        }
+        removes
+    };
+
+    let mut indices: Vec<usize> = (0..patch.len()).collect();
+    if order == OpOrder::Document {
+        return indices;
+    }
+    let paths: Vec<_> = patch.iter().map(|op| tokens(op.path())).collect();
+    let sources: Vec<_> = patch.iter().map(|op| op.from().map(tokens)).collect();
+
+    // Ranges of `indices` sharing the first `depth` reference tokens of their paths
+    let mut stack = vec![(0, indices.len(), 0)];
+    while let Some((start, end, depth)) = stack.pop() {
+        let mut groups: Vec<(Option<&str>, Vec<usize>)> = Vec::new();
+        for &idx in &indices[start..end] {
+            let key = paths[idx].get(depth).copied();
+            match groups.last_mut() {
+                Some((last, group)) if *last == key => group.push(idx),
+                _ => groups.push((key, vec![idx])),
+            }
+        }
+
+        let reorderable = groups.len() > 1
+            && groups
+                .iter()
+                .all(|(key, _)| key.is_some_and(|key| !is_index(key)))
+            && groups
+                .iter()
+                .enumerate()
+                .all(|(i, (key, _))| groups[..i].iter().all(|(other, _)| other != key))
+            && indices[start..end].iter().all(|&idx| {
+                sources[idx]
+                    .as_ref()
+                    .is_none_or(|from| from.len() > depth && from[..=depth] == paths[idx][..=depth])
+            });
+        if reorderable {
+            let mut sorted: Vec<_> = groups
+                .into_iter()
+                .map(|(key, group)| {
+                    let removal = order == OpOrder::RemovesLast
+                        && is_removal(&mut group.iter().map(|&idx| &patch[idx]));
+                    (
+                        removal,
+                        super::unescape(key.unwrap_or_default()),
+                        key,
+                        group,
+                    )
+                })
+                .collect();
+            sorted.sort_by(|(lr, lk, _, _), (rr, rk, _, _)| (lr, lk).cmp(&(rr, rk)));
+            groups = sorted
+                .into_iter()
+                .map(|(_, _, key, group)| (key, group))
+                .collect();
+        }
+
+        let mut pos = start;
+        for (key, group) in groups {
+            let len = group.len();
+            indices[pos..pos + len].copy_from_slice(&group);
+            if key.is_some() && len > 1 {
+                stack.push((pos, pos + len, depth + 1));
+            }
+            pos += len;
+        }
+    }
+    indices
+}
+
+/// Replace `add` operations of objects and arrays equal to one of the unchanged subtrees (or their
+/// descendants) with `copy` operations, if that makes the patch shorter.
+///
+/// The differ does not emit operations inside unchanged subtrees and never shifts array indices of
+/// locations it descends into, so an unchanged subtree stays reachable at its original path for the
+/// whole patch.
+fn find_copies(patch: super::Patch, unchanged: Vec<(String, &Value)>) -> super::Patch {
+    if !patch
+        .iter()
+        .any(|op| matches!(op, super::PatchOperation::Add(_)))
+    {
+        return patch;
+    }
+
+    let mut sources = HashMap::new();
+    for (mut path, value) in unchanged {
+        index_sources(&mut path, value, &mut sources);
+    }
+
+    let ops = patch
+        .0
+        .into_iter()
+        .map(|op| match op {
+            super::PatchOperation::Add(add) => match sources.get(&HashedValue(&add.value)) {
+                Some(from) if crate::estimate::serialized_len(&add.value) > from.len() => {
+                    super::PatchOperation::Copy(super::CopyOperation {
+                        from: from.clone(),
+                        path: add.path,
+                    })
+                }
+                _ => super::PatchOperation::Add(add),
+            },
+            op => op,
+        })
+        .collect();
+    super::Patch(ops)
+}
+
+/// Record the given value and all its descendant objects and arrays as candidate sources for the
+/// `copy` operations. First occurrence of a value wins.
+fn index_sources<'a>(
+    path: &mut String,
+    value: &'a Value,
+    sources: &mut HashMap<HashedValue<'a>, String>,
+) {
+    let len = path.len();
+    match value {
+        Value::Array(arr) if !arr.is_empty() => {
+            sources
+                .entry(HashedValue(value))
+                .or_insert_with(|| path.clone());
+            for (idx, item) in arr.iter().enumerate() {
+                use core::fmt::Write;
+                write!(path, "/{}", idx).unwrap();
+                index_sources(path, item, sources);
+                path.truncate(len);
+            }
+        }
+        Value::Object(obj) if !obj.is_empty() => {
+            sources
+                .entry(HashedValue(value))
+                .or_insert_with(|| path.clone());
+            for (key, item) in obj {
+                path.push('/');
+                append_path(path, key);
+                index_sources(path, item, sources);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Values of the identity field of all of the array elements, or `None` if not all elements are
+/// objects with unique values of the identity field.
+fn identities<'a>(arr: &'a [Value], key: &str) -> Option<Vec<HashedValue<'a>>> {
+    let mut seen = HashSet::with_capacity(arr.len());
+    arr.iter()
+        .map(|item| {
+            let id = HashedValue(item.as_object()?.get(key)?);
+            if seen.insert(id) {
+                Some(id)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Indices of the left elements paired with the right elements by the matcher, or `None` if no
+/// elements match.
+fn match_elements(
+    left: &[Value],
+    right: &[Value],
+    matcher: fn(&Value, &Value) -> MatchQuality,
+) -> Option<Vec<Option<usize>>> {
+    let mut candidates = Vec::new();
+    for (right_idx, right_item) in right.iter().enumerate() {
+        for (left_idx, left_item) in left.iter().enumerate() {
+            let rank = match matcher(left_item, right_item) {
+                MatchQuality::NoMatch => continue,
+                MatchQuality::Partial(score) => (false, score),
+                MatchQuality::Full => (true, 1.0),
+            };
+            candidates.push((rank, left_idx.abs_diff(right_idx), right_idx, left_idx));
+        }
+    }
+    if candidates.is_empty() {
+        return None;
+    }
+    candidates.sort_by(|a, b| {
+        (b.0 .0.cmp(&a.0 .0))
+            .then(b.0 .1.total_cmp(&a.0 .1))
+            .then((a.1, a.2, a.3).cmp(&(b.1, b.2, b.3)))
+    });
+
+    let mut pairs = vec![None; right.len()];
+    let mut paired = vec![false; left.len()];
+    for (_, _, right_idx, left_idx) in candidates {
+        if pairs[right_idx].is_none() && !paired[left_idx] {
+            pairs[right_idx] = Some(left_idx);
+            paired[left_idx] = true;
+        }
+    }
+    Some(pairs)
+}
+
+/// Diff two JSON documents and generate a JSON Patch (RFC 6902).
+///
+/// # Example
+/// Diff two JSONs:
+///
+/// ```rust
+/// #[macro_use]
+/// use json_patch::{Patch, patch, diff};
+/// use serde_json::{json, from_value};
+///
+/// # pub fn main() {
+/// let left = json!({
+///   "title": "Goodbye!",
+///   "author" : {
+///     "givenName" : "John",
+///     "familyName" : "Doe"
+///   },
+///   "tags":[ "example", "sample" ],
+///   "content": "This will be unchanged"
+/// });
+///
+/// let right = json!({
+///   "title": "Hello!",
+///   "author" : {
+///     "givenName" : "John"
+///   },
+///   "tags": [ "example" ],
+///   "content": "This will be unchanged",
+///   "phoneNumber": "+01-123-456-7890"
+/// });
+///
+/// let p = diff(&left, &right);
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "remove", "path": "/author/familyName" },
+///   { "op": "remove", "path": "/tags/1" },
+///   { "op": "replace", "path": "/title", "value": "Hello!" },
+///   { "op": "add", "path": "/phoneNumber", "value": "+01-123-456-7890" },
+/// ])).unwrap());
+///
+/// let mut doc = left.clone();
+/// patch(&mut doc, &p).unwrap();
+/// assert_eq!(doc, right);
+///
+/// # }
+/// ```
+pub fn diff(left: &Value, right: &Value) -> super::Patch {
+    diff_with_options(left, right, &DiffOptions::default())
+}
+
+/// Diff two JSON documents and generate a JSON Patch (RFC 6902), using provided options. With
+/// default options, generates the same patch as [`diff`].
+///
+/// # Panics
+///
+/// Panics if the documents are nested deeper than the [`DiffOptions::depth_limit`], use
+/// [`try_diff_with_options`] to handle that.
+///
+/// # Example
+/// Detect renamed keys:
+///
+/// ```rust
+/// #[macro_use]
+/// use json_patch::{diff_with_options, patch, DiffOptions, Patch};
+/// use serde_json::{json, from_value};
+///
+/// # pub fn main() {
+/// let left = json!({ "user": { "name": "John", "lastName": "Doe" } });
+/// let right = json!({ "person": { "name": "John", "lastName": "Doe" } });
+///
+/// let options = DiffOptions {
+///     detect_moves: true,
+///     ..Default::default()
+/// };
+/// let p = diff_with_options(&left, &right, &options);
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "move", "from": "/user", "path": "/person" },
+/// ])).unwrap());
+///
+/// let mut doc = left.clone();
+/// patch(&mut doc, &p).unwrap();
+/// assert_eq!(doc, right);
+/// # }
+/// ```
+pub fn diff_with_options(left: &Value, right: &Value, options: &DiffOptions) -> super::Patch {
+    match try_diff_with_options(left, right, options) {
+        Ok(patch) => patch,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Same as [`diff_with_options`], but returns an error instead of panicking if the documents are
+/// nested deeper than the [`DiffOptions::depth_limit`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{try_diff_with_options, DiffError, DiffOptions};
+/// use serde_json::json;
+///
+/// let left = json!({ "a": { "b": { "c": 1 } } });
+/// let right = json!({ "a": { "b": { "c": 2 } } });
+///
+/// let options = DiffOptions {
+///     depth_limit: Some(2),
+///     ..Default::default()
+/// };
+/// let err = try_diff_with_options(&left, &right, &options).unwrap_err();
+/// assert!(matches!(err, DiffError::TooDeep { path } if path == "/a/b/c"));
+/// ```
+pub fn try_diff_with_options(
+    left: &Value,
+    right: &Value,
+    options: &DiffOptions,
+) -> Result<super::Patch, DiffError> {
+    trace::span!("diff");
+    trace::event!(
+        trace,
+        left = serialized_len(left),
+        right = serialized_len(right),
+        "documents"
+    );
+    let mut differ = PatchDiffer::new(options);
+    differ.diff_values(left, right)?;
+    let patch = reorder(differ.finish(), options.op_order);
+    let patch = budgeted(patch, "", left, right, options);
+    trace::event!(debug, operations = patch.len(), "diffed");
+    Ok(patch)
+}
+
+/// Differ that can be reused for diffing many pairs of documents with the same options, like
+/// consecutive snapshots of documents in a change feed.
+///
+/// Generates the same patches as [`diff_with_options`], but keeps the storage of its internal
+/// state (such as the table of subtree hashes, which grows with the size of the documents)
+/// between the calls instead of allocating it anew for every pair of documents. The hashes
+/// themselves are not reused, as they are only valid while the documents are borrowed.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{Differ, DiffOptions};
+/// use serde_json::json;
+///
+/// let mut differ = Differ::new(DiffOptions {
+///     detect_moves: true,
+///     ..Default::default()
+/// });
+/// let snapshots = [json!({ "a": 1 }), json!({ "b": 1 }), json!({ "b": 2 })];
+/// let patches: Vec<_> = snapshots
+///     .windows(2)
+///     .map(|pair| differ.diff(&pair[0], &pair[1]))
+///     .collect();
+/// assert_eq!(patches[0].to_string(), r#"[{"op":"move","from":"/a","path":"/b"}]"#);
+/// assert_eq!(patches[1].to_string(), r#"[{"op":"replace","path":"/b","value":2}]"#);
+/// ```
+#[derive(Debug, Default)]
+pub struct Differ {
+    options: DiffOptions,
+    hashes: SubtreeHashes,
+    path: String,
+    location: String,
+}
+
+impl Differ {
+    /// Creates a differ using provided options.
+    pub fn new(options: DiffOptions) -> Self {
+        Differ {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the options of the differ.
+    pub fn options(&self) -> &DiffOptions {
+        &self.options
+    }
+
+    /// Diff two JSON documents, same as [`diff_with_options`] with the options of the differ.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the documents are nested deeper than the [`DiffOptions::depth_limit`], use
+    /// [`Differ::try_diff`] to handle that.
+    pub fn diff(&mut self, left: &Value, right: &Value) -> super::Patch {
+        match self.try_diff(left, right) {
+            Ok(patch) => patch,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Same as [`Differ::diff`], but returns an error instead of panicking if the documents are
+    /// nested deeper than the [`DiffOptions::depth_limit`].
+    pub fn try_diff(&mut self, left: &Value, right: &Value) -> Result<super::Patch, DiffError> {
+        self.diff_reusing(left, right, Vec::new())
+    }
+
+    /// Same as [`Differ::diff`], but generates the patch into `out`, replacing its operations and
+    /// reusing its allocation, so that diffing many documents in a loop does not allocate a new
+    /// patch every time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the documents are nested deeper than the [`DiffOptions::depth_limit`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Differ, Patch};
+    /// use serde_json::json;
+    ///
+    /// let mut differ = Differ::default();
+    /// let mut p = Patch::default();
+    /// for n in 0..3 {
+    ///     differ.diff_into(&json!({ "n": n }), &json!({ "n": n + 1 }), &mut p);
+    ///     assert_eq!(p.len(), 1);
+    /// }
+    /// ```
+    pub fn diff_into(&mut self, left: &Value, right: &Value, out: &mut super::Patch) {
+        let mut ops = core::mem::take(&mut out.0);
+        ops.clear();
+        match self.diff_reusing(left, right, ops) {
+            Ok(patch) => *out = patch,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Diff two JSON documents, generating the patch into the given (empty) vector.
+    fn diff_reusing(
+        &mut self,
+        left: &Value,
+        right: &Value,
+        ops: Vec<super::PatchOperation>,
+    ) -> Result<super::Patch, DiffError> {
+        trace::span!("diff");
+        trace::event!(
+            trace,
+            left = serialized_len(left),
+            right = serialized_len(right),
+            "documents"
+        );
+        let mut differ = PatchDiffer::new(&self.options);
+        differ.patch = super::Patch(ops);
+        differ.hashes = core::mem::take(&mut self.hashes);
+        differ.path = core::mem::take(&mut self.path);
+        differ.location = core::mem::take(&mut self.location);
+        let result = differ.diff_values(left, right);
+
+        self.hashes = core::mem::take(&mut differ.hashes);
+        self.hashes.clear();
+        self.path = core::mem::take(&mut differ.path);
+        self.path.clear();
+        self.location = core::mem::take(&mut differ.location);
+        self.location.clear();
+        result?;
+        let patch = reorder(differ.finish(), self.options.op_order);
+        let patch = budgeted(patch, "", left, right, &self.options);
+        trace::event!(debug, operations = patch.len(), "diffed");
+        Ok(patch)
+    }
+}
+
+/// Diff the values at the `pointer` in two JSON documents, without looking at the rest of the
+/// documents. Operations of the generated patch have fully-qualified paths, so the patch applies
+/// to the left document.
+///
+/// If the value is missing in one of the documents, the patch adds or removes it as a whole.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{diff_at, Patch, Pointer};
+/// use serde_json::{from_value, json};
+///
+/// let left = json!({ "config": { "a": 1, "b": [1] }, "data": [1, 2, 3] });
+/// let right = json!({ "config": { "a": 2, "b": [1] }, "data": [4, 5, 6] });
+///
+/// let config: Pointer = "/config".parse().unwrap();
+/// assert_eq!(diff_at(&left, &right, &config), from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/config/a", "value": 2 },
+/// ])).unwrap());
+/// ```
+pub fn diff_at(left: &Value, right: &Value, pointer: &Pointer) -> super::Patch {
+    diff_at_with_options(left, right, pointer, &DiffOptions::default())
+}
+
+/// Same as [`diff_at`], but using provided options. Pointers in the options refer to the
+/// locations in the whole documents, and depths are counted from their roots. Moves and copies
+/// are only detected within the diffed values.
+///
+/// # Panics
+///
+/// Panics if the documents are nested deeper than the [`DiffOptions::depth_limit`].
+pub fn diff_at_with_options(
+    left: &Value,
+    right: &Value,
+    pointer: &Pointer,
+    options: &DiffOptions,
+) -> super::Patch {
+    let mut differ = PatchDiffer::new(options);
+    differ.path = pointer.to_string();
+    differ.location = differ.path.clone();
+    differ.depth = pointer.tokens().count();
+    let (old, new) = (resolve(left, pointer), resolve(right, pointer));
+    match (old, new) {
+        (Some(old), Some(new)) => {
+            if let Err(e) = differ.diff_values(old, new) {
+                panic!("{e}");
+            }
+        }
+        (None, Some(new)) => {
+            differ.added(new);
+        }
+        (Some(old), None) => {
+            // Only the kind of the key matters: removed object members can be paired into moves
+            let in_array = pointer
+                .parent()
+                .and_then(|parent| resolve(left, &parent))
+                .is_some_and(Value::is_array);
+            let key = if in_array {
+                Key::Index(0)
+            } else {
+                Key::Name("")
+            };
+            differ.removed(key, old);
+        }
+        (None, None) => {}
+    }
+    let patch = reorder(differ.finish(), options.op_order);
+    budgeted_by(patch, pointer.as_str(), options, |path| match (old, new) {
+        (Some(old), Some(new)) => replace_whole(path, old, new.clone(), options),
+        (None, Some(new)) => super::Patch(vec![super::PatchOperation::add(path, new.clone())]),
+        (Some(old), None) => {
+            let mut patch = Vec::new();
+            if options.test_before_destructive {
+                patch.push(super::PatchOperation::test(path, old.clone()));
+            }
+            patch.push(super::PatchOperation::remove(path));
+            super::Patch(patch)
+        }
+        (None, None) => super::Patch::default(),
+    })
+}
+
+/// Same as [`diff_with_options`], but diffs the children of the root objects or arrays in
+/// parallel, using the global thread pool of `rayon`. Generates the same patch.
+///
+/// Requires the `rayon` feature.
+///
+/// # Panics
+///
+/// Panics if the documents are nested deeper than the [`DiffOptions::depth_limit`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{par_diff_with_options, DiffOptions, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let left = json!({ "a": [1, 2], "b": { "c": true } });
+/// let right = json!({ "a": [1, 3], "b": {}, "d": null });
+///
+/// let p = par_diff_with_options(&left, &right, &DiffOptions::default());
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/a/1", "value": 3 },
+///   { "op": "remove", "path": "/b/c" },
+///   { "op": "add", "path": "/d", "value": null },
+/// ])).unwrap());
+/// ```
+#[cfg(feature = "rayon")]
+pub fn par_diff_with_options(left: &Value, right: &Value, options: &DiffOptions) -> super::Patch {
+    use rayon::prelude::*;
+
+    let mut differ = PatchDiffer::new(options);
+    let mut tasks = Vec::new();
+    if let Err(e) = differ.diff_step(left, right, &mut tasks) {
+        panic!("{e}")
+    }
+    // Unless the root is handled in some other way, its common children are scheduled after the
+    // added and removed ones, in reverse
+    let mut subtrees = Vec::new();
+    if matches!(tasks.first(), Some(Task::Children(_))) {
+        for chunk in tasks[1..].chunks(3).rev() {
+            let [Task::Pop, Task::Diff(left, right), Task::Push(key)] = chunk else {
+                unreachable!("unexpected diff tasks for a common child")
+            };
+            subtrees.push((*key, *left, *right));
+        }
+        tasks.truncate(1);
+    }
+    let subtrees: Vec<_> = subtrees
+        .into_par_iter()
+        .map(|(key, left, right)| {
+            let mut subtree = PatchDiffer::new(options);
+            subtree.push(key, 0);
+            subtree.diff_values(left, right).map(|()| Subtree {
+                patch: subtree.patch,
+                removed: subtree.removed,
+                unchanged: subtree.unchanged,
+            })
+        })
+        .collect();
+    for subtree in subtrees {
+        match subtree {
+            Ok(subtree) => differ.append(subtree),
+            Err(e) => panic!("{e}"),
+        }
+    }
+    if let Err(e) = differ.run(tasks) {
+        panic!("{e}")
+    }
+    let patch = reorder(differ.finish(), options.op_order);
+    budgeted(patch, "", left, right, options)
+}
+
+/// Diff two JSON documents and generate a JSON Patch (RFC 6902), consuming the right document.
+/// Generates the same patch as [`diff_with_options`].
+///
+/// Values added or replaced by the patch are moved out of the right document instead of being
+/// cloned, so only the left document and the patch are kept in memory once diffing is done. This
+/// is not possible when detecting moves or copies (those compare the values of the patch while it
+/// is generated), so values are cloned then.
+///
+/// # Panics
+///
+/// Panics if the documents are nested deeper than the [`DiffOptions::depth_limit`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{diff_owned, DiffOptions, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let left = json!({ "items": [1] });
+/// let right = json!({ "items": [1, { "large": "value" }] });
+///
+/// let p = diff_owned(&left, right, &DiffOptions::default());
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "add", "path": "/items/1", "value": { "large": "value" } },
+/// ])).unwrap());
+/// ```
+pub fn diff_owned(left: &Value, mut right: Value, options: &DiffOptions) -> super::Patch {
+    if options.detect_moves || options.detect_copies {
+        return diff_with_options(left, &right, options);
+    }
+    let (mut patch, deferred) = {
+        let mut differ = PatchDiffer::new(options);
+        differ.deferred = Some(Vec::new());
+        if let Err(e) = differ.diff_values(left, &right) {
+            panic!("{e}")
+        }
+        let deferred = differ.deferred.take().unwrap_or_default();
+        (differ.finish(), deferred)
+    };
+    // Deferred values are still placeholders, so count their lengths in
+    let over_budget = over_budget(options, patch.len(), || {
+        deferred
+            .iter()
+            .fold(serialized_len(&patch), |len, (_, location)| {
+                let value = right.pointer(location).map_or(0, serialized_len);
+                len + value - serialized_len(&Value::Null)
+            })
+    });
+    if over_budget {
+        return replace_whole("", left, right, options);
+    }
+    // Operations are never emitted inside added or replaced values, so none of the locations is
+    // nested under another one and taking a value out leaves other locations intact.
+    for (idx, location) in deferred {
+        let value = right.pointer_mut(&location).map(core::mem::take);
+        match (&mut patch.0[idx], value) {
+            (super::PatchOperation::Add(op), Some(value)) => op.value = value,
+            (super::PatchOperation::Replace(op), Some(value)) => op.value = value,
+            _ => unreachable!("deferred value is missing at '{location}'"),
+        }
+    }
+    reorder(patch, options.op_order)
+}
+
+/// Diff two JSON documents lazily, yielding the operations of the JSON Patch (RFC 6902) as they
+/// are discovered. Yields the same operations as [`diff`], in the same order; the rest of the
+/// documents is not compared once the iterator is dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{diff_iter, PatchOperation};
+/// use serde_json::json;
+///
+/// let left = json!({ "a": 1, "b": [1, 2, 3], "c": { "d": true } });
+/// let right = json!({ "a": 2, "b": [1], "c": { "d": false } });
+///
+/// let ops: Vec<PatchOperation> = diff_iter(&left, &right).take(2).collect();
+/// assert_eq!(ops.len(), 2);
+/// assert_eq!(ops[0].path(), "/a");
+/// assert_eq!(ops[1].path(), "/b/1");
+/// ```
+pub fn diff_iter<'a>(
+    left: &'a Value,
+    right: &'a Value,
+) -> impl Iterator<Item = super::PatchOperation> + 'a {
+    static DEFAULT_OPTIONS: DiffOptions = DiffOptions::defaults();
+    DiffIter {
+        differ: PatchDiffer::new(&DEFAULT_OPTIONS),
+        tasks: vec![Task::Diff(left, right)],
+        pending: VecDeque::new(),
+    }
+}
+
+/// Iterator returned by [`diff_iter`].
+struct DiffIter<'a> {
+    differ: PatchDiffer<'a>,
+    tasks: Vec<Task<'a>>,
+    /// Operations emitted by the last performed task, not yielded yet.
+    pending: VecDeque<super::PatchOperation>,
+}
+
+impl Iterator for DiffIter<'_> {
+    type Item = super::PatchOperation;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(op) = self.pending.pop_front() {
+                return Some(op);
+            }
+            let task = self.tasks.pop()?;
+            if let Err(e) = self.differ.perform(task, &mut self.tasks) {
+                unreachable!("no depth limit by default: {e}")
+            }
+            self.pending.extend(self.differ.patch.0.drain(..));
+        }
+    }
+}
+
+/// Diff two JSON documents, reporting the changes found to the visitor, and generate a JSON Patch
+/// (RFC 6902). Generates the same patch as [`diff_with_options`].
+///
+/// # Panics
+///
+/// Panics if the documents are nested deeper than the [`DiffOptions::depth_limit`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{diff_with_visitor, DiffOptions, DiffVisitor};
+/// use serde_json::{json, Value};
+///
+/// #[derive(Default)]
+/// struct Report(Vec<String>);
+///
+/// impl DiffVisitor for Report {
+///     fn added(&mut self, path: &str, value: &Value) {
+///         self.0.push(format!("+ {path}: {value}"));
+///     }
+///
+///     fn removed(&mut self, path: &str, value: &Value) {
+///         self.0.push(format!("- {path}: {value}"));
+///     }
+///
+///     fn modified(&mut self, path: &str, old: &Value, new: &Value) {
+///         self.0.push(format!("~ {path}: {old} -> {new}"));
+///     }
+/// }
+///
+/// let left = json!({ "name": "Andrew", "tags": ["a", "b"] });
+/// let right = json!({ "name": "Maxim", "tags": ["a"], "age": 30 });
+///
+/// let mut report = Report::default();
+/// let p = diff_with_visitor(&left, &right, &DiffOptions::default(), &mut report);
+/// assert_eq!(p.len(), 3);
+/// assert_eq!(report.0, [
+///     "~ /name: \"Andrew\" -> \"Maxim\"",
+///     "- /tags/1: \"b\"",
+///     "+ /age: 30",
+/// ]);
+/// ```
+pub fn diff_with_visitor<V: DiffVisitor>(
+    left: &Value,
+    right: &Value,
+    options: &DiffOptions,
+    visitor: &mut V,
+) -> super::Patch {
+    let mut differ = PatchDiffer::new(options);
+    differ.visitor = Some(visitor);
+    if let Err(e) = differ.diff_values(left, right) {
+        panic!("{e}")
+    }
+    let patch = reorder(differ.finish(), options.op_order);
+    budgeted(patch, "", left, right, options)
+}
+
+/// Diff the JSON representations of two typed values and generate a JSON Patch (RFC 6902), as
+/// [`diff`] would. Returns an error if either of the values cannot be represented as JSON.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{diff_typed, Patch};
+/// use serde::Serialize;
+/// use serde_json::{from_value, json};
+///
+/// #[derive(Serialize)]
+/// struct Person {
+///     name: String,
+///     tags: Vec<String>,
+/// }
+///
+/// let left = Person { name: "Andrew".into(), tags: vec!["a".into()] };
+/// let right = Person { name: "Maxim".into(), tags: vec![] };
+///
+/// let p = diff_typed(&left, &right).unwrap();
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/name", "value": "Maxim" },
+///   { "op": "remove", "path": "/tags/0" },
+/// ])).unwrap());
+/// ```
+pub fn diff_typed<T: Serialize + ?Sized>(
+    left: &T,
+    right: &T,
+) -> Result<super::Patch, serde_json::Error> {
+    let left = serde_json::to_value(left)?;
+    let right = serde_json::to_value(right)?;
+    Ok(diff(&left, &right))
+}
+
+/// Diff two JSON documents and generate both a JSON Patch (RFC 6902) and a human-oriented summary
+/// of the changes, in a single traversal. The patch is the same one [`diff`] would produce.
+///
+/// # Example
+/// Diff two JSONs:
+///
+/// ```rust
+/// #[macro_use]
+/// use json_patch::diff_with_summary;
+/// use serde_json::json;
+///
+/// # pub fn main() {
+/// let left = json!({
+///   "title": "Goodbye!",
+///   "tags": [ "example", "sample" ]
+/// });
+///
+/// let right = json!({
+///   "title": "Hello!",
+///   "tags": [ "example" ],
+///   "phoneNumber": "+01-123-456-7890"
+/// });
+///
+/// let (p, summary) = diff_with_summary(&left, &right);
+/// assert_eq!(p.len(), 3);
+/// assert_eq!(summary.keys["tags"].removed, 1);
+/// assert_eq!(summary.keys["title"].modified, 1);
+/// assert_eq!(
+///   summary.to_string(),
+///   "/phoneNumber: 1 added\n/tags: 1 removed\n/title: 1 modified\n"
+/// );
+/// # }
+/// ```
+pub fn diff_with_summary(left: &Value, right: &Value) -> (super::Patch, DiffSummary) {
+    let options = DiffOptions::default();
+    let mut differ = PatchDiffer::new(&options);
+    differ.summary = Some(DiffSummary::default());
+    if let Err(e) = differ.diff_values(left, right) {
+        unreachable!("no depth limit by default: {e}")
+    }
+    let summary = differ.summary.take().unwrap_or_default();
+    (differ.finish(), summary)
+}
+
+/// Diff two JSON documents and generate both the JSON Patch (RFC 6902) transforming `left` into
+/// `right` and its inverse, transforming `right` back into `left`, in a single traversal. The
+/// patch is the same one [`diff`] would produce; the inverse reverts its operations one by one,
+/// in reverse order, restoring the values of `left`.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{diff_with_inverse, patch, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let left = json!({ "title": "Draft", "tags": ["a", "b"] });
+/// let right = json!({ "title": "Final", "tags": ["a"], "done": true });
+///
+/// let (forward, inverse) = diff_with_inverse(&left, &right);
+/// assert_eq!(inverse, from_value::<Patch>(json!([
+///   { "op": "remove", "path": "/done" },
+///   { "op": "replace", "path": "/title", "value": "Draft" },
+///   { "op": "add", "path": "/tags/1", "value": "b" }
+/// ])).unwrap());
+///
+/// let mut doc = left.clone();
+/// patch(&mut doc, &forward).unwrap();
+/// assert_eq!(doc, right);
+/// patch(&mut doc, &inverse).unwrap();
+/// assert_eq!(doc, left);
+/// ```
+pub fn diff_with_inverse(left: &Value, right: &Value) -> (super::Patch, super::Patch) {
+    let options = DiffOptions::default();
+    let mut differ = PatchDiffer::new(&options);
+    differ.inverse = Some(Vec::new());
+    if let Err(e) = differ.diff_values(left, right) {
+        unreachable!("no depth limit by default: {e}")
+    }
+    let mut inverse = differ.inverse.take().unwrap_or_default();
+    inverse.reverse();
+    (differ.finish(), super::Patch(inverse))
+}
+
+/// Number of leaves (scalars, empty objects and empty arrays) of the value.
+fn leaves(value: &Value) -> usize {
+    let mut count = 0;
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Array(arr) if !arr.is_empty() => stack.extend(arr),
+            Value::Object(obj) if !obj.is_empty() => stack.extend(obj.values()),
+            _ => count += 1,
+        }
+    }
+    count
+}
+
+/// Counts the leaves of the values left unchanged by the diff, see [`similarity`].
+struct UnchangedLeaves(usize);
+
+impl DiffVisitor for UnchangedLeaves {
+    fn unchanged(&mut self, _: &str, value: &Value) {
+        self.0 += leaves(value);
+    }
+}
+
+/// Score the similarity of two JSON documents, from `0.0` (nothing in common) to `1.0` (equal
+/// documents), to help deciding whether to send a patch or the whole document.
+///
+/// The documents are diffed as [`diff`] does, and the score is the proportion of their leaves
+/// (scalars, empty objects and empty arrays) left unchanged: twice the number of unchanged leaves
+/// divided by the total number of leaves of both documents.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::similarity;
+/// use serde_json::json;
+///
+/// let left = json!({ "title": "Goodbye!", "tags": ["a", "b", "c"] });
+/// let right = json!({ "title": "Hello!", "tags": ["a", "b", "c"] });
+///
+/// assert_eq!(similarity(&left, &left), 1.0);
+/// assert_eq!(similarity(&left, &right), 0.75);
+/// assert_eq!(similarity(&left, &json!(null)), 0.0);
+/// ```
+pub fn similarity(left: &Value, right: &Value) -> f64 {
+    let mut unchanged = UnchangedLeaves(0);
+    diff_with_visitor(left, right, &DiffOptions::default(), &mut unchanged);
+    (2 * unchanged.0) as f64 / (leaves(left) + leaves(right)) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    #[test]
+    pub fn replace_all() {
+        let left = json!({"title": "Hello!"});
+        let p = super::diff(&left, &Value::Null);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "", "value": null },
+            ]))
+            .unwrap()
+        );
+        let mut left = json!({"title": "Hello!"});
+        crate::patch(&mut left, &p).unwrap();
+    }
+
+    #[test]
+    pub fn diff_empty_key() {
+        let left = json!({"title": "Something", "": "Hello!"});
+        let right = json!({"title": "Something", "": "Bye!"});
+        let p = super::diff(&left, &right);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "/", "value": "Bye!" },
+            ]))
+            .unwrap()
+        );
+        let mut left_patched = json!({"title": "Something", "": "Hello!"});
+        crate::patch(&mut left_patched, &p).unwrap();
+        assert_eq!(left_patched, right);
+    }
+
+    #[test]
+    pub fn add_all() {
+        let right = json!({"title": "Hello!"});
+        let p = super::diff(&Value::Null, &right);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "", "value": { "title": "Hello!" } },
+            ]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    pub fn remove_all() {
+        let left = json!(["hello", "bye"]);
+        let right = json!([]);
+        let p = super::diff(&left, &right);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "remove", "path": "/0" },
+                { "op": "remove", "path": "/0" },
+            ]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    pub fn remove_tail() {
+        let left = json!(["hello", "bye", "hi"]);
+        let right = json!(["hello"]);
+        let p = super::diff(&left, &right);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "remove", "path": "/1" },
+                { "op": "remove", "path": "/1" },
+            ]))
+            .unwrap()
+        );
+    }
+    #[test]
+    pub fn replace_object() {
+        let left = json!(["hello", "bye"]);
+        let right = json!({"hello": "bye"});
+        let p = super::diff(&left, &right);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "", "value": { "hello": "bye" } },
+            ]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn replace_container_kind() {
+        let cases = [
+            (json!({}), json!([1])),
+            (json!([1]), json!({ "0": 1 })),
+            (json!({ "a": { "0": 1 } }), json!({ "a": [1] })),
+            (json!({ "a": [[], {}] }), json!({ "a": [{}, []] })),
+        ];
+        for (left, right) in &cases {
+            let p = super::diff(left, right);
+            let mut doc = left.clone();
+            crate::patch(&mut doc, &p).unwrap();
+            assert_eq!(&doc, right, "{p}");
+            assert!(
+                p.iter().all(|op| op.op_kind() == crate::OpKind::Replace),
+                "{p}"
+            );
+        }
+    }
+
+    #[test]
+    fn diff_into() {
+        let mut differ = super::Differ::new(super::DiffOptions {
+            detect_moves: true,
+            ..Default::default()
+        });
+        let mut out = crate::Patch::default();
+        for (left, right) in [
+            (
+                json!({ "a": [1, 2, 3], "b": 1 }),
+                json!({ "a": [1], "c": 1 }),
+            ),
+            (json!({ "a": 1 }), json!({ "a": 2 })),
+            (json!(1), json!(1)),
+        ] {
+            differ.diff_into(&left, &right, &mut out);
+            assert_eq!(out, differ.diff(&left, &right));
+        }
+        assert!(out.0.capacity() >= 3);
+    }
+
+    #[test]
+    fn diff_with_inverse() {
+        for (left, right) in [
+            (
+                json!({ "a": 1, "b": [1, 2, 3] }),
+                json!({ "a": 2, "b": [2], "c": {} }),
+            ),
+            (
+                json!([[1, 2], { "x": null }]),
+                json!([[3], { "x": [1] }, true]),
+            ),
+            (
+                json!({ "a": { "b": { "c": "d" } } }),
+                json!({ "a": { "b": 1, "e": [] } }),
+            ),
+            (json!(null), json!({ "a": 1 })),
+            (json!([1, 2]), json!([1, 2])),
+        ] {
+            let (forward, inverse) = super::diff_with_inverse(&left, &right);
+            assert_eq!(forward, super::diff(&left, &right));
+            let mut doc = left.clone();
+            crate::patch(&mut doc, &forward).unwrap();
+            assert_eq!(doc, right);
+            crate::patch(&mut doc, &inverse).unwrap();
+            assert_eq!(doc, left, "{inverse}");
+            assert_eq!(inverse.len(), forward.len());
+        }
+    }
+
+    #[test]
+    fn summary_examples() {
+        let left = json!({ "a~b": { "x": 1, "y": 2 }, "list": [1, 2, 3] });
+        let right = json!({ "a~b": { "x": 10 }, "list": [1] });
+        let (p, summary) = super::diff_with_summary(&left, &right);
+        assert_eq!(p, super::diff(&left, &right));
+        assert_eq!(
+            summary.keys["a~b"],
+            super::KeySummary {
+                added: 0,
+                removed: 1,
+                modified: 1,
+                example: Some(super::ChangeExample {
+                    path: "/a~0b/x".to_string(),
+                    old: Some(json!(1)),
+                    new: Some(json!(10)),
+                }),
+            }
+        );
+        assert_eq!(summary.keys["list"].removed, 2);
+    }
+
+    #[test]
+    pub fn summary_replace_all() {
+        let (_, summary) = super::diff_with_summary(&json!(1), &json!(2));
+        assert_eq!(summary.keys[""].modified, 1);
+        assert_eq!(summary.to_string(), "/: 1 modified\n");
+    }
+
+    fn check_moves(left: Value, right: Value, expected: Value) {
+        let options = super::DiffOptions {
+            detect_moves: true,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(p, serde_json::from_value(expected).unwrap());
+        let mut patched = left;
+        crate::patch(&mut patched, &p).unwrap();
+        assert_eq!(patched, right);
+    }
+
+    #[test]
+    fn detect_moves() {
+        check_moves(
+            json!({ "a": { "k": [1, 2] }, "b": {} }),
+            json!({ "a": {}, "b": { "k": [1, 2] } }),
+            json!([{ "op": "move", "from": "/a/k", "path": "/b/k" }]),
+        );
+        check_moves(
+            json!({ "x": { "y": true }, "list": [1] }),
+            json!({ "list": [1, { "y": true }] }),
+            json!([{ "op": "move", "from": "/x", "path": "/list/1" }]),
+        );
+        // Only one of the equal values can be moved, the other one is added
+        check_moves(
+            json!({ "a": 1, "b": 2 }),
+            json!({ "c": 1, "d": 1, "b": 2 }),
+            json!([
+                { "op": "move", "from": "/a", "path": "/c" },
+                { "op": "add", "path": "/d", "value": 1 },
+            ]),
+        );
+        // Array elements are not moved
+        check_moves(
+            json!({ "a": [1, 2] }),
+            json!({ "a": [1], "b": 2 }),
+            json!([
+                { "op": "remove", "path": "/a/1" },
+                { "op": "add", "path": "/b", "value": 2 },
+            ]),
+        );
+    }
+
+    #[test]
+    fn detect_copies() {
+        let template = json!({ "header": "Lorem ipsum dolor sit amet", "footer": [1, 2, 3] });
+        let left = json!({ "pages": [{ "layout": template }], "extra": { "a": 1 } });
+        let right = json!({
+            "pages": [{ "layout": template }, { "layout": template }],
+            "extra": { "a": 1, "b": template["footer"], "c": [1] },
+        });
+        let options = super::DiffOptions {
+            detect_copies: true,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                // Short values are still added
+                { "op": "add", "path": "/extra/b", "value": [1, 2, 3] },
+                { "op": "add", "path": "/extra/c", "value": [1] },
+                { "op": "copy", "from": "/pages/0", "path": "/pages/1" },
+            ]))
+            .unwrap()
+        );
+        let mut patched = left;
+        crate::patch(&mut patched, &p).unwrap();
+        assert_eq!(patched, right);
+    }
+
+    #[test]
+    fn detect_copies_of_nested() {
+        let left = json!({ "a": { "b": { "c": "some long text value here" } } });
+        let right = json!({
+            "a": { "b": { "c": "some long text value here" } },
+            "d": { "c": "some long text value here" },
+        });
+        let options = super::DiffOptions {
+            detect_copies: true,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "copy", "from": "/a/b", "path": "/d" },
+            ]))
+            .unwrap()
+        );
+    }
+
+    fn check_keyed(left: Value, right: Value, expected: Value) {
+        let mut options = super::DiffOptions::default();
+        options
+            .array_keys
+            .insert("/items".to_string(), "id".to_string());
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(p, serde_json::from_value(expected).unwrap());
+        let mut patched = left;
+        crate::patch(&mut patched, &p).unwrap();
+        assert_eq!(patched, right);
+    }
+
+    #[test]
+    fn keyed_arrays() {
+        // Reorder and update
+        check_keyed(
+            json!({ "items": [{ "id": 1, "v": "a" }, { "id": 2, "v": "b" }, { "id": 3 }] }),
+            json!({ "items": [{ "id": 3 }, { "id": 1, "v": "a" }, { "id": 2, "v": "B" }] }),
+            json!([
+                { "op": "move", "from": "/items/2", "path": "/items/0" },
+                { "op": "replace", "path": "/items/2/v", "value": "B" },
+            ]),
+        );
+        // Insert and remove
+        check_keyed(
+            json!({ "items": [{ "id": 1 }, { "id": 2 }, { "id": 3 }] }),
+            json!({ "items": [{ "id": 4 }, { "id": 3 }, { "id": 1, "x": true }] }),
+            json!([
+                { "op": "remove", "path": "/items/1" },
+                { "op": "add", "path": "/items/0", "value": { "id": 4 } },
+                { "op": "move", "from": "/items/2", "path": "/items/1" },
+                { "op": "add", "path": "/items/2/x", "value": true },
+            ]),
+        );
+        // Nested arrays are keyed, too
+        check_keyed(
+            json!({ "items": [{ "id": 1, "items": [{ "id": "a" }, { "id": "b" }] }] }),
+            json!({ "items": [{ "id": 1, "items": [{ "id": "b" }, { "id": "a" }] }] }),
+            json!([{ "op": "move", "from": "/items/0/items/1", "path": "/items/0/items/0" }]),
+        );
+        // Duplicate identities fall back to positional diff
+        check_keyed(
+            json!({ "items": [{ "id": 1 }, { "id": 1 }] }),
+            json!({ "items": [{ "id": 2 }, { "id": 1 }] }),
+            json!([{ "op": "replace", "path": "/items/0/id", "value": 2 }]),
+        );
+    }
+
+    #[test]
+    fn keyed_arrays_with_moves_and_copies() {
+        let mut options = super::DiffOptions {
+            detect_moves: true,
+            detect_copies: true,
+            ..Default::default()
+        };
+        options.array_keys.insert("".to_string(), "id".to_string());
+        let block = json!({ "text": "some long piece of text" });
+        let left = json!({
+            "a": [{ "id": 1, "block": block }, { "id": 2, "extra": { "x": 1 } }],
+            "z": [],
+        });
+        let right = json!({
+            "a": [{ "id": 2 }, { "id": 1, "block": block }],
+            "z": [block, { "x": 1 }],
+        });
+        let p = super::diff_with_options(&left, &right, &options);
+        // Values inside the reordered elements must not be used as sources
+        assert!(!p
+            .iter()
+            .any(|op| matches!(op, crate::PatchOperation::Copy(_))));
+        let mut patched = left;
+        crate::patch(&mut patched, &p).unwrap();
+        assert_eq!(patched, right);
+    }
+
+    #[test]
+    fn unordered_arrays() {
+        let mut options = super::DiffOptions::default();
+        options.unordered_arrays.insert("/tags".to_string());
+        let left = json!({ "tags": ["a", "b", "c", "b"], "list": [1, 2] });
+        let right = json!({ "tags": ["b", "d", "c", "a"], "list": [2, 1] });
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "/list/0", "value": 2 },
+                { "op": "replace", "path": "/list/1", "value": 1 },
+                { "op": "remove", "path": "/tags/3" },
+                { "op": "add", "path": "/tags/3", "value": "d" },
+            ]))
+            .unwrap()
+        );
+
+        let right = json!({ "tags": ["c", "b", "b", "a"], "list": [1, 2] });
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(p, crate::Patch(vec![]));
+    }
+
+    #[test]
+    fn ignore_paths() {
+        let options = super::DiffOptions {
+            ignore_paths: vec![
+                "/updatedAt".to_string(),
+                "/items/*/meta".to_string(),
+                "/list/1".to_string(),
+            ],
+            ..Default::default()
+        };
+        let left = json!({
+            "updatedAt": 1,
+            "items": [{ "meta": { "v": 1 }, "name": "a" }, { "name": "b" }],
+            "list": [1],
+        });
+        let right = json!({
+            "items": [{ "meta": { "v": 2 }, "name": "A" }, { "name": "b", "meta": {} }],
+            "list": [1, 2, 3],
+        });
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "/items/0/name", "value": "A" },
+                { "op": "add", "path": "/list/1", "value": 3 },
+            ]))
+            .unwrap()
+        );
+        let mut patched = left;
+        crate::patch(&mut patched, &p).unwrap();
+    }
+
+    #[test]
+    fn ignore_paths_in_keyed_arrays() {
+        let mut options = super::DiffOptions {
+            ignore_paths: vec!["/items/0".to_string()],
+            ..Default::default()
+        };
+        options
+            .array_keys
+            .insert("/items".to_string(), "id".to_string());
+        let left = json!({ "items": [{ "id": 1 }, { "id": 2 }] });
+        let right = json!({ "items": [{ "id": 3 }, { "id": 2 }, { "id": 4 }] });
+        let p = super::diff_with_options(&left, &right, &options);
+        let mut patched = left;
+        crate::patch(&mut patched, &p).unwrap();
+        assert_eq!(
+            patched,
+            json!({ "items": [{ "id": 2 }, { "id": 4 }, { "id": 1 }] })
+        );
+    }
+
+    #[test]
+    fn max_depth() {
+        let left = json!({ "a": { "b": { "c": 1, "d": 2 } }, "e": [1, 2], "f": 1 });
+        let right = json!({ "a": { "b": { "c": 1, "d": 3 } }, "e": [1, 2], "f": 2 });
+        let diff_at_depth = |max_depth| {
+            let options = super::DiffOptions {
+                max_depth: Some(max_depth),
+                ..Default::default()
+            };
+            super::diff_with_options(&left, &right, &options)
+        };
+        assert_eq!(
+            diff_at_depth(0),
+            serde_json::from_value(json!([{ "op": "replace", "path": "", "value": right }]))
+                .unwrap()
+        );
+        assert_eq!(
+            diff_at_depth(2),
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "/a/b", "value": { "c": 1, "d": 3 } },
+                { "op": "replace", "path": "/f", "value": 2 },
+            ]))
+            .unwrap()
+        );
+        assert_eq!(diff_at_depth(3), super::diff(&left, &right));
+    }
+
+    #[test]
+    fn coalesce_threshold() {
+        let left = json!({
+            "a": { "b": 1, "c": 2, "d": 3 },
+            "e": [1, 2, 3, 4],
+            "f": { "g": 1, "h": 2 },
+            "j": 1,
+            "k": 2
+        });
+        let right = json!({
+            "a": { "b": 10, "c": 20, "d": 3 },
+            "e": [1, 2, 3, 5],
+            "f": { "g": 10, "i": 2 },
+            "j": 1,
+            "k": 2
+        });
+        let coalesced = |threshold, ignore_paths: &[&str]| {
+            let options = super::DiffOptions {
+                coalesce_threshold: Some(threshold),
+                ignore_paths: ignore_paths.iter().map(|path| path.to_string()).collect(),
+                ..Default::default()
+            };
+            let p = super::diff_with_options(&left, &right, &options);
+            if ignore_paths.is_empty() {
+                let mut doc = left.clone();
+                crate::patch(&mut doc, &p).unwrap();
+                assert_eq!(doc, right);
+            }
+            p
+        };
+
+        assert_eq!(
+            coalesced(0.6, &[]),
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "/a", "value": { "b": 10, "c": 20, "d": 3 } },
+                { "op": "replace", "path": "/e/3", "value": 5 },
+                { "op": "replace", "path": "/f", "value": { "g": 10, "i": 2 } },
+            ]))
+            .unwrap()
+        );
+        assert_eq!(
+            coalesced(0.0, &[]),
+            serde_json::from_value(json!([{ "op": "replace", "path": "", "value": right }]))
+                .unwrap()
+        );
+        assert_eq!(coalesced(1.0, &[]), super::diff(&left, &right));
+        // Objects containing ignored locations are diffed member by member
+        assert_eq!(
+            coalesced(0.6, &["/f/*"]),
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "/a", "value": { "b": 10, "c": 20, "d": 3 } },
+                { "op": "replace", "path": "/e/3", "value": 5 },
+            ]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn element_replacement() {
+        let left = json!({ "a": [1, { "b": 2 }, "c"], "d": 1 });
+        let right = json!({ "a": [1, "b", "c", 4], "d": 2 });
+        let options = super::DiffOptions {
+            element_replacement: super::ElementReplacement::RemoveAndAdd,
+            test_before_destructive: true,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "test", "path": "/a/1", "value": { "b": 2 } },
+                { "op": "add", "path": "/a/1", "value": "b" },
+                { "op": "remove", "path": "/a/2" },
+                { "op": "add", "path": "/a/3", "value": 4 },
+                { "op": "test", "path": "/d", "value": 1 },
+                { "op": "replace", "path": "/d", "value": 2 },
+            ]))
+            .unwrap()
+        );
+        let mut doc = left.clone();
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+    }
+
+    #[test]
+    fn element_replacement_with_moves_and_copies() {
+        use rand::{Rng, SeedableRng};
+
+        fn random_value(rng: &mut rand::rngs::StdRng, depth: usize) -> Value {
+            match rng.gen_range(0..if depth < 3 { 6 } else { 3 }) {
+                0 => Value::Null,
+                1 => Value::Bool(rng.gen()),
+                2 => json!(rng.gen_range(0..3)),
+                3 | 4 => (0..rng.gen_range(0..4))
+                    .map(|_| random_value(rng, depth + 1))
+                    .collect(),
+                _ => {
+                    let mut obj = serde_json::Map::new();
+                    for key in ["a", "b", "z"] {
+                        if rng.gen() {
+                            obj.insert(key.into(), random_value(rng, depth + 1));
+                        }
+                    }
+                    Value::Object(obj)
+                }
+            }
+        }
+
+        let mut pairs = vec![
+            (json!([null, [true]]), json!([[true], [true]])),
+            (
+                json!([3, { "z": false, "a": 1 }]),
+                json!([false, { "a": 1 }]),
+            ),
+        ];
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        pairs.extend((0..2_000).map(|_| (random_value(&mut rng, 0), random_value(&mut rng, 0))));
+
+        for element_replacement in [
+            super::ElementReplacement::Replace,
+            super::ElementReplacement::RemoveAndAdd,
+        ] {
+            for (detect_moves, detect_copies) in
+                [(false, false), (true, false), (false, true), (true, true)]
+            {
+                let options = super::DiffOptions {
+                    element_replacement,
+                    detect_moves,
+                    detect_copies,
+                    ..Default::default()
+                };
+                for (left, right) in &pairs {
+                    let p = super::diff_with_options(left, right, &options);
+                    let mut doc = left.clone();
+                    crate::patch(&mut doc, &p).unwrap_or_else(|e| panic!("{options:?} {p}: {e}"));
+                    assert_eq!(&doc, right, "{options:?} {p}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn null_equals_missing() {
+        let left = json!({ "a": null, "b": 1, "c": [null], "d": { "e": 2 }, "f": null });
+        let right = json!({ "b": null, "c": [], "d": { "e": null, "g": null }, "f": 3, "h": null });
+        let mut options = super::DiffOptions {
+            null_equals_missing: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::diff_with_options(&left, &right, &options),
+            serde_json::from_value(json!([
+                { "op": "replace", "path": "/b", "value": null },
+                { "op": "remove", "path": "/c/0" },
+                { "op": "replace", "path": "/d/e", "value": null },
+                { "op": "replace", "path": "/f", "value": 3 },
+            ]))
+            .unwrap()
+        );
+
+        options.remove_nulls = true;
+        assert_eq!(
+            super::diff_with_options(&left, &right, &options),
+            serde_json::from_value(json!([
+                { "op": "remove", "path": "/c/0" },
+                { "op": "remove", "path": "/d/e" },
+                { "op": "replace", "path": "/f", "value": 3 },
+                { "op": "remove", "path": "/b" },
+            ]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn descending_removals() {
+        let left = json!({
+            "a": [1, 2, 3, 4],
+            "b": [1, 2, 3, 4, 5, 6],
+            "c": [1, 2, 2, 3],
+            "d": [{ "id": 1 }, { "id": 2 }, { "id": 3 }],
+        });
+        let right = json!({
+            "a": [1, 2],
+            "b": [1, 4, 6],
+            "c": [3, 2],
+            "d": [{ "id": 3 }],
+        });
+        let mut options = super::DiffOptions {
+            unordered_arrays: ["/c".into()].into(),
+            array_keys: [("/d".into(), "id".into())].into(),
+            array_diff: super::ArrayDiffAlgorithm::Myers,
+            descending_removals: true,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "remove", "path": "/a/3" },
+                { "op": "remove", "path": "/a/2" },
+                { "op": "remove", "path": "/b/2" },
+                { "op": "remove", "path": "/b/1" },
+                { "op": "remove", "path": "/b/2" },
+                { "op": "remove", "path": "/c/2" },
+                { "op": "remove", "path": "/c/0" },
+                { "op": "remove", "path": "/d/1" },
+                { "op": "remove", "path": "/d/0" },
+            ]))
+            .unwrap()
+        );
+        let mut patched = left.clone();
+        crate::patch(&mut patched, &p).unwrap();
+        assert_eq!(
+            patched,
+            json!({ "a": [1, 2], "b": [1, 4, 6], "c": [2, 3], "d": [{ "id": 3 }] })
+        );
+
+        // Tests precede the removals they assert
+        options.test_before_destructive = true;
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p[..4],
+            serde_json::from_value::<crate::Patch>(json!([
+                { "op": "test", "path": "/a/3", "value": 4 },
+                { "op": "remove", "path": "/a/3" },
+                { "op": "test", "path": "/a/2", "value": 3 },
+                { "op": "remove", "path": "/a/2" },
+            ]))
+            .unwrap()[..]
+        );
+        let mut patched = left.clone();
+        crate::patch(&mut patched, &p).unwrap();
+        assert_eq!(patched["b"], right["b"]);
     }
 
-    fn modified(&mut self, _old: &'a Value, new: &'a Value) {
-        self.patch
-            .0
-            .push(super::PatchOperation::Replace(super::ReplaceOperation {
-                path: self.path.clone(),
-                value: new.clone(),
-            }));
-    }
-}
+    #[test]
+    fn detect_reorders() {
+        let left = json!({ "a": ["x", "y", "z", "w"], "b": [1, 2, 1, 3], "c": [1, 2] });
+        let right = json!({ "a": ["y", "z", "w", "x"], "b": [3, 1, 1, 2], "c": [2, 3] });
+        let options = super::DiffOptions {
+            detect_reorders: true,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "move", "from": "/a/0", "path": "/a/3" },
+                { "op": "move", "from": "/b/3", "path": "/b/0" },
+                { "op": "move", "from": "/b/3", "path": "/b/2" },
+                { "op": "replace", "path": "/c/0", "value": 2 },
+                { "op": "replace", "path": "/c/1", "value": 3 },
+            ]))
+            .unwrap()
+        );
+        let mut doc = left.clone();
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
 
-fn append_path(path: &mut String, key: &str) {
-    path.reserve(key.len());
-    for ch in key.chars() {
-        if ch == '~' {
-            *path += "~0";
-        } else if ch == '/' {
-            *path += "~1";
-        } else {
-            path.push(ch);
+        for (left, right) in [
+            (json!([3, 2, 1]), json!([1, 2, 3])),
+            (json!([1, [2], 3, [2], 4]), json!([[2], 4, 3, 1, [2]])),
+            (json!([]), json!([])),
+        ] {
+            let p = super::diff_with_options(&left, &right, &options);
+            let mut doc = left.clone();
+            crate::patch(&mut doc, &p).unwrap();
+            assert_eq!(doc, right);
         }
     }
-}
 
-/// Diff two JSON documents and generate a JSON Patch (RFC 6902).
-///
-/// # Example
-/// Diff two JSONs:
-///
-/// ```rust
-/// #[macro_use]
-/// use json_patch::{Patch, patch, diff};
-/// use serde_json::{json, from_value};
-///
-/// # pub fn main() {
-/// let left = json!({
-///   "title": "Goodbye!",
-///   "author" : {
-///     "givenName" : "John",
-///     "familyName" : "Doe"
-///   },
-///   "tags":[ "example", "sample" ],
-///   "content": "This will be unchanged"
-/// });
-///
-/// let right = json!({
-///   "title": "Hello!",
-///   "author" : {
-///     "givenName" : "John"
-///   },
-///   "tags": [ "example" ],
-///   "content": "This will be unchanged",
-///   "phoneNumber": "+01-123-456-7890"
-/// });
-///
-/// let p = diff(&left, &right);
-/// assert_eq!(p, from_value::<Patch>(json!([
-///   { "op": "remove", "path": "/author/familyName" },
-///   { "op": "remove", "path": "/tags/1" },
-///   { "op": "replace", "path": "/title", "value": "Hello!" },
-///   { "op": "add", "path": "/phoneNumber", "value": "+01-123-456-7890" },
-/// ])).unwrap());
-///
-/// let mut doc = left.clone();
-/// patch(&mut doc, &p).unwrap();
-/// assert_eq!(doc, right);
-///
-/// # }
-/// ```
-pub fn diff(left: &Value, right: &Value) -> super::Patch {
-    let mut differ = PatchDiffer::new();
-    treediff::diff(left, right, &mut differ);
-    differ.patch
-}
+    #[test]
+    fn array_diff() {
+        use super::ArrayDiffAlgorithm;
 
-#[cfg(test)]
-mod tests {
-    use serde_json::{json, Value};
+        let left = json!([{ "id": 1 }, { "id": 2 }, { "id": 3, "x": 0 }, 4, 4]);
+        let right = json!([{ "id": 1 }, { "id": 5 }, { "id": 2 }, { "id": 3, "x": 1 }, 4]);
+        let with = |array_diff| super::DiffOptions {
+            array_diff,
+            ..Default::default()
+        };
+        let aligned: crate::Patch = serde_json::from_value(json!([
+            { "op": "add", "path": "/1", "value": { "id": 5 } },
+            { "op": "replace", "path": "/3/x", "value": 1 },
+            { "op": "remove", "path": "/4" },
+        ]))
+        .unwrap();
+        for algorithm in [
+            ArrayDiffAlgorithm::Myers,
+            ArrayDiffAlgorithm::Patience,
+            ArrayDiffAlgorithm::Anchored,
+        ] {
+            let p = super::diff_with_options(&left, &right, &with(algorithm));
+            assert_eq!(p, aligned, "{algorithm:?}");
+        }
+        let p = super::diff_with_options(&left, &right, &with(ArrayDiffAlgorithm::Positional));
+        assert_eq!(p, super::diff(&left, &right));
+        assert!(p.len() > aligned.len());
 
-    #[test]
-    pub fn replace_all() {
-        let left = json!({"title": "Hello!"});
-        let p = super::diff(&left, &Value::Null);
+        // Duplicates between the anchors are only aligned by the Myers' algorithm
+        let left = json!(["a", 0, 0, 1, "b"]);
+        let right = json!(["a", 1, 0, 0, "b"]);
+        let p = super::diff_with_options(&left, &right, &with(ArrayDiffAlgorithm::Myers));
         assert_eq!(
             p,
             serde_json::from_value(json!([
-                { "op": "replace", "path": "", "value": null },
+                { "op": "add", "path": "/1", "value": 1 },
+                { "op": "remove", "path": "/4" },
             ]))
             .unwrap()
         );
-        let mut left = json!({"title": "Hello!"});
-        crate::patch(&mut left, &p).unwrap();
+        let p = super::diff_with_options(&left, &right, &with(ArrayDiffAlgorithm::Anchored));
+        assert_eq!(p.len(), 4);
+        let mut doc = left.clone();
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+
+        // Huge arrays with a few elements inserted, removed and changed
+        let left: Vec<Value> = (0..50_000).map(|i| json!({ "t": i })).collect();
+        let mut right = left.clone();
+        right.drain(100..110);
+        right.insert(20_000, json!({ "t": -1 }));
+        right[30_000] = json!({ "t": -2 });
+        let (left, right) = (Value::from(left), Value::from(right));
+        let p = super::diff_with_options(&left, &right, &with(ArrayDiffAlgorithm::Chunked));
+        assert_eq!(p.len(), 12);
+        let mut doc = left.clone();
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+
+        let options = super::DiffOptions {
+            ignore_paths: vec!["/1".into()],
+            append_to_end: true,
+            ..with(ArrayDiffAlgorithm::Patience)
+        };
+        let left = json!([0, "ignored", 2]);
+        let right = json!([0, 2, 3]);
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([{ "op": "add", "path": "/-", "value": 3 }])).unwrap()
+        );
     }
 
     #[test]
-    pub fn diff_empty_key() {
-        let left = json!({"title": "Something", "": "Hello!"});
-        let right = json!({"title": "Something", "": "Bye!"});
-        let p = super::diff(&left, &right);
+    fn element_matcher() {
+        use super::MatchQuality;
+
+        fn by_sku(left: &Value, right: &Value) -> MatchQuality {
+            match (left.get("sku"), right.get("sku")) {
+                (Some(left), Some(right)) if left == right => MatchQuality::Full,
+                _ => MatchQuality::NoMatch,
+            }
+        }
+        let left = json!({
+            "lines": [
+                { "sku": "A", "amount": 1 },
+                { "sku": "B", "amount": 2 },
+                { "sku": "C", "amount": 3 },
+            ],
+            "tags": [1, 2],
+        });
+        let right = json!({
+            "lines": [
+                { "sku": "B", "amount": 5 },
+                { "sku": "D", "amount": 1 },
+                { "sku": "C", "amount": 3 },
+            ],
+            "tags": [2, 1],
+        });
+        let options = super::DiffOptions {
+            element_matcher: Some(by_sku),
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
         assert_eq!(
             p,
             serde_json::from_value(json!([
-                { "op": "replace", "path": "/", "value": "Bye!" },
+                { "op": "remove", "path": "/lines/0" },
+                { "op": "replace", "path": "/lines/0/amount", "value": 5 },
+                { "op": "add", "path": "/lines/1", "value": { "sku": "D", "amount": 1 } },
+                // Nothing matches, so diffed by position
+                { "op": "replace", "path": "/tags/0", "value": 2 },
+                { "op": "replace", "path": "/tags/1", "value": 1 },
+            ]))
+            .unwrap()
+        );
+
+        // Better matches are paired first, reordering elements
+        fn by_closeness(left: &Value, right: &Value) -> MatchQuality {
+            match (left.as_f64(), right.as_f64()) {
+                (Some(left), Some(right)) => {
+                    MatchQuality::Partial((1.0 / (1.0 + (left - right) * (left - right))) as f32)
+                }
+                _ => MatchQuality::NoMatch,
+            }
+        }
+        let options = super::DiffOptions {
+            element_matcher: Some(by_closeness),
+            ..Default::default()
+        };
+        let left = json!([10, 20.5]);
+        let right = json!([20, 10, 30]);
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([
+                { "op": "move", "from": "/1", "path": "/0" },
+                { "op": "replace", "path": "/0", "value": 20 },
+                { "op": "add", "path": "/2", "value": 30 },
             ]))
             .unwrap()
         );
-        let mut left_patched = json!({"title": "Something", "": "Hello!"});
-        crate::patch(&mut left_patched, &p).unwrap();
-        assert_eq!(left_patched, right);
     }
 
     #[test]
-    pub fn add_all() {
-        let right = json!({"title": "Hello!"});
-        let p = super::diff(&Value::Null, &right);
+    fn patch_budget() {
+        let left = json!({ "a": [1, 2, 3], "b": "x" });
+        let right = json!({ "a": [4, 5, 6], "b": "y" });
+        let whole: crate::Patch =
+            serde_json::from_value(json!([{ "op": "replace", "path": "", "value": right }]))
+                .unwrap();
+        let full = super::diff(&left, &right);
+        assert_eq!(full.len(), 4);
+
+        let with_budget = |max_operations, max_patch_bytes| super::DiffOptions {
+            max_operations,
+            max_patch_bytes,
+            ..Default::default()
+        };
+        let size = full.to_string().len();
+        for (options, expected) in [
+            (with_budget(Some(4), None), &full),
+            (with_budget(Some(3), None), &whole),
+            (with_budget(None, Some(size)), &full),
+            (with_budget(None, Some(size - 1)), &whole),
+        ] {
+            assert_eq!(&super::diff_with_options(&left, &right, &options), expected);
+            assert_eq!(&super::diff_owned(&left, right.clone(), &options), expected);
+            assert_eq!(&super::Differ::new(options).diff(&left, &right), expected);
+        }
+
+        let options = super::DiffOptions {
+            test_before_destructive: true,
+            ..with_budget(Some(1), None)
+        };
+        let pointer = "/a".parse().unwrap();
         assert_eq!(
-            p,
+            super::diff_at_with_options(&left, &right, &pointer, &options),
             serde_json::from_value(json!([
-                { "op": "replace", "path": "", "value": { "title": "Hello!" } },
+                { "op": "test", "path": "/a", "value": [1, 2, 3] },
+                { "op": "replace", "path": "/a", "value": [4, 5, 6] },
+            ]))
+            .unwrap()
+        );
+
+        // Values missing on one side are added or removed as a whole when over budget
+        let options = super::DiffOptions {
+            test_before_destructive: true,
+            ..with_budget(Some(0), Some(0))
+        };
+        let pointer = "/c".parse().unwrap();
+        let (without, with) = (json!({}), json!({ "c": { "d": [1] } }));
+        assert_eq!(
+            super::diff_at_with_options(&without, &with, &pointer, &options),
+            serde_json::from_value(json!([
+                { "op": "add", "path": "/c", "value": { "d": [1] } },
+            ]))
+            .unwrap()
+        );
+        assert_eq!(
+            super::diff_at_with_options(&with, &without, &pointer, &options),
+            serde_json::from_value(json!([
+                { "op": "test", "path": "/c", "value": { "d": [1] } },
+                { "op": "remove", "path": "/c" },
             ]))
             .unwrap()
         );
+        assert!(super::diff_at_with_options(&without, &without, &pointer, &options).is_empty());
     }
 
     #[test]
-    pub fn remove_all() {
-        let left = json!(["hello", "bye"]);
-        let right = json!([]);
-        let p = super::diff(&left, &right);
+    fn number_tolerance() {
+        let left = json!({ "a": 1.000000000001, "b": [100.0, 2], "c": 0.5 });
+        let right = json!({ "a": 1.0, "b": [100.5, 2], "c": 0.6 });
+        let mut options = super::DiffOptions {
+            number_tolerance: Some(super::NumberTolerance {
+                absolute: 1e-9,
+                relative: 0.01,
+            }),
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([{ "op": "replace", "path": "/c", "value": 0.6 }]))
+                .unwrap()
+        );
+
+        options.max_depth = Some(1);
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([{ "op": "replace", "path": "/c", "value": 0.6 }]))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn number_equality() {
+        use super::NumberEquality;
+
+        let left: Value = serde_json::from_str(
+            r#"{ "a": 1, "b": [2.0, -3], "c": 1e2, "d": 18446744073709551615, "e": 0.5 }"#,
+        )
+        .unwrap();
+        let right: Value =
+            serde_json::from_str(r#"{ "a": 1.0, "b": [2, -3.0], "c": 100, "d": -1, "e": 0.25 }"#)
+                .unwrap();
+        let mut options = super::DiffOptions {
+            number_equality: NumberEquality::Numeric,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
         assert_eq!(
             p,
             serde_json::from_value(json!([
-                { "op": "remove", "path": "/0" },
-                { "op": "remove", "path": "/0" },
+                { "op": "replace", "path": "/d", "value": -1 },
+                { "op": "replace", "path": "/e", "value": 0.25 },
             ]))
             .unwrap()
         );
+
+        // Tolerance applies on top of the policy
+        options.number_tolerance = Some(super::NumberTolerance {
+            absolute: 0.5,
+            relative: 0.0,
+        });
+        let p = super::diff_with_options(&left, &right, &options);
+        assert_eq!(
+            p,
+            serde_json::from_value(json!([{ "op": "replace", "path": "/d", "value": -1 }]))
+                .unwrap()
+        );
+
+        options.max_depth = Some(0);
+        options.number_tolerance = None;
+        assert_eq!(super::diff_with_options(&left, &right, &options).len(), 1);
+        assert!(super::diff_with_options(&left["b"], &right["b"], &options).is_empty());
+
+        let p = super::diff(&left, &right);
+        assert_eq!(p.len(), 6);
     }
 
     #[test]
-    pub fn remove_tail() {
-        let left = json!(["hello", "bye", "hi"]);
-        let right = json!(["hello"]);
-        let p = super::diff(&left, &right);
+    fn diff_at() {
+        use crate::Pointer;
+
+        let left = json!({
+            "a": { "b": { "x": 1, "y": [10, 20, 30] }, "c": 1 },
+            "list": [{ "k": 1 }, { "k": 2 }],
+        });
+        let right = json!({
+            "a": { "b": { "x": 2, "y": [10, 20, 30], "z": [10, 20, 30] }, "c": 2 },
+            "list": [{ "k": 1 }, { "k": 3 }],
+            "new": true,
+        });
+        let at = |pointer: &str, options: &super::DiffOptions| {
+            let pointer = Pointer::parse(pointer).unwrap();
+            let p = super::diff_at_with_options(&left, &right, &pointer, options);
+            let mut doc = left.clone();
+            crate::patch(&mut doc, &p).unwrap();
+            if options.ignore_paths.is_empty() {
+                assert_eq!(
+                    crate::resolve(&doc, &pointer),
+                    crate::resolve(&right, &pointer)
+                );
+            }
+            p
+        };
+        let defaults = super::DiffOptions::default();
+        assert_eq!(
+            at("/a/b", &defaults),
+            serde_json::from_value::<crate::Patch>(json!([
+                { "op": "replace", "path": "/a/b/x", "value": 2 },
+                { "op": "add", "path": "/a/b/z", "value": [10, 20, 30] },
+            ]))
+            .unwrap()
+        );
+        assert_eq!(at("", &defaults), super::diff(&left, &right));
+        assert_eq!(at("/list/1/k", &defaults).len(), 1);
+        assert!(at("/a/b/y", &defaults).is_empty());
+        assert!(at("/missing", &defaults).is_empty());
+        assert_eq!(at("/new", &defaults)[0].path(), "/new");
+
+        // Options refer to the whole documents
+        let options = super::DiffOptions {
+            ignore_paths: vec!["/a/b/x".into()],
+            max_depth: Some(2),
+            detect_copies: true,
+            test_before_destructive: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            at("/a", &options),
+            serde_json::from_value::<crate::Patch>(json!([
+                { "op": "test", "path": "/a/b", "value": { "x": 1, "y": [10, 20, 30] } },
+                { "op": "replace", "path": "/a/b", "value": { "x": 2, "y": [10, 20, 30], "z": [10, 20, 30] } },
+                { "op": "test", "path": "/a/c", "value": 1 },
+                { "op": "replace", "path": "/a/c", "value": 2 },
+            ]))
+            .unwrap()
+        );
+        let options = super::DiffOptions {
+            ignore_paths: vec!["/a/b/x".into()],
+            detect_copies: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            at("/a/b", &options),
+            serde_json::from_value::<crate::Patch>(json!([
+                { "op": "copy", "from": "/a/b/y", "path": "/a/b/z" },
+            ]))
+            .unwrap()
+        );
+
+        let removed = super::diff_at_with_options(
+            &right,
+            &left,
+            &Pointer::parse("/new").unwrap(),
+            &super::DiffOptions {
+                test_before_destructive: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            removed,
+            serde_json::from_value::<crate::Patch>(json!([
+                { "op": "test", "path": "/new", "value": true },
+                { "op": "remove", "path": "/new" },
+            ]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn reused_differ() {
+        let options = super::DiffOptions {
+            detect_moves: true,
+            detect_copies: true,
+            depth_limit: Some(2),
+            ..Default::default()
+        };
+        let mut differ = super::Differ::new(options.clone());
+        let snapshots = [
+            json!({ "a": { "x": [1, 2, 3] }, "b": 1 }),
+            json!({ "c": { "x": [1, 2, 3] }, "b": 1, "d": { "x": [1, 2, 3] } }),
+            json!([1, 2]),
+            json!({ "a": { "b": { "c": { "d": 1 } } } }),
+            json!({ "a": { "b": { "c": { "d": 2 } } } }),
+            json!({ "b": 1 }),
+        ];
+        for pair in snapshots.windows(2) {
+            let expected = super::try_diff_with_options(&pair[0], &pair[1], &options);
+            let actual = differ.try_diff(&pair[0], &pair[1]);
+            match expected {
+                Ok(expected) => assert_eq!(actual.unwrap(), expected),
+                Err(expected) => assert_eq!(actual.unwrap_err().to_string(), expected.to_string()),
+            }
+        }
+        assert_eq!(differ.options(), &options);
+    }
+
+    #[test]
+    fn test_before_destructive() {
+        let left = json!({ "a": 1, "b": [1, 2], "c": { "x": true } });
+        let right = json!({ "a": 2, "b": [1], "d": { "x": true } });
+        let options = super::DiffOptions {
+            test_before_destructive: true,
+            detect_moves: true,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
         assert_eq!(
             p,
             serde_json::from_value(json!([
-                { "op": "remove", "path": "/1" },
-                { "op": "remove", "path": "/1" },
+                { "op": "test", "path": "/a", "value": 1 },
+                { "op": "replace", "path": "/a", "value": 2 },
+                { "op": "test", "path": "/b/1", "value": 2 },
+                { "op": "remove", "path": "/b/1" },
+                { "op": "test", "path": "/c", "value": { "x": true } },
+                { "op": "move", "from": "/c", "path": "/d" },
             ]))
             .unwrap()
         );
+
+        let mut doc = left.clone();
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+
+        let mut drifted = json!({ "a": 5, "b": [1, 2], "c": { "x": true } });
+        crate::patch(&mut drifted, &p).unwrap_err();
     }
+
     #[test]
-    pub fn replace_object() {
-        let left = json!(["hello", "bye"]);
-        let right = json!({"hello": "bye"});
-        let p = super::diff(&left, &right);
+    fn append_to_end() {
+        let mut options = super::DiffOptions {
+            append_to_end: true,
+            ..Default::default()
+        };
+        options
+            .array_keys
+            .insert("/keyed".to_string(), "id".to_string());
+        let left = json!({ "log": [1], "keyed": [{ "id": 1 }], "obj": {} });
+        let right = json!({
+            "log": [1, 2, 3],
+            "keyed": [{ "id": 0 }, { "id": 1 }, { "id": 2 }],
+            "obj": { "0": 1 },
+        });
+        let p = super::diff_with_options(&left, &right, &options);
         assert_eq!(
             p,
             serde_json::from_value(json!([
-                { "op": "add", "path": "/hello", "value": "bye" },
-                { "op": "remove", "path": "/0" },
-                { "op": "remove", "path": "/0" },
+                { "op": "add", "path": "/keyed/0", "value": { "id": 0 } },
+                { "op": "add", "path": "/keyed/-", "value": { "id": 2 } },
+                { "op": "add", "path": "/log/-", "value": 2 },
+                { "op": "add", "path": "/log/-", "value": 3 },
+                { "op": "add", "path": "/obj/0", "value": 1 },
             ]))
             .unwrap()
         );
+
+        // Patch still applies after the log has grown
+        let mut doc = json!({ "log": [1, 10], "keyed": [{ "id": 1 }], "obj": {} });
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc["log"], json!([1, 10, 2, 3]));
     }
 
     #[test]
@@ -240,4 +3779,355 @@ mod tests {
         crate::patch(&mut left, &patch).unwrap();
         assert_eq!(left, right);
     }
+
+    #[test]
+    fn diff_typed_values() {
+        use alloc::collections::BTreeMap;
+
+        let left = BTreeMap::from([("a", vec![1, 2]), ("b", vec![])]);
+        let right = BTreeMap::from([("a", vec![1]), ("c", vec![3])]);
+        let mut doc = serde_json::to_value(&left).unwrap();
+        let p = super::diff_typed(&left, &right).unwrap();
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, serde_json::to_value(&right).unwrap());
+
+        // Maps with non-string keys cannot be represented as JSON
+        let left = BTreeMap::from([((1, 2), 3)]);
+        assert!(super::diff_typed(&left, &left).is_err());
+    }
+
+    #[test]
+    fn diff_owned_values() {
+        let left = json!({
+            "a": 1,
+            "keyed": [{ "id": 1, "v": 1 }, { "id": 2 }],
+            "set": [1, 2],
+            "deep": { "x": { "y": 1 } },
+        });
+        let right = json!({
+            "a": { "b": 2 },
+            "keyed": [{ "id": 3 }, { "id": 1, "v": 2 }],
+            "set": [1, 3],
+            "deep": { "x": { "y": 2 } },
+            "new": [1, 2],
+        });
+        let options = super::DiffOptions {
+            array_keys: [("/keyed".to_owned(), "id".to_owned())].into(),
+            unordered_arrays: ["/set".to_owned()].into(),
+            max_depth: Some(2),
+            test_before_destructive: true,
+            append_to_end: true,
+            ..Default::default()
+        };
+        let expected = super::diff_with_options(&left, &right, &options);
+        let p = super::diff_owned(&left, right.clone(), &options);
+        assert_eq!(p, expected);
+
+        let mut doc = left.clone();
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+    }
+
+    #[test]
+    fn deeply_nested() {
+        fn nested(depth: usize, leaf: Value) -> Value {
+            (0..depth).fold(leaf, |value, idx| {
+                if idx % 2 == 0 {
+                    Value::Array(vec![value])
+                } else {
+                    Value::Object([("a".to_owned(), value)].into_iter().collect())
+                }
+            })
+        }
+
+        let depth = 2_000;
+        let left = nested(depth, json!(1));
+        let right = nested(depth, json!(2));
+        let p = super::diff(&left, &right);
+        assert_eq!(p.len(), 1);
+        assert_eq!(p[0].path().len(), depth / 2 * "/0/a".len());
+
+        let options = super::DiffOptions {
+            depth_limit: Some(100),
+            ..Default::default()
+        };
+        let err = super::try_diff_with_options(&left, &right, &options).unwrap_err();
+        let expected = "/a/0".repeat(50) + "/a";
+        assert!(matches!(err, super::DiffError::TooDeep { path } if path == expected));
+
+        // Dropping values recursively would overflow the stack, too
+        core::mem::forget((left, right));
+    }
+
+    #[test]
+    fn visitor() {
+        #[derive(Default)]
+        struct Events(Vec<String>);
+
+        impl super::DiffVisitor for Events {
+            fn enter(&mut self, path: &str) {
+                self.0.push(format!("enter {path}"));
+            }
+            fn leave(&mut self, path: &str) {
+                self.0.push(format!("leave {path}"));
+            }
+            fn added(&mut self, path: &str, value: &Value) {
+                self.0.push(format!("added {path} {value}"));
+            }
+            fn removed(&mut self, path: &str, value: &Value) {
+                self.0.push(format!("removed {path} {value}"));
+            }
+            fn modified(&mut self, path: &str, old: &Value, new: &Value) {
+                self.0.push(format!("modified {path} {old} {new}"));
+            }
+            fn unchanged(&mut self, path: &str, value: &Value) {
+                self.0.push(format!("unchanged {path} {value}"));
+            }
+        }
+
+        let left = json!({ "a": [1, 2], "b": { "c": true }, "d": 0 });
+        let right = json!({ "a": [1], "b": { "c": false }, "e": null });
+        let options = super::DiffOptions {
+            ignore_paths: vec!["/e".to_string()],
+            ..Default::default()
+        };
+        let mut events = Events::default();
+        let p = super::diff_with_visitor(&left, &right, &options, &mut events);
+        assert_eq!(p, super::diff_with_options(&left, &right, &options));
+        assert_eq!(
+            events.0,
+            [
+                "enter /a",
+                "enter /a/0",
+                "unchanged /a/0 1",
+                "leave /a/0",
+                "enter /a/1",
+                "removed /a/1 2",
+                "leave /a/1",
+                "leave /a",
+                "enter /b",
+                "enter /b/c",
+                "modified /b/c true false",
+                "leave /b/c",
+                "leave /b",
+                "enter /e",
+                "leave /e",
+                "enter /d",
+                "removed /d 0",
+                "leave /d",
+            ]
+        );
+    }
+
+    #[test]
+    fn lazy_iter() {
+        let cases = [
+            (
+                json!({ "a": { "k": [1, 2] }, "b": {}, "c": 1, "d": [1, 2, 3] }),
+                json!({ "a": {}, "b": { "k": [1, 2] }, "d": [1, 3], "e": { "c": 1 } }),
+            ),
+            (
+                json!([1, [2, 3], { "x": 4 }]),
+                json!([0, [2], { "x": 5 }, 6]),
+            ),
+            (json!({ "a": 1 }), json!([1])),
+            (json!(null), json!(null)),
+        ];
+        for (left, right) in &cases {
+            let ops: Vec<_> = super::diff_iter(left, right).collect();
+            assert_eq!(crate::Patch(ops), super::diff(left, right));
+        }
+
+        let mut iter = super::diff_iter(&cases[1].0, &cases[1].1);
+        assert_eq!(iter.next().unwrap().path(), "/0");
+        assert_eq!(iter.next().unwrap().path(), "/1/1");
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel() {
+        let cases = [
+            (
+                json!({ "a": { "k": [1, 2] }, "b": {}, "c": 1, "d": [1, 2, 3] }),
+                json!({ "a": {}, "b": { "k": [1, 2] }, "d": [1, 3], "e": { "c": 1 } }),
+            ),
+            (
+                json!([{ "id": 1 }, { "id": 2, "x": true }]),
+                json!([{ "id": 2, "x": false }, { "id": 1 }, { "id": 3 }]),
+            ),
+            (json!([{ "id": 1 }, 3, [4]]), json!([{ "id": 2 }, [4, 5]])),
+            (
+                json!({ "x": { "y": true }, "list": [1] }),
+                json!({ "list": [1, { "y": true }] }),
+            ),
+            (json!({ "a": 1 }), json!([1])),
+            (json!(null), json!(null)),
+        ];
+        let options = [
+            super::DiffOptions::default(),
+            super::DiffOptions {
+                detect_moves: true,
+                detect_copies: true,
+                test_before_destructive: true,
+                ..Default::default()
+            },
+            super::DiffOptions {
+                array_keys: [(String::new(), "id".to_string())].into(),
+                ignore_paths: vec!["/c".to_string()],
+                append_to_end: true,
+                ..Default::default()
+            },
+            super::DiffOptions {
+                max_depth: Some(1),
+                ..Default::default()
+            },
+        ];
+        for (left, right) in &cases {
+            for options in &options {
+                assert_eq!(
+                    super::par_diff_with_options(left, right, options),
+                    super::diff_with_options(left, right, options),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn similarity() {
+        use super::similarity;
+
+        assert_eq!(similarity(&json!({}), &json!({})), 1.0);
+        assert_eq!(similarity(&json!({}), &json!([])), 0.0);
+        assert_eq!(
+            similarity(&json!({ "a": 1 }), &json!({ "a": 1, "b": {} })),
+            2.0 / 3.0
+        );
+        assert_eq!(similarity(&json!({ "a": 1 }), &json!([1])), 0.0);
+        assert_eq!(
+            similarity(
+                &json!({ "a": [1, 2, { "x": true }], "b": "b" }),
+                &json!({ "a": [1, 3, { "x": true }], "c": [] })
+            ),
+            0.5
+        );
+    }
+
+    #[test]
+    fn op_order() {
+        use super::OpOrder;
+
+        let left = json!({ "b": 1, "c": { "x": 1 }, "d": 2, "e": [1, 2, 3] });
+        let right = json!({ "a": 0, "b": 2, "c": { "y": 1 }, "e": [1, 5] });
+        let paths = |op_order| {
+            let options = super::DiffOptions {
+                op_order,
+                ..Default::default()
+            };
+            let p = super::diff_with_options(&left, &right, &options);
+            let mut doc = left.clone();
+            crate::patch(&mut doc, &p).unwrap();
+            assert_eq!(doc, right);
+            p.iter()
+                .map(|op| format!("{} {}", op.op_kind(), op.path()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths(OpOrder::Document),
+            [
+                "replace /b",
+                "add /c/y",
+                "remove /c/x",
+                "replace /e/1",
+                "remove /e/2",
+                "add /a",
+                "remove /d",
+            ]
+        );
+        assert_eq!(
+            paths(OpOrder::SortedByPath),
+            [
+                "add /a",
+                "replace /b",
+                "remove /c/x",
+                "add /c/y",
+                "remove /d",
+                "replace /e/1",
+                "remove /e/2",
+            ]
+        );
+        assert_eq!(
+            paths(OpOrder::RemovesLast),
+            [
+                "add /a",
+                "replace /b",
+                "add /c/y",
+                "remove /c/x",
+                "replace /e/1",
+                "remove /e/2",
+                "remove /d",
+            ]
+        );
+
+        // Moves between members pin the order of the members they connect
+        let left = json!({ "z": { "k": [1, 2] }, "m": 1, "a": {} });
+        let right = json!({ "a": { "k": [1, 2] }, "b": 2 });
+        let options = super::DiffOptions {
+            detect_moves: true,
+            test_before_destructive: true,
+            op_order: OpOrder::RemovesLast,
+            ..Default::default()
+        };
+        let p = super::diff_with_options(&left, &right, &options);
+        let mut doc = left.clone();
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+        let document = super::DiffOptions {
+            op_order: OpOrder::Document,
+            ..options
+        };
+        assert_eq!(p, super::diff_with_options(&left, &right, &document));
+    }
+
+    #[test]
+    fn op_order_applies() {
+        use super::OpOrder;
+
+        let cases = [
+            (
+                json!({ "a": { "k": [1, 2] }, "b": {}, "c": 1, "d": [1, 2, 3] }),
+                json!({ "a": {}, "b": { "k": [1, 2] }, "d": [1, 3], "e": { "c": 1 } }),
+            ),
+            (
+                json!([{ "id": 1, "x": { "b": 1, "a": 2 } }, { "id": 2, "x": true }]),
+                json!([{ "id": 2, "x": false }, { "id": 1, "x": { "c": 1 } }, { "id": 3 }]),
+            ),
+            (
+                json!({ "x": { "y": true, "z": [1] }, "list": [1], "w": { "0": 1, "q": 2 } }),
+                json!({ "list": [1, { "y": true }], "x": { "z": [1, 2] }, "w": { "1": 1 } }),
+            ),
+        ];
+        for (left, right) in &cases {
+            for op_order in [
+                OpOrder::Document,
+                OpOrder::SortedByPath,
+                OpOrder::RemovesLast,
+            ] {
+                for (detect, test) in [(false, false), (true, false), (true, true)] {
+                    let options = super::DiffOptions {
+                        detect_moves: detect,
+                        detect_copies: detect,
+                        test_before_destructive: test,
+                        array_keys: [(String::new(), "id".to_string())].into(),
+                        op_order,
+                        ..Default::default()
+                    };
+                    let p = super::diff_with_options(left, right, &options);
+                    let mut doc = left.clone();
+                    crate::patch(&mut doc, &p).unwrap();
+                    assert_eq!(&doc, right, "{:?}", options);
+                    assert_eq!(super::diff_owned(left, right.clone(), &options), p);
+                }
+            }
+        }
+    }
 }
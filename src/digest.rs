@@ -0,0 +1,271 @@
+use crate::deep::drop_operations;
+use crate::{apply_patches, revert, PatchError, PatchOperation};
+use alloc::{vec, vec::Vec};
+use serde_json::Value;
+use thiserror::Error;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// 64-bit FNV-1a hash, which does not depend on the platform or on random seeds.
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_str(&mut self, s: &str) {
+        self.write_u64(s.len() as u64);
+        self.write(s.as_bytes());
+    }
+}
+
+/// Returns a stable 64-bit digest of the JSON value, to cheaply detect changes of documents (for
+/// example, to implement ETag-style optimistic concurrency checks).
+///
+/// The digest follows the same notion of equality as `PartialEq` for `serde_json::Value` and the
+/// differ (with the default options): equal values always have equal digests, in particular
+/// object members are hashed in the order of their keys, so neither the insertion order nor the
+/// `preserve_order` feature of `serde_json` affect the digest. Numbers are hashed as `==`
+/// compares them: `1` and `1.0` are different values with different digests.
+///
+/// The digest is canonical: it does not depend on the platform, on random seeds or on the
+/// process, and it does not change between releases with the same major version (unless a new
+/// major version of `serde_json` changes the equality of values). Different values may collide,
+/// though it is unlikely, and the digest is not a cryptographic hash: it must not be relied upon
+/// when documents are crafted by an adversary. Deeply nested values are digested without
+/// recursion.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::digest;
+/// use serde_json::{from_str, json, Value};
+///
+/// let doc: Value = from_str(r#"{ "b": [1, 2], "a": null }"#).unwrap();
+/// assert_eq!(digest(&doc), digest(&json!({ "a": null, "b": [1, 2] })));
+/// assert_ne!(digest(&doc), digest(&json!({ "a": null, "b": [2, 1] })));
+/// ```
+pub fn digest(value: &Value) -> u64 {
+    let mut hasher = Fnv(FNV_OFFSET);
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Null => hasher.write(&[0]),
+            Value::Bool(b) => hasher.write(&[1, u8::from(*b)]),
+            Value::Number(n) => {
+                hasher.write(&[2]);
+                if let Some(n) = n.as_u64() {
+                    hasher.write(&[0]);
+                    hasher.write_u64(n);
+                } else if let Some(n) = n.as_i64() {
+                    hasher.write(&[1]);
+                    hasher.write_u64(n as u64);
+                } else {
+                    hasher.write(&[2]);
+                    let f = n.as_f64().unwrap_or_default();
+                    // -0.0 == 0.0, so both zeros must hash the same
+                    let f = if f == 0.0 { 0.0 } else { f };
+                    hasher.write_u64(f.to_bits());
+                }
+            }
+            Value::String(s) => {
+                hasher.write(&[3]);
+                hasher.write_str(s);
+            }
+            Value::Array(arr) => {
+                hasher.write(&[4]);
+                hasher.write_u64(arr.len() as u64);
+                stack.extend(arr.iter().rev());
+            }
+            Value::Object(obj) => {
+                hasher.write(&[5]);
+                hasher.write_u64(obj.len() as u64);
+                // Keys are hashed upfront, so the digest of every member value is still
+                // delimited by the digests of the other members' values
+                let mut entries: Vec<_> = obj.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                for (key, _) in &entries {
+                    hasher.write_str(key);
+                }
+                stack.extend(entries.into_iter().rev().map(|(_, value)| value));
+            }
+        }
+    }
+    hasher.0
+}
+
+/// This type represents all possible errors that can occur when applying JSON patch with
+/// [`patch_verified`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum VerifyError {
+    /// Patch cannot be applied to the document.
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+    /// Patched document is not the one the producer of the patch expected.
+    #[error("patched document digest mismatch: expected {expected:016x}, found {actual:016x}")]
+    DigestMismatch {
+        /// Expected digest of the patched document.
+        expected: u64,
+        /// Digest of the document produced by the patch.
+        actual: u64,
+    },
+}
+
+/// Patch provided JSON document (given as `serde_json::Value`) in-place, same as
+/// [`patch`](crate::patch), and verify that the patched document has the [`digest`] the
+/// producer of the patch computed for its own result, to detect implementations applying
+/// patches differently. If the patch fails or the digests differ, the document is left
+/// unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{digest, patch_verified, Patch, VerifyError};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "items": [1, 2] });
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/items/-", "value": 3 }
+/// ])).unwrap();
+///
+/// let expected = digest(&json!({ "items": [1, 2, 3] }));
+/// patch_verified(&mut doc, &p, expected).unwrap();
+/// assert_eq!(doc, json!({ "items": [1, 2, 3] }));
+///
+/// // Applying the patch again produces another document
+/// let err = patch_verified(&mut doc, &p, expected).unwrap_err();
+/// assert!(matches!(err, VerifyError::DigestMismatch { .. }));
+/// assert_eq!(doc, json!({ "items": [1, 2, 3] }));
+/// ```
+pub fn patch_verified(
+    doc: &mut Value,
+    patch: &[PatchOperation],
+    expected_digest: u64,
+) -> Result<(), VerifyError> {
+    let mut undo_stack = Vec::with_capacity(patch.len());
+    if let Err(e) = apply_patches(doc, patch, Some(&mut undo_stack)) {
+        revert(doc, undo_stack);
+        return Err(e.into());
+    }
+    let actual = digest(doc);
+    if actual != expected_digest {
+        revert(doc, undo_stack);
+        return Err(VerifyError::DigestMismatch {
+            expected: expected_digest,
+            actual,
+        });
+    }
+    drop_operations(undo_stack);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{digest, patch_verified, VerifyError};
+    use crate::Patch;
+    use serde_json::{from_value, json, Value};
+
+    #[test]
+    fn digest_values() {
+        let left: Value =
+            serde_json::from_str(r#"{ "a": [1, -2, 3.5], "b": { "c": null } }"#).unwrap();
+        let right: Value =
+            serde_json::from_str(r#"{ "b": { "c": null }, "a": [1, -2, 3.5] }"#).unwrap();
+        assert_eq!(digest(&left), digest(&right));
+
+        // Digests are stable across platforms and releases
+        assert_eq!(digest(&json!(null)), 0xaf63_bd4c_8601_b7df);
+
+        let values = [
+            json!(null),
+            json!(false),
+            json!(true),
+            json!(0),
+            json!(-1),
+            json!(1.0),
+            json!(""),
+            json!("0"),
+            json!([]),
+            json!([[]]),
+            json!([[], []]),
+            json!({}),
+            json!({ "": null }),
+            json!({ "a": "b" }),
+            json!({ "ab": "" }),
+            json!({ "a": [1], "b": [] }),
+            json!({ "a": [], "b": [1] }),
+        ];
+        for (i, left) in values.iter().enumerate() {
+            for right in &values[i + 1..] {
+                assert_ne!(digest(left), digest(right), "{} {}", left, right);
+            }
+        }
+
+        let zero: Value = serde_json::from_str("0.0").unwrap();
+        let negative_zero: Value = serde_json::from_str("-0.0").unwrap();
+        assert_eq!(zero, negative_zero);
+        assert_eq!(digest(&zero), digest(&negative_zero));
+    }
+
+    #[cfg(feature = "diff")]
+    #[test]
+    fn digest_matches_diff() {
+        let values = [
+            json!({ "a": 1, "b": [1, 2] }),
+            json!({ "b": [1, 2], "a": 1 }),
+            json!({ "a": 1.0, "b": [1, 2] }),
+            json!({ "a": 1, "b": [2, 1] }),
+            json!([1, { "c": "d" }]),
+            json!([1, { "c": "d" }, null]),
+        ];
+        for left in &values {
+            for right in &values {
+                let same = crate::diff(left, right).is_empty();
+                assert_eq!(same, left == right);
+                assert_eq!(same, digest(left) == digest(right), "{} {}", left, right);
+            }
+        }
+    }
+
+    #[test]
+    fn verified_patch() {
+        let original = json!({ "a": { "b": 1 }, "c": [] });
+        let p: Patch = from_value(json!([
+            { "op": "move", "from": "/a/b", "path": "/c/0" },
+            { "op": "remove", "path": "/a" },
+        ]))
+        .unwrap();
+        let expected = digest(&json!({ "c": [1] }));
+
+        let mut doc = original.clone();
+        patch_verified(&mut doc, &p, expected).unwrap();
+        assert_eq!(doc, json!({ "c": [1] }));
+
+        let mut doc = original.clone();
+        let err = patch_verified(&mut doc, &p, expected ^ 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "patched document digest mismatch: expected {:016x}, found {:016x}",
+                expected ^ 1,
+                expected
+            )
+        );
+        assert_eq!(doc, original);
+
+        let mut doc = json!({ "c": [] });
+        let err = patch_verified(&mut doc, &p, expected).unwrap_err();
+        assert!(matches!(err, VerifyError::Patch(ref e) if e.operation == 0));
+        assert_eq!(doc, json!({ "c": [] }));
+    }
+}
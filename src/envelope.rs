@@ -0,0 +1,198 @@
+use crate::digest::digest;
+use crate::{Patch, PatchError};
+use alloc::{format, string::String};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// This type represents all possible errors that can occur when applying a [`PatchEnvelope`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum EnvelopeError {
+    /// Envelope was produced by a newer format version than this crate supports.
+    #[error("unsupported envelope version {0}")]
+    UnsupportedVersion(u32),
+    /// Document is not the one the patch was created against.
+    #[error("base document hash mismatch: expected {expected}, found {actual}")]
+    BaseMismatch {
+        /// Hash of the base document recorded in the envelope.
+        expected: String,
+        /// Hash of the document the envelope is applied to.
+        actual: String,
+    },
+    /// Patch cannot be applied to the document.
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+}
+
+/// Patch together with the information about its origin: the revision and the hash of the
+/// document it was created against, its author and creation time, and the version of the
+/// envelope format.
+///
+/// The hash of the base document is computed with [`PatchEnvelope::hash_document`], and is
+/// checked before the patch is applied. Other metadata is only carried along.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{EnvelopeError, Patch, PatchEnvelope};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "title": "Hello" });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/title", "value": "Hello!" }
+/// ])).unwrap();
+///
+/// let envelope = PatchEnvelope {
+///     author: Some("ann".into()),
+///     timestamp: Some("2024-01-01T00:00:00Z".into()),
+///     ..PatchEnvelope::new(p, &doc)
+/// };
+/// let envelope: PatchEnvelope = from_value(serde_json::to_value(&envelope).unwrap()).unwrap();
+///
+/// envelope.apply(&mut doc).unwrap();
+/// assert_eq!(doc, json!({ "title": "Hello!" }));
+///
+/// // The document has changed since the patch was created
+/// let err = envelope.apply(&mut doc).unwrap_err();
+/// assert!(matches!(err, EnvelopeError::BaseMismatch { .. }));
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PatchEnvelope {
+    /// Version of the envelope format, [`PatchEnvelope::VERSION`] for the envelopes created by
+    /// this crate.
+    pub version: u32,
+    /// Revision of the base document, in the format of the application (like a revision number
+    /// or an ETag). Not checked by [`PatchEnvelope::apply`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_revision: Option<String>,
+    /// Hash of the base document, as returned by [`PatchEnvelope::hash_document`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_hash: Option<String>,
+    /// Author of the patch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Time the patch was created at, like an RFC 3339 date and time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// The patch.
+    pub patch: Patch,
+}
+
+impl Default for PatchEnvelope {
+    fn default() -> Self {
+        PatchEnvelope {
+            version: Self::VERSION,
+            base_revision: None,
+            base_hash: None,
+            author: None,
+            timestamp: None,
+            patch: Patch::default(),
+        }
+    }
+}
+
+impl PatchEnvelope {
+    /// The current version of the envelope format.
+    pub const VERSION: u32 = 1;
+
+    /// Creates an envelope for the patch created against the `base` document, recording its
+    /// hash.
+    pub fn new(patch: Patch, base: &Value) -> Self {
+        PatchEnvelope {
+            base_hash: Some(Self::hash_document(base)),
+            patch,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the hash of the document, as recorded in [`PatchEnvelope::base_hash`]: its
+    /// [`digest`](crate::digest) as 16 lowercase hexadecimal digits.
+    pub fn hash_document(doc: &Value) -> String {
+        format!("{:016x}", digest(doc))
+    }
+
+    /// Checks that the envelope can be applied to the document: its format version is supported,
+    /// and the document has the recorded base hash, if any.
+    pub fn check(&self, doc: &Value) -> Result<(), EnvelopeError> {
+        if self.version > Self::VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(self.version));
+        }
+        if let Some(expected) = &self.base_hash {
+            let actual = Self::hash_document(doc);
+            if *expected != actual {
+                return Err(EnvelopeError::BaseMismatch {
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the envelope (see [`PatchEnvelope::check`]) and applies the patch to the document.
+    /// If the check or the patch fails, the document is left unchanged.
+    pub fn apply(&self, doc: &mut Value) -> Result<(), EnvelopeError> {
+        self.check(doc)?;
+        crate::patch(doc, &self.patch)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EnvelopeError, PatchEnvelope};
+    use crate::Patch;
+    use serde_json::{from_value, json, to_value};
+
+    #[test]
+    fn envelope() {
+        let doc = json!({ "a": 1, "b": [true] });
+        let p: Patch = from_value(json!([{ "op": "remove", "path": "/a" }])).unwrap();
+        let envelope = PatchEnvelope {
+            base_revision: Some("42".into()),
+            ..PatchEnvelope::new(p.clone(), &doc)
+        };
+        let hash = PatchEnvelope::hash_document(&doc);
+        assert_eq!(hash.len(), 16);
+        assert_eq!(
+            to_value(&envelope).unwrap(),
+            json!({
+                "version": 1,
+                "base_revision": "42",
+                "base_hash": hash,
+                "patch": [{ "op": "remove", "path": "/a" }],
+            })
+        );
+
+        // Member order does not affect the hash
+        let mut reordered = json!({ "b": [true], "a": 1 });
+        envelope.apply(&mut reordered).unwrap();
+        assert_eq!(reordered, json!({ "b": [true] }));
+
+        let mut changed = json!({ "a": 1, "b": [false] });
+        let err = envelope.apply(&mut changed).unwrap_err();
+        assert!(
+            matches!(err, EnvelopeError::BaseMismatch { ref expected, .. } if *expected == hash)
+        );
+
+        let newer = PatchEnvelope {
+            version: 2,
+            ..envelope.clone()
+        };
+        let err = newer.apply(&mut doc.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported envelope version 2");
+
+        // Without the base hash, only the patch is checked
+        let mut other = json!({ "c": 1 });
+        let unchecked = PatchEnvelope {
+            patch: p,
+            ..Default::default()
+        };
+        let err = unchecked.apply(&mut other).unwrap_err();
+        assert!(matches!(err, EnvelopeError::Patch(_)));
+        assert_eq!(other, json!({ "c": 1 }));
+
+        assert!(from_value::<PatchEnvelope>(json!({ "patch": [] })).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{split_last, unescape, Patch, PatchOperation};
+use serde::Serialize;
+use serde_json::Value;
+
+/// `std::io::Write` sink that only counts the bytes written to it.
+#[cfg(feature = "std")]
+struct ByteCounter(usize);
+
+#[cfg(feature = "std")]
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+/// Length in bytes of the compact JSON serialization of the given value.
+#[cfg(feature = "std")]
+pub(crate) fn serialized_len<T: Serialize + ?Sized>(value: &T) -> usize {
+    let mut counter = ByteCounter(0);
+    // Serializing into the counter cannot fail for the types we have here (no I/O errors, no
+    // non-string map keys).
+    serde_json::to_writer(&mut counter, value).unwrap();
+    counter.0
+}
+
+/// Length in bytes of the compact JSON serialization of the given value.
+#[cfg(not(feature = "std"))]
+pub(crate) fn serialized_len<T: Serialize + ?Sized>(value: &T) -> usize {
+    // Without `std::io`, the value can only be serialized into memory
+    serde_json::to_vec(value).unwrap().len()
+}
+
+/// Estimate of the number of bytes the member at `path` occupies in the serialized document
+/// besides its value (object key, colon, separating comma). Returns `None` if the parent of the
+/// `path` cannot be resolved.
+fn member_overhead(doc: &Value, path: &str) -> Option<usize> {
+    let (parent, last) = split_last(path).ok()?;
+    let comma = |empty: bool| usize::from(!empty);
+    match doc.pointer(parent)? {
+        Value::Object(obj) => {
+            let key = unescape(last);
+            let empty = obj.is_empty() || (obj.len() == 1 && obj.contains_key(key.as_ref()));
+            Some(serialized_len(key.as_ref()) + 1 + comma(empty))
+        }
+        Value::Array(arr) => Some(comma(arr.is_empty())),
+        _ => None,
+    }
+}
+
+fn value_len(doc: &Value, path: &str) -> Option<usize> {
+    doc.pointer(path).map(serialized_len)
+}
+
+fn delta(len: usize) -> i64 {
+    i64::try_from(len).unwrap_or(i64::MAX)
+}
+
+/// Delta of adding a value of `len` bytes at `path`.
+fn add_delta(doc: &Value, path: &str, len: usize) -> i64 {
+    if path.is_empty() {
+        return delta(len) - value_len(doc, path).map_or(0, delta);
+    }
+    match split_last(path)
+        .ok()
+        .and_then(|(parent, _)| doc.pointer(parent))
+    {
+        // Adding to an existing object member replaces its value
+        Some(Value::Object(_)) if doc.pointer(path).is_some() => {
+            delta(len) - value_len(doc, path).map_or(0, delta)
+        }
+        _ => delta(len) + member_overhead(doc, path).map_or(0, delta),
+    }
+}
+
+/// Delta of removing the value at `path`.
+fn remove_delta(doc: &Value, path: &str) -> i64 {
+    match value_len(doc, path) {
+        Some(old) => -delta(old) - member_overhead(doc, path).map_or(0, delta),
+        None => 0,
+    }
+}
+
+pub(crate) fn operation_delta(doc: &Value, op: &PatchOperation) -> i64 {
+    match op {
+        PatchOperation::Add(op) => add_delta(doc, &op.path, serialized_len(&op.value)),
+        PatchOperation::Remove(op) => remove_delta(doc, &op.path),
+        PatchOperation::Replace(op) => match value_len(doc, &op.path) {
+            Some(old) => delta(serialized_len(&op.value)) - delta(old),
+            None => 0,
+        },
+        PatchOperation::Move(op) => match value_len(doc, &op.from) {
+            Some(len) => remove_delta(doc, &op.from) + add_delta(doc, &op.path, len),
+            None => 0,
+        },
+        PatchOperation::Copy(op) => match value_len(doc, &op.from) {
+            Some(len) => add_delta(doc, &op.path, len),
+            None => 0,
+        },
+        PatchOperation::Test(_) => 0,
+    }
+}
+
+impl Patch {
+    /// Returns the size in bytes of the compact JSON serialization of this patch, as it would be
+    /// sent over the wire. The patch is not serialized into a buffer, bytes are only counted.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "add", "path": "/a", "value": 1 }
+    /// ])).unwrap();
+    ///
+    /// assert_eq!(p.serialized_size_estimate(), p.to_string().len());
+    /// ```
+    pub fn serialized_size_estimate(&self) -> usize {
+        serialized_len(self)
+    }
+
+    /// Estimates by how many bytes the compact JSON serialization of `doc` would grow (positive
+    /// value) or shrink (negative value) once this patch is applied, without applying it.
+    ///
+    /// Every operation is evaluated against the original document, so the estimate is exact only
+    /// when operations do not depend on the effects of each other. Operations referring to
+    /// locations that do not exist in the original document contribute nothing, except for the
+    /// values they introduce.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let doc = json!({ "a": "hello" });
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "replace", "path": "/a", "value": "hi" }
+    /// ])).unwrap();
+    ///
+    /// assert_eq!(p.estimated_document_delta(&doc), -3);
+    /// ```
+    pub fn estimated_document_delta(&self, doc: &Value) -> i64 {
+        self.iter().map(|op| operation_delta(doc, op)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Patch;
+    use serde_json::{from_value, json, Value};
+
+    fn check_delta(doc: Value, patch: Value) {
+        let p: Patch = from_value(patch).unwrap();
+        let mut patched = doc.clone();
+        crate::patch(&mut patched, &p).unwrap();
+        let expected = doc_len(&patched) - doc_len(&doc);
+        assert_eq!(p.estimated_document_delta(&doc), expected, "{}", p);
+    }
+
+    fn doc_len(doc: &Value) -> i64 {
+        serde_json::to_string(doc).unwrap().len() as i64
+    }
+
+    #[test]
+    fn serialized_size() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a/b", "value": ["hello", "привет"] },
+            { "op": "move", "from": "/a/b", "path": "/c" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            p.serialized_size_estimate(),
+            serde_json::to_vec(&p).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn document_delta() {
+        let doc = json!({ "a": "hello", "b": [1, 2, 3], "c": {} });
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "add", "path": "/d", "value": true }]),
+        );
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "add", "path": "/a", "value": true }]),
+        );
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "add", "path": "/c/x", "value": 1 }]),
+        );
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "add", "path": "/b/1", "value": 10 }]),
+        );
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "add", "path": "", "value": 10 }]),
+        );
+        check_delta(doc.clone(), json!([{ "op": "remove", "path": "/a" }]));
+        check_delta(doc.clone(), json!([{ "op": "remove", "path": "/b/0" }]));
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "replace", "path": "/a", "value": "" }]),
+        );
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "move", "from": "/a", "path": "/c/a" }]),
+        );
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "move", "from": "/a", "path": "/bb" }]),
+        );
+        check_delta(
+            doc.clone(),
+            json!([{ "op": "copy", "from": "/b", "path": "/c/b" }]),
+        );
+        check_delta(
+            doc,
+            json!([{ "op": "test", "path": "/a", "value": "hello" }]),
+        );
+    }
+}
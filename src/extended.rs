@@ -0,0 +1,1744 @@
+//! Non-standard patch operations, extending JSON Patch (RFC 6902) for more compact patches.
+//!
+//! Extended patches are lists of [`ExtendedOperation`]s, which are either the standard operations
+//! or one of the extended ones:
+//!
+//! * `splice` replaces a range of characters within a string value, so that small edits of long
+//!   text fields do not carry the whole string,
+//! * `remove-range` removes consecutive elements of an array,
+//! * `insert-many` inserts several values into an array at once,
+//! * `move-range` moves consecutive elements of an array to another position (or array),
+//! * `test-digest` tests the [`digest`](crate::digest) of a value, for conditional patches of
+//!   large values (see [`make_conditional_digest`]),
+//! * `test-type`, `test-matches` and `test-contains` test a value against a predicate rather than
+//!   for equality (with the `test-predicates` feature),
+//! * custom operations with any other `op` name, applied by handlers registered in an
+//!   [`OpRegistry`].
+//!
+//! Extended patches are only understood by this crate; [`lower`] expands them into standard
+//! operations for other implementations.
+//!
+//! Requires the `extended-ops` feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! use json_patch::extended::{lower, patch_extended, ExtendedOperation};
+//! use serde_json::{from_value, json};
+//!
+//! let doc = json!({ "text": "The quick brown fox jumps over the lazy dog" });
+//! let p: Vec<ExtendedOperation> = from_value(json!([
+//!   { "op": "splice", "path": "/text", "offset": 10, "remove": 5, "insert": "red" }
+//! ])).unwrap();
+//!
+//! let mut patched = doc.clone();
+//! patch_extended(&mut patched, &p).unwrap();
+//! assert_eq!(patched, json!({ "text": "The quick red fox jumps over the lazy dog" }));
+//!
+//! let standard = lower(&doc, &p).unwrap();
+//! assert_eq!(standard, from_value(json!([
+//!   { "op": "replace", "path": "/text", "value": "The quick red fox jumps over the lazy dog" }
+//! ])).unwrap());
+//! ```
+use crate::deep::{drop_operations, drop_value};
+use crate::{
+    apply_patches, conditional, not_found, parse_index, revert, split_last, test_failed,
+    test_not_found, translate_error, write_json, Patch, PatchEnvelope, PatchError, PatchErrorKind,
+    PatchOperation, Undo,
+};
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+use core::slice;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+#[cfg(feature = "test-predicates")]
+use {
+    crate::deep::values_equal,
+    alloc::{borrow::Cow, string::ToString},
+};
+
+#[cfg(feature = "diff")]
+use crate::diff::operation_order;
+#[cfg(feature = "diff")]
+use crate::{deep::clone_value, diff_with_visitor, DiffOptions, DiffVisitor, OpOrder};
+#[cfg(feature = "diff")]
+use alloc::vec;
+
+/// Extended 'splice' operation, replacing a range of characters within a string value.
+///
+/// Offsets and lengths are counted in characters (Unicode scalar values), not bytes.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SpliceOperation {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a string
+    /// within the target document where the operation is performed.
+    pub path: String,
+    /// Offset of the first character to remove, or where to insert.
+    pub offset: usize,
+    /// Number of characters to remove.
+    pub remove: usize,
+    /// String to insert at the offset.
+    pub insert: String,
+}
+
+impl Display for SpliceOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Extended 'remove-range' operation, removing consecutive elements of an array.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RemoveRangeOperation {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references the first
+    /// array element to remove.
+    pub path: String,
+    /// Number of elements to remove.
+    pub count: usize,
+}
+
+impl Display for RemoveRangeOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Extended 'insert-many' operation, inserting values into an array.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct InsertManyOperation {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references the
+    /// array location to insert the first value at, as in an `add` operation (`-` to append).
+    pub path: String,
+    /// Values to insert.
+    pub values: Vec<Value>,
+}
+
+impl Display for InsertManyOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Extended 'move-range' operation, moving consecutive elements of an array.
+///
+/// Like with the `move` operation, the elements are removed first, and the `path` refers to a
+/// location in the document without them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MoveRangeOperation {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references the first
+    /// array element to move.
+    pub from: String,
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references the
+    /// array location to move the first element to, as in an `add` operation (`-` to append).
+    pub path: String,
+    /// Number of elements to move.
+    pub count: usize,
+}
+
+impl Display for MoveRangeOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Extended 'test-digest' operation, testing that the value has the given digest: its
+/// [`digest`](crate::digest) as 16 hexadecimal digits, as returned by
+/// [`PatchEnvelope::hash_document`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TestDigestOperation {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
+    /// within the target document where the operation is performed.
+    pub path: String,
+    /// Expected digest of the value.
+    pub digest: String,
+}
+
+impl Display for TestDigestOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Type of a JSON value, as tested by the `test-type` operation.
+///
+/// Requires the `test-predicates` feature.
+#[cfg(feature = "test-predicates")]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Boolean,
+    /// Any number
+    Number,
+    /// Number without a fractional part
+    Integer,
+    /// String
+    String,
+    /// Array
+    Array,
+    /// Object
+    Object,
+}
+
+#[cfg(feature = "test-predicates")]
+impl ValueType {
+    /// Returns whether the value is of this type.
+    pub fn matches(self, value: &Value) -> bool {
+        match (self, value) {
+            (ValueType::Null, Value::Null)
+            | (ValueType::Boolean, Value::Bool(_))
+            | (ValueType::Number, Value::Number(_))
+            | (ValueType::String, Value::String(_))
+            | (ValueType::Array, Value::Array(_))
+            | (ValueType::Object, Value::Object(_)) => true,
+            (ValueType::Integer, Value::Number(n)) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|n| n.fract() == 0.0)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Extended 'test-type' operation, testing that the value is of the given type.
+///
+/// Requires the `test-predicates` feature.
+#[cfg(feature = "test-predicates")]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TestTypeOperation {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
+    /// within the target document where the operation is performed.
+    pub path: String,
+    /// Expected type of the value.
+    #[serde(rename = "type")]
+    pub value_type: ValueType,
+}
+
+#[cfg(feature = "test-predicates")]
+impl Display for TestTypeOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Extended 'test-matches' operation, testing that the value is a string matching the regular
+/// expression.
+///
+/// The pattern uses the syntax of the [`regex`](https://docs.rs/regex) crate, and is searched
+/// within the string: use `^` and `$` to match the whole string.
+///
+/// Requires the `test-predicates` feature.
+#[cfg(feature = "test-predicates")]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TestMatchesOperation {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
+    /// within the target document where the operation is performed.
+    pub path: String,
+    /// Regular expression to match.
+    pub pattern: String,
+}
+
+#[cfg(feature = "test-predicates")]
+impl Display for TestMatchesOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Extended 'test-contains' operation, testing that the value contains another one: an array
+/// containing an element equal to it, a string containing it as a substring, or an object
+/// containing all of its members.
+///
+/// Requires the `test-predicates` feature.
+#[cfg(feature = "test-predicates")]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TestContainsOperation {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
+    /// within the target document where the operation is performed.
+    pub path: String,
+    /// Value to look for.
+    pub value: Value,
+}
+
+#[cfg(feature = "test-predicates")]
+impl Display for TestContainsOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Custom operation, with an `op` name other than the names of the standard and extended
+/// operations, applied by the handler registered for its name in an [`OpRegistry`].
+///
+/// Only the `path` member is required besides `op`, the other members are passed to the handler
+/// as `params`.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct CustomOperation {
+    /// Name of the operation.
+    pub op: String,
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
+    /// within the target document where the operation is performed.
+    pub path: String,
+    /// Other members of the operation.
+    #[serde(flatten)]
+    pub params: Map<String, Value>,
+}
+
+impl Display for CustomOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Names of the standard and extended operations, which custom operations cannot have.
+const RESERVED_OPS: &[&str] = &[
+    "add",
+    "remove",
+    "replace",
+    "move",
+    "copy",
+    "test",
+    "splice",
+    "remove-range",
+    "insert-many",
+    "move-range",
+    "test-digest",
+    "test-type",
+    "test-matches",
+    "test-contains",
+];
+
+impl<'de> Deserialize<'de> for CustomOperation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut params = Map::deserialize(deserializer)?;
+        let mut member = |name| match params.remove(name) {
+            Some(Value::String(value)) => Ok(value),
+            Some(_) => Err(de::Error::custom(format_args!(
+                "`{}` must be a string",
+                name
+            ))),
+            None => Err(de::Error::missing_field(name)),
+        };
+        let op = member("op")?;
+        let path = member("path")?;
+        if RESERVED_OPS.contains(&op.as_str()) {
+            return Err(de::Error::custom(format_args!(
+                "invalid `{}` operation",
+                op
+            )));
+        }
+        Ok(CustomOperation { op, path, params })
+    }
+}
+
+/// Operation of an extended patch: one of the standard JSON Patch operations or an extended one.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "op")]
+#[serde(rename_all = "kebab-case")]
+pub enum ExtendedOperation {
+    /// 'splice' operation
+    Splice(SpliceOperation),
+    /// 'remove-range' operation
+    RemoveRange(RemoveRangeOperation),
+    /// 'insert-many' operation
+    InsertMany(InsertManyOperation),
+    /// 'move-range' operation
+    MoveRange(MoveRangeOperation),
+    /// 'test-digest' operation
+    TestDigest(TestDigestOperation),
+    /// 'test-type' operation
+    #[cfg(feature = "test-predicates")]
+    TestType(TestTypeOperation),
+    /// 'test-matches' operation
+    #[cfg(feature = "test-predicates")]
+    TestMatches(TestMatchesOperation),
+    /// 'test-contains' operation
+    #[cfg(feature = "test-predicates")]
+    TestContains(TestContainsOperation),
+    /// Standard JSON Patch operation
+    #[serde(untagged)]
+    Standard(PatchOperation),
+    /// Custom operation
+    #[serde(untagged)]
+    Custom(CustomOperation),
+}
+
+impl Display for ExtendedOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+impl ExtendedOperation {
+    /// Returns the `path` of the operation.
+    pub fn path(&self) -> &str {
+        match self {
+            ExtendedOperation::Splice(op) => &op.path,
+            ExtendedOperation::RemoveRange(op) => &op.path,
+            ExtendedOperation::InsertMany(op) => &op.path,
+            ExtendedOperation::MoveRange(op) => &op.path,
+            ExtendedOperation::TestDigest(op) => &op.path,
+            #[cfg(feature = "test-predicates")]
+            ExtendedOperation::TestType(op) => &op.path,
+            #[cfg(feature = "test-predicates")]
+            ExtendedOperation::TestMatches(op) => &op.path,
+            #[cfg(feature = "test-predicates")]
+            ExtendedOperation::TestContains(op) => &op.path,
+            ExtendedOperation::Standard(op) => op.path(),
+            ExtendedOperation::Custom(op) => &op.path,
+        }
+    }
+}
+
+impl From<PatchOperation> for ExtendedOperation {
+    fn from(op: PatchOperation) -> Self {
+        ExtendedOperation::Standard(op)
+    }
+}
+
+/// Byte offset of the character at the given offset, or of the end of the string if the offset
+/// is at its end.
+fn char_offset(s: &str, offset: usize) -> Option<usize> {
+    s.char_indices()
+        .map(|(idx, _)| idx)
+        .chain(Some(s.len()))
+        .nth(offset)
+}
+
+/// Apply the splice to the string at its path, returning the original string.
+fn splice(doc: &mut Value, op: &SpliceOperation) -> Result<String, PatchErrorKind> {
+    let target = doc
+        .pointer_mut(&op.path)
+        .ok_or_else(|| not_found(&op.path))?;
+    let Value::String(s) = target else {
+        return Err(PatchErrorKind::NotAString);
+    };
+    let start = char_offset(s, op.offset).ok_or(PatchErrorKind::RangeOutOfBounds)?;
+    let end = char_offset(&s[start..], op.remove).ok_or(PatchErrorKind::RangeOutOfBounds)?;
+    let mut spliced = String::with_capacity(s.len() - end + op.insert.len());
+    spliced.push_str(&s[..start]);
+    spliced.push_str(&op.insert);
+    spliced.push_str(&s[start + end..]);
+    Ok(core::mem::replace(s, spliced))
+}
+
+/// Resolves the array element pointer into the array and the index of the element. When
+/// inserting, the index can also refer to the end of the array (including with `-`).
+fn array_index<'a>(
+    doc: &'a mut Value,
+    path: &str,
+    insert: bool,
+) -> Result<(&'a mut Vec<Value>, usize), PatchErrorKind> {
+    let (parent, last) = split_last(path)?;
+    let arr = match doc.pointer_mut(parent) {
+        Some(Value::Array(arr)) => arr,
+        Some(_) => return Err(PatchErrorKind::NotAnArray),
+        None => return Err(not_found(parent)),
+    };
+    let idx = if insert && last == "-" {
+        arr.len()
+    } else {
+        parse_index(last, arr.len() + usize::from(insert))?
+    };
+    Ok((arr, idx))
+}
+
+fn remove_range(doc: &mut Value, op: &RemoveRangeOperation) -> Result<Vec<Value>, PatchErrorKind> {
+    let (arr, start) = array_index(doc, &op.path, false)?;
+    if op.count > arr.len() - start {
+        return Err(PatchErrorKind::RangeOutOfBounds);
+    }
+    Ok(arr.drain(start..start + op.count).collect())
+}
+
+/// Inserts the values, returning the index of the first one.
+fn insert_many(doc: &mut Value, op: &InsertManyOperation) -> Result<usize, PatchErrorKind> {
+    let (arr, start) = array_index(doc, &op.path, true)?;
+    arr.splice(start..start, op.values.iter().cloned());
+    Ok(start)
+}
+
+/// Expands the `move-range` operation into `move` operations, moving the elements one by one.
+fn lower_move_range(
+    doc: &Value,
+    op: &MoveRangeOperation,
+) -> Result<Vec<PatchOperation>, PatchErrorKind> {
+    let from_error = |err| match err {
+        PatchErrorKind::InvalidPointer
+        | PatchErrorKind::PathNotFound
+        | PatchErrorKind::IndexOutOfBounds => PatchErrorKind::InvalidFromPointer,
+        err => err,
+    };
+    let (from, from_last) = split_last(&op.from).map_err(from_error)?;
+    let (path, path_last) = split_last(&op.path)?;
+    let len = match doc.pointer(from) {
+        Some(Value::Array(arr)) => arr.len(),
+        Some(_) => return Err(PatchErrorKind::NotAnArray),
+        None => return Err(PatchErrorKind::InvalidFromPointer),
+    };
+    let start = parse_index(from_last, len).map_err(from_error)?;
+    if op.count > len - start {
+        return Err(PatchErrorKind::RangeOutOfBounds);
+    }
+    let count = op.count;
+    let element = |parent: &str, idx: usize| format!("{}/{}", parent, idx);
+
+    if path == from {
+        // Moving within the array: to the left, move the elements one by one; to the right, move
+        // the elements between the range and the target location to the left of the range
+        let target = if path_last == "-" {
+            len - count
+        } else {
+            parse_index(path_last, len - count + 1)?
+        };
+        return Ok(if target <= start {
+            (0..count)
+                .map(|i| {
+                    PatchOperation::move_from(element(from, start + i), element(from, target + i))
+                })
+                .collect()
+        } else {
+            (0..target - start)
+                .map(|i| {
+                    PatchOperation::move_from(
+                        element(from, start + count + i),
+                        element(from, start + i),
+                    )
+                })
+                .collect()
+        });
+    }
+
+    // Target array nested under one of the elements after the range shifts by one with every
+    // element moved
+    let mut nested = None;
+    if let Some(rest) = path
+        .strip_prefix(from)
+        .and_then(|rest| rest.strip_prefix('/'))
+    {
+        let (token, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if let Ok(idx) = parse_index(token, usize::MAX) {
+            if (start..start + count).contains(&idx) {
+                return Err(PatchErrorKind::CannotMoveInsideItself);
+            } else if idx >= start {
+                nested = Some((idx + count, tail));
+            }
+        }
+    }
+    let target = if path_last == "-" {
+        None
+    } else {
+        Some(parse_index(path_last, usize::MAX)?)
+    };
+    // Source array nested under one of the elements after the target location shifts by one with
+    // every element moved
+    let mut shifted = None;
+    if let (Some(rest), Some(target)) = (
+        from.strip_prefix(path)
+            .and_then(|rest| rest.strip_prefix('/')),
+        target,
+    ) {
+        let (token, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if let Ok(idx) = parse_index(token, usize::MAX) {
+            if idx >= target {
+                shifted = Some((idx, tail));
+            }
+        }
+    }
+    Ok((0..count)
+        .map(|i| {
+            let source = match shifted {
+                Some((idx, tail)) => format!("{}{}/{}", element(path, idx + i), tail, start),
+                None => element(from, start),
+            };
+            let parent = match nested {
+                Some((idx, tail)) => format!("{}{}", element(from, idx - i - 1), tail),
+                None => path.into(),
+            };
+            let path = match target {
+                Some(target) => element(&parent, target + i),
+                None => format!("{}/-", parent),
+            };
+            PatchOperation::move_from(source, path)
+        })
+        .collect())
+}
+
+/// Tests the digest of the value at the path of a `test-digest` operation.
+fn test_digest(doc: &Value, op: &TestDigestOperation) -> Result<(), PatchErrorKind> {
+    let expected = Value::String(op.digest.clone());
+    let target = doc
+        .pointer(&op.path)
+        .ok_or_else(|| test_not_found(&expected, &op.path))?;
+    let actual = PatchEnvelope::hash_document(target);
+    if actual.eq_ignore_ascii_case(&op.digest) {
+        Ok(())
+    } else {
+        Err(test_failed(&expected, Some(&Value::String(actual))))
+    }
+}
+
+/// Tests the value at the path of a predicate operation.
+#[cfg(feature = "test-predicates")]
+fn test_predicate(doc: &Value, op: &ExtendedOperation) -> Result<(), PatchErrorKind> {
+    // Operands of the predicates, reported as the expected values
+    let expected = || match op {
+        ExtendedOperation::TestType(op) => Cow::Owned(serde_json::json!(op.value_type)),
+        ExtendedOperation::TestMatches(op) => Cow::Owned(Value::String(op.pattern.clone())),
+        ExtendedOperation::TestContains(op) => Cow::Borrowed(&op.value),
+        _ => Cow::Owned(Value::Null),
+    };
+    let target = doc
+        .pointer(op.path())
+        .ok_or_else(|| test_not_found(&expected(), op.path()))?;
+    let passed = match op {
+        ExtendedOperation::TestType(op) => op.value_type.matches(target),
+        ExtendedOperation::TestMatches(op) => {
+            let regex = regex::Regex::new(&op.pattern)
+                .map_err(|e| PatchErrorKind::InvalidPattern(e.to_string()))?;
+            target.as_str().is_some_and(|s| regex.is_match(s))
+        }
+        ExtendedOperation::TestContains(op) => match (target, &op.value) {
+            (Value::Array(elements), value) => elements.iter().any(|e| values_equal(e, value)),
+            (Value::String(s), Value::String(part)) => s.contains(part.as_str()),
+            (Value::Object(members), Value::Object(expected)) => expected
+                .iter()
+                .all(|(k, v)| members.get(k).is_some_and(|member| values_equal(member, v))),
+            _ => false,
+        },
+        _ => true,
+    };
+    if passed {
+        Ok(())
+    } else {
+        Err(test_failed(&expected(), Some(target)))
+    }
+}
+
+/// Handler of a custom operation: given the value at the `path` of the operation (`None` if there
+/// is none) and the operation, returns the value to set at the `path`, or an error message.
+pub type OpHandler =
+    dyn Fn(Option<&Value>, &CustomOperation) -> Result<Value, String> + Send + Sync;
+
+/// Handlers of custom operations, by the name of the operation.
+///
+/// Handlers compute the new value at the `path` of the operation, which is then added or replaced
+/// as by an `add` operation, so custom operations are reverted like the standard ones when a patch
+/// fails, and are lowered into `add` operations.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::extended::{patch_with_registry, ExtendedOperation, OpRegistry};
+/// use serde_json::{from_value, json, Value};
+///
+/// let mut registry = OpRegistry::new();
+/// registry.register("increment", |value, op| {
+///     let by = op.params.get("by").and_then(Value::as_i64).unwrap_or(1);
+///     match value {
+///         Some(Value::Number(n)) => n.as_i64().map(|n| json!(n + by)).ok_or("not an integer".into()),
+///         None => Ok(json!(by)),
+///         _ => Err("not a number".into()),
+///     }
+/// });
+///
+/// let mut doc = json!({ "views": 41 });
+/// let p: Vec<ExtendedOperation> = from_value(json!([
+///   { "op": "increment", "path": "/views" },
+///   { "op": "increment", "path": "/likes", "by": 2 }
+/// ])).unwrap();
+///
+/// patch_with_registry(&mut doc, &p, &registry).unwrap();
+/// assert_eq!(doc, json!({ "views": 42, "likes": 2 }));
+/// ```
+#[derive(Default)]
+pub struct OpRegistry {
+    handlers: BTreeMap<String, Box<OpHandler>>,
+}
+
+impl OpRegistry {
+    /// Create a registry without any handlers.
+    pub fn new() -> OpRegistry {
+        OpRegistry::default()
+    }
+
+    /// Register the handler of the custom operations with the given name, replacing the handler
+    /// registered before, if any.
+    pub fn register<F>(&mut self, op: impl Into<String>, handler: F) -> &mut Self
+    where
+        F: Fn(Option<&Value>, &CustomOperation) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.handlers.insert(op.into(), Box::new(handler));
+        self
+    }
+
+    /// Returns the handler of the custom operations with the given name, if any.
+    pub fn get(&self, op: &str) -> Option<&OpHandler> {
+        self.handlers.get(op).map(Box::as_ref)
+    }
+
+    /// Expands the custom operation into the standard operation setting the value computed by its
+    /// handler.
+    fn lower(&self, doc: &Value, op: &CustomOperation) -> Result<PatchOperation, PatchErrorKind> {
+        let handler = self.get(&op.op).ok_or(PatchErrorKind::UnknownOperation)?;
+        let value =
+            handler(doc.pointer(&op.path), op).map_err(PatchErrorKind::CustomOperationFailed)?;
+        Ok(PatchOperation::add(op.path.clone(), value))
+    }
+}
+
+impl fmt::Debug for OpRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+/// Apply the extended operations, recording the standard operations reverting them onto the undo
+/// stack, if any.
+fn apply_extended(
+    doc: &mut Value,
+    patch: &[ExtendedOperation],
+    registry: &OpRegistry,
+    mut undo_stack: Option<&mut Vec<Undo>>,
+) -> Result<(), PatchError> {
+    for (operation, op) in patch.iter().enumerate() {
+        match op {
+            ExtendedOperation::Splice(op) => {
+                let prev = splice(doc, op).map_err(|e| translate_error(e, operation, &op.path))?;
+                if let Some(undo_stack) = undo_stack.as_deref_mut() {
+                    undo_stack
+                        .push(PatchOperation::replace(op.path.clone(), Value::String(prev)).into());
+                }
+            }
+            ExtendedOperation::RemoveRange(op) => {
+                let removed =
+                    remove_range(doc, op).map_err(|e| translate_error(e, operation, &op.path))?;
+                if let Some(undo_stack) = undo_stack.as_deref_mut() {
+                    // Undone in reverse, so the elements are inserted back in order
+                    let (parent, last) = op.path.rsplit_once('/').unwrap_or_default();
+                    let start: usize = last.parse().unwrap_or_default();
+                    for (i, value) in removed.into_iter().enumerate().rev() {
+                        undo_stack.push(
+                            PatchOperation::add(format!("{}/{}", parent, start + i), value).into(),
+                        );
+                    }
+                } else {
+                    removed.into_iter().for_each(drop_value);
+                }
+            }
+            ExtendedOperation::InsertMany(op) => {
+                let start =
+                    insert_many(doc, op).map_err(|e| translate_error(e, operation, &op.path))?;
+                if let Some(undo_stack) = undo_stack.as_deref_mut() {
+                    let (parent, _) = op.path.rsplit_once('/').unwrap_or_default();
+                    for i in 0..op.values.len() {
+                        undo_stack.push(
+                            PatchOperation::remove(format!("{}/{}", parent, start + i)).into(),
+                        );
+                    }
+                }
+            }
+            ExtendedOperation::MoveRange(op) => {
+                let moves = lower_move_range(doc, op)
+                    .map_err(|e| translate_error(e, operation, &op.path))?;
+                apply_patches(doc, &moves, undo_stack.as_deref_mut())
+                    .map_err(|err| translate_error(err.kind, operation, &op.path))?;
+            }
+            ExtendedOperation::TestDigest(op) => {
+                test_digest(doc, op).map_err(|e| translate_error(e, operation, &op.path))?;
+            }
+            #[cfg(feature = "test-predicates")]
+            ExtendedOperation::TestType(_)
+            | ExtendedOperation::TestMatches(_)
+            | ExtendedOperation::TestContains(_) => {
+                test_predicate(doc, op).map_err(|e| translate_error(e, operation, op.path()))?;
+            }
+            ExtendedOperation::Standard(op) => {
+                apply_patches(doc, slice::from_ref(op), undo_stack.as_deref_mut()).map_err(
+                    |mut err| {
+                        err.operation = operation;
+                        err
+                    },
+                )?;
+            }
+            ExtendedOperation::Custom(op) => {
+                let lowered = registry
+                    .lower(doc, op)
+                    .map_err(|e| translate_error(e, operation, &op.path))?;
+                apply_patches(doc, slice::from_ref(&lowered), undo_stack.as_deref_mut())
+                    .map_err(|err| translate_error(err.kind, operation, &op.path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Patch the document in-place with the extended patch, as [`patch`](crate::patch) does. If any
+/// of the operations fails, all previous operations are reverted.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::extended::{patch_extended, ExtendedOperation};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "text": "Hello, world!" });
+/// let p: Vec<ExtendedOperation> = from_value(json!([
+///   { "op": "splice", "path": "/text", "offset": 7, "remove": 5, "insert": "Rust" },
+///   { "op": "add", "path": "/lang", "value": "en" }
+/// ])).unwrap();
+///
+/// patch_extended(&mut doc, &p).unwrap();
+/// assert_eq!(doc, json!({ "text": "Hello, Rust!", "lang": "en" }));
+/// ```
+pub fn patch_extended(doc: &mut Value, patch: &[ExtendedOperation]) -> Result<(), PatchError> {
+    patch_with_registry(doc, patch, &OpRegistry::default())
+}
+
+/// Patch the document in-place with the extended patch, as [`patch_extended`] does, applying the
+/// custom operations with the handlers of the registry. Custom operations without a handler fail
+/// with [`PatchErrorKind::UnknownOperation`].
+///
+/// See [`OpRegistry`] for an example.
+pub fn patch_with_registry(
+    doc: &mut Value,
+    patch: &[ExtendedOperation],
+    registry: &OpRegistry,
+) -> Result<(), PatchError> {
+    let mut undo_stack = Vec::with_capacity(patch.len());
+    if let Err(e) = apply_extended(doc, patch, registry, Some(&mut undo_stack)) {
+        revert(doc, undo_stack);
+        return Err(e);
+    }
+    drop_operations(undo_stack);
+    Ok(())
+}
+
+/// Check that every operation of the extended patch would succeed when applied to the document,
+/// as [`validate`](crate::validate) does for standard patches. Operations are checked in order,
+/// as if the failed ones were skipped, and all the failures are reported.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::extended::{validate_extended, ExtendedOperation};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "a": [1, 2] });
+/// let p: Vec<ExtendedOperation> = from_value(json!([
+///   { "op": "remove-range", "path": "/a/1", "count": 2 },
+///   { "op": "insert-many", "path": "/a/-", "values": [3, 4] }
+/// ])).unwrap();
+///
+/// let errors = validate_extended(&doc, &p).unwrap_err();
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].operation, 0);
+/// ```
+pub fn validate_extended(doc: &Value, patch: &[ExtendedOperation]) -> Result<(), Vec<PatchError>> {
+    validate_with_registry(doc, patch, &OpRegistry::default())
+}
+
+/// Check that every operation of the extended patch would succeed when applied to the document,
+/// as [`validate_extended`] does, applying the custom operations with the handlers of the
+/// registry.
+pub fn validate_with_registry(
+    doc: &Value,
+    patch: &[ExtendedOperation],
+    registry: &OpRegistry,
+) -> Result<(), Vec<PatchError>> {
+    let mut doc = doc.clone();
+    let errors: Vec<_> = patch
+        .iter()
+        .enumerate()
+        .filter_map(|(operation, op)| {
+            patch_with_registry(&mut doc, slice::from_ref(op), registry)
+                .map_err(|mut err| {
+                    err.operation = operation;
+                    err
+                })
+                .err()
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Expand the extended patch into standard JSON Patch operations, having the same effect on the
+/// document. Extended operations depend on the values they modify (a `splice` is lowered into a
+/// `replace` of the whole string), so the patch is applied to a copy of the document while
+/// lowering, and an error is returned if it does not apply. Predicates are lowered into `test`
+/// operations of the values they were checked against, which are stricter.
+///
+/// See the [module documentation](self) for an example.
+pub fn lower(doc: &Value, patch: &[ExtendedOperation]) -> Result<Patch, PatchError> {
+    lower_with_registry(doc, patch, &OpRegistry::default())
+}
+
+/// Expand the extended patch into standard JSON Patch operations, as [`lower`] does, lowering the
+/// custom operations with the handlers of the registry into `add` operations.
+pub fn lower_with_registry(
+    doc: &Value,
+    patch: &[ExtendedOperation],
+    registry: &OpRegistry,
+) -> Result<Patch, PatchError> {
+    let mut doc = doc.clone();
+    let mut lowered = Vec::with_capacity(patch.len());
+    for (operation, op) in patch.iter().enumerate() {
+        // Moves depend on the array lengths before the operation
+        let moves = match op {
+            ExtendedOperation::MoveRange(op) => Some(
+                lower_move_range(&doc, op).map_err(|e| translate_error(e, operation, &op.path))?,
+            ),
+            _ => None,
+        };
+        apply_extended(&mut doc, slice::from_ref(op), registry, None).map_err(|mut err| {
+            err.operation = operation;
+            err
+        })?;
+        match op {
+            ExtendedOperation::Splice(op) => {
+                // The patch applied, so the value is there
+                let value = doc.pointer(&op.path).cloned().unwrap_or_default();
+                lowered.push(PatchOperation::replace(op.path.clone(), value));
+            }
+            ExtendedOperation::RemoveRange(op) => {
+                lowered.extend((0..op.count).map(|_| PatchOperation::remove(op.path.clone())));
+            }
+            ExtendedOperation::InsertMany(op) => {
+                let (parent, last) = op.path.rsplit_once('/').unwrap_or_default();
+                lowered.extend(op.values.iter().enumerate().map(|(i, value)| {
+                    let path = match last.parse::<usize>() {
+                        Ok(start) => format!("{}/{}", parent, start + i),
+                        Err(_) => op.path.clone(),
+                    };
+                    PatchOperation::add(path, value.clone())
+                }));
+            }
+            ExtendedOperation::MoveRange(_) => lowered.extend(moves.unwrap_or_default()),
+            ExtendedOperation::TestDigest(op) => {
+                // The digest matches, so the value is there
+                let value = doc.pointer(&op.path).cloned().unwrap_or_default();
+                lowered.push(PatchOperation::test(op.path.clone(), value));
+            }
+            #[cfg(feature = "test-predicates")]
+            ExtendedOperation::TestType(_)
+            | ExtendedOperation::TestMatches(_)
+            | ExtendedOperation::TestContains(_) => {
+                // The predicate holds, so the value is there
+                let value = doc.pointer(op.path()).cloned().unwrap_or_default();
+                lowered.push(PatchOperation::test(op.path(), value));
+            }
+            ExtendedOperation::Standard(op) => lowered.push(op.clone()),
+            ExtendedOperation::Custom(op) => {
+                // The patch applied, so the value is there
+                let value = doc.pointer(&op.path).cloned().unwrap_or_default();
+                lowered.push(PatchOperation::add(op.path.clone(), value));
+            }
+        }
+    }
+    Ok(Patch(lowered))
+}
+
+/// Make the patch conditional on the document it was created against, as
+/// [`make_conditional`](crate::make_conditional) does, but with `test-digest` operations
+/// asserting the digests of the values the patch depends on rather than the values themselves.
+/// The size of the tests does not depend on the size of the values.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::extended::{make_conditional_digest, patch_extended};
+/// use json_patch::{Patch, PatchEnvelope};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "items": [{ "id": 1, "text": "..." }, { "id": 2, "text": "..." }] });
+/// let p: Patch = from_value(json!([
+///   { "op": "remove", "path": "/items/1" }
+/// ])).unwrap();
+///
+/// let conditional = make_conditional_digest(&p, &doc);
+/// let digest = PatchEnvelope::hash_document(&doc["items"][1]);
+/// assert_eq!(conditional, from_value::<Vec<_>>(json!([
+///   { "op": "test-digest", "path": "/items/1", "digest": digest },
+///   { "op": "remove", "path": "/items/1" }
+/// ])).unwrap());
+///
+/// let mut patched = doc.clone();
+/// patch_extended(&mut patched, &conditional).unwrap();
+/// assert_eq!(patched, json!({ "items": [{ "id": 1, "text": "..." }] }));
+///
+/// // The item was changed concurrently
+/// let mut current = json!({ "items": [{ "id": 1, "text": "..." }, { "id": 2, "text": "!" }] });
+/// assert!(patch_extended(&mut current, &conditional).is_err());
+/// ```
+pub fn make_conditional_digest(patch: &[PatchOperation], doc: &Value) -> Vec<ExtendedOperation> {
+    conditional::targets(patch, doc)
+        .into_iter()
+        .map(|(path, value)| {
+            ExtendedOperation::TestDigest(TestDigestOperation {
+                path: path.into(),
+                digest: PatchEnvelope::hash_document(value),
+            })
+        })
+        .chain(patch.iter().cloned().map(ExtendedOperation::Standard))
+        .collect()
+}
+
+/// Options controlling how [`diff_extended`] generates extended patches.
+///
+/// New options might be added in the future, so construct options with `..Default::default()`.
+///
+/// Requires the `diff` feature.
+#[cfg(feature = "diff")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtendedDiffOptions {
+    /// Options of the standard diff, which produces the operations to be turned into extended
+    /// ones.
+    pub diff: DiffOptions,
+    /// Emit a `splice` operation instead of a `replace` operation when a string is changed by less
+    /// than this fraction of its characters: when the number of characters removed and inserted
+    /// is less than the fraction of the length of the longer of the strings. Strings are always
+    /// replaced if `None`.
+    pub splice_threshold: Option<f64>,
+    /// Emit `remove-range`, `insert-many` and `move-range` operations for runs of `remove`, `add`
+    /// and `move` operations on consecutive elements of an array.
+    pub array_ranges: bool,
+}
+
+/// Collects the paths of the values modified by the diff, in the order of the `replace`
+/// operations, with the original strings.
+#[cfg(feature = "diff")]
+#[derive(Default)]
+struct ReplacedStrings(Vec<(String, Option<String>)>);
+
+#[cfg(feature = "diff")]
+impl DiffVisitor for ReplacedStrings {
+    fn modified(&mut self, path: &str, old: &Value, _: &Value) {
+        self.0.push((path.into(), old.as_str().map(String::from)));
+    }
+}
+
+/// Splice turning `old` into `new`, if it changes less than the `threshold` fraction of their
+/// characters.
+#[cfg(feature = "diff")]
+fn diff_strings(path: &str, old: &str, new: &str, threshold: f64) -> Option<SpliceOperation> {
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+    let suffix = old_rest
+        .chars()
+        .rev()
+        .zip(new_rest.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    let removed = &old_rest[..old_rest.len() - suffix];
+    let inserted = &new_rest[..new_rest.len() - suffix];
+
+    let remove = removed.chars().count();
+    let changed = remove + inserted.chars().count();
+    let len = old.chars().count().max(new.chars().count());
+    if (changed as f64) < threshold * len as f64 {
+        Some(SpliceOperation {
+            path: path.into(),
+            offset: old[..prefix].chars().count(),
+            remove,
+            insert: inserted.into(),
+        })
+    } else {
+        None
+    }
+}
+
+/// Diff two JSON documents and generate an extended patch, as configured by the options. Without
+/// any extended operations enabled, the patch consists of the operations
+/// [`diff_with_options`](crate::diff_with_options) generates.
+///
+/// Requires the `diff` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::extended::{diff_extended, ExtendedDiffOptions, ExtendedOperation};
+/// use serde_json::{from_value, json};
+///
+/// let left = json!({ "text": "The quick brown fox jumps over the lazy dog" });
+/// let right = json!({ "text": "The quick red fox jumps over the lazy dog" });
+///
+/// let options = ExtendedDiffOptions {
+///     splice_threshold: Some(0.5),
+///     ..Default::default()
+/// };
+/// let p = diff_extended(&left, &right, &options);
+/// assert_eq!(p, from_value::<Vec<ExtendedOperation>>(json!([
+///   { "op": "splice", "path": "/text", "offset": 10, "remove": 5, "insert": "red" }
+/// ])).unwrap());
+/// ```
+#[cfg(feature = "diff")]
+pub fn diff_extended(
+    left: &Value,
+    right: &Value,
+    options: &ExtendedDiffOptions,
+) -> Vec<ExtendedOperation> {
+    let patch = match options.splice_threshold {
+        Some(threshold) => {
+            // Strings are matched to the operations in the document order, reordered afterwards
+            let diff_options = DiffOptions {
+                op_order: OpOrder::Document,
+                ..options.diff.clone()
+            };
+            let mut replaced = ReplacedStrings::default();
+            let patch = diff_with_visitor(left, right, &diff_options, &mut replaced);
+            let order = operation_order(&patch, options.diff.op_order);
+            let mut replaced = replaced.0.into_iter();
+            let mut patch: Vec<_> = patch
+                .0
+                .into_iter()
+                .map(|op| match op {
+                    PatchOperation::Replace(op) => {
+                        let splice = match (replaced.next(), op.value.as_str()) {
+                            (Some((path, Some(old))), Some(new)) if path == op.path => {
+                                diff_strings(&op.path, &old, new, threshold)
+                            }
+                            _ => None,
+                        };
+                        match splice {
+                            Some(splice) => Some(ExtendedOperation::Splice(splice)),
+                            None => Some(PatchOperation::Replace(op).into()),
+                        }
+                    }
+                    op => Some(op.into()),
+                })
+                .collect();
+            order
+                .into_iter()
+                .filter_map(|idx| patch[idx].take())
+                .collect()
+        }
+        None => crate::diff_with_options(left, right, &options.diff)
+            .0
+            .into_iter()
+            .map(ExtendedOperation::from)
+            .collect(),
+    };
+    if options.array_ranges {
+        collapse_ranges(left, patch)
+    } else {
+        patch
+    }
+}
+
+/// Splits the pointer into the parent pointer and the array index of the last reference token.
+#[cfg(feature = "diff")]
+fn element_of(path: &str) -> Option<(&str, usize)> {
+    let (parent, last) = split_last(path).ok()?;
+    Some((parent, parse_index(last, usize::MAX).ok()?))
+}
+
+/// Merges the operation into the previous one, if they form a range operation.
+#[cfg(feature = "diff")]
+fn merge_range(prev: &mut ExtendedOperation, op: &PatchOperation) -> bool {
+    use ExtendedOperation::{InsertMany, MoveRange, RemoveRange, Standard};
+
+    let next_index = |path: &str, start: &str, offset: usize| {
+        let (Some((parent, idx)), Some((start_parent, start))) =
+            (element_of(path), element_of(start))
+        else {
+            return false;
+        };
+        parent == start_parent && idx == start + offset
+    };
+    match (&mut *prev, op) {
+        (Standard(PatchOperation::Remove(first)), PatchOperation::Remove(op))
+            if op.path == first.path =>
+        {
+            *prev = RemoveRange(RemoveRangeOperation {
+                path: op.path.clone(),
+                count: 2,
+            });
+        }
+        (RemoveRange(range), PatchOperation::Remove(op)) if op.path == range.path => {
+            range.count += 1;
+        }
+        (Standard(PatchOperation::Add(first)), PatchOperation::Add(op))
+            if next_index(&op.path, &first.path, 1)
+                || (op.path == first.path && op.path.ends_with("/-")) =>
+        {
+            *prev = InsertMany(InsertManyOperation {
+                path: first.path.clone(),
+                values: vec![first.value.clone(), op.value.clone()],
+            });
+        }
+        (InsertMany(range), PatchOperation::Add(op))
+            if next_index(&op.path, &range.path, range.values.len())
+                || (op.path == range.path && op.path.ends_with("/-")) =>
+        {
+            range.values.push(op.value.clone());
+        }
+        (Standard(PatchOperation::Move(first)), PatchOperation::Move(op))
+            if next_index(&op.from, &first.from, 1)
+                && next_index(&op.path, &first.path, 1)
+                && matches!(
+                    (element_of(&first.from), element_of(&first.path)),
+                    (Some((from, start)), Some((path, target))) if from == path && target < start
+                ) =>
+        {
+            *prev = MoveRange(MoveRangeOperation {
+                from: first.from.clone(),
+                path: first.path.clone(),
+                count: 2,
+            });
+        }
+        (MoveRange(range), PatchOperation::Move(op))
+            if next_index(&op.from, &range.from, range.count)
+                && next_index(&op.path, &range.path, range.count) =>
+        {
+            range.count += 1;
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Merges runs of operations on consecutive array elements into range operations.
+#[cfg(feature = "diff")]
+fn collapse_ranges(left: &Value, patch: Vec<ExtendedOperation>) -> Vec<ExtendedOperation> {
+    // Object members can have numeric keys too, so track the document to tell arrays apart
+    let mut doc = clone_value(left);
+    let mut collapsed: Vec<ExtendedOperation> = Vec::with_capacity(patch.len());
+    for op in patch {
+        let merged = match (&op, collapsed.last_mut()) {
+            (ExtendedOperation::Standard(standard), Some(prev)) => {
+                let is_array = split_last(standard.path())
+                    .ok()
+                    .and_then(|(parent, _)| doc.pointer(parent))
+                    .is_some_and(Value::is_array);
+                is_array && merge_range(prev, standard)
+            }
+            _ => false,
+        };
+        let applied = apply_extended(&mut doc, slice::from_ref(&op), &OpRegistry::default(), None);
+        debug_assert!(applied.is_ok(), "generated patch does not apply");
+        if !merged {
+            collapsed.push(op);
+        }
+    }
+    drop_value(doc);
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn serialization() {
+        let value = json!([
+            { "op": "splice", "path": "/a", "offset": 1, "remove": 0, "insert": "x" },
+            { "op": "add", "path": "/b", "value": 1 },
+            { "op": "move", "from": "/b", "path": "/c" },
+        ]);
+        let p: Vec<ExtendedOperation> = from_value(value.clone()).unwrap();
+        assert!(matches!(p[0], ExtendedOperation::Splice(_)));
+        assert_eq!(p[1], PatchOperation::add("/b", json!(1)).into());
+        assert_eq!(serde_json::to_value(&p).unwrap(), value);
+        assert_eq!(
+            p[0].to_string(),
+            r#"{"op":"splice","path":"/a","offset":1,"remove":0,"insert":"x"}"#
+        );
+
+        assert!(from_value::<ExtendedOperation>(json!({ "op": "splice", "path": "/a" })).is_err());
+        assert!(from_value::<ExtendedOperation>(json!({ "op": "add", "path": "/a" })).is_err());
+        assert!(from_value::<ExtendedOperation>(json!({ "op": "unknown" })).is_err());
+
+        let value = json!({ "op": "unknown", "path": "/a", "x": [1] });
+        let op: ExtendedOperation = from_value(value.clone()).unwrap();
+        assert!(matches!(&op, ExtendedOperation::Custom(op) if op.params["x"] == json!([1])));
+        assert_eq!(serde_json::to_value(&op).unwrap(), value);
+    }
+
+    #[test]
+    fn test_digest() {
+        let doc = json!({ "a": { "b": [1, 2] } });
+        let digest = PatchEnvelope::hash_document(&doc["a"]);
+        let p: Vec<ExtendedOperation> = from_value(json!([
+            { "op": "test-digest", "path": "/a", "digest": digest.to_uppercase() },
+            { "op": "remove", "path": "/a/b/0" },
+            { "op": "test-digest", "path": "/a", "digest": digest },
+        ]))
+        .unwrap();
+        assert_eq!(
+            lower(&doc, &p[..2]).unwrap(),
+            from_value::<Patch>(json!([
+                { "op": "test", "path": "/a", "value": { "b": [1, 2] } },
+                { "op": "remove", "path": "/a/b/0" },
+            ]))
+            .unwrap()
+        );
+
+        let mut patched = doc.clone();
+        let err = patch_extended(&mut patched, &p).unwrap_err();
+        assert_eq!(patched, doc);
+        assert_eq!(err.operation, 2);
+        let actual = PatchEnvelope::hash_document(&json!({ "b": [2] }));
+        assert_eq!(
+            err.kind.to_string(),
+            format!("value did not match: expected \"{digest}\", found \"{actual}\"")
+        );
+
+        let missing = ExtendedOperation::TestDigest(TestDigestOperation {
+            path: "/x".into(),
+            digest: digest.clone(),
+        });
+        let err = patch_extended(&mut patched, &[missing]).unwrap_err();
+        assert_eq!(
+            err.kind.to_string(),
+            format!("value did not match: expected \"{digest}\", found nothing")
+        );
+    }
+
+    #[test]
+    fn splice() {
+        let mut doc = json!({ "text": "héllo wörld", "n": 1 });
+        let p: Vec<ExtendedOperation> = from_value(json!([
+            { "op": "splice", "path": "/text", "offset": 0, "remove": 5, "insert": "bye," },
+            { "op": "splice", "path": "/text", "offset": 10, "remove": 0, "insert": "!" },
+        ]))
+        .unwrap();
+        patch_extended(&mut doc, &p).unwrap();
+        assert_eq!(doc, json!({ "text": "bye, wörld!", "n": 1 }));
+
+        for (op, kind) in [
+            (
+                json!({ "op": "splice", "path": "/n", "offset": 0, "remove": 0, "insert": "" }),
+                "value is not a string",
+            ),
+            (
+                json!({ "op": "splice", "path": "/x", "offset": 0, "remove": 0, "insert": "" }),
+                "path does not exist",
+            ),
+            (
+                json!({ "op": "splice", "path": "/text", "offset": 12, "remove": 0, "insert": "" }),
+                "range is out of bounds",
+            ),
+            (
+                json!({ "op": "splice", "path": "/text", "offset": 10, "remove": 2, "insert": "" }),
+                "range is out of bounds",
+            ),
+        ] {
+            let p: Vec<ExtendedOperation> = from_value(json!([
+                { "op": "splice", "path": "/text", "offset": 0, "remove": 3, "insert": "" },
+                { "op": "add", "path": "/m", "value": 2 },
+                op,
+            ]))
+            .unwrap();
+            let err = patch_extended(&mut doc, &p).unwrap_err();
+            assert_eq!(err.operation, 2);
+            assert_eq!(err.kind.to_string(), kind);
+            assert_eq!(doc, json!({ "text": "bye, wörld!", "n": 1 }));
+            assert_eq!(lower(&doc, &p).unwrap_err().operation, 2);
+        }
+    }
+
+    #[test]
+    fn lowering() {
+        let doc = json!({ "text": "abc" });
+        let p: Vec<ExtendedOperation> = from_value(json!([
+            { "op": "splice", "path": "/text", "offset": 1, "remove": 1, "insert": "" },
+            { "op": "copy", "from": "/text", "path": "/copy" },
+            { "op": "splice", "path": "/copy", "offset": 2, "remove": 0, "insert": "d" },
+        ]))
+        .unwrap();
+        let lowered = lower(&doc, &p).unwrap();
+        assert_eq!(
+            serde_json::to_value(&lowered).unwrap(),
+            json!([
+                { "op": "replace", "path": "/text", "value": "ac" },
+                { "op": "copy", "from": "/text", "path": "/copy" },
+                { "op": "replace", "path": "/copy", "value": "acd" },
+            ])
+        );
+    }
+
+    #[test]
+    fn custom() {
+        let mut registry = OpRegistry::new();
+        registry
+            .register("increment", |value, _| match value {
+                Some(Value::Number(n)) => Ok(json!(n.as_i64().unwrap_or_default() + 1)),
+                Some(_) => Err("not a number".into()),
+                None => Ok(json!(1)),
+            })
+            .register("append-string", |value, op| {
+                let suffix = op.params.get("value").and_then(Value::as_str);
+                match (value, suffix) {
+                    (Some(Value::String(s)), Some(suffix)) => Ok(format!("{}{}", s, suffix).into()),
+                    _ => Err("expected strings".into()),
+                }
+            });
+        assert_eq!(
+            format!("{:?}", registry),
+            r#"{"append-string", "increment"}"#
+        );
+
+        let mut doc = json!({ "n": 1, "s": "a" });
+        let p: Vec<ExtendedOperation> = from_value(json!([
+            { "op": "increment", "path": "/n" },
+            { "op": "increment", "path": "/m" },
+            { "op": "append-string", "path": "/s", "value": "bc" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(lower_with_registry(&doc, &p, &registry).unwrap()).unwrap(),
+            json!([
+                { "op": "add", "path": "/n", "value": 2 },
+                { "op": "add", "path": "/m", "value": 1 },
+                { "op": "add", "path": "/s", "value": "abc" },
+            ])
+        );
+        patch_with_registry(&mut doc, &p, &registry).unwrap();
+        assert_eq!(doc, json!({ "n": 2, "s": "abc", "m": 1 }));
+
+        let err = patch_extended(&mut doc, &p).unwrap_err();
+        assert_eq!(err.operation, 0);
+        assert!(matches!(err.kind, PatchErrorKind::UnknownOperation));
+
+        for (op, kind) in [
+            (
+                json!({ "op": "append-string", "path": "/n", "value": "x" }),
+                "custom operation failed: expected strings",
+            ),
+            (
+                json!({ "op": "increment", "path": "/x/y" }),
+                "path does not exist",
+            ),
+            (
+                json!({ "op": "decrement", "path": "/n" }),
+                "unknown operation",
+            ),
+        ] {
+            let p: Vec<ExtendedOperation> = from_value(json!([
+                { "op": "increment", "path": "/n" },
+                { "op": "remove", "path": "/m" },
+                op,
+            ]))
+            .unwrap();
+            let err = patch_with_registry(&mut doc, &p, &registry).unwrap_err();
+            assert_eq!(err.operation, 2);
+            assert_eq!(err.kind.to_string(), kind);
+            assert_eq!(doc, json!({ "n": 2, "s": "abc", "m": 1 }));
+        }
+    }
+
+    #[test]
+    fn validation() {
+        let doc = json!({ "a": [1, 2, 3], "s": "abc" });
+        let p: Vec<ExtendedOperation> = from_value(json!([
+            { "op": "remove-range", "path": "/a/0", "count": 2 },
+            { "op": "remove-range", "path": "/a/0", "count": 2 },
+            { "op": "splice", "path": "/s", "offset": 1, "remove": 1, "insert": "" },
+            { "op": "increment", "path": "/s" },
+        ]))
+        .unwrap();
+        let errors = validate_extended(&doc, &p).unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|err| (err.operation, err.kind.to_string()))
+                .collect::<Vec<_>>(),
+            [
+                (1, "range is out of bounds".into()),
+                (3, "unknown operation".into())
+            ]
+        );
+        assert!(validate_extended(&doc, &p[..1]).is_ok());
+    }
+
+    #[cfg(feature = "test-predicates")]
+    #[test]
+    fn predicates() {
+        let doc = json!({
+            "n": 1,
+            "f": 1.5,
+            "s": "hello world",
+            "a": [1, { "b": 2 }],
+            "o": { "x": 1, "y": [2] },
+        });
+        let passing = json!([
+            { "op": "test-type", "path": "/n", "type": "integer" },
+            { "op": "test-type", "path": "/f", "type": "number" },
+            { "op": "test-type", "path": "", "type": "object" },
+            { "op": "test-matches", "path": "/s", "pattern": "^hel+o\\b" },
+            { "op": "test-contains", "path": "/s", "value": "o w" },
+            { "op": "test-contains", "path": "/a", "value": { "b": 2 } },
+            { "op": "test-contains", "path": "/o", "value": { "y": [2] } },
+        ]);
+        let p: Vec<ExtendedOperation> = from_value(passing.clone()).unwrap();
+        assert!(matches!(p[0], ExtendedOperation::TestType(_)));
+        assert_eq!(serde_json::to_value(&p).unwrap(), passing);
+        assert!(validate_extended(&doc, &p).is_ok());
+        assert_eq!(
+            serde_json::to_value(lower(&doc, &p[..2]).unwrap()).unwrap(),
+            json!([
+                { "op": "test", "path": "/n", "value": 1 },
+                { "op": "test", "path": "/f", "value": 1.5 },
+            ])
+        );
+
+        let failing: Vec<ExtendedOperation> = from_value(json!([
+            { "op": "test-type", "path": "/f", "type": "integer" },
+            { "op": "test-type", "path": "/x", "type": "null" },
+            { "op": "test-matches", "path": "/n", "pattern": "1" },
+            { "op": "test-matches", "path": "/s", "pattern": "(" },
+            { "op": "test-contains", "path": "/a", "value": 2 },
+            { "op": "test-contains", "path": "/o", "value": { "x": 2 } },
+            { "op": "test-contains", "path": "/n", "value": 1 },
+        ]))
+        .unwrap();
+        let errors = validate_extended(&doc, &failing).unwrap_err();
+        assert_eq!(errors.len(), failing.len());
+        assert!(matches!(
+            errors[1].kind,
+            PatchErrorKind::TestFailed { actual: None, .. }
+        ));
+        assert!(matches!(errors[3].kind, PatchErrorKind::InvalidPattern(_)));
+        assert!(errors
+            .iter()
+            .enumerate()
+            .all(|(i, err)| i == 3 || matches!(err.kind, PatchErrorKind::TestFailed { .. })));
+        assert_eq!(
+            errors[4].to_string(),
+            "Operation '/4' failed at path '/a': value did not match: expected 2, found [1,{\"b\":2}]"
+        );
+
+        let mut patched = doc.clone();
+        let p: Vec<ExtendedOperation> = from_value(json!([
+            { "op": "remove", "path": "/n" },
+            { "op": "test-type", "path": "/s", "type": "array" },
+        ]))
+        .unwrap();
+        assert_eq!(patch_extended(&mut patched, &p).unwrap_err().operation, 1);
+        assert_eq!(patched, doc);
+    }
+
+    #[cfg(feature = "diff")]
+    #[test]
+    fn diff_splices() {
+        let left = json!({ "a": "0123456789", "b": ["xyz"], "c": 1, "d": "ab" });
+        let right = json!({ "a": "01234x6789", "b": ["xy"], "c": "1", "d": "ba" });
+        let options = ExtendedDiffOptions {
+            diff: crate::DiffOptions {
+                test_before_destructive: true,
+                ..Default::default()
+            },
+            splice_threshold: Some(0.7),
+            ..Default::default()
+        };
+        let p = diff_extended(&left, &right, &options);
+        assert_eq!(
+            serde_json::to_value(&p).unwrap(),
+            json!([
+                { "op": "test", "path": "/a", "value": "0123456789" },
+                { "op": "splice", "path": "/a", "offset": 5, "remove": 1, "insert": "x" },
+                { "op": "test", "path": "/b/0", "value": "xyz" },
+                { "op": "splice", "path": "/b/0", "offset": 2, "remove": 1, "insert": "" },
+                { "op": "test", "path": "/c", "value": 1 },
+                { "op": "replace", "path": "/c", "value": "1" },
+                { "op": "test", "path": "/d", "value": "ab" },
+                { "op": "replace", "path": "/d", "value": "ba" },
+            ])
+        );
+        let mut doc = left.clone();
+        patch_extended(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+
+        let p = diff_extended(&left, &right, &Default::default());
+        assert_eq!(lower(&left, &p).unwrap(), crate::diff(&left, &right));
+    }
+
+    fn range_patch(value: Value) -> Vec<ExtendedOperation> {
+        from_value(value).unwrap()
+    }
+
+    /// Applies the patch both as extended and lowered, checking the outcomes match.
+    fn check_lowered(doc: &Value, p: &[ExtendedOperation]) -> Value {
+        let mut patched = doc.clone();
+        patch_extended(&mut patched, p).unwrap();
+        let lowered = lower(doc, p).unwrap();
+        assert_eq!(crate::patched(doc, &lowered).unwrap(), patched, "{:?}", p);
+        patched
+    }
+
+    #[test]
+    fn array_ranges() {
+        let doc = json!({ "a": [0, 1, 2, 3, 4], "b": [] });
+        let p = range_patch(json!([
+            { "op": "remove-range", "path": "/a/1", "count": 2 },
+            { "op": "insert-many", "path": "/b/-", "values": ["x", "y"] },
+            { "op": "insert-many", "path": "/b/1", "values": [true] },
+        ]));
+        assert_eq!(
+            check_lowered(&doc, &p),
+            json!({ "a": [0, 3, 4], "b": ["x", true, "y"] })
+        );
+
+        let p = range_patch(json!([
+            { "op": "move-range", "from": "/a/3", "path": "/b/0", "count": 2 },
+            { "op": "move-range", "from": "/a/0", "path": "/a/-", "count": 1 },
+        ]));
+        assert_eq!(
+            check_lowered(&doc, &p),
+            json!({ "a": [1, 2, 0], "b": [3, 4] })
+        );
+
+        for (op, kind) in [
+            (
+                json!({ "op": "remove-range", "path": "/a/4", "count": 2 }),
+                "range is out of bounds",
+            ),
+            (
+                json!({ "op": "remove-range", "path": "/a/-", "count": 0 }),
+                "path is invalid",
+            ),
+            (
+                json!({ "op": "remove-range", "path": "/x/0", "count": 0 }),
+                "path does not exist",
+            ),
+            (
+                json!({ "op": "insert-many", "path": "/a/6", "values": [1] }),
+                "array index is out of bounds",
+            ),
+            (
+                json!({ "op": "insert-many", "path": "/b/x", "values": [] }),
+                "path is invalid",
+            ),
+            (
+                json!({ "op": "move-range", "from": "/a/1", "path": "/a/4", "count": 2 }),
+                "array index is out of bounds",
+            ),
+            (
+                json!({ "op": "move-range", "from": "/a/1", "path": "/a/2/x", "count": 2 }),
+                "cannot move the value inside itself",
+            ),
+            (
+                json!({ "op": "move-range", "from": "/b/1", "path": "/a/0", "count": 1 }),
+                "\"from\" path is invalid",
+            ),
+            (
+                json!({ "op": "move-range", "from": "/a/0", "path": "/c/0", "count": 1 }),
+                "path does not exist",
+            ),
+            (
+                json!({ "op": "move-range", "from": "/a/0", "path": "/b/2", "count": 1 }),
+                "array index is out of bounds",
+            ),
+            (
+                json!({ "op": "move-range", "from": "/c/0", "path": "/a/0", "count": 1 }),
+                "\"from\" path is invalid",
+            ),
+        ] {
+            let p = range_patch(json!([
+                { "op": "remove-range", "path": "/a/0", "count": 0 },
+                { "op": "insert-many", "path": "/b/0", "values": [5] },
+                op,
+            ]));
+            let mut patched = doc.clone();
+            let err = patch_extended(&mut patched, &p).unwrap_err();
+            assert_eq!((err.operation, err.kind.to_string()), (2, kind.into()));
+            assert_eq!(patched, doc);
+            assert_eq!(lower(&doc, &p).unwrap_err().operation, 2);
+        }
+
+        let p = range_patch(json!([{ "op": "remove-range", "path": "/b", "count": 1 }]));
+        let err = patch_extended(&mut json!({ "b": { "c": 1 } }), &p).unwrap_err();
+        assert_eq!(err.kind.to_string(), "value is not an array");
+    }
+
+    #[test]
+    fn move_ranges() {
+        // Moves within an array, against a model of the move
+        let doc = json!([0, 1, 2, 3, 4, 5]);
+        for start in 0..6 {
+            for count in 0..=6 - start {
+                for target in 0..=6 - count {
+                    let p = range_patch(json!([{
+                        "op": "move-range",
+                        "from": format!("/{}", start),
+                        "path": format!("/{}", target),
+                        "count": count,
+                    }]));
+                    let mut expected: Vec<_> = (0..6).collect();
+                    let moved: Vec<_> = expected.drain(start..start + count).collect();
+                    expected.splice(target..target, moved);
+                    assert_eq!(check_lowered(&doc, &p), json!(expected), "{:?}", p);
+                }
+            }
+        }
+
+        // Moves between nested arrays
+        let doc = json!([0, 1, [2, [3, 4]], 5, [6, 7]]);
+        for (from, path, expected) in [
+            // Paths refer to the locations after the range is removed
+            ("/0", "/2/2", json!([[2, [3, 4]], 5, [6, 7, 0, 1]])),
+            ("/0", "/2/1", json!([[2, [3, 4]], 5, [6, 0, 1, 7]])),
+            ("/0", "/2/-", json!([[2, [3, 4]], 5, [6, 7, 0, 1]])),
+            ("/2/1/0", "/0", json!([3, 4, 0, 1, [2, []], 5, [6, 7]])),
+            ("/2/1/0", "/4", json!([0, 1, [2, []], 5, 3, 4, [6, 7]])),
+            ("/2/1/0", "/-", json!([0, 1, [2, []], 5, [6, 7], 3, 4])),
+            ("/2/1/0", "/4/1", json!([0, 1, [2, []], 5, [6, 3, 4, 7]])),
+            ("/4/0", "/2/1/1", json!([0, 1, [2, [3, 6, 7, 4]], 5, []])),
+        ] {
+            let p = range_patch(json!([
+                { "op": "move-range", "from": from, "path": path, "count": 2 }
+            ]));
+            assert_eq!(check_lowered(&doc, &p), expected, "{:?}", p);
+        }
+    }
+
+    #[cfg(feature = "diff")]
+    #[test]
+    fn diff_ranges() {
+        let options = ExtendedDiffOptions {
+            diff: crate::DiffOptions {
+                array_keys: [("/k".into(), "id".into())].into(),
+                ..Default::default()
+            },
+            array_ranges: true,
+            ..Default::default()
+        };
+        let left = json!({
+            "a": [1, 2, 3, 4, 5],
+            "b": [1],
+            "k": [{ "id": 1 }, { "id": 2 }, { "id": 3 }, { "id": 4 }],
+            "o": { "1": 1, "2": 2 },
+        });
+        let right = json!({
+            "a": [1, 5],
+            "b": [1, 2, 3],
+            "k": [{ "id": 3 }, { "id": 4 }, { "id": 1 }, { "id": 2 }],
+            "o": {},
+        });
+        let p = diff_extended(&left, &right, &options);
+        assert_eq!(
+            serde_json::to_value(&p).unwrap(),
+            json!([
+                { "op": "replace", "path": "/a/1", "value": 5 },
+                { "op": "remove-range", "path": "/a/2", "count": 3 },
+                { "op": "insert-many", "path": "/b/1", "values": [2, 3] },
+                { "op": "move-range", "from": "/k/2", "path": "/k/0", "count": 2 },
+                { "op": "remove", "path": "/o/1" },
+                { "op": "remove", "path": "/o/2" },
+            ])
+        );
+        assert_eq!(check_lowered(&left, &p), right);
+    }
+}
@@ -0,0 +1,539 @@
+//! Patching and diffing of JSON documents represented by value types other than
+//! `serde_json::Value`, through the [`JsonLike`] trait.
+//!
+//! Values of patch operations are still `serde_json::Value`s, they are converted to and from the
+//! document value type when the operations are applied or generated.
+//!
+//! # Example
+//!
+//! ```rust
+//! use json_patch::generic;
+//! use json_patch::Patch;
+//! use serde_json::{from_value, json};
+//!
+//! let mut doc = json!({ "a": [1, 2] });
+//! let p: Patch = from_value(json!([
+//!   { "op": "add", "path": "/a/-", "value": 3 },
+//!   { "op": "remove", "path": "/a/0" },
+//! ])).unwrap();
+//!
+//! generic::patch(&mut doc, &p).unwrap();
+//! assert_eq!(doc, json!({ "a": [2, 3] }));
+//! ```
+use crate::deep::{clone_value, drop_value, values_equal};
+#[cfg(feature = "diff")]
+use crate::diff::{children, Key};
+use crate::{
+    add, copy, member_position, mov, move_positions, remove, replace, restore, test,
+    translate_error,
+};
+use crate::{PatchError, PatchOperation};
+#[cfg(feature = "diff")]
+use alloc::vec;
+use alloc::{string::String, vec::Vec};
+use serde_json::Value;
+
+/// JSON value which can be patched and diffed.
+///
+/// Methods modifying arrays and objects are only called on values for which [`as_array`] or
+/// [`is_object`] tell they are arrays or objects, with indices within the bounds.
+///
+/// [`as_array`]: JsonLike::as_array
+/// [`is_object`]: JsonLike::is_object
+pub trait JsonLike: Clone + PartialEq + Sized {
+    /// Converts a value of a patch operation.
+    fn from_json(value: &Value) -> Self;
+
+    /// Converts the value into a value of a patch operation.
+    fn to_json(&self) -> Value;
+
+    /// Elements of the array, `None` if the value is not an array.
+    fn as_array(&self) -> Option<&[Self]>;
+
+    /// Mutable elements of the array, `None` if the value is not an array.
+    fn as_array_mut(&mut self) -> Option<&mut [Self]>;
+
+    /// Inserts the element into the array at the given index, shifting subsequent elements.
+    fn insert_element(&mut self, index: usize, value: Self);
+
+    /// Removes the element of the array at the given index, shifting subsequent elements.
+    fn remove_element(&mut self, index: usize) -> Self;
+
+    /// Checks if the value is an object.
+    fn is_object(&self) -> bool;
+
+    /// Members of the object, in any order. `None` if the value is not an object.
+    fn members(&self) -> Option<Vec<(&str, &Self)>>;
+
+    /// Member of the object with the given key.
+    fn member(&self, key: &str) -> Option<&Self>;
+
+    /// Mutable member of the object with the given key.
+    fn member_mut(&mut self, key: &str) -> Option<&mut Self>;
+
+    /// Inserts the member into the object, returning the previous value of the member.
+    fn insert_member(&mut self, key: String, value: Self) -> Option<Self>;
+
+    /// Removes the member from the object, returning its value.
+    fn remove_member(&mut self, key: &str) -> Option<Self>;
+
+    /// Position of the member among the members of the object, for putting it back there when a
+    /// failed patch is reverted. `None` by default, for objects whose members are not ordered.
+    fn member_position(&self, key: &str) -> Option<usize> {
+        let _ = key;
+        None
+    }
+
+    /// Inserts the member into the object at the given position (see
+    /// [`member_position`](JsonLike::member_position)), returning the previous value of the
+    /// member. Same as [`insert_member`](JsonLike::insert_member) by default.
+    fn insert_member_at(&mut self, position: usize, key: String, value: Self) -> Option<Self> {
+        let _ = position;
+        self.insert_member(key, value)
+    }
+
+    /// Clones the value for a `copy` operation. Same as [`Clone::clone`] by default, values
+    /// whose `Clone` implementation recurses into nested values can avoid overflowing the stack
+    /// on deeply nested documents.
+    fn clone_value(&self) -> Self {
+        self.clone()
+    }
+
+    /// Compares the value with the value of a `test` operation.
+    fn eq_json(&self, value: &Value) -> bool {
+        *self == Self::from_json(value)
+    }
+
+    /// Drops a value removed from the document or replaced in it. Values whose `Drop`
+    /// implementation recurses into nested values can avoid overflowing the stack on deeply nested
+    /// documents.
+    fn drop_value(self) {}
+}
+
+impl JsonLike for Value {
+    fn from_json(value: &Value) -> Self {
+        clone_value(value)
+    }
+
+    fn to_json(&self) -> Value {
+        clone_value(self)
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        self.as_array().map(Vec::as_slice)
+    }
+
+    fn as_array_mut(&mut self) -> Option<&mut [Self]> {
+        self.as_array_mut().map(Vec::as_mut_slice)
+    }
+
+    fn insert_element(&mut self, index: usize, value: Self) {
+        match self {
+            Value::Array(arr) => arr.insert(index, value),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn remove_element(&mut self, index: usize) -> Self {
+        match self {
+            Value::Array(arr) => arr.remove(index),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn is_object(&self) -> bool {
+        self.is_object()
+    }
+
+    fn members(&self) -> Option<Vec<(&str, &Self)>> {
+        self.as_object()
+            .map(|obj| obj.iter().map(|(k, v)| (k.as_str(), v)).collect())
+    }
+
+    fn member(&self, key: &str) -> Option<&Self> {
+        self.as_object()?.get(key)
+    }
+
+    fn member_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_object_mut()?.get_mut(key)
+    }
+
+    fn insert_member(&mut self, key: String, value: Self) -> Option<Self> {
+        self.as_object_mut()?.insert(key, value)
+    }
+
+    fn remove_member(&mut self, key: &str) -> Option<Self> {
+        crate::remove_member(self.as_object_mut()?, key)
+    }
+
+    #[cfg(feature = "preserve_order")]
+    fn member_position(&self, key: &str) -> Option<usize> {
+        self.as_object()?.keys().position(|k| k == key)
+    }
+
+    #[cfg(feature = "preserve_order")]
+    fn insert_member_at(&mut self, position: usize, key: String, value: Self) -> Option<Self> {
+        let obj = self.as_object_mut()?;
+        let position = position.min(obj.len());
+        obj.shift_insert(position, key, value)
+    }
+
+    fn clone_value(&self) -> Self {
+        clone_value(self)
+    }
+
+    fn eq_json(&self, value: &Value) -> bool {
+        values_equal(self, value)
+    }
+
+    fn drop_value(self) {
+        drop_value(self)
+    }
+}
+
+/// Inverse of an applied operation, to revert the document when a subsequent one fails. `Add`
+/// and `Move` carry the position of the object member they put back, see
+/// [`crate::member_position`].
+enum Undo<V> {
+    Add(String, V, Option<usize>),
+    Remove(String),
+    Replace(String, V),
+    Move(String, String, Option<usize>),
+}
+
+/// Drops the values of the undo stack, see [`JsonLike::drop_value`].
+fn drop_undo_stack<V: JsonLike>(undo_stack: Vec<Undo<V>>) {
+    for undo in undo_stack {
+        if let Undo::Add(_, value, _) | Undo::Replace(_, value) = undo {
+            value.drop_value();
+        }
+    }
+}
+
+/// Patch the JSON document in place, the same way [`crate::patch`] does. If any of the operations
+/// fails, all previous operations are reverted.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{generic, Patch, PatchErrorKind};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "a": 1 });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/a", "value": 2 },
+///   { "op": "remove", "path": "/b" },
+/// ])).unwrap();
+///
+/// let err = generic::patch(&mut doc, &p).unwrap_err();
+/// assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+/// assert_eq!(doc, json!({ "a": 1 }));
+/// ```
+pub fn patch<V: JsonLike>(doc: &mut V, patch: &[PatchOperation]) -> Result<(), PatchError> {
+    let mut undo_stack = Vec::with_capacity(patch.len());
+    if let Err(e) = apply_patches(doc, patch, &mut undo_stack) {
+        if let Err(e) = undo_patches(doc, undo_stack) {
+            unreachable!("unable to undo applied patches: {e}")
+        }
+        return Err(e);
+    }
+    drop_undo_stack(undo_stack);
+    Ok(())
+}
+
+/// Apply the patch to a copy of the JSON document, returning the patched copy, the same way
+/// [`crate::patched`] does. The original document is left intact.
+///
+/// The document is copied by cloning it: with values sharing their subtrees, like the
+/// `SharedValue` of the `shared` feature, the patched copy shares the subtrees the patch leaves
+/// unchanged with the original document.
+pub fn patched<V: JsonLike>(doc: &V, patch: &[PatchOperation]) -> Result<V, PatchError> {
+    let mut doc = doc.clone();
+    let mut undo_stack = Vec::new();
+    let result = apply_patches(&mut doc, patch, &mut undo_stack);
+    drop_undo_stack(undo_stack);
+    match result {
+        Ok(()) => Ok(doc),
+        Err(e) => {
+            doc.drop_value();
+            Err(e)
+        }
+    }
+}
+
+fn undo_patches<V: JsonLike>(doc: &mut V, undo_stack: Vec<Undo<V>>) -> Result<(), PatchError> {
+    for (operation, undo) in undo_stack.into_iter().enumerate().rev() {
+        let (path, result) = match undo {
+            Undo::Add(path, value, position) => {
+                let result = restore(doc, path.as_str(), value, position)
+                    .map(|prev| prev.map_or((), V::drop_value));
+                (path, result)
+            }
+            Undo::Remove(path) => {
+                let result = remove(doc, path.as_str(), true).map(V::drop_value);
+                (path, result)
+            }
+            Undo::Replace(path, value) => {
+                let result = replace(doc, path.as_str(), value).map(V::drop_value);
+                (path, result)
+            }
+            Undo::Move(from, path, position) => {
+                let result = remove(doc, from.as_str(), true)
+                    .and_then(|value| restore(doc, path.as_str(), value, position))
+                    .map(|prev| prev.map_or((), V::drop_value));
+                (path, result)
+            }
+        };
+        result.map_err(|e| translate_error(e, operation, &path))?;
+    }
+    Ok(())
+}
+
+/// Apply patches, recording the inverse of all the changes in the `undo_stack`.
+fn apply_patches<V: JsonLike>(
+    doc: &mut V,
+    patches: &[PatchOperation],
+    undo_stack: &mut Vec<Undo<V>>,
+) -> Result<(), PatchError> {
+    for (operation, patch) in patches.iter().enumerate() {
+        let path = patch.path();
+        let result = match patch {
+            PatchOperation::Add(op) => add(doc, path, V::from_json(&op.value)).map(|prev| {
+                undo_stack.push(match prev {
+                    None => Undo::Remove(op.path.clone()),
+                    Some(v) => Undo::Add(op.path.clone(), v, None),
+                })
+            }),
+            PatchOperation::Remove(op) => {
+                let position = member_position(doc, path);
+                remove(doc, path, false)
+                    .map(|prev| undo_stack.push(Undo::Add(op.path.clone(), prev, position)))
+            }
+            PatchOperation::Replace(op) => replace(doc, path, V::from_json(&op.value))
+                .map(|prev| undo_stack.push(Undo::Replace(op.path.clone(), prev))),
+            PatchOperation::Move(op) => {
+                let (from_position, path_position) = move_positions(doc, op.from.as_str(), path);
+                mov(doc, op.from.as_str(), path, false).map(|prev| {
+                    if let Some(prev) = prev {
+                        undo_stack.push(Undo::Add(op.path.clone(), prev, path_position));
+                    }
+                    undo_stack.push(Undo::Move(op.path.clone(), op.from.clone(), from_position));
+                })
+            }
+            PatchOperation::Copy(op) => copy(doc, op.from.as_str(), path).map(|prev| {
+                undo_stack.push(match prev {
+                    None => Undo::Remove(op.path.clone()),
+                    Some(v) => Undo::Add(op.path.clone(), v, None),
+                })
+            }),
+            PatchOperation::Test(op) => test(doc, path, &op.value),
+        };
+        result.map_err(|e| translate_error(e, operation, path))?;
+    }
+    Ok(())
+}
+
+/// Diff two JSON documents and generate a JSON Patch (RFC 6902), the same one [`crate::diff`]
+/// generates for the documents represented as `serde_json::Value`.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{generic, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let left = json!({ "a": [1, 2], "b": true });
+/// let right = json!({ "a": [1], "c": null });
+///
+/// let p = generic::diff(&left, &right);
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "remove", "path": "/a/1" },
+///   { "op": "add", "path": "/c", "value": null },
+///   { "op": "remove", "path": "/b" },
+/// ])).unwrap());
+/// ```
+#[cfg(feature = "diff")]
+pub fn diff<V: JsonLike>(left: &V, right: &V) -> crate::Patch {
+    use crate::{AddOperation, RemoveOperation, ReplaceOperation};
+
+    /// Unit of work of the differ, performed in reverse order of scheduling.
+    enum Task<'a, V> {
+        Diff(&'a V, &'a V),
+        Push(Key<'a>),
+        Pop,
+        Added(Key<'a>, &'a V),
+        Removed(Key<'a>),
+    }
+
+    let mut ops = Vec::new();
+    let mut path = String::new();
+    let mut tasks = vec![Task::Diff(left, right)];
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Diff(left, right) => {
+                if left == right {
+                    continue;
+                }
+                let (left_children, right_children) = match (children(left), children(right)) {
+                    (Some(left_children), Some(right_children))
+                        if left.is_object() == right.is_object() =>
+                    {
+                        (left_children, right_children)
+                    }
+                    _ => {
+                        ops.push(PatchOperation::Replace(ReplaceOperation {
+                            path: path.clone(),
+                            value: right.to_json(),
+                        }));
+                        continue;
+                    }
+                };
+
+                let mut common = Vec::new();
+                let mut added = Vec::new();
+                let mut removed = Vec::new();
+                let mut left_iter = left_children.into_iter().peekable();
+                let mut right_iter = right_children.into_iter().peekable();
+                loop {
+                    match (left_iter.peek(), right_iter.peek()) {
+                        (Some((lk, _)), Some((rk, _))) if lk == rk => {
+                            let (key, lv) = left_iter.next().unwrap();
+                            let (_, rv) = right_iter.next().unwrap();
+                            common.push((key, lv, rv));
+                        }
+                        (Some((lk, _)), Some((rk, _))) if lk < rk => {
+                            removed.push(left_iter.next().unwrap().0)
+                        }
+                        (Some(_), Some(_)) | (None, Some(_)) => {
+                            added.push(right_iter.next().unwrap())
+                        }
+                        (Some(_), None) => removed.push(left_iter.next().unwrap().0),
+                        (None, None) => break,
+                    }
+                }
+
+                // Removed array elements shift indices of the subsequent ones
+                let mut shift = 0;
+                let removed: Vec<_> = removed
+                    .into_iter()
+                    .map(|key| match key {
+                        Key::Index(idx) => {
+                            shift += 1;
+                            Key::Index(idx + 1 - shift)
+                        }
+                        key => key,
+                    })
+                    .collect();
+                tasks.extend(removed.into_iter().rev().map(Task::Removed));
+                tasks.extend(added.into_iter().rev().map(|(key, v)| Task::Added(key, v)));
+                for (key, lv, rv) in common.into_iter().rev() {
+                    tasks.push(Task::Pop);
+                    tasks.push(Task::Diff(lv, rv));
+                    tasks.push(Task::Push(key));
+                }
+            }
+            Task::Push(key) => push(&mut path, key),
+            Task::Pop => pop(&mut path),
+            Task::Added(key, value) => {
+                push(&mut path, key);
+                ops.push(PatchOperation::Add(AddOperation {
+                    path: path.clone(),
+                    value: value.to_json(),
+                }));
+                pop(&mut path);
+            }
+            Task::Removed(key) => {
+                push(&mut path, key);
+                ops.push(PatchOperation::Remove(RemoveOperation {
+                    path: path.clone(),
+                }));
+                pop(&mut path);
+            }
+        }
+    }
+    crate::Patch(ops)
+}
+
+#[cfg(feature = "diff")]
+fn push(path: &mut String, key: Key) {
+    match key {
+        Key::Index(idx) => {
+            use core::fmt::Write;
+            write!(path, "/{}", idx).unwrap();
+        }
+        Key::Name(name) => {
+            path.push('/');
+            crate::append_path(path, name);
+        }
+    }
+}
+
+#[cfg(feature = "diff")]
+fn pop(path: &mut String) {
+    // Escaped reference tokens never contain `/`
+    path.truncate(path.rfind('/').unwrap_or(0));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deep::{drop_operations, drop_value};
+    use crate::PatchOperation;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn deep_values() {
+        let mut nested = Value::Null;
+        for _ in 0..100_000 {
+            nested = Value::Array(vec![nested]);
+        }
+        let p = vec![
+            PatchOperation::add("/a", nested),
+            PatchOperation::copy_from("/a", "/b"),
+            PatchOperation::remove("/c"),
+        ];
+        let mut doc = json!({});
+        assert!(super::patch(&mut doc, &p).is_err());
+        assert_eq!(doc, json!({}));
+        assert!(super::patch(&mut doc, &p[..2]).is_ok());
+        drop_value(doc);
+        drop_operations(p);
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn member_positions_reverted() {
+        let original = r#"{"a/b":0,"x":null}"#;
+        let p = [
+            PatchOperation::remove("/a~1b"),
+            PatchOperation::copy_from("/new", "/x"),
+        ];
+        let mut doc: Value = serde_json::from_str(original).unwrap();
+        assert!(super::patch(&mut doc, &p).is_err());
+        assert_eq!(doc.to_string(), original);
+    }
+
+    #[cfg(feature = "diff")]
+    #[test]
+    fn diff_like_values() {
+        let cases = [
+            (
+                json!({ "a": { "k": [1, 2, 3] }, "b": {}, "c/d": 1, "e": [1] }),
+                json!({ "a": { "k": [1] }, "b": { "~": [1, 2] }, "e": [1, { "0": 1 }] }),
+            ),
+            (
+                json!([1, [2, 3], { "x": 4 }]),
+                json!([0, [2, 3, 4], { "x": 5 }, 6]),
+            ),
+            (json!({ "a": 1 }), json!(1)),
+            (json!({ "a": { "0": 1 } }), json!({ "a": [1] })),
+            (json!(null), json!(null)),
+        ];
+        for (left, right) in &cases {
+            let p = super::diff(left, right);
+            assert_eq!(p, crate::diff(left, right));
+            let mut doc = left.clone();
+            super::patch(&mut doc, &p).unwrap();
+            assert_eq!(&doc, right);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use crate::pointer::check_escapes;
+use crate::{Pointer, PointerError};
+use alloc::{borrow::ToOwned, string::String};
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+
+/// Glob of JSON pointers: a JSON pointer where a `*` reference token matches any single key or
+/// array index, and a `**` reference token matches any number of them (including none), like
+/// `/items/*/price` or `/audit/**`. Other reference tokens match themselves.
+///
+/// Globs are used by [`DiffOptions::ignore_paths`](crate::DiffOptions::ignore_paths) and
+/// [`PatchPolicy`](crate::PatchPolicy).
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{Pointer, PointerGlob};
+///
+/// let glob: PointerGlob = "/items/*/price".parse().unwrap();
+/// assert!(glob.matches(&"/items/0/price".parse().unwrap()));
+/// assert!(!glob.matches(&"/items/0/price/currency".parse().unwrap()));
+/// assert!(!glob.matches(&"/items/0".parse().unwrap()));
+///
+/// // The location is nested under a matching one
+/// assert!(glob.covers(&"/items/0/price/currency".parse().unwrap()));
+/// // Locations nested under the location can match
+/// assert!(glob.may_match_under(&"/items/0".parse().unwrap()));
+///
+/// let glob = PointerGlob::parse("/audit/**").unwrap();
+/// assert!(glob.matches(&Pointer::from_tokens(["audit"])));
+/// assert!(glob.matches(&Pointer::from_tokens(["audit", "log", "0"])));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PointerGlob(String);
+
+impl PointerGlob {
+    /// Parse and validate the glob, which has the syntax of a JSON pointer.
+    pub fn parse(glob: &str) -> Result<PointerGlob, PointerError> {
+        if !glob.is_empty() && !glob.starts_with('/') {
+            return Err(PointerError::MissingLeadingSlash);
+        }
+        check_escapes(glob)?;
+        Ok(PointerGlob(glob.to_owned()))
+    }
+
+    /// Returns the glob as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns whether the glob matches the pointer.
+    pub fn matches(&self, pointer: &Pointer) -> bool {
+        matches(&self.0, pointer.as_str())
+    }
+
+    /// Returns whether the glob matches the pointer or one of its ancestors, that is whether the
+    /// location is a matching one or is nested under one.
+    pub fn covers(&self, pointer: &Pointer) -> bool {
+        covers(&self.0, pointer.as_str())
+    }
+
+    /// Returns whether the glob can match locations nested under the pointer (not the pointer
+    /// itself).
+    pub fn may_match_under(&self, pointer: &Pointer) -> bool {
+        may_match_under(&self.0, pointer.as_str())
+    }
+}
+
+impl Display for PointerGlob {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for PointerGlob {
+    type Err = PointerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PointerGlob::parse(s)
+    }
+}
+
+/// Splits the first reference token off the pointer, returning it and the rest of the pointer.
+fn split_first(pointer: &str) -> Option<(&str, &str)> {
+    let rest = pointer.strip_prefix('/')?;
+    let len = rest.find('/').unwrap_or(rest.len());
+    Some((&rest[..len], &rest[len..]))
+}
+
+/// Checks if the JSON Pointer `path` matches the `glob`, both given as escaped JSON pointers.
+pub(crate) fn matches(glob: &str, path: &str) -> bool {
+    match split_first(glob) {
+        None => path.is_empty(),
+        Some(("**", glob)) => {
+            let mut path = path;
+            loop {
+                if matches(glob, path) {
+                    return true;
+                }
+                match split_first(path) {
+                    Some((_, rest)) => path = rest,
+                    None => return false,
+                }
+            }
+        }
+        Some((first, glob)) => split_first(path)
+            .is_some_and(|(token, path)| (first == "*" || first == token) && matches(glob, path)),
+    }
+}
+
+/// Checks if the `glob` matches the `path` or one of its ancestors.
+pub(crate) fn covers(glob: &str, path: &str) -> bool {
+    path.match_indices('/')
+        .map(|(end, _)| &path[..end])
+        .chain([path])
+        .any(|ancestor| matches(glob, ancestor))
+}
+
+/// Checks if the `glob` can match locations nested under the `path`.
+pub(crate) fn may_match_under(glob: &str, path: &str) -> bool {
+    match (split_first(glob), split_first(path)) {
+        (Some(("**", _)), _) | (Some(_), None) => true,
+        (Some((first, glob)), Some((token, path))) => {
+            (first == "*" || first == token) && may_match_under(glob, path)
+        }
+        (None, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{covers, matches, may_match_under, PointerGlob};
+    use crate::PointerError;
+
+    #[test]
+    fn globs() {
+        for (glob, path, matched, covered, under) in [
+            ("", "", true, true, false),
+            ("", "/a", false, true, false),
+            ("/a", "", false, false, true),
+            ("/a/*/c", "/a/b/c", true, true, false),
+            ("/a/*/c", "/a/b", false, false, true),
+            ("/a/*/c", "/a/b/c/d", false, true, false),
+            ("/a/*/c", "/a//c", true, true, false),
+            ("/a/*/c", "/x/b/c", false, false, false),
+            ("/a/**", "/a", true, true, true),
+            ("/a/**", "/a/b/c", true, true, true),
+            ("/a/**", "/b", false, false, false),
+            ("/**/c", "/c", true, true, true),
+            ("/**/c", "/a/b/c", true, true, true),
+            ("/**/c", "/a/b", false, false, true),
+            ("/**/c/**/e", "/c/d/c/e", true, true, true),
+            ("/a~1b/~0", "/a~1b/~0", true, true, false),
+            ("/a~1b/~0", "/a/b/~0", false, false, false),
+        ] {
+            assert_eq!(matches(glob, path), matched, "{glob} {path}");
+            assert_eq!(covers(glob, path), covered, "{glob} {path}");
+            assert_eq!(may_match_under(glob, path), under, "{glob} {path}");
+        }
+
+        assert_eq!(
+            PointerGlob::parse("a/*"),
+            Err(PointerError::MissingLeadingSlash)
+        );
+        assert_eq!(
+            PointerGlob::parse("/*/~2"),
+            Err(PointerError::InvalidEscape(3))
+        );
+        assert_eq!(PointerGlob::parse("/**").unwrap().to_string(), "/**");
+    }
+}
@@ -0,0 +1,174 @@
+use alloc::{vec, vec::Vec};
+use core::hash::{BuildHasher, Hash, Hasher};
+use hashbrown::{DefaultHashBuilder, HashMap};
+use serde_json::Value;
+
+/// Feeds the structure of the JSON value into the hasher. Values equal according to `PartialEq`
+/// produce equal hashes; in particular, object members are hashed in the order of their keys, so
+/// key insertion order does not matter.
+pub(crate) fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    match value {
+        Value::Null => state.write_u8(0),
+        Value::Bool(b) => {
+            state.write_u8(1);
+            b.hash(state);
+        }
+        Value::Number(n) => {
+            state.write_u8(2);
+            if let Some(n) = n.as_u64() {
+                state.write_u8(0);
+                n.hash(state);
+            } else if let Some(n) = n.as_i64() {
+                state.write_u8(1);
+                n.hash(state);
+            } else {
+                state.write_u8(2);
+                n.as_f64().unwrap_or_default().to_bits().hash(state);
+            }
+        }
+        Value::String(s) => {
+            state.write_u8(3);
+            s.hash(state);
+        }
+        Value::Array(arr) => {
+            state.write_u8(4);
+            state.write_usize(arr.len());
+            for item in arr {
+                hash_value(item, state);
+            }
+        }
+        Value::Object(obj) => {
+            state.write_u8(5);
+            state.write_usize(obj.len());
+            let mut entries: Vec<_> = obj.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            for (key, value) in entries {
+                key.hash(state);
+                hash_value(value, state);
+            }
+        }
+    }
+}
+
+/// Borrowed JSON value that can be used as a key in hash-based collections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct HashedValue<'a>(pub &'a Value);
+
+impl Hash for HashedValue<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_value(self.0, state)
+    }
+}
+
+/// Hashes of the objects and arrays nested in JSON documents, by their addresses. Computing the
+/// hash of a value memoizes the hashes of all the objects and arrays nested in it, so that values
+/// with different hashes are known to differ without comparing them structurally.
+#[derive(Debug, Default)]
+pub(crate) struct SubtreeHashes {
+    hashes: HashMap<usize, u64>,
+    state: DefaultHashBuilder,
+}
+
+impl SubtreeHashes {
+    /// Hash of the value, equal for values equal according to `PartialEq`. Values are expected to
+    /// stay in place while borrowed by the differ, so their addresses identify them.
+    pub(crate) fn get(&mut self, value: &Value) -> u64 {
+        if let Some(hash) = self.memoized(value) {
+            return hash;
+        }
+        // Post-order traversal without recursion: values are hashed once all their children are
+        let mut stack = vec![(value, false)];
+        while let Some((value, visited)) = stack.pop() {
+            if visited {
+                let hash = self.combine(value);
+                self.hashes.insert(address(value), hash);
+                continue;
+            }
+            stack.push((value, true));
+            let pending = |child: &&Value| {
+                (child.is_array() || child.is_object())
+                    && !self.hashes.contains_key(&address(child))
+            };
+            match value {
+                Value::Array(arr) => stack.extend(arr.iter().filter(pending).map(|v| (v, false))),
+                Value::Object(obj) => {
+                    stack.extend(obj.values().filter(pending).map(|v| (v, false)))
+                }
+                _ => unreachable!("scalars are never memoized"),
+            }
+        }
+        self.hashes[&address(value)]
+    }
+
+    /// Forget the memoized hashes, keeping the allocated storage.
+    pub(crate) fn clear(&mut self) {
+        self.hashes.clear();
+    }
+
+    /// Memoized hash of the object or array, or the hash of the scalar value.
+    fn memoized(&self, value: &Value) -> Option<u64> {
+        match value {
+            Value::Array(_) | Value::Object(_) => self.hashes.get(&address(value)).copied(),
+            scalar => {
+                let mut state = self.state.build_hasher();
+                hash_value(scalar, &mut state);
+                Some(state.finish())
+            }
+        }
+    }
+
+    /// Hash of the object or array, given the hashes of its children are memoized.
+    fn combine(&self, value: &Value) -> u64 {
+        let child = |value| match self.memoized(value) {
+            Some(hash) => hash,
+            None => unreachable!("children are hashed first"),
+        };
+        let mut state = self.state.build_hasher();
+        match value {
+            Value::Array(arr) => {
+                state.write_u8(4);
+                state.write_usize(arr.len());
+                for item in arr {
+                    state.write_u64(child(item));
+                }
+            }
+            Value::Object(obj) => {
+                state.write_u8(5);
+                state.write_usize(obj.len());
+                let mut entries: Vec<_> = obj.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                for (key, value) in entries {
+                    key.hash(&mut state);
+                    state.write_u64(child(value));
+                }
+            }
+            _ => unreachable!("scalars are never memoized"),
+        }
+        state.finish()
+    }
+}
+
+fn address(value: &Value) -> usize {
+    value as *const Value as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubtreeHashes;
+    use serde_json::json;
+
+    #[test]
+    fn subtree_hashes() {
+        let left = json!({ "a": [1, { "b": null }], "c": "d" });
+        let right = json!({ "c": "d", "a": [1, { "b": null }] });
+        let other = json!({ "a": [1, { "b": false }], "c": "d" });
+
+        let mut hashes = SubtreeHashes::default();
+        assert_eq!(hashes.get(&left), hashes.get(&right));
+        assert_ne!(hashes.get(&left), hashes.get(&other));
+        assert_eq!(hashes.get(&left["a"]), hashes.get(&right["a"]));
+        assert_ne!(hashes.get(&left["a"][1]), hashes.get(&other["a"][1]));
+        assert_eq!(hashes.get(&json!(1)), hashes.get(&left["a"][0]));
+        assert_ne!(hashes.get(&json!(1)), hashes.get(&json!(1.0)));
+    }
+}
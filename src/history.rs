@@ -0,0 +1,345 @@
+use crate::{apply_patches, Patch, PatchError};
+use alloc::vec::Vec;
+use core::ops::Range;
+use serde_json::Value;
+use thiserror::Error;
+
+/// Revision history of a JSON document: the base document and the ordered list of patches applied
+/// to it.
+///
+/// Version `0` is the base document, version `n` is the document after the first `n` patches are
+/// applied. The latest version is kept along with the base, other versions are reconstructed by
+/// replaying patches.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{History, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut history = History::new(json!({ "title": "Draft" }));
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/title", "value": "Hello" }
+/// ])).unwrap();
+/// assert_eq!(history.append(p).unwrap(), 1);
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/tags", "value": ["greeting"] }
+/// ])).unwrap();
+/// assert_eq!(history.append(p).unwrap(), 2);
+///
+/// assert_eq!(history.checkout(1), Some(json!({ "title": "Hello" })));
+/// assert_eq!(history.head(), &json!({ "title": "Hello", "tags": ["greeting"] }));
+///
+/// history.squash(0..2);
+/// assert_eq!(history.version(), 1);
+/// assert_eq!(history.patches()[0], from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/title", "value": "Hello" },
+///   { "op": "add", "path": "/tags", "value": ["greeting"] }
+/// ])).unwrap());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    base: Value,
+    head: Value,
+    patches: Vec<Patch>,
+}
+
+impl History {
+    /// Start the history with the base document.
+    pub fn new(base: Value) -> History {
+        History {
+            head: base.clone(),
+            base,
+            patches: Vec::new(),
+        }
+    }
+
+    /// Returns the base document, version `0`.
+    pub fn base(&self) -> &Value {
+        &self.base
+    }
+
+    /// Returns the latest version of the document.
+    pub fn head(&self) -> &Value {
+        &self.head
+    }
+
+    /// Returns the number of the latest version.
+    pub fn version(&self) -> usize {
+        self.patches.len()
+    }
+
+    /// Returns the patches of the history. The patch at index `n` turns version `n` into version
+    /// `n + 1`.
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    /// Apply the patch to the latest version of the document, creating a new version. Returns the
+    /// number of the new version. If the patch fails, the history is left intact.
+    pub fn append(&mut self, patch: Patch) -> Result<usize, PatchError> {
+        crate::patch(&mut self.head, &patch)?;
+        self.patches.push(patch);
+        Ok(self.patches.len())
+    }
+
+    /// Returns the given version of the document, or `None` if there is no such version.
+    pub fn checkout(&self, version: usize) -> Option<Value> {
+        if version == self.patches.len() {
+            return Some(self.head.clone());
+        }
+        let mut doc = self.base.clone();
+        for patch in self.patches.get(..version)? {
+            // Every patch was applied to this very document when it was appended
+            if let Err(e) = apply_patches(&mut doc, patch, None) {
+                unreachable!("unable to replay history: {e}")
+            }
+        }
+        Some(doc)
+    }
+
+    /// Replace the patches between the given versions with a single, normalized (see
+    /// [`Patch::normalize`]) patch. The versions in between are dropped and the later versions
+    /// are renumbered accordingly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end or the end is greater than the
+    /// latest version.
+    pub fn squash(&mut self, versions: Range<usize>) {
+        let start = versions.start;
+        let squashed: Patch = self.patches.drain(versions).flatten().collect();
+        self.patches.insert(start, squashed.normalize());
+    }
+
+    /// Diff two versions of the document, returning the patch turning version `from` into version
+    /// `to`, or `None` if there is no such version.
+    #[cfg(feature = "diff")]
+    pub fn diff_between(&self, from: usize, to: usize) -> Option<Patch> {
+        Some(crate::diff(&self.checkout(from)?, &self.checkout(to)?))
+    }
+}
+
+/// Error of replaying patches with [`replay`].
+#[derive(Debug, Error)]
+#[error("patch {patch} failed: {source}")]
+pub struct ReplayError {
+    /// Index of the patch which failed to apply.
+    pub patch: usize,
+    /// Error of applying the patch.
+    #[source]
+    pub source: PatchError,
+}
+
+/// Diff each pair of consecutive snapshots of a document, returning the patches turning every
+/// snapshot into the next one: the patch at index `n` turns snapshot `n` into snapshot `n + 1`.
+/// Together with the first snapshot, the patches are enough to restore all the others (see
+/// [`replay`]).
+///
+/// Snapshots are consumed one by one, only the previous one is kept in memory. Patches are
+/// generated by a single [`Differ`](crate::Differ) with default options, use
+/// [`changelog_with_options`] for other options.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{changelog, replay};
+/// use serde_json::json;
+///
+/// let snapshots = vec![
+///     json!({ "count": 1 }),
+///     json!({ "count": 2 }),
+///     json!({ "count": 2, "done": true }),
+/// ];
+/// let patches = changelog(snapshots.clone());
+/// assert_eq!(patches.len(), 2);
+/// assert_eq!(patches[0].to_string(), r#"[{"op":"replace","path":"/count","value":2}]"#);
+///
+/// assert_eq!(replay(&snapshots[0], &patches).unwrap(), snapshots[2]);
+/// ```
+#[cfg(feature = "diff")]
+pub fn changelog<I>(snapshots: I) -> Vec<Patch>
+where
+    I: IntoIterator<Item = Value>,
+{
+    changelog_with_options(snapshots, &crate::DiffOptions::default())
+}
+
+/// Same as [`changelog`], but generates the patches with provided options.
+#[cfg(feature = "diff")]
+pub fn changelog_with_options<I>(snapshots: I, options: &crate::DiffOptions) -> Vec<Patch>
+where
+    I: IntoIterator<Item = Value>,
+{
+    let mut differ = crate::Differ::new(options.clone());
+    let mut snapshots = snapshots.into_iter();
+    let Some(mut previous) = snapshots.next() else {
+        return Vec::new();
+    };
+    let mut patches = Vec::with_capacity(snapshots.size_hint().0);
+    for snapshot in snapshots {
+        patches.push(differ.diff(&previous, &snapshot));
+        previous = snapshot;
+    }
+    patches
+}
+
+/// Apply the patches to the base document one after another, returning the resulting document.
+/// The base document is left intact.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{replay, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let patches: Vec<Patch> = from_value(json!([
+///   [{ "op": "add", "path": "/a", "value": 1 }],
+///   [{ "op": "remove", "path": "/b" }]
+/// ])).unwrap();
+///
+/// let err = replay(&json!({}), &patches).unwrap_err();
+/// assert_eq!(err.patch, 1);
+/// assert_eq!(err.source.path, "/b");
+/// ```
+pub fn replay(base: &Value, patches: &[Patch]) -> Result<Value, ReplayError> {
+    let mut doc = base.clone();
+    for (idx, patch) in patches.iter().enumerate() {
+        if let Err(source) = apply_patches(&mut doc, patch, None) {
+            return Err(ReplayError { patch: idx, source });
+        }
+    }
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+    use crate::Patch;
+    use serde_json::{from_value, json, Value};
+
+    fn patch(ops: Value) -> Patch {
+        from_value(ops).unwrap()
+    }
+
+    #[test]
+    fn history() {
+        let mut history = History::new(json!({ "a": 1 }));
+        history
+            .append(patch(json!([{ "op": "add", "path": "/b", "value": 2 }])))
+            .unwrap();
+        history
+            .append(patch(
+                json!([{ "op": "replace", "path": "/b", "value": 3 }]),
+            ))
+            .unwrap();
+        history
+            .append(patch(json!([{ "op": "remove", "path": "/a" }])))
+            .unwrap();
+
+        let err = history
+            .append(patch(json!([{ "op": "remove", "path": "/a" }])))
+            .unwrap_err();
+        assert_eq!(err.operation, 0);
+        assert_eq!(history.version(), 3);
+        assert_eq!(history.head(), &json!({ "b": 3 }));
+
+        assert_eq!(history.checkout(0), Some(json!({ "a": 1 })));
+        assert_eq!(history.checkout(2), Some(json!({ "a": 1, "b": 3 })));
+        assert_eq!(history.checkout(4), None);
+
+        history.squash(0..2);
+        assert_eq!(history.version(), 2);
+        assert_eq!(
+            history.patches()[0],
+            patch(json!([{ "op": "add", "path": "/b", "value": 3 }]))
+        );
+        assert_eq!(history.checkout(1), Some(json!({ "a": 1, "b": 3 })));
+        assert_eq!(history.head(), &json!({ "b": 3 }));
+
+        history.squash(2..2);
+        assert_eq!(history.version(), 3);
+        assert!(history.patches()[2].is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "diff")]
+    fn diff_between() {
+        let mut history = History::new(json!({ "a": 1 }));
+        history
+            .append(patch(json!([{ "op": "add", "path": "/b", "value": 2 }])))
+            .unwrap();
+        history
+            .append(patch(json!([{ "op": "remove", "path": "/a" }])))
+            .unwrap();
+
+        assert_eq!(
+            history.diff_between(2, 0),
+            Some(patch(json!([
+                { "op": "add", "path": "/a", "value": 1 },
+                { "op": "remove", "path": "/b" },
+            ])))
+        );
+        assert_eq!(history.diff_between(0, 3), None);
+    }
+
+    #[test]
+    #[cfg(feature = "diff")]
+    fn changelog() {
+        let snapshots = [
+            json!({ "a": [1, 2], "b": { "c": true } }),
+            json!({ "a": [1, 2, 3], "b": { "c": true } }),
+            json!({ "a": [1, 2, 3], "b": { "c": true } }),
+            json!("text"),
+            json!({ "d": null }),
+        ];
+        let patches = super::changelog(snapshots.clone());
+        assert_eq!(patches.len(), 4);
+        assert!(patches[1].is_empty());
+        for (idx, snapshot) in snapshots.iter().enumerate() {
+            assert_eq!(
+                &super::replay(&snapshots[0], &patches[..idx]).unwrap(),
+                snapshot
+            );
+        }
+
+        let options = crate::DiffOptions {
+            append_to_end: true,
+            ..Default::default()
+        };
+        let patches = super::changelog_with_options(snapshots.iter().take(2).cloned(), &options);
+        assert_eq!(
+            patches,
+            [patch(json!([{ "op": "add", "path": "/a/-", "value": 3 }]))]
+        );
+
+        assert!(super::changelog([json!(1)]).is_empty());
+        assert!(super::changelog(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn replay() {
+        let base = json!({ "a": 1 });
+        let patches = [
+            patch(json!([{ "op": "add", "path": "/b", "value": 2 }])),
+            patch(json!([{ "op": "remove", "path": "/a" }])),
+            patch(json!([
+                { "op": "add", "path": "/c", "value": 3 },
+                { "op": "remove", "path": "/a" },
+            ])),
+        ];
+        assert_eq!(
+            super::replay(&base, &patches[..2]).unwrap(),
+            json!({ "b": 2 })
+        );
+        assert_eq!(super::replay(&base, &[]).unwrap(), base);
+
+        let err = super::replay(&base, &patches).unwrap_err();
+        assert_eq!(err.patch, 2);
+        assert_eq!(err.source.operation, 1);
+        assert_eq!(
+            err.to_string(),
+            "patch 2 failed: Operation '/1' failed at path '/a': path does not exist"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Handling of HTTP `PATCH` requests ([RFC 5789](https://tools.ietf.org/html/rfc5789)): the
+//! format of the request body is chosen by its content type, either JSON Patch
+//! (`application/json-patch+json`) or JSON Merge Patch (`application/merge-patch+json`).
+//!
+//! # Example
+//!
+//! ```rust
+//! use json_patch::http::{apply, HttpPatchError};
+//! use serde_json::json;
+//!
+//! let mut doc = json!({ "title": "Hello", "tags": [] });
+//!
+//! let body = br#"[{ "op": "add", "path": "/tags/-", "value": "greeting" }]"#;
+//! apply(&mut doc, "application/json-patch+json", body).unwrap();
+//!
+//! let body = br#"{ "title": "Hello!" }"#;
+//! apply(&mut doc, "application/merge-patch+json; charset=utf-8", body).unwrap();
+//! assert_eq!(doc, json!({ "title": "Hello!", "tags": ["greeting"] }));
+//!
+//! let body = br#"[{ "op": "test", "path": "/title", "value": "Hello" }]"#;
+//! let err = apply(&mut doc, "application/json-patch+json", body).unwrap_err();
+//! assert_eq!(err.status(), 409);
+//!
+//! let err = apply(&mut doc, "text/plain", b"title=Hello").unwrap_err();
+//! assert!(matches!(err, HttpPatchError::UnsupportedMediaType(_)));
+//! assert_eq!(err.status(), 415);
+//! ```
+use crate::{Patch, PatchError, PatchErrorKind};
+use alloc::string::{String, ToString};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Value of the `Accept-Patch` header listing the patch formats supported by [`apply`], to be
+/// sent in responses to `OPTIONS` requests and with `415 Unsupported Media Type` errors.
+pub const ACCEPT_PATCH: &str = "application/json-patch+json, application/merge-patch+json";
+
+/// Format of the body of a `PATCH` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PatchFormat {
+    /// JSON Patch (RFC 6902), `application/json-patch+json`.
+    JsonPatch,
+    /// JSON Merge Patch (RFC 7396), `application/merge-patch+json`.
+    MergePatch,
+}
+
+impl PatchFormat {
+    /// Returns the format with the given media type, ignoring parameters (like `charset`) and
+    /// the case of the type, or `None` if the format is not supported.
+    pub fn from_content_type(content_type: &str) -> Option<PatchFormat> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim();
+        [PatchFormat::JsonPatch, PatchFormat::MergePatch]
+            .into_iter()
+            .find(|format| format.content_type().eq_ignore_ascii_case(media_type))
+    }
+
+    /// Returns the media type of the format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            PatchFormat::JsonPatch => "application/json-patch+json",
+            PatchFormat::MergePatch => "application/merge-patch+json",
+        }
+    }
+}
+
+/// This type represents all possible errors that can occur when handling a `PATCH` request with
+/// [`apply`]. The status code of the response is given by [`HttpPatchError::status`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum HttpPatchError {
+    /// Content type of the request is not a supported patch format.
+    #[error("unsupported patch format '{0}'")]
+    UnsupportedMediaType(String),
+    /// Request body is not a valid patch document of its format.
+    #[error("malformed patch document: {0}")]
+    Malformed(#[source] serde_json::Error),
+    /// Patch cannot be applied to the document.
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+}
+
+impl HttpPatchError {
+    /// Returns the HTTP status code of the error, following
+    /// [RFC 5789](https://tools.ietf.org/html/rfc5789#section-2.2):
+    ///
+    /// * `415 Unsupported Media Type` for unsupported patch formats;
+    /// * `400 Bad Request` for malformed patch documents, including invalid JSON pointers;
+    /// * `409 Conflict` for failed `test` operations, as the document is not in the state the
+    ///   client expected;
+    /// * `422 Unprocessable Entity` for other patches which cannot be applied to the document
+    ///   (for example, referring to a missing member).
+    pub fn status(&self) -> u16 {
+        match self {
+            HttpPatchError::UnsupportedMediaType(_) => 415,
+            HttpPatchError::Malformed(_) => 400,
+            HttpPatchError::Patch(e) => match e.kind {
+                PatchErrorKind::InvalidPointer | PatchErrorKind::InvalidFromPointer => 400,
+                PatchErrorKind::TestFailed { .. } => 409,
+                _ => 422,
+            },
+        }
+    }
+}
+
+/// Parse the body of a `PATCH` request in the format given by its content type, and apply it to
+/// the document. If the request fails, the document is left unchanged.
+pub fn apply(doc: &mut Value, content_type: &str, body: &[u8]) -> Result<(), HttpPatchError> {
+    match PatchFormat::from_content_type(content_type) {
+        Some(PatchFormat::JsonPatch) => {
+            let patch: Patch = serde_json::from_slice(body).map_err(HttpPatchError::Malformed)?;
+            crate::patch(doc, &patch)?;
+        }
+        Some(PatchFormat::MergePatch) => {
+            let patch: Value = serde_json::from_slice(body).map_err(HttpPatchError::Malformed)?;
+            crate::merge(doc, &patch);
+        }
+        None => {
+            return Err(HttpPatchError::UnsupportedMediaType(
+                content_type.to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, PatchFormat};
+    use serde_json::json;
+
+    #[test]
+    fn content_types() {
+        for (content_type, format) in [
+            ("application/json-patch+json", Some(PatchFormat::JsonPatch)),
+            (
+                " Application/JSON-Patch+JSON ; charset=utf-8",
+                Some(PatchFormat::JsonPatch),
+            ),
+            (
+                "application/merge-patch+json",
+                Some(PatchFormat::MergePatch),
+            ),
+            ("application/json", None),
+            ("", None),
+        ] {
+            assert_eq!(PatchFormat::from_content_type(content_type), format);
+        }
+    }
+
+    #[test]
+    fn statuses() {
+        let doc = json!({ "a": [1] });
+        let json_patch = "application/json-patch+json";
+        for (content_type, body, status) in [
+            (json_patch, "[{ \"op\": \"add\" }]", 400),
+            (json_patch, "{}", 400),
+            ("application/merge-patch+json", "{", 400),
+            (json_patch, r#"[{ "op": "remove", "path": "a" }]"#, 400),
+            (
+                json_patch,
+                r#"[{ "op": "test", "path": "/a", "value": [] }]"#,
+                409,
+            ),
+            (json_patch, r#"[{ "op": "remove", "path": "/b" }]"#, 422),
+            (
+                json_patch,
+                r#"[{ "op": "add", "path": "/a/2", "value": 2 }]"#,
+                422,
+            ),
+            ("application/xml", "<a/>", 415),
+        ] {
+            let mut patched = doc.clone();
+            let err = apply(&mut patched, content_type, body.as_bytes()).unwrap_err();
+            assert_eq!(err.status(), status, "{body}: {err}");
+            assert_eq!(patched, doc);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::deep::{drop_operations, values_equal};
+use crate::{apply_patches, resolve_pointer, revert, split_last};
+use crate::{PatchError, PatchOperation};
+use alloc::vec::Vec;
+use serde_json::Value;
+
+/// Patch provided JSON document (given as `serde_json::Value`) in-place, same as
+/// [`patch`](crate::patch), skipping the operations whose effect is already present in the
+/// document, so that patches delivered more than once (for example, by an at-least-once message
+/// queue) can be applied again. Returns the indices of the skipped operations.
+///
+/// An operation is skipped if, when it is reached:
+///
+/// * `add`, `replace` or `copy`: the value at its `path` is already equal to the value it would
+///   put there (for `add` operations inserting into arrays, the element at that index is);
+/// * `remove`: there is no value at its `path`;
+/// * `move`: there is no value at its `from` location, but there is one at its `path`.
+///
+/// Appending to an array with the `-` token is never skipped, as appending the same value
+/// twice cannot be told from re-applying the operation: re-applied appends duplicate elements.
+/// `test` operations are never skipped either, so patches guarded by tests of the values they
+/// change fail when applied again. If any of the operations fails, all previous operations are
+/// reverted.
+///
+/// # Limitations
+///
+/// Only the document tells whether an operation was applied, so an operation is applied again if
+/// the values it depends on changed in the meantime, even if it was applied before. In
+/// particular, a `copy` delivered again after a later operation of the patch changed the value at
+/// its `from` location (or at its `path`) copies the changed value again, overwriting the copy
+/// made the first time: patches whose copied values are modified by subsequent operations are not
+/// idempotent.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_idempotent, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "name": "Andrew", "draft": true });
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/tags", "value": ["new"] },
+///   { "op": "remove", "path": "/draft" },
+///   { "op": "move", "from": "/name", "path": "/author" }
+/// ])).unwrap();
+///
+/// assert!(patch_idempotent(&mut doc, &p).unwrap().is_empty());
+/// // Delivered again
+/// assert_eq!(patch_idempotent(&mut doc, &p).unwrap(), [0, 1, 2]);
+/// assert_eq!(doc, json!({ "author": "Andrew", "tags": ["new"] }));
+/// ```
+pub fn patch_idempotent(
+    doc: &mut Value,
+    patch: &[PatchOperation],
+) -> Result<Vec<usize>, PatchError> {
+    let mut skipped = Vec::new();
+    let mut undo_stack = Vec::with_capacity(patch.len());
+    for (operation, op) in patch.iter().enumerate() {
+        if is_satisfied(doc, op) {
+            skipped.push(operation);
+            continue;
+        }
+        if let Err(mut e) = apply_patches(doc, core::slice::from_ref(op), Some(&mut undo_stack)) {
+            revert(doc, undo_stack);
+            e.operation = operation;
+            return Err(e);
+        }
+    }
+    drop_operations(undo_stack);
+    Ok(skipped)
+}
+
+/// Checks if the effect of the operation is already present in the document.
+fn is_satisfied(doc: &Value, op: &PatchOperation) -> bool {
+    let get = |pointer: &str| {
+        let pointer = resolve_pointer(doc, pointer).ok()?;
+        doc.pointer(&pointer)
+    };
+    let has_value = |value: &Value| get(op.path()).is_some_and(|found| values_equal(found, value));
+    match op {
+        PatchOperation::Add(op) => !is_append(&op.path) && has_value(&op.value),
+        PatchOperation::Replace(op) => has_value(&op.value),
+        PatchOperation::Copy(op) => !is_append(&op.path) && get(&op.from).is_some_and(has_value),
+        PatchOperation::Remove(op) => is_pointer(&op.path) && get(&op.path).is_none(),
+        PatchOperation::Move(op) => {
+            is_pointer(&op.from) && get(&op.from).is_none() && get(&op.path).is_some()
+        }
+        PatchOperation::Test(_) => false,
+    }
+}
+
+/// Checks if the pointer appends to an array with the `-` token.
+fn is_append(pointer: &str) -> bool {
+    split_last(pointer).is_ok_and(|(_, last)| last == "-")
+}
+
+/// Whether the pointer refers to a location under the root, so that invalid pointers (and the
+/// root, which cannot be removed) are not mistaken for missing values.
+fn is_pointer(pointer: &str) -> bool {
+    pointer.starts_with('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::patch_idempotent;
+    use crate::{Patch, PatchErrorKind};
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn reapplied_patches() {
+        let original = json!({ "a": [1, 2], "b": { "c": 3 }, "d": "x", "g": true });
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a/1", "value": 5 },
+            { "op": "replace", "path": "/d", "value": "y" },
+            { "op": "copy", "from": "/b", "path": "/e" },
+            { "op": "move", "from": "/g", "path": "/f" },
+            { "op": "remove", "path": "/a/2" },
+            { "op": "test", "path": "/a", "value": [1, 5] },
+        ]))
+        .unwrap();
+        let mut doc = original.clone();
+        assert!(patch_idempotent(&mut doc, &p).unwrap().is_empty());
+        let patched = json!({ "a": [1, 5], "b": { "c": 3 }, "d": "y", "e": { "c": 3 }, "f": true });
+        assert_eq!(doc, patched);
+
+        assert_eq!(patch_idempotent(&mut doc, &p).unwrap(), [0, 1, 2, 3, 4]);
+        assert_eq!(doc, patched);
+
+        // Appends are applied again
+        let p: Patch = from_value(json!([{ "op": "add", "path": "/a/-", "value": 5 }])).unwrap();
+        assert!(patch_idempotent(&mut doc, &p).unwrap().is_empty());
+        assert_eq!(doc["a"], json!([1, 5, 5]));
+
+        // Failures are reported and reverted as usual
+        let p: Patch = from_value(json!([
+            { "op": "remove", "path": "/d" },
+            { "op": "remove", "path": "a" },
+        ]))
+        .unwrap();
+        let err = patch_idempotent(&mut doc, &p).unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert!(matches!(err.kind, PatchErrorKind::InvalidPointer));
+        assert_eq!(doc["d"], "y");
+    }
+
+    #[test]
+    fn redelivered_copy_of_changed_value() {
+        let p: Patch = from_value(json!([
+            { "op": "copy", "from": "/b", "path": "/e" },
+            { "op": "move", "from": "/b/c", "path": "/f" },
+        ]))
+        .unwrap();
+        let mut doc = json!({ "b": { "c": 3 } });
+        assert!(patch_idempotent(&mut doc, &p).unwrap().is_empty());
+        assert_eq!(doc, json!({ "b": {}, "e": { "c": 3 }, "f": 3 }));
+
+        // Known limitation: `/b` changed after it was copied, so the copy is applied again,
+        // overwriting the first copy with the changed value
+        assert_eq!(patch_idempotent(&mut doc, &p).unwrap(), [1]);
+        assert_eq!(doc, json!({ "b": {}, "e": {}, "f": 3 }));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{apply_patches, Patch, PatchError, PatchOperation};
+use alloc::{format, string::String, vec::Vec};
+use serde_json::Value;
+
+/// Replaces the trailing `-` reference token of the path of an element appended to an array with
+/// the index of that element, given the document after the element was appended.
+pub(crate) fn resolve_appended(doc: &Value, path: &str) -> Option<String> {
+    let parent = path.strip_suffix("/-")?;
+    let len = doc.pointer(parent)?.as_array()?.len();
+    Some(format!("{}/{}", parent, len - 1))
+}
+
+/// Generate the inverse of the patch: the patch that transforms the result of applying `patch` to
+/// `doc` back into `doc`.
+///
+/// The patch is applied to a copy of the original document to recover the values removed or
+/// replaced by it. Operations of the inverse patch go in reverse order: `add` operations become
+/// `remove` operations (or `add` operations restoring the replaced object member), `remove` and
+/// `replace` operations restore the old values and `move` operations swap directions. `test`
+/// operations are kept as they are. Elements appended with the `-` token are referred to by their
+/// indices.
+///
+/// Returns an error if the patch cannot be applied to the document.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{invert, patch, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let original = json!({ "title": "Goodbye!", "tags": ["example"] });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/title", "value": "Hello!" },
+///   { "op": "add", "path": "/tags/-", "value": "sample" }
+/// ])).unwrap();
+///
+/// let inverse = invert(&p, &original).unwrap();
+/// assert_eq!(inverse, from_value(json!([
+///   { "op": "remove", "path": "/tags/1" },
+///   { "op": "replace", "path": "/title", "value": "Goodbye!" }
+/// ])).unwrap());
+///
+/// let mut doc = original.clone();
+/// patch(&mut doc, &p).unwrap();
+/// patch(&mut doc, &inverse).unwrap();
+/// assert_eq!(doc, original);
+/// ```
+pub fn invert(patch: &[PatchOperation], doc: &Value) -> Result<Patch, PatchError> {
+    let mut doc = doc.clone();
+    let mut inverse = Vec::with_capacity(patch.len());
+    for (operation, op) in patch.iter().enumerate() {
+        let start = inverse.len();
+        apply_patches(&mut doc, core::slice::from_ref(op), Some(&mut inverse)).map_err(
+            |mut e| {
+                e.operation = operation;
+                e
+            },
+        )?;
+
+        let path = match op {
+            PatchOperation::Add(op) => &op.path,
+            PatchOperation::Move(op) => &op.path,
+            PatchOperation::Copy(op) => &op.path,
+            PatchOperation::Test(op) => {
+                inverse.push(PatchOperation::Test(op.clone()).into());
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some(resolved) = resolve_appended(&doc, path) {
+            for undo in &mut inverse[start..] {
+                match &mut undo.op {
+                    PatchOperation::Remove(undo) if undo.path == *path => {
+                        undo.path.clone_from(&resolved)
+                    }
+                    PatchOperation::Move(undo) if undo.from == *path => {
+                        undo.from.clone_from(&resolved)
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(inverse.into_iter().rev().map(|undo| undo.op).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::invert;
+    use crate::{patch, Patch, PatchErrorKind};
+    use serde_json::{from_value, json, Value};
+
+    fn check_invert(doc: Value, p: Value) -> Patch {
+        let p: Patch = from_value(p).unwrap();
+        let inverse = invert(&p, &doc).unwrap();
+        let mut patched = doc.clone();
+        patch(&mut patched, &p).unwrap();
+        patch(&mut patched, &inverse).unwrap();
+        assert_eq!(patched, doc, "{}", inverse);
+        inverse
+    }
+
+    #[test]
+    fn invert_operations() {
+        let doc = json!({ "a": { "b": 1 }, "c": [1, 2, 3], "d": "x" });
+        let inverse = check_invert(
+            doc.clone(),
+            json!([
+                { "op": "add", "path": "/a/b", "value": 2 },
+                { "op": "add", "path": "/a/e", "value": 3 },
+                { "op": "remove", "path": "/c/0" },
+                { "op": "test", "path": "/c/0", "value": 2 },
+                { "op": "move", "from": "/d", "path": "/a/b" },
+                { "op": "copy", "from": "/a", "path": "/f" },
+            ]),
+        );
+        assert_eq!(
+            inverse,
+            from_value(json!([
+                { "op": "remove", "path": "/f" },
+                { "op": "move", "from": "/a/b", "path": "/d" },
+                { "op": "add", "path": "/a/b", "value": 2 },
+                { "op": "test", "path": "/c/0", "value": 2 },
+                { "op": "add", "path": "/c/0", "value": 1 },
+                { "op": "remove", "path": "/a/e" },
+                { "op": "add", "path": "/a/b", "value": 1 },
+            ]))
+            .unwrap()
+        );
+
+        check_invert(
+            doc.clone(),
+            json!([{ "op": "replace", "path": "", "value": [] }]),
+        );
+        check_invert(
+            doc,
+            json!([
+                { "op": "move", "from": "/c/0", "path": "/c/-" },
+                { "op": "copy", "from": "/d", "path": "/c/-" },
+                { "op": "add", "path": "/c/-", "value": 4 },
+            ]),
+        );
+    }
+
+    #[test]
+    fn invert_failure() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a", "value": 1 },
+            { "op": "remove", "path": "/b" },
+        ]))
+        .unwrap();
+        let err = invert(&p, &json!({})).unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+    }
+}
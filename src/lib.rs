@@ -9,6 +9,31 @@
 //! json-patch = "*"
 //! ```
 //!
+//! The crate supports `no_std` environments with an allocator: disable the default `std`
+//! feature (keeping `diff`, if needed) to only depend on `core` and `alloc`. Streaming and
+//! serialization into `std::io` writers, as well as the integrations with other crates, require
+//! `std`.
+//!
+//! ```toml
+//! [dependencies]
+//! json-patch = { version = "*", default-features = false, features = ["diff"] }
+//! ```
+//!
+//! # Order of object members
+//!
+//! The crate works the same whether or not the `preserve_order` feature of `serde_json` is
+//! enabled. Patches produced by the differ only depend on the contents of the documents, never
+//! on the order of object members: members are compared in the order of their keys. Similarly,
+//! documents compare equal and have equal [`digest`]s regardless of the order of members.
+//!
+//! Without `preserve_order`, objects are kept sorted by keys. With it, applying a patch keeps
+//! the members of objects in place: replaced members keep their positions, added members are
+//! appended and removed members leave the remaining ones in their relative order. The latter
+//! requires the `preserve_order` feature of this crate, which enables the one of `serde_json`:
+//! if `serde_json` gets `preserve_order` from another crate only, removing a member moves the
+//! last member of the object into its place. When a patch fails and the document is restored,
+//! members it removed are put back at their positions.
+//!
 //! # Examples
 //! Create and patch document using JSON Patch:
 //!
@@ -77,26 +102,174 @@
 //! # }
 //! ```
 #![warn(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
+use crate::deep::{clone_value, drop_operations, drop_value};
+use crate::generic::JsonLike;
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    string::String,
+    vec::Vec,
+};
+use core::fmt::{self, Display, Formatter};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
-use std::{
-    borrow::Cow,
-    fmt::{self, Display, Formatter},
-};
 use thiserror::Error;
 
+#[cfg(feature = "diff")]
+mod align;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod binary;
+#[cfg(feature = "bson")]
+mod bson;
+#[cfg(feature = "cbor")]
+mod cbor;
+mod compiled;
+#[cfg(feature = "std")]
+mod concurrent;
+mod conditional;
+mod de;
+mod deep;
+mod delta;
+mod dependency;
 #[cfg(feature = "diff")]
 mod diff;
+mod digest;
+mod envelope;
+mod estimate;
+#[cfg(feature = "extended-ops")]
+pub mod extended;
+pub mod generic;
+mod glob;
+#[cfg(feature = "diff")]
+mod hash;
+mod history;
+pub mod http;
+mod idempotent;
+mod invert;
+mod macros;
+mod merge3;
+pub mod merge_patch;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+#[cfg(feature = "negative-indices")]
+mod negative;
+mod normalize;
+mod observe;
+mod options;
+mod pointer;
+mod policy;
+#[cfg(feature = "predicate")]
+pub mod predicate;
+#[cfg(feature = "raw_value")]
+mod raw;
+pub mod render;
+#[cfg(feature = "std")]
+mod ser;
+#[cfg(feature = "shared")]
+pub mod shared;
+#[cfg(feature = "simd-json")]
+mod simd;
+mod stats;
+mod store;
+#[cfg(feature = "std")]
+mod stream;
+mod subscribe;
+mod syntax;
+mod text;
+mod trace;
+mod tracked;
+mod transform;
+mod typed;
+mod undo;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "yaml")]
+mod yaml;
 
+pub use self::binary::BinaryDecodeError;
+#[cfg(feature = "bson")]
+pub use self::bson::{diff_bson, patch_bson};
+#[cfg(feature = "cbor")]
+pub use self::cbor::{diff_cbor, patch_cbor};
+pub use self::compiled::CompiledPatch;
+#[cfg(feature = "std")]
+pub use self::concurrent::SharedDocument;
+pub use self::conditional::{make_conditional, patch_preconditioned};
+pub use self::de::{
+    annotated_from_slice_with_options, annotated_from_str_with_options,
+    annotated_from_value_with_options, from_slice_with_options, from_str_with_options,
+    from_value_with_options, patch_annotated, AnnotatedOperation, AnnotatedPatch,
+    DeserializeOptions, UnknownFields,
+};
+pub use self::delta::{from_delta, to_delta, DeltaError};
+#[cfg(feature = "rayon")]
+pub use self::diff::par_diff_with_options;
 #[cfg(feature = "diff")]
-pub use self::diff::diff;
+pub use self::diff::{
+    diff, diff_at, diff_at_with_options, diff_iter, diff_owned, diff_typed, diff_with_inverse,
+    diff_with_options, diff_with_summary, diff_with_visitor, similarity, try_diff_with_options,
+    ArrayDiffAlgorithm, ChangeExample, DiffError, DiffOptions, DiffSummary, DiffVisitor, Differ,
+    ElementReplacement, KeySummary, MatchQuality, NumberEquality, OpOrder,
+};
+pub use self::digest::{digest, patch_verified, VerifyError};
+pub use self::envelope::{EnvelopeError, PatchEnvelope};
+pub use self::glob::PointerGlob;
+#[cfg(feature = "diff")]
+pub use self::history::{changelog, changelog_with_options};
+pub use self::history::{replay, History, ReplayError};
+pub use self::idempotent::patch_idempotent;
+pub use self::invert::invert;
+pub use self::merge3::{merge3, merge3_with_options, Merge3Options, MergeConflict, MergeStrategy};
+#[cfg(feature = "msgpack")]
+pub use self::msgpack::{from_msgpack, to_msgpack};
+pub use self::normalize::{equivalent, minimize, structurally_equivalent};
+pub use self::observe::{patch_with_observer, PatchObserver};
+pub use self::options::{patch_with_options, validate_with_options, NumberTolerance, PatchOptions};
+pub use self::pointer::{
+    escape_token, exists, resolve, resolve_mut, split_pointer, unescape_token, Pointer,
+    PointerError, RelativePointer,
+};
+pub use self::policy::PatchPolicy;
+#[cfg(feature = "raw_value")]
+pub use self::raw::{RawOperation, RawPatch};
+#[cfg(feature = "std")]
+pub use self::ser::{to_string_with_options, to_writer_with_options, SerializeOptions};
+#[cfg(feature = "simd-json")]
+pub use self::simd::{diff_simd, from_slice_simd, patch_simd};
+pub use self::stats::{PatchStats, TouchedPointers};
+pub use self::store::{patch_store, DocumentStore, Shape, StoreError};
+#[cfg(feature = "std")]
+pub use self::stream::{patch_stream, PatchReader, PatchWriter, StreamError};
+pub use self::subscribe::{SubscriptionId, Subscriptions};
+pub use self::syntax::{SyntaxIssue, SyntaxIssueKind};
+#[cfg(feature = "diff")]
+pub use self::text::diff_str;
+pub use self::text::{patch_str, TextError};
+pub use self::tracked::TrackedValue;
+pub use self::transform::{transform, transform_with_document};
+pub use self::typed::{apply_to, TypedPatchError};
+pub use self::undo::UndoStack;
+#[cfg(feature = "yaml")]
+pub use self::yaml::{diff_yaml, patch_yaml};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec;
+    pub use serde_json::json;
+}
 
+#[cfg(feature = "std")]
 struct WriteAdapter<'a>(&'a mut dyn fmt::Write);
 
+#[cfg(feature = "std")]
 impl<'a> std::io::Write for WriteAdapter<'a> {
     fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
-        let s = std::str::from_utf8(buf).unwrap();
+        let s = core::str::from_utf8(buf).unwrap();
         self.0
             .write_str(s)
             .map_err(|_| std::io::Error::from(std::io::ErrorKind::Other))?;
@@ -108,31 +281,46 @@ impl<'a> std::io::Write for WriteAdapter<'a> {
     }
 }
 
+/// Write the value as JSON into the formatter, indented in the alternate mode (`{:#}`).
+#[cfg(feature = "std")]
+fn write_json<T: Serialize>(f: &mut Formatter<'_>, value: &T) -> fmt::Result {
+    if f.alternate() {
+        serde_json::to_writer_pretty(WriteAdapter(f), value).map_err(|_| fmt::Error)
+    } else {
+        serde_json::to_writer(WriteAdapter(f), value).map_err(|_| fmt::Error)
+    }
+}
+
+/// Write the value as JSON into the formatter, indented in the alternate mode (`{:#}`).
+#[cfg(not(feature = "std"))]
+fn write_json<T: Serialize>(f: &mut Formatter<'_>, value: &T) -> fmt::Result {
+    let json = if f.alternate() {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    f.write_str(&json.map_err(|_| fmt::Error)?)
+}
+
 macro_rules! impl_display {
     ($name:ident) => {
         impl Display for $name {
             fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-                let alternate = f.alternate();
-                if alternate {
-                    serde_json::to_writer_pretty(WriteAdapter(f), self)
-                        .map_err(|_| std::fmt::Error)?;
-                } else {
-                    serde_json::to_writer(WriteAdapter(f), self).map_err(|_| std::fmt::Error)?;
-                }
-                Ok(())
+                write_json(f, self)
             }
         }
     };
 }
 
 /// Representation of JSON Patch (list of patch operations)
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Patch(pub Vec<PatchOperation>);
 
 impl_display!(Patch);
 
-impl std::ops::Deref for Patch {
+impl core::ops::Deref for Patch {
     type Target = [PatchOperation];
 
     fn deref(&self) -> &[PatchOperation] {
@@ -140,9 +328,64 @@ impl std::ops::Deref for Patch {
     }
 }
 
+impl Patch {
+    /// Returns the number of operations in the patch.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks if the patch has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<PatchOperation>> for Patch {
+    fn from(ops: Vec<PatchOperation>) -> Self {
+        Patch(ops)
+    }
+}
+
+impl From<Patch> for Vec<PatchOperation> {
+    fn from(patch: Patch) -> Self {
+        patch.0
+    }
+}
+
+impl IntoIterator for Patch {
+    type Item = PatchOperation;
+    type IntoIter = alloc::vec::IntoIter<PatchOperation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Patch {
+    type Item = &'a PatchOperation;
+    type IntoIter = core::slice::Iter<'a, PatchOperation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl FromIterator<PatchOperation> for Patch {
+    fn from_iter<T: IntoIterator<Item = PatchOperation>>(iter: T) -> Self {
+        Patch(iter.into_iter().collect())
+    }
+}
+
+impl Extend<PatchOperation> for Patch {
+    fn extend<T: IntoIterator<Item = PatchOperation>>(&mut self, iter: T) {
+        self.0.extend(iter)
+    }
+}
+
 /// JSON Patch 'add' operation representation
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct AddOperation {
     /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
     /// within the target document where the operation is performed.
@@ -153,9 +396,20 @@ pub struct AddOperation {
 
 impl_display!(AddOperation);
 
+impl AddOperation {
+    /// Create an 'add' operation with a valid JSON pointer.
+    pub fn new(path: Pointer, value: Value) -> AddOperation {
+        AddOperation {
+            path: path.into(),
+            value,
+        }
+    }
+}
+
 /// JSON Patch 'remove' operation representation
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct RemoveOperation {
     /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
     /// within the target document where the operation is performed.
@@ -164,9 +418,17 @@ pub struct RemoveOperation {
 
 impl_display!(RemoveOperation);
 
+impl RemoveOperation {
+    /// Create a 'remove' operation with a valid JSON pointer.
+    pub fn new(path: Pointer) -> RemoveOperation {
+        RemoveOperation { path: path.into() }
+    }
+}
+
 /// JSON Patch 'replace' operation representation
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ReplaceOperation {
     /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
     /// within the target document where the operation is performed.
@@ -177,9 +439,20 @@ pub struct ReplaceOperation {
 
 impl_display!(ReplaceOperation);
 
+impl ReplaceOperation {
+    /// Create a 'replace' operation with a valid JSON pointer.
+    pub fn new(path: Pointer, value: Value) -> ReplaceOperation {
+        ReplaceOperation {
+            path: path.into(),
+            value,
+        }
+    }
+}
+
 /// JSON Patch 'move' operation representation
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MoveOperation {
     /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
     /// to move value from.
@@ -191,9 +464,39 @@ pub struct MoveOperation {
 
 impl_display!(MoveOperation);
 
+impl MoveOperation {
+    /// Create a 'move' operation with valid JSON pointers. Fails if the value would be moved
+    /// inside itself, which always fails to apply. Moving the value to its own location is
+    /// allowed, having no effect.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{MoveOperation, Pointer, SyntaxIssueKind};
+    ///
+    /// let from = Pointer::from_tokens(["items", "0"]);
+    /// let op = MoveOperation::new(from.clone(), Pointer::from_tokens(["archive", "-"])).unwrap();
+    /// assert_eq!(op.from, "/items/0");
+    ///
+    /// let err = MoveOperation::new(from.clone(), Pointer::from_tokens(["items", "0", "tags"]));
+    /// assert_eq!(err.unwrap_err(), SyntaxIssueKind::MoveInsideItself);
+    /// assert!(MoveOperation::new(from.clone(), from).is_ok());
+    /// ```
+    pub fn new(from: Pointer, path: Pointer) -> Result<MoveOperation, SyntaxIssueKind> {
+        if from != path && is_prefix(from.as_str(), path.as_str()) {
+            return Err(SyntaxIssueKind::MoveInsideItself);
+        }
+        Ok(MoveOperation {
+            from: from.into(),
+            path: path.into(),
+        })
+    }
+}
+
 /// JSON Patch 'copy' operation representation
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct CopyOperation {
     /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
     /// to copy value from.
@@ -205,9 +508,20 @@ pub struct CopyOperation {
 
 impl_display!(CopyOperation);
 
+impl CopyOperation {
+    /// Create a 'copy' operation with valid JSON pointers.
+    pub fn new(from: Pointer, path: Pointer) -> CopyOperation {
+        CopyOperation {
+            from: from.into(),
+            path: path.into(),
+        }
+    }
+}
+
 /// JSON Patch 'test' operation representation
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct TestOperation {
     /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
     /// within the target document where the operation is performed.
@@ -218,9 +532,20 @@ pub struct TestOperation {
 
 impl_display!(TestOperation);
 
+impl TestOperation {
+    /// Create a 'test' operation with a valid JSON pointer.
+    pub fn new(path: Pointer, value: Value) -> TestOperation {
+        TestOperation {
+            path: path.into(),
+            value,
+        }
+    }
+}
+
 /// JSON Patch single patch operation
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(tag = "op")]
 #[serde(rename_all = "lowercase")]
 pub enum PatchOperation {
@@ -240,22 +565,222 @@ pub enum PatchOperation {
 
 impl_display!(PatchOperation);
 
+/// Kind of a JSON Patch operation, the value of its `op` member.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum OpKind {
+    /// 'add' operation
+    Add,
+    /// 'remove' operation
+    Remove,
+    /// 'replace' operation
+    Replace,
+    /// 'move' operation
+    Move,
+    /// 'copy' operation
+    Copy,
+    /// 'test' operation
+    Test,
+}
+
+impl OpKind {
+    /// Returns the name of the operation, as used in the `op` member.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OpKind::Add => "add",
+            OpKind::Remove => "remove",
+            OpKind::Replace => "replace",
+            OpKind::Move => "move",
+            OpKind::Copy => "copy",
+            OpKind::Test => "test",
+        }
+    }
+}
+
+impl Display for OpKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PatchOperation {
+    /// Create an 'add' operation.
+    pub fn add(path: impl Into<String>, value: Value) -> PatchOperation {
+        PatchOperation::Add(AddOperation {
+            path: path.into(),
+            value,
+        })
+    }
+
+    /// Create a 'remove' operation.
+    pub fn remove(path: impl Into<String>) -> PatchOperation {
+        PatchOperation::Remove(RemoveOperation { path: path.into() })
+    }
+
+    /// Create a 'replace' operation.
+    pub fn replace(path: impl Into<String>, value: Value) -> PatchOperation {
+        PatchOperation::Replace(ReplaceOperation {
+            path: path.into(),
+            value,
+        })
+    }
+
+    /// Create a 'move' operation.
+    pub fn move_from(from: impl Into<String>, path: impl Into<String>) -> PatchOperation {
+        PatchOperation::Move(MoveOperation {
+            from: from.into(),
+            path: path.into(),
+        })
+    }
+
+    /// Create a 'copy' operation.
+    pub fn copy_from(from: impl Into<String>, path: impl Into<String>) -> PatchOperation {
+        PatchOperation::Copy(CopyOperation {
+            from: from.into(),
+            path: path.into(),
+        })
+    }
+
+    /// Create a 'test' operation.
+    pub fn test(path: impl Into<String>, value: Value) -> PatchOperation {
+        PatchOperation::Test(TestOperation {
+            path: path.into(),
+            value,
+        })
+    }
+
+    /// Returns the kind of the operation.
+    pub fn op_kind(&self) -> OpKind {
+        match self {
+            PatchOperation::Add(_) => OpKind::Add,
+            PatchOperation::Remove(_) => OpKind::Remove,
+            PatchOperation::Replace(_) => OpKind::Replace,
+            PatchOperation::Move(_) => OpKind::Move,
+            PatchOperation::Copy(_) => OpKind::Copy,
+            PatchOperation::Test(_) => OpKind::Test,
+        }
+    }
+
+    /// Returns the `path` of the operation.
+    pub fn path(&self) -> &str {
+        match self {
+            PatchOperation::Add(op) => &op.path,
+            PatchOperation::Remove(op) => &op.path,
+            PatchOperation::Replace(op) => &op.path,
+            PatchOperation::Move(op) => &op.path,
+            PatchOperation::Copy(op) => &op.path,
+            PatchOperation::Test(op) => &op.path,
+        }
+    }
+
+    /// Returns the `value` of 'add', 'replace' and 'test' operations.
+    pub fn value(&self) -> Option<&Value> {
+        match self {
+            PatchOperation::Add(op) => Some(&op.value),
+            PatchOperation::Replace(op) => Some(&op.value),
+            PatchOperation::Test(op) => Some(&op.value),
+            _ => None,
+        }
+    }
+
+    /// Returns the `from` of 'move' and 'copy' operations.
+    pub fn from(&self) -> Option<&str> {
+        match self {
+            PatchOperation::Move(op) => Some(&op.from),
+            PatchOperation::Copy(op) => Some(&op.from),
+            _ => None,
+        }
+    }
+}
+
+/// Displays the value found by a failed `test` operation.
+struct Found<'a>(&'a Option<Box<Value>>);
+
+impl Display for Found<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(actual) => Display::fmt(actual, f),
+            None => f.write_str("nothing"),
+        }
+    }
+}
+
 /// This type represents all possible errors that can occur when applying JSON patch
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum PatchErrorKind {
-    /// `test` operation failed because values did not match.
-    #[error("value did not match")]
-    TestFailed,
+    /// `test` operation failed because values did not match, or because there is no value at
+    /// its path.
+    #[error("value did not match: expected {expected}, found {}", Found(.actual))]
+    TestFailed {
+        /// Value the operation expected (the operand, for predicate tests of
+        /// `extended::ExtendedOperation`).
+        expected: Box<Value>,
+        /// Value found at the path of the operation, `None` if the path does not exist.
+        actual: Option<Box<Value>>,
+    },
     /// `from` JSON pointer in a `move` or a `copy` operation was incorrect.
     #[error("\"from\" path is invalid")]
     InvalidFromPointer,
     /// `path` JSON pointer is incorrect.
     #[error("path is invalid")]
     InvalidPointer,
+    /// `path` JSON pointer is well-formed, but does not refer to an existing location.
+    #[error("path does not exist")]
+    PathNotFound,
+    /// Array index in the `path` JSON pointer is past the end of the array.
+    #[error("array index is out of bounds")]
+    IndexOutOfBounds,
     /// `move` operation failed because target is inside the `from` location.
     #[error("cannot move the value inside itself")]
     CannotMoveInsideItself,
+    /// Operation modifies or takes its value from a protected location, see
+    /// [`PatchOptions::protected_paths`].
+    #[error("path is protected")]
+    ProtectedPath,
+    /// Operation modifies or reads a location not permitted by a [`PatchPolicy`].
+    #[error("path is not allowed by the policy")]
+    PathNotAllowed,
+    /// Kind of the operation is not permitted, see [`PatchOptions::allowed_ops`].
+    #[error("operation is not allowed")]
+    OperationNotAllowed,
+    /// Patch has more operations than permitted, see [`PatchOptions::max_operations`].
+    #[error("too many operations")]
+    TooManyOperations,
+    /// Value of an `add` or a `replace` operation is larger than permitted, see
+    /// [`PatchOptions::max_value_size`].
+    #[error("value is too large")]
+    ValueTooLarge,
+    /// Operation makes the document grow more than permitted, see
+    /// [`PatchOptions::max_growth`].
+    #[error("document grows too large")]
+    GrowthLimitExceeded,
+    /// JSON pointer of the operation is nested deeper than permitted, see
+    /// [`PatchOptions::max_pointer_depth`].
+    #[error("path is too deep")]
+    PointerTooDeep,
+    /// Value at the `path` of an operation working on strings is not a string.
+    #[error("value is not a string")]
+    NotAString,
+    /// Value at the `path` of an operation working on arrays is not an array.
+    #[error("value is not an array")]
+    NotAnArray,
+    /// Custom operation has no handler registered, see [`extended::OpRegistry`].
+    #[cfg(feature = "extended-ops")]
+    #[error("unknown operation")]
+    UnknownOperation,
+    /// Handler of a custom operation failed with the given message, see
+    /// [`extended::OpRegistry`].
+    #[cfg(feature = "extended-ops")]
+    #[error("custom operation failed: {0}")]
+    CustomOperationFailed(String),
+    /// Pattern of a `test-matches` operation is not a valid regular expression.
+    #[cfg(feature = "test-predicates")]
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+    /// Range of an operation working on parts of a value is outside of the value.
+    #[error("range is out of bounds")]
+    RangeOutOfBounds,
 }
 
 /// This type represents all possible errors that can occur when applying JSON patch
@@ -271,6 +796,14 @@ pub struct PatchError {
     pub kind: PatchErrorKind,
 }
 
+impl PatchError {
+    /// Returns the `path` of the failed operation as a [`Pointer`], or `None` if it is not a
+    /// valid JSON pointer.
+    pub fn pointer(&self) -> Option<Pointer> {
+        Pointer::parse(&self.path).ok()
+    }
+}
+
 fn translate_error(kind: PatchErrorKind, operation: usize, path: &str) -> PatchError {
     PatchError {
         operation,
@@ -279,7 +812,7 @@ fn translate_error(kind: PatchErrorKind, operation: usize, path: &str) -> PatchE
     }
 }
 
-fn unescape(s: &str) -> Cow<str> {
+fn unescape(s: &str) -> Cow<'_, str> {
     if s.contains('~') {
         Cow::Owned(s.replace("~1", "/").replace("~0", "~"))
     } else {
@@ -287,6 +820,27 @@ fn unescape(s: &str) -> Cow<str> {
     }
 }
 
+/// Append the key to the JSON pointer, escaping it as a reference token.
+fn append_path(path: &mut String, key: &str) {
+    path.reserve(key.len());
+    for ch in key.chars() {
+        if ch == '~' {
+            *path += "~0";
+        } else if ch == '/' {
+            *path += "~1";
+        } else {
+            path.push(ch);
+        }
+    }
+}
+
+/// Checks if the JSON Pointer `prefix` refers to the same location as `path` or to one of its
+/// ancestors.
+fn is_prefix(prefix: &str, path: &str) -> bool {
+    path.starts_with(prefix)
+        && (path.len() == prefix.len() || path[prefix.len()..].starts_with('/'))
+}
+
 fn parse_index(str: &str, len: usize) -> Result<usize, PatchErrorKind> {
     // RFC 6901 prohibits leading zeroes in index
     if (str.starts_with('0') && str.len() != 1) || str.starts_with('+') {
@@ -294,100 +848,385 @@ fn parse_index(str: &str, len: usize) -> Result<usize, PatchErrorKind> {
     }
     match str.parse::<usize>() {
         Ok(index) if index < len => Ok(index),
-        _ => Err(PatchErrorKind::InvalidPointer),
+        Ok(_) => Err(PatchErrorKind::IndexOutOfBounds),
+        Err(_) => Err(PatchErrorKind::InvalidPointer),
+    }
+}
+
+/// Error of a `test` operation which expected the value, but found the `actual` one (`None` if
+/// there is no value at its path).
+fn test_failed(expected: &Value, actual: Option<&Value>) -> PatchErrorKind {
+    PatchErrorKind::TestFailed {
+        expected: Box::new(clone_value(expected)),
+        actual: actual.map(|actual| Box::new(clone_value(actual))),
+    }
+}
+
+/// Error of a `test` operation whose pointer does not resolve to a value in the document.
+fn test_not_found(expected: &Value, pointer: &str) -> PatchErrorKind {
+    match not_found(pointer) {
+        PatchErrorKind::PathNotFound => test_failed(expected, None),
+        err => err,
+    }
+}
+
+/// Error for a JSON pointer that does not resolve to a value in the document.
+fn not_found(pointer: &str) -> PatchErrorKind {
+    if pointer.starts_with('/') {
+        PatchErrorKind::PathNotFound
+    } else {
+        PatchErrorKind::InvalidPointer
+    }
+}
+
+/// Removes the member of the object, keeping the remaining members in their order (see the
+/// crate documentation on the order of object members).
+fn remove_member(obj: &mut Map<String, Value>, key: &str) -> Option<Value> {
+    #[cfg(feature = "preserve_order")]
+    return obj.shift_remove(key);
+    #[cfg(not(feature = "preserve_order"))]
+    obj.remove(key)
+}
+
+/// Position of the object member at the location among the members of its object, to put it
+/// back there when the document is reverted. `None` if the location is not an object member, or
+/// if the members of the object are not ordered (see [`JsonLike::member_position`]).
+fn member_position<V: JsonLike, P: Location + ?Sized>(doc: &V, path: &P) -> Option<usize> {
+    let (parent, last) = path.split_last().ok()?;
+    pointer(doc, parent)?.member_position(&last.key)
+}
+
+/// Positions of the `from` and `path` members of a `move` operation, see [`member_position`].
+/// When reverting the operation, the moved member is put back once the member it replaced is
+/// taken out of the object.
+fn move_positions<V: JsonLike, P: Location + PartialEq + ?Sized>(
+    doc: &V,
+    from: &P,
+    path: &P,
+) -> (Option<usize>, Option<usize>) {
+    let from_position = member_position(doc, from);
+    let path_position = member_position(doc, path);
+    fn parent<P: Location + ?Sized>(pointer: &P) -> Option<&P> {
+        pointer.split_last().ok().map(|(parent, _)| parent)
+    }
+    match (from_position, path_position) {
+        (Some(from_position), Some(path_position))
+            if path_position < from_position && parent(from) == parent(path) =>
+        {
+            (Some(from_position - 1), Some(path_position))
+        }
+        positions => positions,
     }
 }
 
-fn split_pointer(pointer: &str) -> Result<(&str, &str), PatchErrorKind> {
+fn split_last(pointer: &str) -> Result<(&str, &str), PatchErrorKind> {
     pointer
         .rfind('/')
         .ok_or(PatchErrorKind::InvalidPointer)
         .map(|idx| (&pointer[0..idx], &pointer[idx + 1..]))
 }
 
-fn add(doc: &mut Value, path: &str, value: Value) -> Result<Option<Value>, PatchErrorKind> {
-    if path.is_empty() {
-        return Ok(Some(std::mem::replace(doc, value)));
+/// Reference token of a JSON pointer.
+#[derive(Debug, Clone, PartialEq)]
+struct Token<'a> {
+    /// Unescaped token, used as the key of an object member.
+    key: Cow<'a, str>,
+    /// Token used as an array index, `None` if it is not a valid index.
+    index: Option<usize>,
+}
+
+impl<'a> Token<'a> {
+    fn new(token: &'a str) -> Token<'a> {
+        // RFC 6901 prohibits leading zeroes in index
+        let index = if (token.starts_with('0') && token.len() != 1) || token.starts_with('+') {
+            None
+        } else {
+            token.parse().ok()
+        };
+        Token {
+            key: unescape(token),
+            index,
+        }
     }
 
-    let (parent, last_unescaped) = split_pointer(path)?;
-    let parent = doc
-        .pointer_mut(parent)
-        .ok_or(PatchErrorKind::InvalidPointer)?;
+    fn into_owned(self) -> Token<'static> {
+        Token {
+            key: Cow::Owned(self.key.into_owned()),
+            index: self.index,
+        }
+    }
 
-    match *parent {
-        Value::Object(ref mut obj) => Ok(obj.insert(unescape(last_unescaped).into_owned(), value)),
-        Value::Array(ref mut arr) if last_unescaped == "-" => {
-            arr.push(value);
-            Ok(None)
+    fn borrowed(&self) -> Token<'_> {
+        Token {
+            key: Cow::Borrowed(&self.key),
+            index: self.index,
         }
-        Value::Array(ref mut arr) => {
-            let idx = parse_index(last_unescaped, arr.len() + 1)?;
-            arr.insert(idx, value);
-            Ok(None)
+    }
+
+    fn is_last(&self) -> bool {
+        self.key == "-"
+    }
+
+    /// Same as [`parse_index`], for an array of the given length.
+    fn index(&self, len: usize) -> Result<usize, PatchErrorKind> {
+        match self.index {
+            Some(index) if index < len => Ok(index),
+            Some(_) => Err(PatchErrorKind::IndexOutOfBounds),
+            None => Err(PatchErrorKind::InvalidPointer),
         }
-        _ => Err(PatchErrorKind::InvalidPointer),
     }
 }
 
-fn remove(doc: &mut Value, path: &str, allow_last: bool) -> Result<Value, PatchErrorKind> {
-    let (parent, last_unescaped) = split_pointer(path)?;
-    let parent = doc
-        .pointer_mut(parent)
+/// JSON pointer the operations are applied at, either as written in the patch or split into its
+/// reference tokens in advance (see [`Patch::compile`]).
+trait Location {
+    /// Returns `true` if the pointer refers to the whole document.
+    fn is_root(&self) -> bool;
+
+    /// Reference tokens of the pointer, `None` if it is malformed.
+    fn tokens(&self) -> Option<impl Iterator<Item = Token<'_>>>;
+
+    /// Splits the pointer into the pointer to the parent and the last reference token.
+    fn split_last(&self) -> Result<(&Self, Token<'_>), PatchErrorKind>;
+
+    /// Error for a pointer that does not resolve to a value in the document.
+    fn not_found(&self) -> PatchErrorKind;
+
+    /// Returns `true` if the pointer refers to a value inside the one at `other`.
+    fn is_inside(&self, other: &Self) -> bool;
+}
+
+impl Location for str {
+    fn is_root(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn tokens(&self) -> Option<impl Iterator<Item = Token<'_>>> {
+        let tokens = match self.strip_prefix('/') {
+            Some(rest) => Some(rest.split('/')),
+            None if self.is_empty() => None,
+            None => return None,
+        };
+        Some(tokens.into_iter().flatten().map(Token::new))
+    }
+
+    fn split_last(&self) -> Result<(&str, Token<'_>), PatchErrorKind> {
+        split_last(self).map(|(parent, last)| (parent, Token::new(last)))
+    }
+
+    fn not_found(&self) -> PatchErrorKind {
+        not_found(self)
+    }
+
+    fn is_inside(&self, other: &str) -> bool {
+        self.starts_with(other) && self[other.len()..].starts_with('/')
+    }
+}
+
+impl Location for [Token<'_>] {
+    fn is_root(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn tokens(&self) -> Option<impl Iterator<Item = Token<'_>>> {
+        Some(self.iter().map(Token::borrowed))
+    }
+
+    fn split_last(&self) -> Result<(&Self, Token<'_>), PatchErrorKind> {
+        let (last, parent) = <[_]>::split_last(self).ok_or(PatchErrorKind::InvalidPointer)?;
+        Ok((parent, last.borrowed()))
+    }
+
+    fn not_found(&self) -> PatchErrorKind {
+        PatchErrorKind::PathNotFound
+    }
+
+    fn is_inside(&self, other: &Self) -> bool {
+        self.len() > other.len() && self.iter().zip(other).all(|(a, b)| a.key == b.key)
+    }
+}
+
+/// Resolve the JSON pointer against the document.
+fn pointer<'a, V: JsonLike, P: Location + ?Sized>(doc: &'a V, pointer: &P) -> Option<&'a V> {
+    let mut target = doc;
+    for token in pointer.tokens()? {
+        target = match target.as_array() {
+            Some(arr) => arr.get(token.index(arr.len()).ok()?)?,
+            None => target.member(&token.key)?,
+        };
+    }
+    Some(target)
+}
+
+/// Resolve the JSON pointer against the document, for modification.
+fn pointer_mut<'a, V: JsonLike, P: Location + ?Sized>(
+    doc: &'a mut V,
+    pointer: &P,
+) -> Option<&'a mut V> {
+    let mut target = doc;
+    for token in pointer.tokens()? {
+        target = match target.as_array().map(<[V]>::len) {
+            Some(len) => {
+                let idx = token.index(len).ok()?;
+                target.as_array_mut()?.get_mut(idx)?
+            }
+            None => target.member_mut(&token.key)?,
+        };
+    }
+    Some(target)
+}
+
+fn add<V: JsonLike, P: Location + ?Sized>(
+    doc: &mut V,
+    path: &P,
+    value: V,
+) -> Result<Option<V>, PatchErrorKind> {
+    insert(doc, path, value).map_err(|(err, value)| {
+        value.drop_value();
+        err
+    })
+}
+
+/// Same as `add`, but gives the value back if it cannot be added.
+fn insert<V: JsonLike, P: Location + ?Sized>(
+    doc: &mut V,
+    path: &P,
+    value: V,
+) -> Result<Option<V>, (PatchErrorKind, V)> {
+    if path.is_root() {
+        return Ok(Some(core::mem::replace(doc, value)));
+    }
+
+    let (parent, last) = match path.split_last() {
+        Ok(split) => split,
+        Err(err) => return Err((err, value)),
+    };
+    let Some(parent_value) = pointer_mut(doc, parent) else {
+        return Err((parent.not_found(), value));
+    };
+
+    if parent_value.is_object() {
+        return Ok(parent_value.insert_member(last.key.into_owned(), value));
+    }
+    let Some(len) = parent_value.as_array().map(<[V]>::len) else {
+        return Err((PatchErrorKind::InvalidPointer, value));
+    };
+    let idx = if last.is_last() {
+        len
+    } else {
+        match last.index(len + 1) {
+            Ok(idx) => idx,
+            Err(err) => return Err((err, value)),
+        }
+    };
+    parent_value.insert_element(idx, value);
+    Ok(None)
+}
+
+fn remove<V: JsonLike, P: Location + ?Sized>(
+    doc: &mut V,
+    path: &P,
+    allow_last: bool,
+) -> Result<V, PatchErrorKind> {
+    let (parent, last) = path.split_last()?;
+    let parent = pointer_mut(doc, parent).ok_or_else(|| parent.not_found())?;
+
+    if parent.is_object() {
+        return parent
+            .remove_member(&last.key)
+            .ok_or(PatchErrorKind::PathNotFound);
+    }
+    let len = parent
+        .as_array()
+        .map(<[V]>::len)
         .ok_or(PatchErrorKind::InvalidPointer)?;
+    let idx = if allow_last && last.is_last() && len > 0 {
+        len - 1
+    } else {
+        last.index(len)?
+    };
+    Ok(parent.remove_element(idx))
+}
 
-    match *parent {
-        Value::Object(ref mut obj) => match obj.remove(unescape(last_unescaped).as_ref()) {
-            None => Err(PatchErrorKind::InvalidPointer),
-            Some(val) => Ok(val),
-        },
-        Value::Array(ref mut arr) if allow_last && last_unescaped == "-" => Ok(arr.pop().unwrap()),
-        Value::Array(ref mut arr) => {
-            let idx = parse_index(last_unescaped, arr.len())?;
-            Ok(arr.remove(idx))
+/// Same as `add`, putting an object member back at its position, if given (see
+/// [`member_position`]).
+fn restore<V: JsonLike, P: Location + ?Sized>(
+    doc: &mut V,
+    path: &P,
+    value: V,
+    position: Option<usize>,
+) -> Result<Option<V>, PatchErrorKind> {
+    if let Some(position) = position {
+        let (parent, last) = path.split_last()?;
+        if let Some(parent) = pointer_mut(doc, parent).filter(|parent| parent.is_object()) {
+            return Ok(parent.insert_member_at(position, last.key.into_owned(), value));
         }
-        _ => Err(PatchErrorKind::InvalidPointer),
     }
+    add(doc, path, value)
 }
 
-fn replace(doc: &mut Value, path: &str, value: Value) -> Result<Value, PatchErrorKind> {
-    let target = doc
-        .pointer_mut(path)
-        .ok_or(PatchErrorKind::InvalidPointer)?;
-    Ok(std::mem::replace(target, value))
+fn replace<V: JsonLike, P: Location + ?Sized>(
+    doc: &mut V,
+    path: &P,
+    value: V,
+) -> Result<V, PatchErrorKind> {
+    let target = pointer_mut(doc, path).ok_or_else(|| path.not_found())?;
+    Ok(core::mem::replace(target, value))
 }
 
-fn mov(
-    doc: &mut Value,
-    from: &str,
-    path: &str,
+fn mov<V: JsonLike, P: Location + ?Sized>(
+    doc: &mut V,
+    from: &P,
+    path: &P,
     allow_last: bool,
-) -> Result<Option<Value>, PatchErrorKind> {
+) -> Result<Option<V>, PatchErrorKind> {
     // Check we are not moving inside own child
-    if path.starts_with(from) && path[from.len()..].starts_with('/') {
+    if path.is_inside(from) {
         return Err(PatchErrorKind::CannotMoveInsideItself);
     }
     let val = remove(doc, from, allow_last).map_err(|err| match err {
-        PatchErrorKind::InvalidPointer => PatchErrorKind::InvalidFromPointer,
+        PatchErrorKind::InvalidPointer
+        | PatchErrorKind::PathNotFound
+        | PatchErrorKind::IndexOutOfBounds => PatchErrorKind::InvalidFromPointer,
         err => err,
     })?;
-    add(doc, path, val)
+    insert(doc, path, val).map_err(|(err, val)| {
+        // Put the value back, so the failed operation has no effect
+        let restored = insert(doc, from, val);
+        debug_assert!(restored.is_ok(), "unable to restore moved value");
+        err
+    })
 }
 
-fn copy(doc: &mut Value, from: &str, path: &str) -> Result<Option<Value>, PatchErrorKind> {
-    let source = doc
-        .pointer(from)
-        .ok_or(PatchErrorKind::InvalidFromPointer)?
-        .clone();
+fn copy<V: JsonLike, P: Location + ?Sized>(
+    doc: &mut V,
+    from: &P,
+    path: &P,
+) -> Result<Option<V>, PatchErrorKind> {
+    let source = pointer(doc, from)
+        .map(JsonLike::clone_value)
+        .ok_or(PatchErrorKind::InvalidFromPointer)?;
     add(doc, path, source)
 }
 
-fn test(doc: &Value, path: &str, expected: &Value) -> Result<(), PatchErrorKind> {
-    let target = doc.pointer(path).ok_or(PatchErrorKind::InvalidPointer)?;
-    if *target == *expected {
+fn test<V: JsonLike, P: Location + ?Sized>(
+    doc: &V,
+    path: &P,
+    expected: &Value,
+) -> Result<(), PatchErrorKind> {
+    let Some(target) = pointer(doc, path) else {
+        return match path.not_found() {
+            PatchErrorKind::PathNotFound => Err(test_failed(expected, None)),
+            err => Err(err),
+        };
+    };
+    if target.eq_json(expected) {
         Ok(())
     } else {
-        Err(PatchErrorKind::TestFailed)
+        Err(PatchErrorKind::TestFailed {
+            expected: Box::new(clone_value(expected)),
+            actual: Some(Box::new(target.to_json())),
+        })
     }
 }
 
@@ -423,13 +1262,21 @@ fn test(doc: &Value, path: &str, expected: &Value) -> Result<(), PatchErrorKind>
 /// # }
 /// ```
 pub fn patch(doc: &mut Value, patch: &[PatchOperation]) -> Result<(), PatchError> {
+    trace::span!("patch", operations = patch.len());
+    trace::event!(trace, size = estimate::serialized_len(doc), "document");
     let mut undo_stack = Vec::with_capacity(patch.len());
     if let Err(e) = apply_patches(doc, patch, Some(&mut undo_stack)) {
-        if let Err(e) = undo_patches(doc, &undo_stack) {
-            unreachable!("unable to undo applied patches: {e}")
-        }
+        trace::failure!(e);
+        trace::span!("revert", operations = undo_stack.len());
+        revert(doc, undo_stack);
         return Err(e);
     }
+    drop_operations(undo_stack);
+    trace::event!(
+        trace,
+        size = estimate::serialized_len(doc),
+        "patched document"
+    );
     Ok(())
 }
 
@@ -437,6 +1284,10 @@ pub fn patch(doc: &mut Value, patch: &[PatchOperation]) -> Result<(), PatchError
 /// if any patch failed, the document is left in an inconsistent state. In case of internal error
 /// resulting in panic, document might be left in inconsistent state.
 ///
+/// No undo log is kept while applying operations, so this is faster than [`patch`] and is
+/// suitable for patches known to apply (for example, checked with [`validate`] beforehand). On
+/// error, the document is left with all the operations preceding the failed one applied.
+///
 /// # Example
 /// Create and patch document:
 ///
@@ -468,29 +1319,236 @@ pub fn patch_unsafe(doc: &mut Value, patch: &[PatchOperation]) -> Result<(), Pat
     apply_patches(doc, patch, None)
 }
 
+/// Patch provided JSON document (given as `serde_json::Value`) in-place, consuming the patch:
+/// the values of `add` and `replace` operations are moved into the document instead of being
+/// cloned, which saves copying large values. If any of the patch is failed, all previous
+/// operations are reverted, as with [`patch`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_owned, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "items": [] });
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/items/-", "value": { "large": ["value"] } },
+///   { "op": "test", "path": "/items/0/large/0", "value": "value" }
+/// ])).unwrap();
+///
+/// patch_owned(&mut doc, p).unwrap();
+/// assert_eq!(doc, json!({ "items": [{ "large": ["value"] }] }));
+/// ```
+pub fn patch_owned(doc: &mut Value, mut patch: Patch) -> Result<(), PatchError> {
+    trace::span!("patch", operations = patch.len());
+    let mut values: Vec<Option<Value>> = patch
+        .0
+        .iter_mut()
+        .map(|op| match op {
+            PatchOperation::Add(AddOperation { value, .. })
+            | PatchOperation::Replace(ReplaceOperation { value, .. }) => Some(value.take()),
+            _ => None,
+        })
+        .collect();
+    let mut undo_stack = Vec::with_capacity(patch.len());
+    let result = apply_patches_taking(doc, &patch, Some(&mut undo_stack), &mut values);
+    // Values of the operations following a failed one are left
+    values.into_iter().flatten().for_each(drop_value);
+    drop_operations(patch.0);
+    if let Err(e) = result {
+        trace::failure!(e);
+        trace::span!("revert", operations = undo_stack.len());
+        revert(doc, undo_stack);
+        return Err(e);
+    }
+    drop_operations(undo_stack);
+    Ok(())
+}
+
+/// Apply the patch to a copy of the provided JSON document (given as `serde_json::Value`),
+/// returning the patched copy. The original document is left intact, whether the patch succeeds
+/// or not.
+///
+/// `serde_json::Value` owns all of its subtrees, so the whole document is copied. Since the copy
+/// is discarded on error, no undo log is kept while applying operations, unlike [`patch`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patched, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "name": "Andrew" });
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/happy", "value": true }
+/// ])).unwrap();
+///
+/// let new_doc = patched(&doc, &p).unwrap();
+/// assert_eq!(new_doc, json!({ "name": "Andrew", "happy": true }));
+/// assert_eq!(doc, json!({ "name": "Andrew" }));
+/// ```
+pub fn patched(doc: &Value, patch: &[PatchOperation]) -> Result<Value, PatchError> {
+    let mut doc = clone_value(doc);
+    if let Err(e) = apply_patches(&mut doc, patch, None) {
+        drop_value(doc);
+        return Err(e);
+    }
+    Ok(doc)
+}
+
+/// Error reported by [`validate`] for an operation that would fail.
+pub type PatchValidationError = PatchError;
+
+/// Check that every operation of the patch would succeed when applied to the document, without
+/// modifying it. Operations are checked in order, as if the failed ones were skipped, and all the
+/// failures are reported.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{validate, Patch, PatchErrorKind};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "a": [1, 2] });
+/// let p: Patch = from_value(json!([
+///   { "op": "test", "path": "/a/0", "value": 10 },
+///   { "op": "remove", "path": "/a/1" },
+///   { "op": "remove", "path": "/a/1" }
+/// ])).unwrap();
+///
+/// let errors = validate(&doc, &p).unwrap_err();
+/// assert_eq!(errors.len(), 2);
+/// assert!(matches!(errors[0].kind, PatchErrorKind::TestFailed { .. }));
+/// assert_eq!(errors[1].operation, 2);
+/// assert_eq!(doc, json!({ "a": [1, 2] }));
+/// ```
+pub fn validate(doc: &Value, patch: &[PatchOperation]) -> Result<(), Vec<PatchValidationError>> {
+    let mut doc = clone_value(doc);
+    let errors: Vec<_> = patch_lenient(&mut doc, patch)
+        .into_iter()
+        .filter_map(Result::err)
+        .collect();
+    drop_value(doc);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Patch provided JSON document (given as `serde_json::Value`) in-place, applying as many
+/// operations as possible. Each operation is applied on its own: the failed ones are skipped and
+/// leave the document unchanged, while the rest of the patch is still applied. The outcome of
+/// every operation is reported, in the order of the operations.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_lenient, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "a": 1 });
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/b", "value": 2 },
+///   { "op": "remove", "path": "/c" },
+///   { "op": "replace", "path": "/a", "value": 3 }
+/// ])).unwrap();
+///
+/// let results = patch_lenient(&mut doc, &p);
+/// assert!(results[0].is_ok());
+/// assert_eq!(results[1].as_ref().unwrap_err().operation, 1);
+/// assert!(results[2].is_ok());
+/// assert_eq!(doc, json!({ "a": 3, "b": 2 }));
+/// ```
+pub fn patch_lenient(doc: &mut Value, patch: &[PatchOperation]) -> Vec<Result<(), PatchError>> {
+    patch
+        .iter()
+        .enumerate()
+        .map(|(operation, op)| apply_operation(doc, operation, op))
+        .collect()
+}
+
+/// Apply a single operation, leaving the document intact if it fails.
+fn apply_operation(
+    doc: &mut Value,
+    operation: usize,
+    op: &PatchOperation,
+) -> Result<(), PatchError> {
+    patch(doc, core::slice::from_ref(op)).map_err(|mut err| {
+        err.operation = operation;
+        err
+    })
+}
+
+/// Change reverting an operation applied by `apply_patches`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Undo {
+    /// Operation reverting the change.
+    op: PatchOperation,
+    /// Position of the object member the operation puts back, see [`member_position`].
+    position: Option<usize>,
+}
+
+impl From<PatchOperation> for Undo {
+    fn from(op: PatchOperation) -> Undo {
+        Undo { op, position: None }
+    }
+}
+
+impl From<Undo> for PatchOperation {
+    fn from(undo: Undo) -> PatchOperation {
+        undo.op
+    }
+}
+
+/// Reverts the document to its original state using the undo stack recorded by `apply_patches`.
+fn revert(doc: &mut Value, undo_stack: Vec<Undo>) {
+    if let Err(e) = undo_patches(doc, undo_stack) {
+        unreachable!("unable to undo applied patches: {e}")
+    }
+}
+
 /// Undoes operations performed by `apply_patches`. This is useful to recover the original document
 /// in case of an error.
-fn undo_patches(doc: &mut Value, undo_patches: &[PatchOperation]) -> Result<(), PatchError> {
-    for (operation, patch) in undo_patches.iter().enumerate().rev() {
+fn undo_patches(doc: &mut Value, undo_patches: Vec<Undo>) -> Result<(), PatchError> {
+    for (
+        operation,
+        Undo {
+            op: patch,
+            position,
+        },
+    ) in undo_patches.into_iter().enumerate().rev()
+    {
         match patch {
             PatchOperation::Add(op) => {
-                add(doc, &op.path, op.value.clone())
+                let prev = restore(doc, op.path.as_str(), op.value, position)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
+                if let Some(prev) = prev {
+                    drop_value(prev);
+                }
             }
             PatchOperation::Remove(op) => {
-                remove(doc, &op.path, true).map_err(|e| translate_error(e, operation, &op.path))?;
+                let prev = remove(doc, op.path.as_str(), true)
+                    .map_err(|e| translate_error(e, operation, &op.path))?;
+                drop_value(prev);
             }
             PatchOperation::Replace(op) => {
-                replace(doc, &op.path, op.value.clone())
+                let prev = replace(doc, op.path.as_str(), op.value)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
+                drop_value(prev);
             }
             PatchOperation::Move(op) => {
-                mov(doc, op.from.as_str(), &op.path, true)
+                let value = remove(doc, op.from.as_str(), true)
+                    .map_err(|e| translate_error(e, operation, &op.path))?;
+                restore(doc, op.path.as_str(), value, position)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
             }
             PatchOperation::Copy(op) => {
-                copy(doc, op.from.as_str(), &op.path)
+                let prev = copy(doc, op.from.as_str(), &op.path)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
+                if let Some(prev) = prev {
+                    drop_value(prev);
+                }
             }
             _ => unreachable!(),
         }
@@ -505,78 +1563,123 @@ fn undo_patches(doc: &mut Value, undo_patches: &[PatchOperation]) -> Result<(),
 fn apply_patches(
     doc: &mut Value,
     patches: &[PatchOperation],
-    undo_stack: Option<&mut Vec<PatchOperation>>,
+    undo_stack: Option<&mut Vec<Undo>>,
+) -> Result<(), PatchError> {
+    apply_patches_taking(doc, patches, undo_stack, &mut [])
+}
+
+/// Same as `apply_patches`, taking the values of `add` and `replace` operations from `values`
+/// (by the index of the operation) instead of cloning them, where present.
+fn apply_patches_taking(
+    doc: &mut Value,
+    patches: &[PatchOperation],
+    undo_stack: Option<&mut Vec<Undo>>,
+    values: &mut [Option<Value>],
 ) -> Result<(), PatchError> {
     for (operation, patch) in patches.iter().enumerate() {
+        let path = resolve_pointer(doc, patch.path())
+            .map_err(|e| translate_error(e, operation, patch.path()))?;
         match patch {
             PatchOperation::Add(ref op) => {
-                let prev = add(doc, &op.path, op.value.clone())
-                    .map_err(|e| translate_error(e, operation, &op.path))?;
+                let value = take_value(values, operation, &op.value);
+                let prev =
+                    add(doc, &*path, value).map_err(|e| translate_error(e, operation, &op.path))?;
                 if let Some(&mut ref mut undo_stack) = undo_stack {
-                    undo_stack.push(match prev {
+                    undo_stack.push(Undo::from(match prev {
                         None => PatchOperation::Remove(RemoveOperation {
-                            path: op.path.clone(),
+                            path: path.into_owned(),
                         }),
                         Some(v) => PatchOperation::Add(AddOperation {
-                            path: op.path.clone(),
+                            path: path.into_owned(),
                             value: v,
                         }),
-                    })
+                    }))
+                } else if let Some(prev) = prev {
+                    drop_value(prev);
                 }
             }
             PatchOperation::Remove(ref op) => {
-                let prev = remove(doc, &op.path, false)
+                let position = undo_stack
+                    .as_ref()
+                    .and_then(|_| member_position(doc, &*path));
+                let prev = remove(doc, &*path, false)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
                 if let Some(&mut ref mut undo_stack) = undo_stack {
-                    undo_stack.push(PatchOperation::Add(AddOperation {
-                        path: op.path.clone(),
-                        value: prev,
-                    }))
+                    undo_stack.push(Undo {
+                        op: PatchOperation::Add(AddOperation {
+                            path: path.into_owned(),
+                            value: prev,
+                        }),
+                        position,
+                    })
+                } else {
+                    drop_value(prev);
                 }
             }
             PatchOperation::Replace(ref op) => {
-                let prev = replace(doc, &op.path, op.value.clone())
+                let value = take_value(values, operation, &op.value);
+                let prev = replace(doc, &*path, value)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
                 if let Some(&mut ref mut undo_stack) = undo_stack {
-                    undo_stack.push(PatchOperation::Replace(ReplaceOperation {
-                        path: op.path.clone(),
+                    undo_stack.push(Undo::from(PatchOperation::Replace(ReplaceOperation {
+                        path: path.into_owned(),
                         value: prev,
-                    }))
+                    })))
+                } else {
+                    drop_value(prev);
                 }
             }
             PatchOperation::Move(ref op) => {
-                let prev = mov(doc, op.from.as_str(), &op.path, false)
+                let from = resolve_pointer(doc, &op.from)
+                    .map_err(|e| translate_error(e, operation, &op.path))?;
+                let (from_position, path_position) = match undo_stack {
+                    Some(_) => move_positions(doc, &*from, &*path),
+                    None => (None, None),
+                };
+                let prev = mov(doc, &*from, &*path, false)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
                 if let Some(&mut ref mut undo_stack) = undo_stack {
                     if let Some(prev) = prev {
-                        undo_stack.push(PatchOperation::Add(AddOperation {
-                            path: op.path.clone(),
-                            value: prev,
-                        }));
+                        undo_stack.push(Undo {
+                            op: PatchOperation::Add(AddOperation {
+                                path: path.clone().into_owned(),
+                                value: prev,
+                            }),
+                            position: path_position,
+                        });
                     }
-                    undo_stack.push(PatchOperation::Move(MoveOperation {
-                        from: op.path.clone(),
-                        path: op.from.clone(),
-                    }));
+                    undo_stack.push(Undo {
+                        op: PatchOperation::Move(MoveOperation {
+                            from: path.into_owned(),
+                            path: from.into_owned(),
+                        }),
+                        position: from_position,
+                    });
+                } else if let Some(prev) = prev {
+                    drop_value(prev);
                 }
             }
             PatchOperation::Copy(ref op) => {
-                let prev = copy(doc, op.from.as_str(), &op.path)
+                let from = resolve_pointer(doc, &op.from)
+                    .map_err(|e| translate_error(e, operation, &op.path))?;
+                let prev = copy(doc, &*from, &*path)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
                 if let Some(&mut ref mut undo_stack) = undo_stack {
-                    undo_stack.push(match prev {
+                    undo_stack.push(Undo::from(match prev {
                         None => PatchOperation::Remove(RemoveOperation {
-                            path: op.path.clone(),
+                            path: path.into_owned(),
                         }),
                         Some(v) => PatchOperation::Add(AddOperation {
-                            path: op.path.clone(),
+                            path: path.into_owned(),
                             value: v,
                         }),
-                    })
+                    }))
+                } else if let Some(prev) = prev {
+                    drop_value(prev);
                 }
             }
             PatchOperation::Test(ref op) => {
-                test(doc, &op.path, &op.value)
+                test(doc, &*path, &op.value)
                     .map_err(|e| translate_error(e, operation, &op.path))?;
             }
         }
@@ -585,6 +1688,28 @@ fn apply_patches(
     Ok(())
 }
 
+/// Resolves the negative array indices of the pointer against the document, with the
+/// `negative-indices` feature.
+#[cfg(feature = "negative-indices")]
+fn resolve_pointer<'a>(doc: &Value, pointer: &'a str) -> Result<Cow<'a, str>, PatchErrorKind> {
+    negative::resolve(doc, pointer)
+}
+
+/// Resolves the negative array indices of the pointer against the document, with the
+/// `negative-indices` feature.
+#[cfg(not(feature = "negative-indices"))]
+fn resolve_pointer<'a>(_doc: &Value, pointer: &'a str) -> Result<Cow<'a, str>, PatchErrorKind> {
+    Ok(Cow::Borrowed(pointer))
+}
+
+/// Returns the value of an operation, taken from `values` if present there.
+fn take_value(values: &mut [Option<Value>], operation: usize, value: &Value) -> Value {
+    values
+        .get_mut(operation)
+        .and_then(Option::take)
+        .unwrap_or_else(|| clone_value(value))
+}
+
 /// Patch provided JSON document (given as `serde_json::Value`) in place with JSON Merge Patch
 /// (RFC 7396).
 ///
@@ -640,7 +1765,7 @@ pub fn merge(doc: &mut Value, patch: &Value) {
     let map = doc.as_object_mut().unwrap();
     for (key, value) in patch.as_object().unwrap() {
         if value.is_null() {
-            map.remove(key.as_str());
+            remove_member(map, key);
         } else {
             merge(map.entry(key.as_str()).or_insert(Value::Null), value);
         }
@@ -0,0 +1,112 @@
+/// Construct a [`Patch`](crate::Patch) from inline JSON patch syntax.
+///
+/// Every operation is written as a JSON object with members in the order used by RFC 6902:
+/// `"op"` first, then `"from"` (for `move` and `copy`), `"path"` and `"value"` (for `add`,
+/// `replace` and `test`). Paths are arbitrary expressions converting into `String` (like `&str`
+/// or [`Pointer`](crate::Pointer)), and values use the syntax of `serde_json::json!`. Unknown
+/// operations and missing members are reported at compile time.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{json_patch, Patch, Pointer};
+/// use serde_json::{from_value, json};
+///
+/// let name = "Andrew";
+/// let p = json_patch![
+///     { "op": "test", "path": "/0/name", "value": name },
+///     { "op": "add", "path": Pointer::from_tokens(["0", "happy"]), "value": true },
+///     { "op": "move", "from": "/1", "path": "/2" },
+/// ];
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "test", "path": "/0/name", "value": "Andrew" },
+///   { "op": "add", "path": "/0/happy", "value": true },
+///   { "op": "move", "from": "/1", "path": "/2" }
+/// ])).unwrap());
+/// ```
+///
+/// Missing members do not compile:
+///
+/// ```compile_fail
+/// let p = json_patch::json_patch![{ "op": "add", "path": "/a" }];
+/// ```
+#[macro_export]
+macro_rules! json_patch {
+    (@op "op": "add", "path": $path:expr, "value": $($value:tt)+) => {
+        $crate::PatchOperation::Add($crate::AddOperation {
+            path: ::core::convert::Into::into($path),
+            value: $crate::__private::json!($($value)+),
+        })
+    };
+    (@op "op": "remove", "path": $path:expr $(,)?) => {
+        $crate::PatchOperation::Remove($crate::RemoveOperation {
+            path: ::core::convert::Into::into($path),
+        })
+    };
+    (@op "op": "replace", "path": $path:expr, "value": $($value:tt)+) => {
+        $crate::PatchOperation::Replace($crate::ReplaceOperation {
+            path: ::core::convert::Into::into($path),
+            value: $crate::__private::json!($($value)+),
+        })
+    };
+    (@op "op": "move", "from": $from:expr, "path": $path:expr $(,)?) => {
+        $crate::PatchOperation::Move($crate::MoveOperation {
+            from: ::core::convert::Into::into($from),
+            path: ::core::convert::Into::into($path),
+        })
+    };
+    (@op "op": "copy", "from": $from:expr, "path": $path:expr $(,)?) => {
+        $crate::PatchOperation::Copy($crate::CopyOperation {
+            from: ::core::convert::Into::into($from),
+            path: ::core::convert::Into::into($path),
+        })
+    };
+    (@op "op": "test", "path": $path:expr, "value": $($value:tt)+) => {
+        $crate::PatchOperation::Test($crate::TestOperation {
+            path: ::core::convert::Into::into($path),
+            value: $crate::__private::json!($($value)+),
+        })
+    };
+    (@op $($op:tt)*) => {
+        ::core::compile_error!(::core::concat!(
+            "invalid JSON patch operation: { ",
+            ::core::stringify!($($op)*),
+            " }"
+        ))
+    };
+    ($({ $($op:tt)* }),* $(,)?) => {
+        $crate::Patch($crate::__private::vec![$($crate::json_patch!(@op $($op)*)),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Patch, Pointer};
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn all_operations() {
+        let value = json!({ "b": [1] });
+        let p = json_patch![
+            { "op": "add", "path": "/a", "value": value },
+            { "op": "remove", "path": String::from("/a/b") },
+            { "op": "replace", "path": Pointer::from_tokens(["a"]), "value": -1 },
+            { "op": "move", "from": "/a", "path": "/b" },
+            { "op": "copy", "from": "/b", "path": "/c", },
+            { "op": "test", "path": "/c", "value": [-1, { "x": null }] },
+        ];
+        assert_eq!(
+            p,
+            from_value::<Patch>(json!([
+                { "op": "add", "path": "/a", "value": { "b": [1] } },
+                { "op": "remove", "path": "/a/b" },
+                { "op": "replace", "path": "/a", "value": -1 },
+                { "op": "move", "from": "/a", "path": "/b" },
+                { "op": "copy", "from": "/b", "path": "/c" },
+                { "op": "test", "path": "/c", "value": [-1, { "x": null }] },
+            ]))
+            .unwrap()
+        );
+        assert_eq!(json_patch![], Patch(Vec::new()));
+    }
+}
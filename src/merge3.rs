@@ -0,0 +1,349 @@
+use crate::{append_path, is_prefix};
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Location changed differently on both sides of a three-way merge.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    /// JSON pointer to the conflicting location.
+    pub path: String,
+    /// Value at the location in the left document, or `None` if it was removed there.
+    pub left: Option<Value>,
+    /// Value at the location in the right document, or `None` if it was removed there.
+    pub right: Option<Value>,
+}
+
+/// Resolution of the conflicts of a three-way merge, see [`Merge3Options::strategies`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MergeStrategy {
+    /// Report the conflict.
+    #[default]
+    Fail,
+    /// Keep the left version ("ours"), including its removal of the value.
+    Left,
+    /// Keep the right version ("theirs"), including its removal of the value.
+    Right,
+    /// Keep the changes of both sides: arrays keep the elements added on either side and lose
+    /// the elements removed on either side, and values removed on one side but changed on the
+    /// other one are kept. Other conflicts are reported.
+    Union,
+}
+
+/// Options controlling how [`merge3_with_options`] merges documents.
+///
+/// New options might be added in the future, so construct options with `..Default::default()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Merge3Options {
+    /// Resolution of conflicts, as a map from the JSON Pointer of a location to the strategy for
+    /// the conflicts at the location or nested under it. The longest matching pointer wins (use
+    /// `""` to set the strategy for the whole document). Conflicts are reported if no pointer
+    /// matches.
+    ///
+    /// Changes made on one side only are merged whatever the strategy.
+    pub strategies: BTreeMap<String, MergeStrategy>,
+}
+
+struct Merger<'a> {
+    path: String,
+    conflicts: Vec<MergeConflict>,
+    options: &'a Merge3Options,
+}
+
+impl Merger<'_> {
+    /// Strategy for the conflicts at the current path.
+    fn strategy(&self) -> MergeStrategy {
+        self.options
+            .strategies
+            .iter()
+            .filter(|(prefix, _)| is_prefix(prefix, &self.path))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(MergeStrategy::Fail, |(_, strategy)| *strategy)
+    }
+
+    fn merge(
+        &mut self,
+        base: Option<&Value>,
+        left: Option<&Value>,
+        right: Option<&Value>,
+    ) -> Option<Value> {
+        if left == right || right == base {
+            return left.cloned();
+        }
+        if left == base {
+            return right.cloned();
+        }
+        match (base, left, right) {
+            // Objects added or replaced on both sides are merged as if they were empty before
+            (base, Some(Value::Object(left)), Some(Value::Object(right))) => {
+                let empty = Map::new();
+                let base = base.and_then(Value::as_object).unwrap_or(&empty);
+                Some(Value::Object(self.merge_objects(base, left, right)))
+            }
+            (base, Some(Value::Array(left)), Some(Value::Array(right)))
+                if self.strategy() == MergeStrategy::Union =>
+            {
+                let base = base
+                    .and_then(Value::as_array)
+                    .map_or(&[][..], Vec::as_slice);
+                Some(Value::Array(union(base, left, right)))
+            }
+            (Some(Value::Array(base)), Some(Value::Array(left)), Some(Value::Array(right)))
+                if base.len() == left.len() && base.len() == right.len() =>
+            {
+                Some(Value::Array(self.merge_arrays(base, left, right)))
+            }
+            _ => match self.strategy() {
+                MergeStrategy::Left => left.cloned(),
+                MergeStrategy::Right => right.cloned(),
+                MergeStrategy::Union if left.is_none() || right.is_none() => {
+                    left.or(right).cloned()
+                }
+                _ => {
+                    self.conflicts.push(MergeConflict {
+                        path: self.path.clone(),
+                        left: left.cloned(),
+                        right: right.cloned(),
+                    });
+                    left.cloned()
+                }
+            },
+        }
+    }
+
+    fn merge_objects(
+        &mut self,
+        base: &Map<String, Value>,
+        left: &Map<String, Value>,
+        right: &Map<String, Value>,
+    ) -> Map<String, Value> {
+        let keys = left
+            .keys()
+            .chain(right.keys().filter(|key| !left.contains_key(*key)));
+        let mut merged = Map::new();
+        for key in keys {
+            let len = self.path.len();
+            self.path.push('/');
+            append_path(&mut self.path, key);
+            if let Some(value) = self.merge(base.get(key), left.get(key), right.get(key)) {
+                merged.insert(key.clone(), value);
+            }
+            self.path.truncate(len);
+        }
+        merged
+    }
+
+    fn merge_arrays(&mut self, base: &[Value], left: &[Value], right: &[Value]) -> Vec<Value> {
+        let mut merged = Vec::with_capacity(base.len());
+        for (idx, base) in base.iter().enumerate() {
+            let len = self.path.len();
+            self.path += &format!("/{}", idx);
+            // Elements are never removed, as arrays of the same length are merged element-wise
+            merged.extend(self.merge(Some(base), Some(&left[idx]), Some(&right[idx])));
+            self.path.truncate(len);
+        }
+        merged
+    }
+}
+
+/// Union of the changes of two arrays: elements of the left array not removed from the right
+/// one, followed by the elements added to the right array.
+fn union(base: &[Value], left: &[Value], right: &[Value]) -> Vec<Value> {
+    let kept = left
+        .iter()
+        .filter(|value| right.contains(value) || !base.contains(value));
+    let added = right
+        .iter()
+        .filter(|value| !left.contains(value) && !base.contains(value));
+    kept.chain(added).cloned().collect()
+}
+
+/// Merge changes made independently to the `base` document in the `left` and the `right`
+/// documents (three-way merge).
+///
+/// Changes are merged recursively: object members are merged one by one, and so are elements of
+/// arrays whose length has not changed on either side. Arrays are replaced as a whole otherwise.
+/// Locations changed differently on both sides are reported as conflicts.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{merge3, MergeConflict};
+/// use serde_json::json;
+///
+/// let base = json!({ "title": "Hello", "tags": ["a"], "draft": true });
+/// let left = json!({ "title": "Hello!", "tags": ["a"], "draft": true });
+/// let right = json!({ "title": "Hello", "tags": ["b"] });
+/// assert_eq!(
+///     merge3(&base, &left, &right).unwrap(),
+///     json!({ "title": "Hello!", "tags": ["b"] })
+/// );
+///
+/// let right = json!({ "title": "Hello?", "tags": ["a"] });
+/// assert_eq!(
+///     merge3(&base, &left, &right).unwrap_err(),
+///     vec![MergeConflict {
+///         path: "/title".to_string(),
+///         left: Some(json!("Hello!")),
+///         right: Some(json!("Hello?")),
+///     }]
+/// );
+/// ```
+pub fn merge3(base: &Value, left: &Value, right: &Value) -> Result<Value, Vec<MergeConflict>> {
+    merge3_with_options(base, left, right, &Merge3Options::default())
+}
+
+/// Merge changes made independently to the `base` document in the `left` and the `right`
+/// documents, using provided options. With default options, same as [`merge3`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{merge3_with_options, Merge3Options, MergeStrategy};
+/// use serde_json::json;
+///
+/// let options = Merge3Options {
+///     strategies: [
+///         ("/meta".to_string(), MergeStrategy::Right),
+///         ("/content".to_string(), MergeStrategy::Left),
+///         ("/content/tags".to_string(), MergeStrategy::Union),
+///     ]
+///     .into(),
+///     ..Default::default()
+/// };
+///
+/// let base = json!({ "meta": { "rev": 1 }, "content": { "text": "a", "tags": ["x"] } });
+/// let left = json!({ "meta": { "rev": 2 }, "content": { "text": "b", "tags": ["x", "y"] } });
+/// let right = json!({ "meta": { "rev": 3 }, "content": { "text": "c", "tags": ["z"] } });
+/// assert_eq!(
+///     merge3_with_options(&base, &left, &right, &options).unwrap(),
+///     json!({ "meta": { "rev": 3 }, "content": { "text": "b", "tags": ["y", "z"] } })
+/// );
+/// ```
+pub fn merge3_with_options(
+    base: &Value,
+    left: &Value,
+    right: &Value,
+    options: &Merge3Options,
+) -> Result<Value, Vec<MergeConflict>> {
+    let mut merger = Merger {
+        path: String::new(),
+        conflicts: Vec::new(),
+        options,
+    };
+    // Documents themselves are never removed
+    let merged = merger
+        .merge(Some(base), Some(left), Some(right))
+        .unwrap_or_default();
+    if merger.conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(merger.conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge3, merge3_with_options, Merge3Options, MergeConflict, MergeStrategy};
+    use serde_json::json;
+
+    #[test]
+    fn merge_changes() {
+        let base = json!({ "a": { "b": 1, "c": 2 }, "d": [1, 2], "e": [1], "f": "x" });
+        let left = json!({ "a": { "b": 10, "c": 2 }, "d": [1, 20], "e": [1], "g": 1 });
+        let right = json!({ "a": { "b": 1 }, "d": [10, 2], "e": [1, 2], "g": 1, "h": {} });
+        assert_eq!(
+            merge3(&base, &left, &right).unwrap(),
+            json!({ "a": { "b": 10 }, "d": [10, 20], "e": [1, 2], "g": 1, "h": {} })
+        );
+
+        assert_eq!(
+            merge3(&json!(1), &json!({ "a": 1 }), &json!({ "b": 2 })).unwrap(),
+            json!({ "a": 1, "b": 2 })
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        let base = json!({ "a": { "b": 1 }, "c": [1], "d": 1 });
+        let left = json!({ "a": { "b": 2 }, "c": [1, 2], "d~/": 1 });
+        let right = json!({ "a": {}, "c": [1, 3], "d": 2, "d~/": 2 });
+        assert_eq!(
+            merge3(&base, &left, &right).unwrap_err(),
+            vec![
+                MergeConflict {
+                    path: "/a/b".to_string(),
+                    left: Some(json!(2)),
+                    right: None,
+                },
+                MergeConflict {
+                    path: "/c".to_string(),
+                    left: Some(json!([1, 2])),
+                    right: Some(json!([1, 3])),
+                },
+                MergeConflict {
+                    path: "/d~0~1".to_string(),
+                    left: Some(json!(1)),
+                    right: Some(json!(2)),
+                },
+                MergeConflict {
+                    path: "/d".to_string(),
+                    left: None,
+                    right: Some(json!(2)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn strategies() {
+        let base = json!({ "a": { "b": 1, "c": 1 }, "d": [1, 2, 3], "e": 1, "f": { "g": 1 } });
+        let left = json!({ "a": { "b": 2 }, "d": [1, 3, 4], "e": 2, "f": { "g": 2 } });
+        let right = json!({ "a": { "b": 3, "c": 3 }, "d": [2, 3, 5], "e": 3 });
+        let merge = |strategies: &[(&str, MergeStrategy)]| {
+            let options = Merge3Options {
+                strategies: strategies
+                    .iter()
+                    .map(|(path, strategy)| (path.to_string(), *strategy))
+                    .collect(),
+            };
+            merge3_with_options(&base, &left, &right, &options)
+        };
+
+        assert_eq!(
+            merge(&[("", MergeStrategy::Left)]).unwrap(),
+            json!({ "a": { "b": 2 }, "d": [2, 3, 4], "e": 2, "f": { "g": 2 } })
+        );
+        assert_eq!(
+            merge(&[("", MergeStrategy::Right)]).unwrap(),
+            json!({ "a": { "b": 3, "c": 3 }, "d": [2, 3, 5], "e": 3 })
+        );
+        assert_eq!(
+            merge(&[
+                ("", MergeStrategy::Union),
+                ("/a/b", MergeStrategy::Left),
+                ("/e", MergeStrategy::Right)
+            ])
+            .unwrap(),
+            json!({ "a": { "b": 2, "c": 3 }, "d": [3, 4, 5], "e": 3, "f": { "g": 2 } })
+        );
+
+        // Conflicts of scalars are not resolved by the union
+        let conflicts = merge(&[("", MergeStrategy::Union), ("/a/c", MergeStrategy::Left)]);
+        let paths: Vec<_> = conflicts
+            .unwrap_err()
+            .into_iter()
+            .map(|conflict| conflict.path)
+            .collect();
+        assert_eq!(paths, ["/a/b", "/e"]);
+
+        // Strategies do not apply to the paths which are only prefixes of their strings
+        assert!(merge(&[
+            ("/a/b", MergeStrategy::Left),
+            ("/e", MergeStrategy::Left),
+            ("/", MergeStrategy::Right)
+        ])
+        .is_err());
+    }
+}
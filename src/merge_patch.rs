@@ -0,0 +1,438 @@
+//! [JSON Merge Patch (RFC 7396)](https://tools.ietf.org/html/rfc7396) generation and
+//! application, as used by `application/merge-patch+json` APIs.
+//!
+//! # Example
+//!
+//! ```rust
+//! use json_patch::merge_patch::{merge_apply, merge_diff};
+//! use serde_json::json;
+//!
+//! let left = json!({ "title": "Goodbye!", "author": { "name": "John", "age": 42 } });
+//! let right = json!({ "title": "Hello!", "author": { "name": "John" } });
+//!
+//! let patch = merge_diff(&left, &right);
+//! assert_eq!(patch, json!({ "title": "Hello!", "author": { "age": null } }));
+//!
+//! let mut doc = left.clone();
+//! merge_apply(&mut doc, &patch);
+//! assert_eq!(doc, right);
+//! ```
+use crate::{
+    append_path, unescape, AddOperation, Patch, PatchOperation, RemoveOperation, ReplaceOperation,
+};
+use alloc::{string::String, vec::Vec};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// Generate a merge patch that transforms `left` into `right`.
+///
+/// Merge patches cannot express `null` values inside objects (`null` means "remove the member"),
+/// so object members of `right` having `null` values are omitted from the generated patch. Arrays
+/// are always replaced as a whole.
+pub fn merge_diff(left: &Value, right: &Value) -> Value {
+    match (left, right) {
+        (Value::Object(left), Value::Object(right)) => Value::Object(diff_objects(left, right)),
+        _ => strip_nulls(right),
+    }
+}
+
+fn diff_objects(left: &Map<String, Value>, right: &Map<String, Value>) -> Map<String, Value> {
+    let mut patch = Map::new();
+    for key in left.keys() {
+        if !right.contains_key(key) {
+            patch.insert(key.clone(), Value::Null);
+        }
+    }
+    for (key, value) in right {
+        match left.get(key) {
+            Some(old) if old == value => {}
+            // Member is set to `null`, which cannot be represented, so remove it instead
+            _ if value.is_null() => {
+                if left.contains_key(key) {
+                    patch.insert(key.clone(), Value::Null);
+                }
+            }
+            Some(Value::Object(old)) if value.is_object() => {
+                let nested = diff_objects(old, value.as_object().unwrap());
+                // Skip objects only differing in `null` members of the right one
+                if !nested.is_empty() {
+                    patch.insert(key.clone(), Value::Object(nested));
+                }
+            }
+            _ => {
+                patch.insert(key.clone(), strip_nulls(value));
+            }
+        }
+    }
+    patch
+}
+
+/// Object values in a merge patch are merged into an (absent or non-object) target, so `null`
+/// members have to be dropped for the patch to mean "set this value".
+fn strip_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(obj) => Value::Object(
+            obj.iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), strip_nulls(value)))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Apply the merge patch to the document, as described in
+/// [RFC 7396](https://tools.ietf.org/html/rfc7396#section-2). Same as [`crate::merge`].
+pub fn merge_apply(doc: &mut Value, merge_patch: &Value) {
+    crate::merge(doc, merge_patch)
+}
+
+/// This type represents all possible reasons a JSON patch cannot be converted into a merge patch.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MergePatchErrorKind {
+    /// Operation refers to an array element, while merge patches can only replace arrays as a
+    /// whole.
+    #[error("array elements cannot be addressed")]
+    ArrayIndex,
+    /// `move`, `copy` and `test` operations depend on document contents and cannot be expressed.
+    #[error("operation is not supported")]
+    UnsupportedOperation,
+    /// Operation sets a value to `null`, which means removal in merge patches.
+    #[error("null values cannot be represented")]
+    NullValue,
+    /// `path` JSON pointer is incorrect.
+    #[error("path is invalid")]
+    InvalidPointer,
+    /// `replace` operation with an object value, which merge patches merge into the replaced
+    /// value if it is an object, instead of replacing it.
+    #[error("replacing with objects cannot be represented")]
+    ObjectValue,
+    /// Patch without operations: the empty merge patch (`{}`) is not a no-op, it turns values
+    /// other than objects into empty objects.
+    #[error("empty patches cannot be represented")]
+    EmptyPatch,
+}
+
+/// This type represents all possible errors that can occur when converting JSON patch into a
+/// merge patch.
+#[derive(Debug, Error)]
+#[error("Operation '/{operation}' at path '{path}' cannot be converted: {kind}")]
+#[non_exhaustive]
+pub struct MergePatchError {
+    /// Index of the operation that cannot be converted.
+    pub operation: usize,
+    /// `path` of the operation.
+    pub path: String,
+    /// Kind of the error.
+    pub kind: MergePatchErrorKind,
+}
+
+/// Sets the merge patch member at `path` (a JSON pointer) to the value, creating intermediate
+/// objects.
+fn set_member(
+    merge_patch: &mut Value,
+    path: &str,
+    value: Value,
+) -> Result<(), MergePatchErrorKind> {
+    if path.is_empty() {
+        *merge_patch = value;
+        return Ok(());
+    }
+    let tokens = path
+        .strip_prefix('/')
+        .ok_or(MergePatchErrorKind::InvalidPointer)?;
+    let mut target = merge_patch;
+    let mut tokens = tokens.split('/').peekable();
+    while let Some(token) = tokens.next() {
+        if token == "-" || token.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(MergePatchErrorKind::ArrayIndex);
+        }
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+        let obj = target.as_object_mut().unwrap();
+        let key = unescape(token).into_owned();
+        if tokens.peek().is_none() {
+            obj.insert(key, value);
+            break;
+        }
+        target = obj.entry(key).or_insert(Value::Null);
+    }
+    Ok(())
+}
+
+impl Patch {
+    /// Convert this patch into an equivalent merge patch (RFC 7396).
+    ///
+    /// Only `add`, `replace` and `remove` operations on object members can be converted. Reference
+    /// tokens consisting of digits only (or `-`) are treated as array indices and rejected.
+    ///
+    /// Note that an object value in a merge patch is merged into the existing member rather than
+    /// replacing it, so `add` operations with object values are converted exactly only when the
+    /// target member does not exist or is not an object, as is the case for patches generated by
+    /// [`crate::diff`]. `replace` operations with object values, whose target always exists, and
+    /// empty patches, which have no equivalent merge patch, are rejected.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "replace", "path": "/title", "value": "Hello!" },
+    ///   { "op": "remove", "path": "/author/familyName" }
+    /// ])).unwrap();
+    ///
+    /// assert_eq!(
+    ///     p.try_into_merge_patch().unwrap(),
+    ///     json!({ "title": "Hello!", "author": { "familyName": null } })
+    /// );
+    /// ```
+    pub fn try_into_merge_patch(&self) -> Result<Value, MergePatchError> {
+        if self.is_empty() {
+            return Err(MergePatchError {
+                operation: 0,
+                path: String::new(),
+                kind: MergePatchErrorKind::EmptyPatch,
+            });
+        }
+        let mut merge_patch = Value::Object(Map::new());
+        for (operation, op) in self.iter().enumerate() {
+            let (path, value) = match op {
+                PatchOperation::Replace(ReplaceOperation { path, value }) if value.is_object() => {
+                    (path, Err(MergePatchErrorKind::ObjectValue))
+                }
+                PatchOperation::Add(AddOperation { path, value })
+                | PatchOperation::Replace(ReplaceOperation { path, value }) => {
+                    if has_null(value) {
+                        (path, Err(MergePatchErrorKind::NullValue))
+                    } else {
+                        (path, Ok(value.clone()))
+                    }
+                }
+                PatchOperation::Remove(RemoveOperation { path }) if path.is_empty() => {
+                    (path, Err(MergePatchErrorKind::InvalidPointer))
+                }
+                PatchOperation::Remove(RemoveOperation { path }) => (path, Ok(Value::Null)),
+                PatchOperation::Move(op) => {
+                    (&op.path, Err(MergePatchErrorKind::UnsupportedOperation))
+                }
+                PatchOperation::Copy(op) => {
+                    (&op.path, Err(MergePatchErrorKind::UnsupportedOperation))
+                }
+                PatchOperation::Test(op) => {
+                    (&op.path, Err(MergePatchErrorKind::UnsupportedOperation))
+                }
+            };
+            value
+                .and_then(|value| set_member(&mut merge_patch, path, value))
+                .map_err(|kind| MergePatchError {
+                    operation,
+                    path: path.clone(),
+                    kind,
+                })?;
+        }
+        Ok(merge_patch)
+    }
+
+    /// Convert the merge patch (RFC 7396) into an equivalent JSON patch.
+    ///
+    /// Merge patches do not distinguish adding and replacing members, and removing a missing member
+    /// is not an error for them, unlike JSON patches. Members set to `null` are converted into
+    /// `remove` operations and nested non-empty objects into operations on their members, so the
+    /// resulting patch expects removed members and objects being merged into to exist in the
+    /// document. All other values (including empty objects) are converted into `add` operations.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p = Patch::from_merge_patch(&json!({ "title": "Hello!", "author": { "familyName": null } }));
+    /// assert_eq!(p, from_value(json!([
+    ///   { "op": "add", "path": "/title", "value": "Hello!" },
+    ///   { "op": "remove", "path": "/author/familyName" }
+    /// ])).unwrap());
+    /// ```
+    pub fn from_merge_patch(merge_patch: &Value) -> Patch {
+        let mut ops = Vec::new();
+        let mut path = String::new();
+        match merge_patch {
+            Value::Object(obj) => from_members(obj, &mut path, &mut ops),
+            value => ops.push(PatchOperation::Replace(ReplaceOperation {
+                path,
+                value: value.clone(),
+            })),
+        }
+        Patch(ops)
+    }
+}
+
+/// Whether the value is `null` or is an object with a `null` member somewhere inside it.
+fn has_null(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Object(obj) => obj.values().any(has_null),
+        _ => false,
+    }
+}
+
+fn from_members(obj: &Map<String, Value>, path: &mut String, ops: &mut Vec<PatchOperation>) {
+    for (key, value) in obj {
+        let len = path.len();
+        path.push('/');
+        append_path(path, key);
+        match value {
+            Value::Null => ops.push(PatchOperation::Remove(RemoveOperation {
+                path: path.clone(),
+            })),
+            Value::Object(obj) if !obj.is_empty() => from_members(obj, path, ops),
+            value => ops.push(PatchOperation::Add(AddOperation {
+                path: path.clone(),
+                value: value.clone(),
+            })),
+        }
+        path.truncate(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_apply, merge_diff, MergePatchErrorKind};
+    use crate::Patch;
+    use serde_json::{from_value, json, Value};
+
+    fn check_round_trip(left: Value, right: Value) {
+        let patch = merge_diff(&left, &right);
+        let mut doc = left.clone();
+        merge_apply(&mut doc, &patch);
+        assert_eq!(doc, right, "{}", patch);
+    }
+
+    #[test]
+    fn round_trip() {
+        check_round_trip(json!({}), json!({}));
+        check_round_trip(json!({ "a": 1 }), json!({ "b": [1, 2] }));
+        check_round_trip(
+            json!({ "a": { "b": { "c": 1, "d": 2 } }, "e": [1] }),
+            json!({ "a": { "b": { "c": 1, "x": {} } }, "e": [2] }),
+        );
+        check_round_trip(json!({ "a": 1 }), json!({ "a": { "b": 2 } }));
+        check_round_trip(json!([1]), json!({ "a": 1 }));
+        check_round_trip(json!({ "a": 1 }), json!("text"));
+    }
+
+    #[test]
+    fn minimal_patch() {
+        assert_eq!(
+            merge_diff(
+                &json!({ "a": { "b": 1 }, "c": 2, "d": 3 }),
+                &json!({ "a": { "b": 1 }, "c": 3 })
+            ),
+            json!({ "c": 3, "d": null })
+        );
+    }
+
+    #[test]
+    fn nulls_are_dropped() {
+        assert_eq!(
+            merge_diff(&json!({ "a": 1 }), &json!({ "a": null, "b": null })),
+            json!({ "a": null })
+        );
+        assert_eq!(
+            merge_diff(&json!(1), &json!({ "a": { "b": null, "c": 1 } })),
+            json!({ "a": { "c": 1 } })
+        );
+    }
+
+    #[test]
+    fn from_merge_patch() {
+        let left = json!({ "a": { "b": 1, "c": 2 }, "d": [1], "e": "x", "g": {} });
+        let right = json!({ "a": { "b": 1, "f": {} }, "d": [2], "g": { "h": 1 } });
+        let merge_patch = merge_diff(&left, &right);
+        let p = Patch::from_merge_patch(&merge_patch);
+        assert_eq!(
+            p,
+            from_value(json!([
+                { "op": "remove", "path": "/e" },
+                { "op": "remove", "path": "/a/c" },
+                { "op": "add", "path": "/a/f", "value": {} },
+                { "op": "add", "path": "/d", "value": [2] },
+                { "op": "add", "path": "/g/h", "value": 1 },
+            ]))
+            .unwrap()
+        );
+        let mut doc = left;
+        crate::patch(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+        assert_eq!(p.try_into_merge_patch().unwrap(), merge_patch);
+
+        assert_eq!(
+            Patch::from_merge_patch(&json!([null])),
+            from_value(json!([{ "op": "replace", "path": "", "value": [null] }])).unwrap()
+        );
+    }
+
+    #[test]
+    fn into_merge_patch() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a", "value": { "x": 1 } },
+            { "op": "add", "path": "/a/y", "value": [null] },
+            { "op": "remove", "path": "/b~1c/d" },
+            { "op": "replace", "path": "/e", "value": "" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            p.try_into_merge_patch().unwrap(),
+            json!({ "a": { "x": 1, "y": [null] }, "b/c": { "d": null }, "e": "" })
+        );
+    }
+
+    #[test]
+    fn into_merge_patch_errors() {
+        let check = |op: Value, kind: MergePatchErrorKind| {
+            let p: Patch = from_value(json!([{ "op": "remove", "path": "/a" }, op])).unwrap();
+            let err = p.try_into_merge_patch().unwrap_err();
+            assert_eq!(err.operation, 1);
+            assert_eq!(err.kind.to_string(), kind.to_string());
+        };
+        check(
+            json!({ "op": "add", "path": "/a/0", "value": 1 }),
+            MergePatchErrorKind::ArrayIndex,
+        );
+        check(
+            json!({ "op": "remove", "path": "/a/-" }),
+            MergePatchErrorKind::ArrayIndex,
+        );
+        check(
+            json!({ "op": "add", "path": "/a", "value": { "b": null } }),
+            MergePatchErrorKind::NullValue,
+        );
+        check(
+            json!({ "op": "move", "from": "/a", "path": "/b" }),
+            MergePatchErrorKind::UnsupportedOperation,
+        );
+        check(
+            json!({ "op": "test", "path": "/a", "value": 1 }),
+            MergePatchErrorKind::UnsupportedOperation,
+        );
+        check(
+            json!({ "op": "remove", "path": "" }),
+            MergePatchErrorKind::InvalidPointer,
+        );
+        check(
+            json!({ "op": "replace", "path": "/b", "value": { "c": 1 } }),
+            MergePatchErrorKind::ObjectValue,
+        );
+        check(
+            json!({ "op": "replace", "path": "", "value": {} }),
+            MergePatchErrorKind::ObjectValue,
+        );
+
+        let err = Patch(Vec::new()).try_into_merge_patch().unwrap_err();
+        assert!(matches!(err.kind, MergePatchErrorKind::EmptyPatch));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::Patch;
+
+/// Encode the patch into MessagePack. Operations are encoded as maps with the same keys as in the
+/// JSON representation, and values of the operations as the corresponding MessagePack values.
+///
+/// Requires the `msgpack` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{from_msgpack, to_msgpack, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/a", "value": [1, 2.5, "three"] },
+///   { "op": "move", "from": "/a", "path": "/b" },
+/// ])).unwrap();
+///
+/// let bytes = to_msgpack(&p).unwrap();
+/// assert_eq!(from_msgpack(&bytes).unwrap(), p);
+/// ```
+pub fn to_msgpack(patch: &Patch) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    rmp_serde::to_vec_named(patch)
+}
+
+/// Decode the patch encoded into MessagePack by [`to_msgpack`].
+///
+/// Requires the `msgpack` feature.
+pub fn from_msgpack(bytes: &[u8]) -> Result<Patch, rmp_serde::decode::Error> {
+    rmp_serde::from_slice(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_msgpack, to_msgpack};
+    use crate::Patch;
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn round_trip() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "", "value": { "nested": { "deep": [[], {}, [null]] } } },
+            { "op": "remove", "path": "/a~1b/~0c" },
+            { "op": "replace", "path": "/ünïcode", "value": "☕ \u{0} \u{1F600}" },
+            { "op": "move", "from": "/x/0", "path": "/y/-" },
+            { "op": "copy", "from": "/x", "path": "/z" },
+            { "op": "test", "path": "/numbers", "value": [
+                0, -1, 127, 128, -32, -33, 255, 65536,
+                u64::MAX, i64::MIN, 0.5, -1e300, f64::MIN_POSITIVE,
+            ] },
+            { "op": "test", "path": "/scalars", "value": [true, false, null, "", "x"] },
+        ]))
+        .unwrap();
+
+        let bytes = to_msgpack(&p).unwrap();
+        let decoded = from_msgpack(&bytes).unwrap();
+        assert_eq!(decoded, p);
+        // Numbers keep their representation, not just their value
+        assert_eq!(
+            serde_json::to_string(&decoded).unwrap(),
+            serde_json::to_string(&p).unwrap()
+        );
+        assert_eq!(to_msgpack(&decoded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn invalid() {
+        let bytes = to_msgpack(&Patch::default()).unwrap();
+        assert!(from_msgpack(&bytes[..0]).is_err());
+        let bytes = rmp_serde::to_vec_named(&json!([{ "op": "unknown", "path": "" }])).unwrap();
+        assert!(from_msgpack(&bytes).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Negative array indices in JSON pointers (like `/items/-1` for the last element), an extension
+//! of JSON Patch accepted by some implementations, enabled by the `negative-indices` feature.
+//!
+//! Index `-n` refers to the `n`-th element from the end of the array, as it is before the
+//! operation (both pointers of `move` and `copy` operations are resolved before the value is
+//! moved or copied). For `add` operations, the new element is inserted at that index, before the
+//! element found there. Tokens like `-1` are keys as usual in objects.
+use crate::{unescape, PatchErrorKind};
+use alloc::{borrow::Cow, string::String};
+use core::fmt::Write;
+use serde_json::Value;
+
+/// Returns the pointer with negative array indices replaced with the indices they refer to in
+/// the document. Resolution stops at the first location missing from the document, leaving the
+/// rest of the pointer for the operation to fail on.
+pub(crate) fn resolve<'a>(doc: &Value, pointer: &'a str) -> Result<Cow<'a, str>, PatchErrorKind> {
+    // Fast path for pointers without negative indices
+    if !pointer.starts_with('/') || !pointer.contains("/-") {
+        return Ok(Cow::Borrowed(pointer));
+    }
+    let mut resolved = String::with_capacity(pointer.len());
+    let mut current = Some(doc);
+    for token in pointer.split('/').skip(1) {
+        let value = current.take();
+        match value {
+            Some(Value::Array(arr)) => match negative_index(token, arr.len())? {
+                Some(idx) => {
+                    write!(resolved, "/{}", idx).unwrap();
+                    current = arr.get(idx);
+                }
+                None => {
+                    resolved.push('/');
+                    resolved.push_str(token);
+                    current = token.parse::<usize>().ok().and_then(|idx| arr.get(idx));
+                }
+            },
+            Some(Value::Object(obj)) => {
+                resolved.push('/');
+                resolved.push_str(token);
+                current = obj.get(unescape(token).as_ref());
+            }
+            _ => {
+                resolved.push('/');
+                resolved.push_str(token);
+            }
+        }
+    }
+    Ok(Cow::Owned(resolved))
+}
+
+/// Parses the token as a negative index into an array of the given length, returning the
+/// index it refers to, or `None` if the token is not a negative index.
+fn negative_index(token: &str, len: usize) -> Result<Option<usize>, PatchErrorKind> {
+    let Some(digits) = token.strip_prefix('-') else {
+        return Ok(None);
+    };
+    // Leading zeroes are prohibited, as in other indices (`-0` does not refer to any element)
+    if digits.is_empty() || digits.starts_with('0') || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+    match digits.parse::<usize>() {
+        Ok(n) if n <= len => Ok(Some(len - n)),
+        _ => Err(PatchErrorKind::IndexOutOfBounds),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{patch, Patch, PatchErrorKind};
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn negative_indices() {
+        let mut doc = json!({ "items": [1, 2, 3], "-1": { "a": [[4, 5]] } });
+        let p: Patch = from_value(json!([
+            { "op": "test", "path": "/items/-1", "value": 3 },
+            { "op": "replace", "path": "/items/-3", "value": 0 },
+            { "op": "add", "path": "/items/-1", "value": 9 },
+            { "op": "remove", "path": "/-1/a/-1/-2" },
+            { "op": "move", "from": "/items/-1", "path": "/items/0" },
+            { "op": "copy", "from": "/items/-2", "path": "/-1/a/-" },
+        ]))
+        .unwrap();
+        patch(&mut doc, &p).unwrap();
+        assert_eq!(
+            doc,
+            json!({ "items": [3, 0, 2, 9], "-1": { "a": [[5], 2] } })
+        );
+
+        let p: Patch = from_value(json!([
+            { "op": "remove", "path": "/items/0" },
+            { "op": "remove", "path": "/items/-5" },
+        ]))
+        .unwrap();
+        let err = patch(&mut doc, &p).unwrap_err();
+        assert_eq!((err.operation, err.path.as_str()), (1, "/items/-5"));
+        assert!(matches!(err.kind, PatchErrorKind::IndexOutOfBounds));
+        assert_eq!(doc["items"], json!([3, 0, 2, 9]));
+
+        // Not a negative index
+        let p: Patch = from_value(json!([{ "op": "remove", "path": "/items/-0" }])).unwrap();
+        let err = patch(&mut doc, &p).unwrap_err();
+        assert!(matches!(err.kind, PatchErrorKind::InvalidPointer));
+    }
+}
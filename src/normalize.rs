@@ -0,0 +1,661 @@
+use crate::deep::{clone_value, drop_value, values_equal};
+use crate::{
+    append_path, is_prefix, split_last, unescape, AddOperation, Patch, PatchOperation,
+    ReplaceOperation,
+};
+use alloc::{borrow::ToOwned, collections::VecDeque, string::String, vec::Vec};
+use core::slice;
+use serde_json::Value;
+
+/// Re-escapes every reference token of the JSON pointer, so pointers to the same location are
+/// equal. Malformed pointers are returned as is.
+fn canonical(pointer: &str) -> String {
+    let Some(tokens) = pointer.strip_prefix('/') else {
+        return pointer.to_owned();
+    };
+    let mut result = String::with_capacity(pointer.len());
+    for token in tokens.split('/') {
+        result.push('/');
+        append_path(&mut result, &unescape(token));
+    }
+    result
+}
+
+fn canonicalize(op: &PatchOperation) -> PatchOperation {
+    let mut op = op.clone();
+    match &mut op {
+        PatchOperation::Add(op) => op.path = canonical(&op.path),
+        PatchOperation::Remove(op) => op.path = canonical(&op.path),
+        PatchOperation::Replace(op) => op.path = canonical(&op.path),
+        PatchOperation::Move(op) => {
+            op.from = canonical(&op.from);
+            op.path = canonical(&op.path);
+        }
+        PatchOperation::Copy(op) => {
+            op.from = canonical(&op.from);
+            op.path = canonical(&op.path);
+        }
+        PatchOperation::Test(op) => op.path = canonical(&op.path),
+    }
+    op
+}
+
+/// Checks if the operation at `pointer` can shift the array element at `path` (or one of its
+/// ancestors) by inserting or removing an array element before it.
+fn shifts(pointer: &str, path: &str) -> bool {
+    match split_last(pointer) {
+        Ok((parent, last)) => {
+            let index =
+                last == "-" || (!last.is_empty() && last.bytes().all(|b| b.is_ascii_digit()));
+            index && is_prefix(parent, path)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Checks if the operation neither reads nor modifies the value at `path` and does not change
+/// the location it is found at, so the operations on `path` can be reordered with it.
+fn independent(op: &PatchOperation, path: &str) -> bool {
+    let unrelated = |pointer: &str| !is_prefix(pointer, path) && !is_prefix(path, pointer);
+    match op {
+        PatchOperation::Add(op) => unrelated(&op.path) && !shifts(&op.path, path),
+        PatchOperation::Remove(op) => unrelated(&op.path) && !shifts(&op.path, path),
+        PatchOperation::Replace(op) => unrelated(&op.path),
+        PatchOperation::Move(op) => {
+            unrelated(&op.from)
+                && unrelated(&op.path)
+                && !shifts(&op.from, path)
+                && !shifts(&op.path, path)
+        }
+        PatchOperation::Copy(op) => {
+            unrelated(&op.from) && unrelated(&op.path) && !shifts(&op.path, path)
+        }
+        PatchOperation::Test(op) => unrelated(&op.path),
+    }
+}
+
+/// Appends the operation to the normalized operations, combining it with the previous operation
+/// on the same path if possible.
+fn push(ops: &mut Vec<PatchOperation>, op: PatchOperation) {
+    let path = match &op {
+        PatchOperation::Add(op) => &op.path,
+        PatchOperation::Remove(op) => &op.path,
+        PatchOperation::Replace(op) => &op.path,
+        PatchOperation::Move(mov) if mov.from == mov.path => return,
+        _ => {
+            ops.push(op);
+            return;
+        }
+    };
+    let Some(idx) = ops.iter().rposition(|prev| !independent(prev, path)) else {
+        ops.push(op);
+        return;
+    };
+
+    ops[idx] = match (&ops[idx], op) {
+        (PatchOperation::Add(prev), PatchOperation::Replace(op)) if prev.path == op.path => {
+            PatchOperation::Add(AddOperation {
+                path: op.path,
+                value: op.value,
+            })
+        }
+        (PatchOperation::Replace(prev), PatchOperation::Replace(op)) if prev.path == op.path => {
+            PatchOperation::Replace(op)
+        }
+        (PatchOperation::Replace(prev), PatchOperation::Remove(op)) if prev.path == op.path => {
+            PatchOperation::Remove(op)
+        }
+        (PatchOperation::Remove(prev), PatchOperation::Add(op)) if prev.path == op.path => {
+            PatchOperation::Replace(ReplaceOperation {
+                path: op.path,
+                value: op.value,
+            })
+        }
+        // `add` followed by `remove` is kept: if the `add` replaced an existing object member,
+        // the pair removes it
+        (_, op) => {
+            ops.push(op);
+            return;
+        }
+    };
+}
+
+impl Patch {
+    /// Returns an equivalent patch with redundant operations eliminated and JSON pointers
+    /// canonically escaped.
+    ///
+    /// The following operations on the same path are combined, as long as there are no operations
+    /// in between that read or modify the value at that path (or shift elements of the array it
+    /// belongs to):
+    ///
+    /// * `add` or `replace` followed by `replace` turns into the first operation with the new
+    ///   value;
+    /// * `replace` followed by `remove` turns into `remove`;
+    /// * `remove` followed by `add` turns into `replace`.
+    ///
+    /// `add` followed by `remove` is kept, as it removes the object member the `add` replaced, if
+    /// any (see [`crate::minimize`] for dropping it given the document).
+    ///
+    /// Besides, `move` operations with the same `from` and `path` are dropped.
+    ///
+    /// Apart from that, the normalized patch has the same effect on documents the original patch
+    /// applies to, but may apply to documents the original patch fails on.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "replace", "path": "/draft", "value": {} },
+    ///   { "op": "replace", "path": "/title", "value": "Hello" },
+    ///   { "op": "remove", "path": "/draft" },
+    ///   { "op": "replace", "path": "/title", "value": "Hello!" },
+    ///   { "op": "add", "path": "/a~1b/~01", "value": 1 }
+    /// ])).unwrap();
+    ///
+    /// assert_eq!(p.normalize(), from_value(json!([
+    ///   { "op": "remove", "path": "/draft" },
+    ///   { "op": "replace", "path": "/title", "value": "Hello!" },
+    ///   { "op": "add", "path": "/a~1b/~01", "value": 1 }
+    /// ])).unwrap());
+    /// ```
+    pub fn normalize(&self) -> Patch {
+        let mut ops = Vec::with_capacity(self.len());
+        for op in self.iter() {
+            push(&mut ops, canonicalize(op));
+        }
+        Patch(ops)
+    }
+}
+
+/// Returns the JSON pointers the operation reads or modifies.
+fn pointers(op: &PatchOperation) -> [Option<&str>; 2] {
+    match op {
+        PatchOperation::Add(op) => [Some(&op.path), None],
+        PatchOperation::Remove(op) => [Some(&op.path), None],
+        PatchOperation::Replace(op) => [Some(&op.path), None],
+        PatchOperation::Move(op) => [Some(&op.from), Some(&op.path)],
+        PatchOperation::Copy(op) => [Some(&op.from), Some(&op.path)],
+        PatchOperation::Test(op) => [Some(&op.path), None],
+    }
+}
+
+/// Checks if applying the two operations in either order has the same effect on any document.
+pub(crate) fn commute(left: &PatchOperation, right: &PatchOperation) -> bool {
+    let independent_of = |op: &PatchOperation, other: &PatchOperation| {
+        pointers(other)
+            .into_iter()
+            .flatten()
+            .all(|path| independent(op, path))
+    };
+    independent_of(left, right) && independent_of(right, left)
+}
+
+/// Checks if the two patches produce the same result when applied to `doc`.
+///
+/// The patches are equivalent if both of them apply and leave the document equal, or if both of
+/// them fail to apply (possibly with different errors).
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{equivalent, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "a": 1, "b": 2 });
+/// let left: Patch = from_value(json!([
+///   { "op": "remove", "path": "/a" },
+///   { "op": "add", "path": "/c", "value": 1 }
+/// ])).unwrap();
+/// let right: Patch = from_value(json!([
+///   { "op": "move", "from": "/a", "path": "/c" }
+/// ])).unwrap();
+///
+/// assert!(equivalent(&left, &right, &doc));
+/// assert!(!equivalent(&left, &right, &json!({ "a": 2 })));
+/// ```
+pub fn equivalent(a: &Patch, b: &Patch, doc: &Value) -> bool {
+    match (crate::patched(doc, a), crate::patched(doc, b)) {
+        (Ok(left), Ok(right)) => left == right,
+        (Err(_), Err(_)) => true,
+        _ => false,
+    }
+}
+
+/// Checks if the two patches produce the same result on every document, without applying them.
+///
+/// Both patches are [normalized](Patch::normalize) first, and then are considered equivalent if
+/// one can be turned into the other by reordering operations that neither read nor modify each
+/// other's locations (and do not shift array elements the other operation refers to).
+///
+/// The check is conservative: `true` means the patches are equivalent, but `false` only means
+/// that equivalence could not be established without a document. For example, a `move` is not
+/// recognized as equivalent to the corresponding `remove` and `add`, as that depends on the value
+/// being moved. Use [`equivalent`] to compare the patches on a particular document.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{structurally_equivalent, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let left: Patch = from_value(json!([
+///   { "op": "replace", "path": "/a", "value": 1 },
+///   { "op": "add", "path": "/b", "value": 2 },
+///   { "op": "replace", "path": "/a", "value": 3 }
+/// ])).unwrap();
+/// let right: Patch = from_value(json!([
+///   { "op": "add", "path": "/b", "value": 2 },
+///   { "op": "replace", "path": "/a", "value": 3 }
+/// ])).unwrap();
+///
+/// assert!(structurally_equivalent(&left, &right));
+/// ```
+pub fn structurally_equivalent(a: &Patch, b: &Patch) -> bool {
+    let left = a.normalize();
+    let mut right = b.normalize().0;
+    if left.len() != right.len() {
+        return false;
+    }
+    for op in left.0 {
+        let Some(idx) = right.iter().position(|other| *other == op) else {
+            return false;
+        };
+        if !right[..idx].iter().all(|other| commute(other, &op)) {
+            return false;
+        }
+        right.remove(idx);
+    }
+    true
+}
+
+/// Returns the patch without the operations which have no effect on the document, for patches
+/// re-asserting much of the state of the document they are applied to. The patch is
+/// [normalized](Patch::normalize) first, which combines operations on the same path, and then the
+/// following operations are dropped:
+///
+/// * `replace` operations, and `add` and `copy` operations on object members (or on the whole
+///   document), setting the value already there;
+/// * `remove` operations of object members (or of values under missing parents) already absent
+///   from the document, which would fail to apply: the minimized patch applies leniently;
+/// * `move` operations with the same `from` and `path`;
+/// * `add` operations of object members absent from the document, together with the `remove`
+///   operations of the same members following them.
+///
+/// `test` operations are kept. Operations are checked against the document as it is when they
+/// are applied; once an operation fails to apply, the remaining operations are kept as they are.
+/// The minimized patch has the same effect on the document as the original one, apart from the
+/// absent values it does not fail on, but not necessarily on other documents.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{minimize, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "title": "Hello", "tags": ["a"], "draft": true });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/title", "value": "Hello" },
+///   { "op": "add", "path": "/tags/-", "value": "b" },
+///   { "op": "replace", "path": "/draft", "value": false },
+///   { "op": "replace", "path": "/draft", "value": true },
+///   { "op": "remove", "path": "/author" },
+///   { "op": "add", "path": "/tags/0", "value": "a" }
+/// ])).unwrap();
+///
+/// assert_eq!(minimize(&p, &doc), from_value(json!([
+///   { "op": "add", "path": "/tags/-", "value": "b" },
+///   { "op": "add", "path": "/tags/0", "value": "a" }
+/// ])).unwrap());
+/// ```
+pub fn minimize(patch: &[PatchOperation], doc: &Value) -> Patch {
+    let normalized = Patch(patch.to_vec()).normalize();
+    let mut doc = clone_value(doc);
+    let mut ops = Vec::with_capacity(normalized.len());
+    let mut remaining = VecDeque::from(normalized.0);
+    while let Some(op) = remaining.pop_front() {
+        if has_no_effect(&op, &doc) {
+            continue;
+        }
+        if let Some(idx) = cancelling_removal(&op, &remaining, &doc) {
+            remaining.remove(idx);
+            continue;
+        }
+        let applied = crate::patch(&mut doc, slice::from_ref(&op));
+        ops.push(op);
+        if applied.is_err() {
+            break;
+        }
+    }
+    ops.extend(remaining);
+    drop_value(doc);
+    Patch(ops)
+}
+
+/// Checks if the operation would leave the document unchanged, or would only fail because the
+/// value it removes is absent.
+fn has_no_effect(op: &PatchOperation, doc: &Value) -> bool {
+    match op {
+        PatchOperation::Replace(op) => doc
+            .pointer(&op.path)
+            .is_some_and(|current| values_equal(current, &op.value)),
+        PatchOperation::Add(op) => sets_member(doc, &op.path, &op.value),
+        PatchOperation::Copy(op) => doc
+            .pointer(&op.from)
+            .is_some_and(|value| sets_member(doc, &op.path, value)),
+        PatchOperation::Move(op) => op.from == op.path,
+        PatchOperation::Remove(op) => match split_last(&op.path) {
+            Ok((parent, last)) => match doc.pointer(parent) {
+                Some(Value::Object(obj)) => !obj.contains_key(unescape(last).as_ref()),
+                Some(_) => false,
+                None => parent.starts_with('/'),
+            },
+            Err(_) => false,
+        },
+        PatchOperation::Test(_) => false,
+    }
+}
+
+/// Index of the following `remove` operation of the object member added by the `add` operation,
+/// if the member is absent from the document, so the pair has no effect. Operations in between
+/// must neither read nor modify the member.
+fn cancelling_removal(
+    op: &PatchOperation,
+    following: &VecDeque<PatchOperation>,
+    doc: &Value,
+) -> Option<usize> {
+    let PatchOperation::Add(add) = op else {
+        return None;
+    };
+    let (parent, last) = split_last(&add.path).ok()?;
+    match doc.pointer(parent)? {
+        Value::Object(obj) if !obj.contains_key(unescape(last).as_ref()) => {}
+        _ => return None,
+    }
+    let idx = following
+        .iter()
+        .position(|next| !independent(next, &add.path))?;
+    match &following[idx] {
+        PatchOperation::Remove(remove) if remove.path == add.path => Some(idx),
+        _ => None,
+    }
+}
+
+/// Checks if the object member (or the whole document) at the path is already set to the value.
+fn sets_member(doc: &Value, path: &str, value: &Value) -> bool {
+    if path.is_empty() {
+        return values_equal(doc, value);
+    }
+    let Ok((parent, last)) = split_last(path) else {
+        return false;
+    };
+    match doc.pointer(parent) {
+        Some(Value::Object(obj)) => obj
+            .get(unescape(last).as_ref())
+            .is_some_and(|current| values_equal(current, value)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Patch;
+    use serde_json::{from_value, json, Value};
+
+    fn check_normalize(doc: Value, p: Value, expected: Value) {
+        let p: Patch = from_value(p).unwrap();
+        let normalized = p.normalize();
+        assert_eq!(normalized, from_value(expected).unwrap());
+
+        let mut left = doc.clone();
+        crate::patch(&mut left, &p).unwrap();
+        let mut right = doc;
+        crate::patch(&mut right, &normalized).unwrap();
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn minimize() {
+        let doc = json!({ "a": { "b": 1 }, "c": [1, 1], "d": 1 });
+        let p: Patch = from_value(json!([
+            { "op": "copy", "from": "/d", "path": "/a/b" },
+            { "op": "move", "from": "/a", "path": "/a" },
+            { "op": "add", "path": "/c/0", "value": 1 },
+            { "op": "remove", "path": "/x/y" },
+            { "op": "test", "path": "/d", "value": 1 },
+            { "op": "replace", "path": "", "value": { "a": { "b": 1 }, "c": [1, 1, 1], "d": 1 } },
+            { "op": "remove", "path": "/c/5" },
+            { "op": "replace", "path": "/d", "value": 1 },
+        ]))
+        .unwrap();
+        assert_eq!(
+            crate::minimize(&p, &doc),
+            from_value::<Patch>(json!([
+                { "op": "add", "path": "/c/0", "value": 1 },
+                { "op": "test", "path": "/d", "value": 1 },
+                { "op": "remove", "path": "/c/5" },
+                { "op": "replace", "path": "/d", "value": 1 },
+            ]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn minimize_added_and_removed() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/y", "value": null },
+            { "op": "replace", "path": "/a", "value": 2 },
+            { "op": "remove", "path": "/y" },
+        ]))
+        .unwrap();
+        // The pair removes the existing member
+        let doc = json!({ "a": 1, "y": 1 });
+        let minimized = crate::minimize(&p, &doc);
+        assert_eq!(minimized, p);
+        assert!(crate::equivalent(&minimized, &p, &doc));
+
+        let doc = json!({ "a": 1 });
+        let minimized = crate::minimize(&p, &doc);
+        assert_eq!(
+            minimized,
+            from_value::<Patch>(json!([{ "op": "replace", "path": "/a", "value": 2 }])).unwrap()
+        );
+        assert!(crate::equivalent(&minimized, &p, &doc));
+    }
+
+    #[test]
+    fn combine_operations() {
+        check_normalize(
+            json!({ "a": 1, "b": [1, 2, 3] }),
+            json!([
+                { "op": "replace", "path": "/a", "value": 2 },
+                { "op": "add", "path": "/b/1", "value": 4 },
+                { "op": "replace", "path": "/a", "value": 3 },
+                { "op": "replace", "path": "/b/1", "value": 5 },
+                { "op": "remove", "path": "/b/0" },
+                { "op": "add", "path": "/b/0", "value": 6 },
+                { "op": "add", "path": "/c", "value": 7 },
+                { "op": "remove", "path": "/c" },
+                { "op": "replace", "path": "/a", "value": 8 },
+                { "op": "move", "from": "/a", "path": "/a" },
+            ]),
+            json!([
+                { "op": "replace", "path": "/a", "value": 8 },
+                { "op": "add", "path": "/b/1", "value": 5 },
+                { "op": "replace", "path": "/b/0", "value": 6 },
+                { "op": "add", "path": "/c", "value": 7 },
+                { "op": "remove", "path": "/c" },
+            ]),
+        );
+
+        // Adding an existing member replaces it, so the pair removes it
+        check_normalize(
+            json!({ "y": 1 }),
+            json!([
+                { "op": "add", "path": "/y", "value": null },
+                { "op": "remove", "path": "/y" },
+            ]),
+            json!([
+                { "op": "add", "path": "/y", "value": null },
+                { "op": "remove", "path": "/y" },
+            ]),
+        );
+    }
+
+    #[test]
+    fn keep_dependent_operations() {
+        check_normalize(
+            json!({ "a": { "x": 0 }, "b": [1, 2] }),
+            json!([
+                { "op": "replace", "path": "/a/x", "value": 1 },
+                { "op": "test", "path": "/a", "value": { "x": 1 } },
+                { "op": "replace", "path": "/a/x", "value": 2 },
+                { "op": "remove", "path": "/b/0" },
+                { "op": "add", "path": "/b/0", "value": 3 },
+                { "op": "remove", "path": "/b/1" },
+            ]),
+            json!([
+                { "op": "replace", "path": "/a/x", "value": 1 },
+                { "op": "test", "path": "/a", "value": { "x": 1 } },
+                { "op": "replace", "path": "/a/x", "value": 2 },
+                { "op": "replace", "path": "/b/0", "value": 3 },
+                { "op": "remove", "path": "/b/1" },
+            ]),
+        );
+
+        // Insertion shifts the element being replaced
+        check_normalize(
+            json!([1, 2]),
+            json!([
+                { "op": "replace", "path": "/1", "value": 3 },
+                { "op": "add", "path": "/0", "value": 4 },
+                { "op": "replace", "path": "/1", "value": 5 },
+            ]),
+            json!([
+                { "op": "replace", "path": "/1", "value": 3 },
+                { "op": "add", "path": "/0", "value": 4 },
+                { "op": "replace", "path": "/1", "value": 5 },
+            ]),
+        );
+    }
+
+    #[test]
+    fn canonical_escaping() {
+        let p: Patch = from_value(json!([
+            { "op": "copy", "from": "/~2", "path": "/a~1b~0" },
+            { "op": "remove", "path": "invalid" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            p.normalize(),
+            from_value(json!([
+                { "op": "copy", "from": "/~02", "path": "/a~1b~0" },
+                { "op": "remove", "path": "invalid" },
+            ]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn equivalence() {
+        let patch = |p: Value| -> Patch { from_value(p).unwrap() };
+        let doc = json!({ "a": { "x": 1 }, "b": [1, 2, 3] });
+
+        // Independent operations may be reordered
+        let left = patch(json!([
+            { "op": "replace", "path": "/a/x", "value": 2 },
+            { "op": "remove", "path": "/b/0" },
+            { "op": "add", "path": "/c", "value": 3 },
+        ]));
+        let right = patch(json!([
+            { "op": "add", "path": "/c", "value": 3 },
+            { "op": "remove", "path": "/b/0" },
+            { "op": "replace", "path": "/a/x", "value": 2 },
+        ]));
+        assert!(crate::structurally_equivalent(&left, &right));
+        assert!(crate::equivalent(&left, &right, &doc));
+
+        // Removing array elements shifts the following ones
+        let left = patch(json!([
+            { "op": "remove", "path": "/b/0" },
+            { "op": "remove", "path": "/b/1" },
+        ]));
+        let right = patch(json!([
+            { "op": "remove", "path": "/b/1" },
+            { "op": "remove", "path": "/b/0" },
+        ]));
+        assert!(!crate::structurally_equivalent(&left, &right));
+        assert!(!crate::equivalent(&left, &right, &doc));
+
+        // Reading a location orders the operations modifying it
+        let left = patch(json!([
+            { "op": "test", "path": "/a", "value": { "x": 1 } },
+            { "op": "replace", "path": "/a/x", "value": 2 },
+        ]));
+        let right = patch(json!([
+            { "op": "replace", "path": "/a/x", "value": 2 },
+            { "op": "test", "path": "/a", "value": { "x": 1 } },
+        ]));
+        assert!(!crate::structurally_equivalent(&left, &right));
+        assert!(!crate::equivalent(&left, &right, &doc));
+
+        // Only the document tells a move from a removal and an addition
+        let left = patch(json!([{ "op": "move", "from": "/a", "path": "/d" }]));
+        let right = patch(json!([
+            { "op": "remove", "path": "/a" },
+            { "op": "add", "path": "/d", "value": { "x": 1 } },
+        ]));
+        assert!(!crate::structurally_equivalent(&left, &right));
+        assert!(crate::equivalent(&left, &right, &doc));
+        assert!(!crate::equivalent(&left, &right, &json!({ "a": 1 })));
+        assert!(crate::equivalent(&left, &right, &json!({})));
+
+        // Adding an existing member replaces it, so the pair is not a no-op
+        let pair = patch(json!([
+            { "op": "add", "path": "/y", "value": null },
+            { "op": "remove", "path": "/y" },
+        ]));
+        assert!(!crate::structurally_equivalent(&pair, &patch(json!([]))));
+        assert!(!crate::equivalent(
+            &pair,
+            &patch(json!([])),
+            &json!({ "y": 1 })
+        ));
+
+        assert!(crate::structurally_equivalent(
+            &patch(json!([])),
+            &patch(json!([]))
+        ));
+        assert!(!crate::structurally_equivalent(
+            &left,
+            &patch(json!([{ "op": "move", "from": "/a", "path": "/e" }]))
+        ));
+    }
+
+    #[cfg(feature = "diff")]
+    #[test]
+    fn diff_strategies() {
+        use crate::diff::{DiffOptions, OpOrder};
+
+        let left = json!({ "a": { "k": [1, 2] }, "b": 1, "c": [1, 2, 3], "d": "x" });
+        let right = json!({ "a": {}, "b": 2, "c": [1, 3], "e": "x", "f": { "k": [1, 2] } });
+        let document = crate::diff(&left, &right);
+        for op_order in [OpOrder::SortedByPath, OpOrder::RemovesLast] {
+            let options = DiffOptions {
+                op_order,
+                ..Default::default()
+            };
+            let reordered = crate::diff_with_options(&left, &right, &options);
+            assert!(crate::structurally_equivalent(&document, &reordered));
+        }
+
+        let options = DiffOptions {
+            detect_moves: true,
+            ..Default::default()
+        };
+        let moves = crate::diff_with_options(&left, &right, &options);
+        assert!(crate::equivalent(&document, &moves, &left));
+    }
+}
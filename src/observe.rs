@@ -0,0 +1,149 @@
+use crate::invert::resolve_appended;
+use crate::{apply_patches, revert, PatchError, PatchOperation};
+use alloc::vec::Vec;
+use serde_json::Value;
+
+/// Observer of the operations applied by [`patch_with_observer`].
+///
+/// Both callbacks receive the operation (its target location is given by
+/// [`PatchOperation::path`]) and the value at that location before the operation is applied.
+/// All the methods do nothing by default.
+pub trait PatchObserver {
+    /// Called before the operation is applied. `old` is the value at the `path` of the operation,
+    /// or `None` if there is no such value yet.
+    fn before_op(&mut self, op: &PatchOperation, old: Option<&Value>) {
+        let _ = (op, old);
+    }
+
+    /// Called after the operation is successfully applied. `old` is the value at the `path` of the
+    /// operation before it was applied and `new` is the value at that location after it, `None`
+    /// if there is no value (for example, after a `remove` operation). For elements appended with
+    /// the `-` token, `new` is the appended element.
+    fn after_op(&mut self, op: &PatchOperation, old: Option<&Value>, new: Option<&Value>) {
+        let _ = (op, old, new);
+    }
+}
+
+/// Patch provided JSON document (given as `serde_json::Value`) in-place, same as
+/// [`patch`](crate::patch), notifying the observer about every operation applied.
+///
+/// If any of the operations fails, all previous operations are reverted, but the observer has
+/// already been notified about them. The failed operation is only passed to
+/// [`PatchObserver::before_op`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_with_observer, Patch, PatchObserver, PatchOperation};
+/// use serde_json::{from_value, json, Value};
+///
+/// #[derive(Default)]
+/// struct Audit(Vec<String>);
+///
+/// impl PatchObserver for Audit {
+///     fn after_op(&mut self, op: &PatchOperation, old: Option<&Value>, new: Option<&Value>) {
+///         self.0.push(format!("{} {}: {:?} -> {:?}", op.op_kind(), op.path(), old, new));
+///     }
+/// }
+///
+/// let mut doc = json!({ "name": "Andrew" });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/name", "value": "Maxim" },
+///   { "op": "add", "path": "/happy", "value": true }
+/// ])).unwrap();
+///
+/// let mut audit = Audit::default();
+/// patch_with_observer(&mut doc, &p, &mut audit).unwrap();
+/// assert_eq!(audit.0, [
+///   r#"replace /name: Some(String("Andrew")) -> Some(String("Maxim"))"#,
+///   "add /happy: None -> Some(Bool(true))",
+/// ]);
+/// ```
+pub fn patch_with_observer<O>(
+    doc: &mut Value,
+    patch: &[PatchOperation],
+    observer: &mut O,
+) -> Result<(), PatchError>
+where
+    O: PatchObserver + ?Sized,
+{
+    let mut undo_stack = Vec::with_capacity(patch.len());
+    for (operation, op) in patch.iter().enumerate() {
+        let path = op.path();
+        let old = doc.pointer(path).cloned();
+        observer.before_op(op, old.as_ref());
+        if let Err(mut e) = apply_patches(doc, core::slice::from_ref(op), Some(&mut undo_stack)) {
+            revert(doc, undo_stack);
+            e.operation = operation;
+            return Err(e);
+        }
+        let new = match resolve_appended(doc, path) {
+            Some(appended) => doc.pointer(&appended),
+            None => doc.pointer(path),
+        };
+        observer.after_op(op, old.as_ref(), new);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{patch_with_observer, PatchObserver};
+    use crate::{Patch, PatchOperation};
+    use serde_json::{from_value, json, Value};
+
+    #[derive(Default)]
+    struct Recorder(Vec<(String, Option<Value>, Option<Value>)>, usize);
+
+    impl PatchObserver for Recorder {
+        fn before_op(&mut self, _op: &PatchOperation, _old: Option<&Value>) {
+            self.1 += 1;
+        }
+
+        fn after_op(&mut self, op: &PatchOperation, old: Option<&Value>, new: Option<&Value>) {
+            self.0
+                .push((op.path().to_owned(), old.cloned(), new.cloned()));
+        }
+    }
+
+    #[test]
+    fn observe_operations() {
+        let mut doc = json!({ "a": [1], "b": 2 });
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a/-", "value": 3 },
+            { "op": "remove", "path": "/b" },
+            { "op": "move", "from": "/a/0", "path": "/c" },
+            { "op": "test", "path": "/c", "value": 1 },
+        ]))
+        .unwrap();
+        let mut recorder = Recorder::default();
+        patch_with_observer(&mut doc, &p, &mut recorder).unwrap();
+        assert_eq!(doc, json!({ "a": [3], "c": 1 }));
+        assert_eq!(recorder.1, 4);
+        assert_eq!(
+            recorder.0,
+            [
+                ("/a/-".to_owned(), None, Some(json!(3))),
+                ("/b".to_owned(), Some(json!(2)), None),
+                ("/c".to_owned(), None, Some(json!(1))),
+                ("/c".to_owned(), Some(json!(1)), Some(json!(1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn observe_failure() {
+        let mut doc = json!({ "a": 1 });
+        let p: Patch = from_value(json!([
+            { "op": "replace", "path": "/a", "value": 2 },
+            { "op": "remove", "path": "/b" },
+        ]))
+        .unwrap();
+        let mut recorder = Recorder::default();
+        let err = patch_with_observer(&mut doc, &p, &mut recorder).unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert_eq!(doc, json!({ "a": 1 }));
+        assert_eq!(recorder.1, 2);
+        assert_eq!(recorder.0.len(), 1);
+    }
+}
@@ -0,0 +1,564 @@
+use crate::deep::values_equal_by;
+use crate::estimate::{operation_delta, serialized_len};
+use crate::pointer::check_pointer;
+use crate::trace;
+use crate::{
+    apply_operation, apply_patches, is_prefix, revert, test_failed, test_not_found,
+    translate_error, unescape, OpKind, PatchError, PatchErrorKind, PatchOperation,
+    PatchValidationError, Undo,
+};
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+use serde_json::Value;
+
+/// Options restricting which patches can be applied by [`patch_with_options`] (and checked by
+/// [`validate_with_options`]), to guard against untrusted patches.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_with_options, Patch, PatchErrorKind, PatchOptions};
+/// use serde_json::{from_value, json};
+///
+/// let options = PatchOptions {
+///     protected_paths: vec!["/id".into(), "/meta/created".into()],
+///     ..Default::default()
+/// };
+///
+/// let mut doc = json!({ "id": 1, "meta": { "created": "2023-01-01" } });
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/name", "value": "Andrew" },
+///   { "op": "move", "from": "/meta", "path": "/info" }
+/// ])).unwrap();
+///
+/// let err = patch_with_options(&mut doc, &p, &options).unwrap_err();
+/// assert_eq!(err.operation, 1);
+/// assert!(matches!(err.kind, PatchErrorKind::ProtectedPath));
+/// assert_eq!(doc, json!({ "id": 1, "meta": { "created": "2023-01-01" } }));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PatchOptions {
+    /// JSON Pointers of read-only locations. Operations modifying a protected location, a location
+    /// nested under it or one of its ancestors are rejected, and so are `move` and `copy`
+    /// operations taking their value from such a location. `test` operations are allowed.
+    ///
+    /// Pointers are compared as written, so array indices in protected paths do not follow the
+    /// elements shifted by the patch.
+    pub protected_paths: Vec<String>,
+    /// Kinds of operations permitted in the patch, all kinds are permitted if `None`.
+    pub allowed_ops: Option<BTreeSet<OpKind>>,
+    /// Maximum number of operations in the patch.
+    pub max_operations: Option<usize>,
+    /// Maximum size in bytes of the compact JSON serialization of the value of any `add` or
+    /// `replace` operation.
+    pub max_value_size: Option<usize>,
+    /// Maximum number of bytes by which the compact JSON serialization of the document can grow
+    /// while the patch is applied. The growth is tracked operation by operation, so it also
+    /// limits the amplification by `copy` operations duplicating large values.
+    pub max_growth: Option<usize>,
+    /// Maximum number of reference tokens in the `path` and `from` JSON pointers.
+    pub max_pointer_depth: Option<usize>,
+    /// Reject the JSON pointers violating RFC 6901 which are otherwise accepted: invalid `~`
+    /// escape sequences, array indices with leading zeros (also in `test` operations and `from`
+    /// pointers, which otherwise report them as not found), and array-index-like tokens (like
+    /// `0`) addressing object members. Pointers are checked against the document as it is before
+    /// each operation.
+    pub strict: bool,
+    /// Tolerance for comparing numbers in `test` operations: numbers which differ by no more
+    /// than the tolerance are equal, so values with floating-point numbers which went through
+    /// serialization round trips in other languages still pass. By default, numbers are compared
+    /// exactly.
+    pub test_tolerance: Option<NumberTolerance>,
+}
+
+/// Tolerance for comparing numbers, see [`PatchOptions::test_tolerance`] and
+/// `DiffOptions::number_tolerance`. Two numbers are considered equal if the difference between
+/// them does not exceed either of the tolerances.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NumberTolerance {
+    /// Maximum absolute difference between the numbers.
+    pub absolute: f64,
+    /// Maximum difference between the numbers, relative to the largest of their magnitudes.
+    pub relative: f64,
+}
+
+impl NumberTolerance {
+    /// Checks if two numbers are within the tolerance.
+    pub fn within(&self, left: f64, right: f64) -> bool {
+        let delta = (left - right).abs();
+        delta <= self.absolute || delta <= self.relative * left.abs().max(right.abs())
+    }
+}
+
+impl PatchOptions {
+    /// Check the operations of the patch against the options, without applying them.
+    fn check(&self, patch: &[PatchOperation]) -> Result<(), PatchError> {
+        let result = patch
+            .iter()
+            .enumerate()
+            .try_for_each(|(operation, op)| self.check_operation(operation, op));
+        if let Err(e) = &result {
+            trace::failure!(e);
+        }
+        result
+    }
+
+    /// Check the operation at the given index of the patch against the options.
+    fn check_operation(&self, operation: usize, op: &PatchOperation) -> Result<(), PatchError> {
+        let kind = if self.max_operations.is_some_and(|max| operation >= max) {
+            PatchErrorKind::TooManyOperations
+        } else if self
+            .allowed_ops
+            .as_ref()
+            .is_some_and(|allowed| !allowed.contains(&op.op_kind()))
+        {
+            PatchErrorKind::OperationNotAllowed
+        } else if self.is_protected(op) {
+            PatchErrorKind::ProtectedPath
+        } else if self.max_value_size.is_some_and(|max| {
+            matches!(op, PatchOperation::Add(_) | PatchOperation::Replace(_))
+                && op.value().is_some_and(|value| serialized_len(value) > max)
+        }) {
+            PatchErrorKind::ValueTooLarge
+        } else if self.max_pointer_depth.is_some_and(|max| {
+            let depth = |pointer: &str| pointer.matches('/').count();
+            depth(op.path()) > max || op.from().is_some_and(|from| depth(from) > max)
+        }) {
+            PatchErrorKind::PointerTooDeep
+        } else {
+            return Ok(());
+        };
+        Err(translate_error(kind, operation, op.path()))
+    }
+
+    /// Add the growth of the document caused by the operation to `growth`, checking it against
+    /// the limit.
+    fn check_growth(
+        &self,
+        doc: &Value,
+        operation: usize,
+        op: &PatchOperation,
+        growth: &mut i64,
+    ) -> Result<(), PatchError> {
+        let Some(max) = self.max_growth else {
+            return Ok(());
+        };
+        let grown = *growth + operation_delta(doc, op);
+        if grown > i64::try_from(max).unwrap_or(i64::MAX) {
+            return Err(translate_error(
+                PatchErrorKind::GrowthLimitExceeded,
+                operation,
+                op.path(),
+            ));
+        }
+        *growth = grown;
+        Ok(())
+    }
+
+    /// Check the pointers of the operation against the document, in strict mode.
+    fn check_strict(
+        &self,
+        doc: &Value,
+        operation: usize,
+        op: &PatchOperation,
+    ) -> Result<(), PatchError> {
+        if !self.strict {
+            return Ok(());
+        }
+        let kind = if !is_strict_pointer(doc, op.path()) {
+            PatchErrorKind::InvalidPointer
+        } else if op.from().is_some_and(|from| !is_strict_pointer(doc, from)) {
+            PatchErrorKind::InvalidFromPointer
+        } else {
+            return Ok(());
+        };
+        Err(translate_error(kind, operation, op.path()))
+    }
+
+    /// Apply the operation, comparing numbers within the tolerance if it is a `test` operation.
+    fn apply(
+        &self,
+        doc: &mut Value,
+        operation: usize,
+        op: &PatchOperation,
+        undo_stack: Option<&mut Vec<Undo>>,
+    ) -> Result<(), PatchError> {
+        let (PatchOperation::Test(test), Some(tolerance)) = (op, &self.test_tolerance) else {
+            return match undo_stack {
+                Some(undo_stack) => apply_patches(doc, core::slice::from_ref(op), Some(undo_stack))
+                    .map_err(|mut e| {
+                        e.operation = operation;
+                        e
+                    }),
+                None => apply_operation(doc, operation, op),
+            };
+        };
+        let target = doc.pointer(&test.path).ok_or_else(|| {
+            translate_error(
+                test_not_found(&test.value, &test.path),
+                operation,
+                &test.path,
+            )
+        })?;
+        let equal = values_equal_by(target, &test.value, |l, r| {
+            l == r
+                || match (l.as_f64(), r.as_f64()) {
+                    (Some(l), Some(r)) => tolerance.within(l, r),
+                    _ => false,
+                }
+        });
+        if equal {
+            Ok(())
+        } else {
+            let kind = test_failed(&test.value, Some(target));
+            Err(translate_error(kind, operation, &test.path))
+        }
+    }
+
+    fn is_protected(&self, op: &PatchOperation) -> bool {
+        let from = match op {
+            PatchOperation::Test(_) => return false,
+            PatchOperation::Move(op) => Some(op.from.as_str()),
+            PatchOperation::Copy(op) => Some(op.from.as_str()),
+            _ => None,
+        };
+        self.is_protected_path(op.path()) || from.is_some_and(|from| self.is_protected_path(from))
+    }
+
+    fn is_protected_path(&self, path: &str) -> bool {
+        self.protected_paths
+            .iter()
+            .any(|protected| is_prefix(protected, path) || is_prefix(path, protected))
+    }
+}
+
+/// Returns whether the pointer complies with RFC 6901 when resolved against the document, as far
+/// as it resolves.
+fn is_strict_pointer(doc: &Value, pointer: &str) -> bool {
+    if check_pointer(pointer).is_err() {
+        return false;
+    }
+    let is_index = |token: &str| {
+        !token.is_empty()
+            && token.bytes().all(|b| b.is_ascii_digit())
+            && (token == "0" || !token.starts_with('0'))
+    };
+    let mut target = doc;
+    for token in pointer.split('/').skip(1) {
+        target = match target {
+            Value::Object(_) if is_index(token) => return false,
+            Value::Object(members) => match members.get(&*unescape(token)) {
+                Some(member) => member,
+                None => return true,
+            },
+            Value::Array(_) if token != "-" && !is_index(token) => return false,
+            Value::Array(elements) => {
+                match token.parse().ok().and_then(|idx: usize| elements.get(idx)) {
+                    Some(element) => element,
+                    None => return true,
+                }
+            }
+            _ => return true,
+        };
+    }
+    true
+}
+
+/// Patch provided JSON document (given as `serde_json::Value`) in-place, same as
+/// [`patch`](crate::patch), rejecting the patch if any of its operations is not permitted by the
+/// options. Rejected patches are not applied at all.
+pub fn patch_with_options(
+    doc: &mut Value,
+    patch: &[PatchOperation],
+    options: &PatchOptions,
+) -> Result<(), PatchError> {
+    options.check(patch)?;
+    if options.max_growth.is_none() && !options.strict && options.test_tolerance.is_none() {
+        return crate::patch(doc, patch);
+    }
+
+    trace::span!("patch_with_options", operations = patch.len());
+    let mut growth = 0;
+    let mut undo_stack = Vec::with_capacity(patch.len());
+    for (operation, op) in patch.iter().enumerate() {
+        let result = options
+            .check_strict(doc, operation, op)
+            .and_then(|()| options.check_growth(doc, operation, op, &mut growth))
+            .and_then(|()| options.apply(doc, operation, op, Some(&mut undo_stack)));
+        if let Err(e) = result {
+            trace::failure!(e);
+            trace::span!("revert", operations = undo_stack.len());
+            revert(doc, undo_stack);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Same as [`validate`](crate::validate), but also reports the operations which are not permitted
+/// by the options. Such operations are skipped when checking the rest of the patch.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{validate_with_options, OpKind, Patch, PatchErrorKind, PatchOptions};
+/// use serde_json::{from_value, json};
+///
+/// let options = PatchOptions {
+///     allowed_ops: Some([OpKind::Add, OpKind::Remove, OpKind::Replace, OpKind::Test].into()),
+///     max_operations: Some(2),
+///     ..Default::default()
+/// };
+///
+/// let doc = json!({ "a": 1 });
+/// let p: Patch = from_value(json!([
+///   { "op": "copy", "from": "/a", "path": "/b" },
+///   { "op": "remove", "path": "/b" },
+///   { "op": "add", "path": "/c", "value": 2 }
+/// ])).unwrap();
+///
+/// let errors = validate_with_options(&doc, &p, &options).unwrap_err();
+/// assert!(matches!(errors[0].kind, PatchErrorKind::OperationNotAllowed));
+/// assert!(matches!(errors[1].kind, PatchErrorKind::PathNotFound));
+/// assert!(matches!(errors[2].kind, PatchErrorKind::TooManyOperations));
+/// ```
+pub fn validate_with_options(
+    doc: &Value,
+    patch: &[PatchOperation],
+    options: &PatchOptions,
+) -> Result<(), Vec<PatchValidationError>> {
+    let mut doc = doc.clone();
+    let mut growth = 0;
+    let errors: Vec<_> = patch
+        .iter()
+        .enumerate()
+        .filter_map(|(operation, op)| {
+            let mut grown = growth;
+            let result = options
+                .check_operation(operation, op)
+                .and_then(|()| options.check_strict(&doc, operation, op))
+                .and_then(|()| options.check_growth(&doc, operation, op, &mut grown))
+                .and_then(|()| options.apply(&mut doc, operation, op, None));
+            if result.is_ok() {
+                growth = grown;
+            }
+            result.err()
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{patch_with_options, validate_with_options, NumberTolerance, PatchOptions};
+    use crate::{OpKind, Patch, PatchErrorKind};
+    use serde_json::{from_value, json, Value};
+
+    #[test]
+    fn protected_paths() {
+        let options = PatchOptions {
+            protected_paths: vec!["/id".into(), "/meta/created".into()],
+            ..Default::default()
+        };
+        let doc = json!({ "id": 1, "idx": 2, "meta": { "created": 3, "tags": [] } });
+        let apply = |op: Value| {
+            let p: Patch = from_value(json!([op])).unwrap();
+            patch_with_options(&mut doc.clone(), &p, &options)
+        };
+        let rejected = |op: Value| {
+            let err = apply(op).unwrap_err();
+            matches!(err.kind, PatchErrorKind::ProtectedPath)
+        };
+
+        assert!(rejected(
+            json!({ "op": "replace", "path": "/id", "value": 0 })
+        ));
+        assert!(rejected(
+            json!({ "op": "add", "path": "/id/x", "value": 0 })
+        ));
+        assert!(rejected(json!({ "op": "remove", "path": "/meta" })));
+        assert!(rejected(
+            json!({ "op": "replace", "path": "", "value": {} })
+        ));
+        assert!(rejected(
+            json!({ "op": "move", "from": "/meta/created", "path": "/x" })
+        ));
+        assert!(rejected(
+            json!({ "op": "copy", "from": "/id", "path": "/x" })
+        ));
+
+        assert!(apply(json!({ "op": "test", "path": "/id", "value": 1 })).is_ok());
+        assert!(apply(json!({ "op": "remove", "path": "/idx" })).is_ok());
+        assert!(apply(json!({ "op": "add", "path": "/meta/tags/-", "value": 0 })).is_ok());
+        assert!(apply(json!({ "op": "copy", "from": "/idx", "path": "/meta/x" })).is_ok());
+    }
+
+    #[test]
+    fn operation_policy() {
+        let options = PatchOptions {
+            allowed_ops: Some([OpKind::Add, OpKind::Test].into()),
+            max_operations: Some(2),
+            ..Default::default()
+        };
+        let apply = |p: Value| {
+            let p: Patch = from_value(p).unwrap();
+            let mut doc = json!({ "a": 1 });
+            patch_with_options(&mut doc, &p, &options).map(|()| doc)
+        };
+
+        let doc = apply(json!([
+            { "op": "test", "path": "/a", "value": 1 },
+            { "op": "add", "path": "/b", "value": 2 },
+        ]));
+        assert_eq!(doc.unwrap(), json!({ "a": 1, "b": 2 }));
+
+        let err = apply(json!([
+            { "op": "add", "path": "/b", "value": 2 },
+            { "op": "move", "from": "/a", "path": "/c" },
+        ]))
+        .unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert!(matches!(err.kind, PatchErrorKind::OperationNotAllowed));
+
+        let err = apply(json!([
+            { "op": "add", "path": "/b", "value": 2 },
+            { "op": "add", "path": "/c", "value": 3 },
+            { "op": "add", "path": "/d", "value": 4 },
+        ]))
+        .unwrap_err();
+        assert_eq!(err.operation, 2);
+        assert!(matches!(err.kind, PatchErrorKind::TooManyOperations));
+    }
+
+    #[test]
+    fn resource_limits() {
+        let options = PatchOptions {
+            max_value_size: Some(8),
+            max_growth: Some(40),
+            max_pointer_depth: Some(2),
+            ..Default::default()
+        };
+        let apply = |p: Value| {
+            let p: Patch = from_value(p).unwrap();
+            let mut doc = json!({ "a": { "b": "0123456789" } });
+            let result = patch_with_options(&mut doc, &p, &options);
+            if result.is_err() {
+                assert_eq!(doc, json!({ "a": { "b": "0123456789" } }));
+            }
+            result.map_err(|err| (err.operation, err.kind))
+        };
+
+        let err = apply(json!([{ "op": "add", "path": "/x", "value": "0123456789" }]));
+        assert!(matches!(err, Err((0, PatchErrorKind::ValueTooLarge))));
+        let err = apply(json!([{ "op": "add", "path": "/a/b/c", "value": 1 }]));
+        assert!(matches!(err, Err((0, PatchErrorKind::PointerTooDeep))));
+        let err = apply(json!([{ "op": "copy", "from": "/a/b", "path": "/a/b/c" }]));
+        assert!(matches!(err, Err((0, PatchErrorKind::PointerTooDeep))));
+
+        let err = apply(json!([
+            { "op": "copy", "from": "/a", "path": "/c" },
+            { "op": "copy", "from": "/a", "path": "/d" },
+        ]));
+        assert!(matches!(err, Err((1, PatchErrorKind::GrowthLimitExceeded))));
+        let ok = apply(json!([
+            { "op": "copy", "from": "/a", "path": "/c" },
+            { "op": "remove", "path": "/a" },
+            { "op": "copy", "from": "/c", "path": "/d" },
+        ]));
+        assert!(ok.is_ok());
+    }
+
+    #[test]
+    fn strict() {
+        let options = PatchOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let doc = json!({ "a": [1, 2], "o": { "1": true, "01": false, "~2": 0 } });
+        let apply = |op: Value, options: &PatchOptions| {
+            let p: Patch = from_value(json!([op])).unwrap();
+            let mut doc = doc.clone();
+            patch_with_options(&mut doc, &p, options).map_err(|err| err.kind)
+        };
+
+        for op in [
+            json!({ "op": "test", "path": "/a/01", "value": 2 }),
+            json!({ "op": "remove", "path": "/o/~2" }),
+            json!({ "op": "remove", "path": "/o/1" }),
+            json!({ "op": "add", "path": "/o/2", "value": 0 }),
+        ] {
+            let err = apply(op.clone(), &options);
+            assert!(matches!(err, Err(PatchErrorKind::InvalidPointer)), "{}", op);
+        }
+        let lenient = apply(
+            json!({ "op": "remove", "path": "/o/~2" }),
+            &Default::default(),
+        );
+        assert!(lenient.is_ok());
+        let lenient = apply(
+            json!({ "op": "remove", "path": "/o/1" }),
+            &Default::default(),
+        );
+        assert!(lenient.is_ok());
+        let err = apply(
+            json!({ "op": "copy", "from": "/a/+1", "path": "/b" }),
+            &options,
+        );
+        assert!(matches!(err, Err(PatchErrorKind::InvalidFromPointer)));
+
+        for op in [
+            json!({ "op": "remove", "path": "/o/01" }),
+            json!({ "op": "add", "path": "/a/-", "value": 3 }),
+            json!({ "op": "add", "path": "/a/2/x", "value": 3 }),
+            json!({ "op": "copy", "from": "/a/1", "path": "/o/x" }),
+        ] {
+            let result = apply(op.clone(), &options);
+            assert!(
+                !matches!(result, Err(PatchErrorKind::InvalidPointer)),
+                "{}",
+                op
+            );
+        }
+    }
+
+    #[test]
+    fn test_tolerance() {
+        let options = PatchOptions {
+            test_tolerance: Some(NumberTolerance {
+                absolute: 1e-9,
+                relative: 0.0,
+            }),
+            ..Default::default()
+        };
+        let mut doc = json!({ "a": 0.30000000000000004, "b": [1, "x"], "c": 2 });
+        let p: Patch = from_value(json!([
+            { "op": "test", "path": "/a", "value": 0.3 },
+            { "op": "test", "path": "/b", "value": [1.0000000001, "x"] },
+            { "op": "replace", "path": "/c", "value": 3 },
+        ]))
+        .unwrap();
+        assert!(crate::patch(&mut doc.clone(), &p).is_err());
+        patch_with_options(&mut doc, &p, &options).unwrap();
+        assert_eq!(doc["c"], json!(3));
+
+        let p: Patch = from_value(json!([
+            { "op": "replace", "path": "/c", "value": 4 },
+            { "op": "test", "path": "/a", "value": 0.31 },
+        ]))
+        .unwrap();
+        let err = patch_with_options(&mut doc, &p, &options).unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert!(matches!(err.kind, PatchErrorKind::TestFailed { .. }));
+        assert_eq!(doc["c"], json!(3));
+
+        let p: Patch = from_value(json!([{ "op": "test", "path": "/d", "value": 1 }])).unwrap();
+        let errors = validate_with_options(&doc, &p, &options).unwrap_err();
+        assert!(matches!(
+            errors[0].kind,
+            PatchErrorKind::TestFailed { actual: None, .. }
+        ));
+    }
+}
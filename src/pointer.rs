@@ -0,0 +1,933 @@
+use crate::{
+    append_path, parse_index, unescape, Patch, PatchError, PatchErrorKind, PatchOperation,
+};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use thiserror::Error;
+
+/// This type represents all possible errors that can occur when parsing a JSON pointer.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum PointerError {
+    /// Non-empty JSON pointer does not start with `/`.
+    #[error("pointer must start with '/'")]
+    MissingLeadingSlash,
+    /// `~` is not followed by `0` or `1`.
+    #[error("invalid escape sequence at position {0}")]
+    InvalidEscape(usize),
+    /// Relative JSON pointer does not start with a non-negative integer, or has invalid index
+    /// manipulation.
+    #[error("invalid relative pointer prefix")]
+    InvalidRelativePrefix,
+}
+
+/// JSON pointer ([RFC 6901](https://tools.ietf.org/html/rfc6901)), which is guaranteed to be
+/// valid.
+///
+/// Reference tokens are escaped and unescaped by the pointer itself, so that `/` and `~` in
+/// object keys never have to be escaped by hand. Pointers convert into `String` for use in
+/// patch operations.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{AddOperation, Pointer};
+/// use serde_json::json;
+///
+/// let mut pointer = Pointer::root();
+/// pointer.push("a/b");
+/// pointer.push("~c");
+/// assert_eq!(pointer.to_string(), "/a~1b/~0c");
+/// assert_eq!(pointer.tokens().collect::<Vec<_>>(), ["a/b", "~c"]);
+/// assert_eq!(pointer.parent(), Some("/a~1b".parse().unwrap()));
+///
+/// let op = AddOperation {
+///     path: pointer.join("0").into(),
+///     value: json!(1),
+/// };
+/// assert_eq!(op.path, "/a~1b/~0c/0");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pointer(String);
+
+impl Pointer {
+    /// Pointer to the whole document.
+    pub fn root() -> Pointer {
+        Pointer(String::new())
+    }
+
+    /// Parse and validate the JSON pointer.
+    pub fn parse(pointer: &str) -> Result<Pointer, PointerError> {
+        check_pointer(pointer)?;
+        Ok(Pointer(pointer.to_owned()))
+    }
+
+    /// Create the pointer from unescaped reference tokens.
+    pub fn from_tokens<I, T>(tokens: I) -> Pointer
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut pointer = Pointer::root();
+        for token in tokens {
+            pointer.push(token.as_ref());
+        }
+        pointer
+    }
+
+    /// Returns the pointer as an escaped string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Checks if the pointer refers to the whole document.
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over unescaped reference tokens of the pointer.
+    pub fn tokens(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        self.0.split('/').skip(1).map(unescape)
+    }
+
+    /// Returns the last unescaped reference token, or `None` for the root pointer.
+    pub fn last(&self) -> Option<Cow<'_, str>> {
+        self.0.rfind('/').map(|idx| unescape(&self.0[idx + 1..]))
+    }
+
+    /// Returns the pointer to the parent value, or `None` for the root pointer.
+    pub fn parent(&self) -> Option<Pointer> {
+        self.0
+            .rfind('/')
+            .map(|idx| Pointer(self.0[..idx].to_owned()))
+    }
+
+    /// Append unescaped reference token to the pointer.
+    pub fn push(&mut self, token: &str) {
+        self.0.push('/');
+        append_path(&mut self.0, token);
+    }
+
+    /// Remove the last reference token from the pointer, returning it unescaped.
+    pub fn pop(&mut self) -> Option<String> {
+        let idx = self.0.rfind('/')?;
+        let token = unescape(&self.0[idx + 1..]).into_owned();
+        self.0.truncate(idx);
+        Some(token)
+    }
+
+    /// Returns the pointer with the unescaped reference token appended.
+    pub fn join(&self, token: &str) -> Pointer {
+        let mut pointer = self.clone();
+        pointer.push(token);
+        pointer
+    }
+
+    /// Returns the pointer with all reference tokens of `other` appended.
+    pub fn concat(&self, other: &Pointer) -> Pointer {
+        Pointer(format!("{}{}", self.0, other.0))
+    }
+
+    /// Checks if the pointer refers to the same value as `other` or to one of its ancestors.
+    pub fn is_prefix_of(&self, other: &Pointer) -> bool {
+        crate::is_prefix(&self.0, &other.0)
+    }
+}
+
+impl Display for Pointer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for Pointer {
+    type Err = PointerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pointer::parse(s)
+    }
+}
+
+impl TryFrom<&str> for Pointer {
+    type Error = PointerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Pointer::parse(value)
+    }
+}
+
+impl TryFrom<String> for Pointer {
+    type Error = PointerError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Pointer::parse(&value)?;
+        Ok(Pointer(value))
+    }
+}
+
+impl From<Pointer> for String {
+    fn from(pointer: Pointer) -> Self {
+        pointer.0
+    }
+}
+
+impl AsRef<str> for Pointer {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Pointer {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Pointer {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl Serialize for Pointer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pointer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Pointer::try_from(s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Resolve the JSON pointer in the document, returning the value it refers to.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{exists, resolve, resolve_mut, Pointer};
+/// use serde_json::json;
+///
+/// let mut doc = json!({ "a/b": [1, 2] });
+/// let pointer = Pointer::from_tokens(["a/b", "1"]);
+/// assert_eq!(resolve(&doc, &pointer), Some(&json!(2)));
+///
+/// *resolve_mut(&mut doc, &pointer).unwrap() = json!(3);
+/// assert_eq!(doc, json!({ "a/b": [1, 3] }));
+/// assert!(!exists(&doc, &pointer.join("x")));
+/// ```
+pub fn resolve<'a>(doc: &'a Value, pointer: &Pointer) -> Option<&'a Value> {
+    pointer
+        .tokens()
+        .try_fold(doc, |target, token| match target {
+            Value::Object(obj) => obj.get(token.as_ref()),
+            Value::Array(arr) => arr.get(parse_index(&token, arr.len()).ok()?),
+            _ => None,
+        })
+}
+
+/// Resolve the JSON pointer in the document, returning a mutable reference to the value it refers
+/// to.
+pub fn resolve_mut<'a>(doc: &'a mut Value, pointer: &Pointer) -> Option<&'a mut Value> {
+    pointer
+        .tokens()
+        .try_fold(doc, |target, token| match target {
+            Value::Object(obj) => obj.get_mut(token.as_ref()),
+            Value::Array(arr) => {
+                let idx = parse_index(&token, arr.len()).ok()?;
+                arr.get_mut(idx)
+            }
+            _ => None,
+        })
+}
+
+/// Checks if the JSON pointer refers to an existing value in the document.
+pub fn exists(doc: &Value, pointer: &Pointer) -> bool {
+    resolve(doc, pointer).is_some()
+}
+
+/// Escape the key as a JSON Pointer reference token, replacing `~` with `~0` and `/` with `~1`.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{escape_token, split_pointer, unescape_token};
+///
+/// let path = format!("/{}/{}", escape_token("a/b"), escape_token("~c"));
+/// assert_eq!(path, "/a~1b/~0c");
+/// assert_eq!(split_pointer(&path).unwrap(), ["a/b", "~c"]);
+/// assert_eq!(unescape_token("~0c").unwrap(), "~c");
+/// ```
+pub fn escape_token(key: &str) -> Cow<'_, str> {
+    if key.contains(['~', '/']) {
+        let mut token = String::with_capacity(key.len() + 1);
+        append_path(&mut token, key);
+        Cow::Owned(token)
+    } else {
+        Cow::Borrowed(key)
+    }
+}
+
+/// Unescape the JSON Pointer reference token into the key it refers to, replacing `~1` with `/`
+/// and `~0` with `~`. Fails if `~` is not followed by `0` or `1`.
+pub fn unescape_token(token: &str) -> Result<Cow<'_, str>, PointerError> {
+    check_escapes(token)?;
+    Ok(unescape(token))
+}
+
+/// Split the JSON pointer into its unescaped reference tokens, which is empty for the pointer to
+/// the whole document. Fails if the pointer is not valid.
+pub fn split_pointer(pointer: &str) -> Result<Vec<Cow<'_, str>>, PointerError> {
+    check_pointer(pointer)?;
+    Ok(pointer.split('/').skip(1).map(unescape).collect())
+}
+
+/// Checks that the pointer is a valid JSON pointer, as defined by RFC 6901.
+pub(crate) fn check_pointer(pointer: &str) -> Result<(), PointerError> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return Err(PointerError::MissingLeadingSlash);
+    }
+    check_escapes(pointer)
+}
+
+/// Checks that every `~` is followed by `0` or `1`.
+pub(crate) fn check_escapes(s: &str) -> Result<(), PointerError> {
+    let bytes = s.as_bytes();
+    for (idx, &b) in bytes.iter().enumerate() {
+        if b == b'~' && !matches!(bytes.get(idx + 1), Some(b'0' | b'1')) {
+            return Err(PointerError::InvalidEscape(idx));
+        }
+    }
+    Ok(())
+}
+
+/// Target of a relative JSON pointer, after going up the document.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum RelativeTarget {
+    /// `#`: key or index of the value in its parent.
+    Key,
+    /// JSON pointer to the value, relative to the value the pointer goes up to.
+    Pointer(Pointer),
+}
+
+/// Relative JSON pointer
+/// ([draft-bhutton-relative-json-pointer](https://datatracker.ietf.org/doc/html/draft-bhutton-relative-json-pointer-00)),
+/// like `0/title`, `1/0` or `2#`, referring to a value relative to some other value of the
+/// document.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{Pointer, RelativePointer};
+/// use serde_json::json;
+///
+/// let doc = json!({ "items": [{ "name": "a" }, { "name": "b" }] });
+/// let base: Pointer = "/items/0/name".parse().unwrap();
+///
+/// let sibling: RelativePointer = "1-0/name".parse().unwrap();
+/// assert_eq!(sibling.resolve_pointer(&base).unwrap(), "/items/0/name");
+/// let next: RelativePointer = "1+1/name".parse().unwrap();
+/// assert_eq!(next.resolve(&doc, &base).unwrap().into_owned(), json!("b"));
+/// let key: RelativePointer = "1#".parse().unwrap();
+/// assert_eq!(key.resolve(&doc, &base).unwrap().into_owned(), json!(0));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RelativePointer {
+    up: usize,
+    offset: isize,
+    target: RelativeTarget,
+}
+
+/// Parses a non-negative integer without leading zeroes at the start of `s`, returning the rest.
+fn parse_prefix(s: &str) -> Option<(usize, &str)> {
+    let len = s.bytes().take_while(u8::is_ascii_digit).count();
+    let (digits, rest) = s.split_at(len);
+    if digits.is_empty() || (digits.len() > 1 && digits.starts_with('0')) {
+        return None;
+    }
+    Some((digits.parse().ok()?, rest))
+}
+
+impl RelativePointer {
+    /// Parse and validate the relative JSON pointer.
+    pub fn parse(pointer: &str) -> Result<RelativePointer, PointerError> {
+        let (up, rest) = parse_prefix(pointer).ok_or(PointerError::InvalidRelativePrefix)?;
+        let (offset, rest) = match rest.as_bytes().first() {
+            Some(sign @ (b'+' | b'-')) => {
+                let (offset, rest) =
+                    parse_prefix(&rest[1..]).ok_or(PointerError::InvalidRelativePrefix)?;
+                let offset =
+                    isize::try_from(offset).map_err(|_| PointerError::InvalidRelativePrefix)?;
+                (if *sign == b'-' { -offset } else { offset }, rest)
+            }
+            _ => (0, rest),
+        };
+        let target = if rest == "#" {
+            RelativeTarget::Key
+        } else {
+            RelativeTarget::Pointer(Pointer::parse(rest)?)
+        };
+        Ok(RelativePointer { up, offset, target })
+    }
+
+    /// Checks if the pointer refers to the key or index of the value (ends with `#`) rather than
+    /// to the value itself.
+    pub fn is_key(&self) -> bool {
+        self.target == RelativeTarget::Key
+    }
+
+    /// Pointer to the value the pointer goes up to from `base`, with index manipulation applied.
+    fn origin(&self, base: &Pointer) -> Option<Pointer> {
+        let mut origin = base.clone();
+        for _ in 0..self.up {
+            origin.pop()?;
+        }
+        if self.offset != 0 {
+            let idx = origin.pop()?;
+            let idx = parse_index(&idx, usize::MAX).ok()?;
+            let idx = idx.checked_add_signed(self.offset)?;
+            origin.push(&idx.to_string());
+        }
+        Some(origin)
+    }
+
+    /// Resolve the pointer relative to the value at `base` into an absolute JSON pointer. Returns
+    /// `None` if the pointer goes up past the document root, if index manipulation is applied
+    /// to something other than an array index, or if the pointer ends with `#`.
+    pub fn resolve_pointer(&self, base: &Pointer) -> Option<Pointer> {
+        match &self.target {
+            RelativeTarget::Key => None,
+            RelativeTarget::Pointer(pointer) => Some(self.origin(base)?.concat(pointer)),
+        }
+    }
+
+    /// Resolve the pointer relative to the value at `base` in the document. Pointers ending with
+    /// `#` resolve to the index (for array elements) or the key (for object members) of the value
+    /// the pointer goes up to. Returns `None` if the pointer does not refer to any value.
+    pub fn resolve<'a>(&self, doc: &'a Value, base: &Pointer) -> Option<Cow<'a, Value>> {
+        match &self.target {
+            RelativeTarget::Key => {
+                let origin = self.origin(base)?;
+                let parent = resolve(doc, &origin.parent()?)?;
+                let key = origin.last()?;
+                match parent {
+                    Value::Array(arr) => {
+                        let idx = parse_index(&key, arr.len()).ok()?;
+                        Some(Cow::Owned(Value::from(idx)))
+                    }
+                    Value::Object(obj) if obj.contains_key(key.as_ref()) => {
+                        Some(Cow::Owned(Value::from(key.into_owned())))
+                    }
+                    _ => None,
+                }
+            }
+            RelativeTarget::Pointer(_) => {
+                resolve(doc, &self.resolve_pointer(base)?).map(Cow::Borrowed)
+            }
+        }
+    }
+}
+
+impl Display for RelativePointer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.up)?;
+        if self.offset != 0 {
+            write!(f, "{:+}", self.offset)?;
+        }
+        match &self.target {
+            RelativeTarget::Key => f.write_str("#"),
+            RelativeTarget::Pointer(pointer) => f.write_str(pointer.as_str()),
+        }
+    }
+}
+
+impl FromStr for RelativePointer {
+    type Err = PointerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RelativePointer::parse(s)
+    }
+}
+
+impl Patch {
+    /// Returns the patch with relative JSON pointers (see [`RelativePointer`]) in `path` and
+    /// `from` of operations resolved relative to the `base` value. Pointers starting with `/`
+    /// (and empty ones) are absolute and are left as is.
+    ///
+    /// This is an extension of JSON patch, which can be used to build patches from templates
+    /// operating on values at different locations.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Patch, Pointer};
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "replace", "path": "0/name", "value": "b" },
+    ///   { "op": "copy", "from": "/template", "path": "1/-" }
+    /// ])).unwrap();
+    /// let base: Pointer = "/items/0".parse().unwrap();
+    /// assert_eq!(p.resolve_relative(&base).unwrap(), from_value(json!([
+    ///   { "op": "replace", "path": "/items/0/name", "value": "b" },
+    ///   { "op": "copy", "from": "/template", "path": "/items/-" }
+    /// ])).unwrap());
+    /// ```
+    pub fn resolve_relative(&self, base: &Pointer) -> Result<Patch, PatchError> {
+        self.rewrite_pointers(|pointer| {
+            if pointer.is_empty() || pointer.starts_with('/') {
+                return Some(pointer.to_owned());
+            }
+            let relative = RelativePointer::parse(pointer).ok()?;
+            relative.resolve_pointer(base).map(String::from)
+        })
+    }
+
+    /// Returns the patch with `prefix` prepended to `path` and `from` of every operation, so that
+    /// a patch generated against a subdocument applies to the subdocument mounted at `prefix`
+    /// inside a larger document.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Patch, Pointer};
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "replace", "path": "", "value": {} },
+    ///   { "op": "move", "from": "/a", "path": "/b" }
+    /// ])).unwrap();
+    /// let mount: Pointer = "/items/0".parse().unwrap();
+    /// assert_eq!(p.prepend_path(&mount), from_value(json!([
+    ///   { "op": "replace", "path": "/items/0", "value": {} },
+    ///   { "op": "move", "from": "/items/0/a", "path": "/items/0/b" }
+    /// ])).unwrap());
+    /// ```
+    pub fn prepend_path(&self, prefix: &Pointer) -> Patch {
+        let prepended = self.rewrite_pointers(|pointer| Some(format!("{}{}", prefix, pointer)));
+        prepended.expect("prepending never fails")
+    }
+
+    /// Returns the patch with `prefix` removed from `path` and `from` of every operation, so that
+    /// a patch for a larger document applies to its subdocument at `prefix` on its own. This is
+    /// the inverse of [`Patch::prepend_path`].
+    ///
+    /// Returns an error if a pointer of some operation is outside of the subdocument (see
+    /// [`Patch::filter_prefix`] to select the operations inside it first).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Patch, Pointer};
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "replace", "path": "/items/0", "value": {} },
+    ///   { "op": "copy", "from": "/items/0/a", "path": "/items/0/b" }
+    /// ])).unwrap();
+    /// let mount: Pointer = "/items/0".parse().unwrap();
+    /// assert_eq!(p.strip_prefix(&mount).unwrap(), from_value(json!([
+    ///   { "op": "replace", "path": "", "value": {} },
+    ///   { "op": "copy", "from": "/a", "path": "/b" }
+    /// ])).unwrap());
+    ///
+    /// let other: Pointer = "/items/1".parse().unwrap();
+    /// assert!(p.strip_prefix(&other).is_err());
+    /// ```
+    pub fn strip_prefix(&self, prefix: &Pointer) -> Result<Patch, PatchError> {
+        self.rewrite_pointers(|pointer| {
+            if !crate::is_prefix(prefix.as_str(), pointer) {
+                return None;
+            }
+            Some(pointer[prefix.as_str().len()..].to_owned())
+        })
+    }
+
+    /// Rewrites `path` and `from` of every operation, failing with an invalid pointer error on
+    /// the first pointer `rewrite` returns `None` for.
+    fn rewrite_pointers<F>(&self, mut rewrite: F) -> Result<Patch, PatchError>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut rewrite = |operation: usize, pointer: &mut String| {
+            *pointer = rewrite(pointer).ok_or_else(|| {
+                crate::translate_error(PatchErrorKind::InvalidPointer, operation, pointer)
+            })?;
+            Ok(())
+        };
+        let mut ops = self.0.clone();
+        for (operation, op) in ops.iter_mut().enumerate() {
+            match op {
+                PatchOperation::Add(op) => rewrite(operation, &mut op.path)?,
+                PatchOperation::Remove(op) => rewrite(operation, &mut op.path)?,
+                PatchOperation::Replace(op) => rewrite(operation, &mut op.path)?,
+                PatchOperation::Move(op) => {
+                    rewrite(operation, &mut op.from)?;
+                    rewrite(operation, &mut op.path)?;
+                }
+                PatchOperation::Copy(op) => {
+                    rewrite(operation, &mut op.from)?;
+                    rewrite(operation, &mut op.path)?;
+                }
+                PatchOperation::Test(op) => rewrite(operation, &mut op.path)?,
+            }
+        }
+        Ok(Patch(ops))
+    }
+
+    /// Returns the operations of the patch that touch the subtree at `prefix`, in their original
+    /// order.
+    ///
+    /// An operation touches the subtree if it modifies or tests a value in it, replaces or removes
+    /// one of its ancestors, or inserts or removes an array element before it (shifting the
+    /// subtree to another index). A `move` touches both the subtree it moves from and the one it
+    /// moves to, while a `copy` only touches the subtree it copies to, as its source is left
+    /// intact. Pointers of the operations are left as is.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Patch, Pointer};
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "replace", "path": "/users/1/name", "value": "b" },
+    ///   { "op": "remove", "path": "/users/0" },
+    ///   { "op": "add", "path": "/orders/-", "value": 1 },
+    ///   { "op": "copy", "from": "/orders/0", "path": "/users/0/order" }
+    /// ])).unwrap();
+    /// let users: Pointer = "/users".parse().unwrap();
+    /// assert_eq!(p.filter_prefix(&users), from_value(json!([
+    ///   { "op": "replace", "path": "/users/1/name", "value": "b" },
+    ///   { "op": "remove", "path": "/users/0" },
+    ///   { "op": "copy", "from": "/orders/0", "path": "/users/0/order" }
+    /// ])).unwrap());
+    ///
+    /// let first: Pointer = "/users/0".parse().unwrap();
+    /// let second: Pointer = "/users/1".parse().unwrap();
+    /// assert_eq!(p.filter_prefix(&first).len(), 2);
+    /// assert_eq!(p.filter_prefix(&second).len(), 2);
+    /// ```
+    pub fn filter_prefix(&self, prefix: &Pointer) -> Patch {
+        self.iter()
+            .filter(|op| touches(op, prefix.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// Splits the patch into one patch for each of the `prefixes`, with the operations touching
+    /// the subtree at that prefix (see [`Patch::filter_prefix`]).
+    ///
+    /// Operations touching several subtrees, such as a `move` between them or a `replace` of a
+    /// common ancestor, are included in each of the corresponding patches. Operations touching
+    /// none of them are not included in any.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Patch, Pointer};
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "add", "path": "/a/x", "value": 1 },
+    ///   { "op": "move", "from": "/a/y", "path": "/b/y" },
+    ///   { "op": "remove", "path": "/c" }
+    /// ])).unwrap();
+    /// let prefixes: [Pointer; 2] = ["/a".parse().unwrap(), "/b".parse().unwrap()];
+    /// assert_eq!(p.split_by_prefix(&prefixes), [
+    ///   from_value::<Patch>(json!([
+    ///     { "op": "add", "path": "/a/x", "value": 1 },
+    ///     { "op": "move", "from": "/a/y", "path": "/b/y" }
+    ///   ])).unwrap(),
+    ///   from_value(json!([
+    ///     { "op": "move", "from": "/a/y", "path": "/b/y" }
+    ///   ])).unwrap(),
+    /// ]);
+    /// ```
+    pub fn split_by_prefix(&self, prefixes: &[Pointer]) -> Vec<Patch> {
+        let mut parts = vec![Patch::default(); prefixes.len()];
+        for op in self.iter() {
+            for (part, prefix) in parts.iter_mut().zip(prefixes) {
+                if touches(op, prefix.as_str()) {
+                    part.0.push(op.clone());
+                }
+            }
+        }
+        parts
+    }
+}
+
+/// Checks if modifying the value at `pointer` affects the subtree at `prefix`: either one of them
+/// contains the other, or `pointer` refers to an element of an array that comes before the
+/// subtree.
+fn affects(pointer: &str, prefix: &str) -> bool {
+    if crate::is_prefix(pointer, prefix) || crate::is_prefix(prefix, pointer) {
+        return true;
+    }
+    let Some((parent, last)) = pointer.rsplit_once('/') else {
+        return false;
+    };
+    let Some(rest) = prefix
+        .strip_prefix(parent)
+        .and_then(|rest| rest.strip_prefix('/'))
+    else {
+        return false;
+    };
+    let next = rest.split('/').next().unwrap_or_default();
+    match (last.parse::<usize>(), next.parse::<usize>()) {
+        (Ok(index), Ok(next)) => index < next,
+        _ => false,
+    }
+}
+
+/// Checks if the operation modifies or tests values in the subtree at `prefix`.
+fn touches(op: &PatchOperation, prefix: &str) -> bool {
+    match op {
+        PatchOperation::Add(op) => affects(&op.path, prefix),
+        PatchOperation::Remove(op) => affects(&op.path, prefix),
+        PatchOperation::Replace(op) => {
+            crate::is_prefix(&op.path, prefix) || crate::is_prefix(prefix, &op.path)
+        }
+        PatchOperation::Move(op) => affects(&op.from, prefix) || affects(&op.path, prefix),
+        PatchOperation::Copy(op) => affects(&op.path, prefix),
+        PatchOperation::Test(op) => {
+            crate::is_prefix(&op.path, prefix) || crate::is_prefix(prefix, &op.path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        escape_token, exists, resolve, resolve_mut, split_pointer, unescape_token, Pointer,
+        PointerError, RelativePointer,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Pointer::parse("").unwrap(), Pointer::root());
+        assert_eq!(
+            Pointer::parse("/a~0/~1b/")
+                .unwrap()
+                .tokens()
+                .collect::<Vec<_>>(),
+            ["a~", "/b", ""]
+        );
+        assert_eq!(Pointer::parse("a"), Err(PointerError::MissingLeadingSlash));
+        assert_eq!(Pointer::parse("/a~2"), Err(PointerError::InvalidEscape(2)));
+        assert_eq!(Pointer::parse("/a~"), Err(PointerError::InvalidEscape(2)));
+        assert!(serde_json::from_str::<Pointer>(r#""/~""#).is_err());
+        for pointer in ["", "/", "/a", "/~0~1", "/a/0/-", "//"] {
+            assert!(Pointer::parse(pointer).is_ok(), "{}", pointer);
+        }
+        for pointer in ["a", "/~", "/a~", "/~2", "/a~b/c"] {
+            assert!(Pointer::parse(pointer).is_err(), "{}", pointer);
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        for key in ["", "a", "~", "/", "~1", "a/~0/b~", "~/~/"] {
+            let token = escape_token(key);
+            assert!(!token.contains('/'), "{token}");
+            assert_eq!(unescape_token(&token).unwrap(), key);
+            assert_eq!(split_pointer(&format!("/x/{token}")).unwrap(), ["x", key]);
+            assert_eq!(
+                Pointer::from_tokens([key]),
+                Pointer::parse(&format!("/{token}")).unwrap()
+            );
+        }
+        assert_eq!(escape_token("~01"), "~001");
+        assert_eq!(unescape_token("~01").unwrap(), "~1");
+        assert_eq!(unescape_token("a~"), Err(PointerError::InvalidEscape(1)));
+        assert_eq!(unescape_token("~2"), Err(PointerError::InvalidEscape(0)));
+
+        assert!(split_pointer("").unwrap().is_empty());
+        assert_eq!(split_pointer("/").unwrap(), [""]);
+        assert_eq!(split_pointer("/a//b").unwrap(), ["a", "", "b"]);
+        assert_eq!(split_pointer("a"), Err(PointerError::MissingLeadingSlash));
+        assert_eq!(split_pointer("/a/~"), Err(PointerError::InvalidEscape(3)));
+    }
+
+    #[test]
+    fn navigate() {
+        let mut pointer = Pointer::from_tokens(["a", "b/c"]);
+        assert_eq!(pointer, "/a/b~1c");
+        assert_eq!(pointer.last().unwrap(), "b/c");
+        assert_eq!(pointer.pop().as_deref(), Some("b/c"));
+        assert_eq!(pointer.parent(), Some(Pointer::root()));
+        assert_eq!(Pointer::root().parent(), None);
+        assert_eq!(Pointer::root().last(), None);
+
+        let other = Pointer::from_tokens(["~"]);
+        assert_eq!(pointer.concat(&other), "/a/~0");
+        assert!(pointer.is_prefix_of(&pointer.join("x")));
+        assert!(!pointer.is_prefix_of(&Pointer::from_tokens(["ab"])));
+        assert_eq!(serde_json::to_string(&other).unwrap(), r#""/~0""#);
+    }
+
+    #[test]
+    fn relative_pointer() {
+        let doc = json!({ "a": { "b": [10, 20, "c"] }, "d": 1 });
+        let base = Pointer::parse("/a/b/1").unwrap();
+        let check = |pointer: &str, expected: Option<serde_json::Value>| {
+            let relative = RelativePointer::parse(pointer).unwrap();
+            assert_eq!(relative.to_string(), pointer);
+            assert_eq!(
+                relative.resolve(&doc, &base).map(|v| v.into_owned()),
+                expected,
+                "{}",
+                pointer
+            );
+        };
+        check("0", Some(json!(20)));
+        check("0#", Some(json!(1)));
+        check("0+1", Some(json!("c")));
+        check("0-1#", Some(json!(0)));
+        check("1#", Some(json!("b")));
+        check("2/b/2", Some(json!("c")));
+        check("3/d", Some(json!(1)));
+        check("3", Some(doc.clone()));
+        check("3#", None);
+        check("4/d", None);
+        check("0-2", None);
+        check("1+1", None);
+
+        for invalid in ["", "01", "-1", "1+", "1+01", "1x", "1/~2"] {
+            assert!(RelativePointer::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn resolve_relative_patch() {
+        let base = Pointer::parse("/a").unwrap();
+        let p: crate::Patch = serde_json::from_value(json!([
+            { "op": "move", "from": "0/b", "path": "1/c" },
+            { "op": "remove", "path": "2/x" },
+        ]))
+        .unwrap();
+        let err = p.resolve_relative(&base).unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert_eq!(err.path, "2/x");
+    }
+
+    #[test]
+    fn accessors() {
+        let mut doc = json!({ "a": [{ "": 1 }, 2], "~": null });
+        let check =
+            |doc: &serde_json::Value, pointer: &str, expected: Option<serde_json::Value>| {
+                let pointer = Pointer::parse(pointer).unwrap();
+                assert_eq!(resolve(doc, &pointer).cloned(), expected, "{}", pointer);
+                assert_eq!(exists(doc, &pointer), expected.is_some(), "{}", pointer);
+            };
+        check(&doc, "", Some(doc.clone()));
+        check(&doc, "/a/0/", Some(json!(1)));
+        check(&doc, "/~0", Some(json!(null)));
+        check(&doc, "/a/01", None);
+        check(&doc, "/a/-", None);
+        check(&doc, "/a/1/x", None);
+
+        let pointer = Pointer::parse("/a/1").unwrap();
+        *resolve_mut(&mut doc, &pointer).unwrap() = json!(3);
+        assert_eq!(doc["a"][1], json!(3));
+        assert!(resolve_mut(&mut doc, &pointer.join("x")).is_none());
+    }
+
+    #[test]
+    fn split_by_prefix() {
+        use crate::Patch;
+        use serde_json::from_value;
+
+        let p: Patch = from_value(json!([
+            { "op": "replace", "path": "", "value": {} },
+            { "op": "test", "path": "/a", "value": {} },
+            { "op": "add", "path": "/a/x", "value": 1 },
+            { "op": "replace", "path": "/a/x", "value": 2 },
+            { "op": "move", "from": "/b/0", "path": "/a/y" },
+            { "op": "copy", "from": "/a/x", "path": "/b/-" },
+            { "op": "add", "path": "/b/2", "value": 3 },
+            { "op": "remove", "path": "/b/1/z" },
+            { "op": "remove", "path": "/ab" },
+        ]))
+        .unwrap();
+        let indices = |part: &Patch| {
+            part.iter()
+                .map(|op| p.iter().position(|other| other == op).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let prefixes = ["/a", "/a/y", "/b/1", "/b/2", "/c"].map(|p| Pointer::parse(p).unwrap());
+        let parts = p.split_by_prefix(&prefixes);
+        assert_eq!(
+            parts.iter().map(indices).collect::<Vec<_>>(),
+            [
+                vec![0, 1, 2, 3, 4],
+                vec![0, 1, 4],
+                vec![0, 4, 7],
+                vec![0, 4, 6],
+                vec![0],
+            ]
+        );
+        for (part, prefix) in parts.iter().zip(&prefixes) {
+            assert_eq!(&p.filter_prefix(prefix), part);
+        }
+        assert_eq!(p.filter_prefix(&Pointer::root()), p);
+        assert!(p.split_by_prefix(&[]).is_empty());
+    }
+
+    #[test]
+    fn rewrite_prefix() {
+        use crate::{Patch, PatchErrorKind};
+        use serde_json::from_value;
+
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a~1b/-", "value": 1 },
+            { "op": "test", "path": "/x", "value": 1 },
+            { "op": "move", "from": "/x", "path": "/y/0" },
+        ]))
+        .unwrap();
+        let mount = Pointer::from_tokens(["m", "~"]);
+        let prepended = p.prepend_path(&mount);
+        assert_eq!(
+            prepended,
+            from_value(json!([
+                { "op": "add", "path": "/m/~0/a~1b/-", "value": 1 },
+                { "op": "test", "path": "/m/~0/x", "value": 1 },
+                { "op": "move", "from": "/m/~0/x", "path": "/m/~0/y/0" },
+            ]))
+            .unwrap()
+        );
+        assert_eq!(prepended.strip_prefix(&mount).unwrap(), p);
+        assert_eq!(p.prepend_path(&Pointer::root()), p);
+        assert_eq!(p.strip_prefix(&Pointer::root()).unwrap(), p);
+
+        let doc = json!({ "m": { "~": { "a/b": [], "x": 1, "y": [] } } });
+        let mut patched = doc.clone();
+        crate::patch(&mut patched, &prepended).unwrap();
+        let mut sub = doc["m"]["~"].clone();
+        crate::patch(&mut sub, &p).unwrap();
+        assert_eq!(patched["m"]["~"], sub);
+
+        // Pointers outside of the subdocument, including siblings sharing a name prefix
+        let err = prepended
+            .strip_prefix(&Pointer::from_tokens(["m", "~", "a/b"]))
+            .unwrap_err();
+        assert!(matches!(err.kind, PatchErrorKind::InvalidPointer));
+        assert_eq!(err.operation, 1);
+        let p: Patch =
+            from_value(json!([{ "op": "copy", "from": "/ab", "path": "/a/0" }])).unwrap();
+        let err = p.strip_prefix(&Pointer::from_tokens(["a"])).unwrap_err();
+        assert_eq!(err.path, "/ab");
+    }
+}
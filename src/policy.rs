@@ -0,0 +1,170 @@
+use crate::glob;
+use crate::{Patch, PatchError, PatchErrorKind, PatchOperation};
+use alloc::{string::String, vec::Vec};
+
+/// Policy restricting the locations patches can access, with globs of JSON Pointers, to sanitize
+/// untrusted patches before applying them: non-conforming operations are either rejected with
+/// [`PatchPolicy::check`] or stripped with [`PatchPolicy::sanitize`].
+///
+/// Locations are given as [`PointerGlob`](crate::PointerGlob)s, like `/items/*/price` or
+/// `/audit/**`.
+///
+/// Locations modified by an operation (its `path`, and the `from` location of a `move`) must be
+/// allowed and not denied. Locations only read (the `path` of a `test` and the `from` location of
+/// a `copy`) must not be denied. A glob allows or denies the matching locations together with
+/// everything nested under them; a denied location is also protected from operations accessing
+/// one of its ancestors, which would access it as well. Pointers not starting with `/` (other
+/// than `""`) are never permitted.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{Patch, PatchErrorKind, PatchPolicy};
+/// use serde_json::{from_value, json};
+///
+/// let policy = PatchPolicy {
+///     allowed_paths: vec!["/items/*/price".into(), "/notes".into()],
+///     denied_paths: vec!["/audit/**".into()],
+/// };
+///
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/items/0/price", "value": 10 },
+///   { "op": "replace", "path": "/items/0/name", "value": "free" },
+///   { "op": "copy", "from": "/audit/log", "path": "/notes" },
+///   { "op": "copy", "from": "/items/0/name", "path": "/notes" }
+/// ])).unwrap();
+///
+/// let err = policy.check(&p).unwrap_err();
+/// assert_eq!(err.operation, 1);
+/// assert!(matches!(err.kind, PatchErrorKind::PathNotAllowed));
+///
+/// assert_eq!(policy.sanitize(&p), from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/items/0/price", "value": 10 },
+///   { "op": "copy", "from": "/items/0/name", "path": "/notes" }
+/// ])).unwrap());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchPolicy {
+    /// Globs of the locations operations can modify. Any location can be modified if empty.
+    pub allowed_paths: Vec<String>,
+    /// Globs of the locations operations can neither modify nor read.
+    pub denied_paths: Vec<String>,
+}
+
+impl PatchPolicy {
+    /// Returns whether the operation conforms to the policy.
+    pub fn permits(&self, op: &PatchOperation) -> bool {
+        match op {
+            PatchOperation::Test(op) => self.permits_read(&op.path),
+            PatchOperation::Copy(op) => {
+                self.permits_modification(&op.path) && self.permits_read(&op.from)
+            }
+            PatchOperation::Move(op) => {
+                self.permits_modification(&op.path) && self.permits_modification(&op.from)
+            }
+            _ => self.permits_modification(op.path()),
+        }
+    }
+
+    /// Checks that all the operations of the patch conform to the policy, reporting the first
+    /// one which does not.
+    pub fn check(&self, patch: &[PatchOperation]) -> Result<(), PatchError> {
+        match patch.iter().position(|op| !self.permits(op)) {
+            Some(operation) => Err(PatchError {
+                operation,
+                path: patch[operation].path().into(),
+                kind: PatchErrorKind::PathNotAllowed,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the patch without the operations which do not conform to the policy.
+    pub fn sanitize(&self, patch: &[PatchOperation]) -> Patch {
+        patch
+            .iter()
+            .filter(|op| self.permits(op))
+            .cloned()
+            .collect()
+    }
+
+    fn permits_modification(&self, pointer: &str) -> bool {
+        self.permits_read(pointer)
+            && (self.allowed_paths.is_empty()
+                || self
+                    .allowed_paths
+                    .iter()
+                    .any(|glob| glob::covers(glob, pointer)))
+    }
+
+    fn permits_read(&self, pointer: &str) -> bool {
+        is_pointer(pointer) && !self.denies(pointer)
+    }
+
+    /// Whether a denied location is the one at the pointer, one of its ancestors or nested under
+    /// it.
+    fn denies(&self, pointer: &str) -> bool {
+        self.denied_paths
+            .iter()
+            .any(|glob| glob::covers(glob, pointer) || glob::may_match_under(glob, pointer))
+    }
+}
+
+/// Whether the string is a JSON pointer.
+fn is_pointer(pointer: &str) -> bool {
+    pointer.is_empty() || pointer.starts_with('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PatchPolicy;
+    use crate::PatchOperation;
+    use serde_json::json;
+
+    #[test]
+    fn globs() {
+        let policy = PatchPolicy {
+            allowed_paths: vec!["/items/*/price".into(), "/docs/**/text".into()],
+            denied_paths: vec!["/items/*/secret".into(), "/audit/**".into()],
+        };
+        for (pointer, modified, read) in [
+            ("/items/0/price", true, true),
+            ("/items/0/price/currency", true, true),
+            ("/items/0/secret", false, false),
+            ("/items/0/secret/x", false, false),
+            ("/items/0/name", false, true),
+            ("/items/0", false, false),
+            ("/items", false, false),
+            ("", false, false),
+            ("/docs/text", true, true),
+            ("/docs/a/b/text", true, true),
+            ("/docs/a/b", false, true),
+            ("/audit", false, false),
+            ("/audit/log/0", false, false),
+            ("/other", false, true),
+            ("items/0/price", false, false),
+        ] {
+            let replace = PatchOperation::replace(pointer, json!(1));
+            assert_eq!(policy.permits(&replace), modified, "{pointer}");
+            let test = PatchOperation::test(pointer, json!(1));
+            assert_eq!(policy.permits(&test), read, "{pointer}");
+        }
+
+        // Moves modify their `from` location, copies only read it
+        let copy = PatchOperation::copy_from("/items/0/name", "/items/1/price");
+        assert!(policy.permits(&copy));
+        let mv = PatchOperation::move_from("/items/0/name", "/items/1/price");
+        assert!(!policy.permits(&mv));
+        let copy = PatchOperation::copy_from("/audit/log", "/items/1/price");
+        assert!(!policy.permits(&copy));
+
+        // Everything not denied is allowed without allowed paths
+        let policy = PatchPolicy {
+            denied_paths: vec!["/a/*".into()],
+            ..Default::default()
+        };
+        assert!(policy.permits(&PatchOperation::remove("/b")));
+        assert!(!policy.permits(&PatchOperation::add("/a", json!({}))));
+        assert!(!policy.permits(&PatchOperation::add("/a/b/c", json!({}))));
+    }
+}
@@ -0,0 +1,667 @@
+//! JSON Predicates ([draft-snell-json-test-07](https://tools.ietf.org/html/draft-snell-json-test-07)),
+//! testing conditions on a JSON document.
+//!
+//! A [`Predicate`] is either a first-order predicate testing the value at its `path` (`contains`,
+//! `defined`, `ends`, `in`, `less`, `matches`, `more`, `starts`, `test`, `type`, `undefined`), or
+//! a second-order predicate combining the predicates in its `apply` member (`and`, `not`, `or`),
+//! whose paths are relative to its own `path`.
+//!
+//! Predicates can be embedded as preconditions of a patch with [`ConditionalPatch`], applied with
+//! [`patch_conditional`] only if all of them hold.
+//!
+//! Requires the `predicate` feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! use json_patch::predicate::Predicate;
+//! use serde_json::{from_value, json};
+//!
+//! let doc = json!({ "name": "Andrew", "age": 30, "tags": ["a"] });
+//! let p: Predicate = from_value(json!({
+//!   "op": "and",
+//!   "apply": [
+//!     { "op": "starts", "path": "/name", "value": "AND", "ignore_case": true },
+//!     { "op": "less", "path": "/age", "value": 40 },
+//!     { "op": "not", "path": "/tags", "apply": [{ "op": "defined", "path": "/1" }] }
+//!   ]
+//! })).unwrap();
+//!
+//! assert!(p.evaluate(&doc).unwrap());
+//! ```
+use crate::{patch, write_json, Patch, PatchError};
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Display, Formatter};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// This type represents all possible errors that can occur when evaluating a JSON predicate.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PredicateError {
+    /// `path` JSON pointer of the predicate is incorrect.
+    #[error("path '{0}' is invalid")]
+    InvalidPointer(String),
+    /// `value` of a `matches` predicate is not a valid regular expression.
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+    /// `value` of the predicate is not of the type required by its operation.
+    #[error("value of '{0}' predicate is invalid")]
+    InvalidValue(&'static str),
+}
+
+/// First-order predicate comparing the value at its `path` to its `value`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ValuePredicate {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references the
+    /// value to test, relative to the path of the enclosing second-order predicate, if any.
+    pub path: String,
+    /// Value to compare the target value to.
+    pub value: Value,
+    /// Whether strings are compared case-insensitively.
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub ignore_case: bool,
+}
+
+/// First-order predicate testing whether its `path` refers to an existing value.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PathPredicate {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references the
+    /// value to test, relative to the path of the enclosing second-order predicate, if any.
+    pub path: String,
+}
+
+/// Type tested by the `type` predicate.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum PredicateType {
+    /// Any number
+    Number,
+    /// Any string
+    String,
+    /// `true` or `false`
+    Boolean,
+    /// Object
+    Object,
+    /// Array
+    Array,
+    /// `null`
+    Null,
+    /// No value: the path does not exist
+    Undefined,
+    /// String with an RFC 3339 `full-date`
+    Date,
+    /// String with an RFC 3339 `date-time`
+    DateTime,
+    /// String with an RFC 3339 `full-time`
+    Time,
+    /// String with an RFC 4646 language tag
+    Lang,
+    /// String with an RFC 4647 language range
+    LangRange,
+    /// String with an RFC 3987 IRI
+    Iri,
+    /// String with an RFC 3987 absolute IRI, without a fragment
+    AbsoluteIri,
+}
+
+/// First-order `type` predicate, testing the type of the value at its `path`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TypePredicate {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references the
+    /// value to test, relative to the path of the enclosing second-order predicate, if any.
+    pub path: String,
+    /// Expected type.
+    pub value: PredicateType,
+}
+
+/// Second-order predicate, combining the predicates of its `apply` member.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CompoundPredicate {
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that the paths of the
+    /// combined predicates are relative to.
+    #[serde(default)]
+    pub path: String,
+    /// Combined predicates.
+    pub apply: Vec<Predicate>,
+}
+
+/// JSON predicate.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "op")]
+#[serde(rename_all = "lowercase")]
+pub enum Predicate {
+    /// 'contains' predicate: the target is a string containing the value
+    Contains(ValuePredicate),
+    /// 'defined' predicate: the target exists
+    Defined(PathPredicate),
+    /// 'ends' predicate: the target is a string ending with the value
+    Ends(ValuePredicate),
+    /// 'in' predicate: the target is equal to one of the elements of the value
+    In(ValuePredicate),
+    /// 'less' predicate: the target is a number less than the value
+    Less(ValuePredicate),
+    /// 'matches' predicate: the target is a string matching the value as a regular expression
+    Matches(ValuePredicate),
+    /// 'more' predicate: the target is a number greater than the value
+    More(ValuePredicate),
+    /// 'starts' predicate: the target is a string starting with the value
+    Starts(ValuePredicate),
+    /// 'test' predicate: the target is equal to the value
+    Test(ValuePredicate),
+    /// 'type' predicate: the target is of the type
+    Type(TypePredicate),
+    /// 'undefined' predicate: the target does not exist
+    Undefined(PathPredicate),
+    /// 'and' predicate: all the predicates hold
+    And(CompoundPredicate),
+    /// 'not' predicate: none of the predicates hold
+    Not(CompoundPredicate),
+    /// 'or' predicate: any of the predicates holds
+    Or(CompoundPredicate),
+}
+
+impl Display for Predicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Compares the strings, ignoring the case if requested.
+fn strings_equal(left: &str, right: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        left.to_lowercase() == right.to_lowercase()
+    } else {
+        left == right
+    }
+}
+
+/// Compares the values, ignoring the case of strings if requested.
+fn values_equal(left: &Value, right: &Value, ignore_case: bool) -> bool {
+    match (left, right) {
+        (Value::String(left), Value::String(right)) => strings_equal(left, right, ignore_case),
+        _ => crate::deep::values_equal(left, right),
+    }
+}
+
+/// Pattern of the string values of the types checked by their format.
+fn type_pattern(value_type: PredicateType) -> Option<&'static str> {
+    Some(match value_type {
+        PredicateType::Date => r"^\d{4}-\d{2}-\d{2}$",
+        PredicateType::DateTime => {
+            r"^\d{4}-\d{2}-\d{2}[Tt]\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})$"
+        }
+        PredicateType::Time => r"^\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})$",
+        PredicateType::Lang => r"^[A-Za-z]{1,8}(-[A-Za-z0-9]{1,8})*$",
+        PredicateType::LangRange => r"^(\*|[A-Za-z]{1,8})(-(\*|[A-Za-z0-9]{1,8}))*$",
+        PredicateType::Iri => r"^[A-Za-z][A-Za-z0-9+.-]*:[^\s]*$",
+        PredicateType::AbsoluteIri => r"^[A-Za-z][A-Za-z0-9+.-]*:[^\s#]*$",
+        _ => return None,
+    })
+}
+
+/// Returns whether the value, or its absence, is of the type.
+fn has_type(value: Option<&Value>, value_type: PredicateType) -> bool {
+    let value = match value {
+        Some(value) => value,
+        None => return value_type == PredicateType::Undefined,
+    };
+    match (value_type, value) {
+        (PredicateType::Number, Value::Number(_))
+        | (PredicateType::String, Value::String(_))
+        | (PredicateType::Boolean, Value::Bool(_))
+        | (PredicateType::Object, Value::Object(_))
+        | (PredicateType::Array, Value::Array(_))
+        | (PredicateType::Null, Value::Null) => true,
+        (value_type, Value::String(s)) => type_pattern(value_type)
+            .is_some_and(|pattern| Regex::new(pattern).is_ok_and(|regex| regex.is_match(s))),
+        _ => false,
+    }
+}
+
+impl Predicate {
+    /// Evaluate the predicate against the document.
+    ///
+    /// Returns an error if the predicate is not valid, like a `less` predicate with a string
+    /// value, even if the evaluation does not depend on it.
+    pub fn evaluate(&self, doc: &Value) -> Result<bool, PredicateError> {
+        self.evaluate_at(doc, "")
+    }
+
+    /// Evaluate the predicate, with its path relative to the given prefix.
+    fn evaluate_at(&self, doc: &Value, prefix: &str) -> Result<bool, PredicateError> {
+        let path = match self {
+            Predicate::Contains(p)
+            | Predicate::Ends(p)
+            | Predicate::In(p)
+            | Predicate::Less(p)
+            | Predicate::Matches(p)
+            | Predicate::More(p)
+            | Predicate::Starts(p)
+            | Predicate::Test(p) => &p.path,
+            Predicate::Defined(p) | Predicate::Undefined(p) => &p.path,
+            Predicate::Type(p) => &p.path,
+            Predicate::And(p) | Predicate::Not(p) | Predicate::Or(p) => &p.path,
+        };
+        if !path.is_empty() && !path.starts_with('/') {
+            return Err(PredicateError::InvalidPointer(path.clone()));
+        }
+        let path = if prefix.is_empty() {
+            Cow::Borrowed(path.as_str())
+        } else {
+            let mut full = String::from(prefix);
+            full.push_str(path);
+            Cow::Owned(full)
+        };
+        let target = doc.pointer(&path);
+
+        let holds = match self {
+            Predicate::Contains(p) => text(target, p, "contains", |t, v| t.contains(v))?,
+            Predicate::Ends(p) => text(target, p, "ends", |t, v| t.ends_with(v))?,
+            Predicate::Starts(p) => text(target, p, "starts", |t, v| t.starts_with(v))?,
+            Predicate::In(p) => {
+                let values = p
+                    .value
+                    .as_array()
+                    .ok_or(PredicateError::InvalidValue("in"))?;
+                target.is_some_and(|target| {
+                    values
+                        .iter()
+                        .any(|value| values_equal(target, value, p.ignore_case))
+                })
+            }
+            Predicate::Less(p) | Predicate::More(p) => {
+                let less = matches!(self, Predicate::Less(_));
+                let value = p
+                    .value
+                    .as_f64()
+                    .ok_or(PredicateError::InvalidValue(if less {
+                        "less"
+                    } else {
+                        "more"
+                    }))?;
+                match target.and_then(Value::as_f64) {
+                    Some(target) if less => target < value,
+                    Some(target) => target > value,
+                    None => false,
+                }
+            }
+            Predicate::Matches(p) => {
+                let pattern = p
+                    .value
+                    .as_str()
+                    .ok_or(PredicateError::InvalidValue("matches"))?;
+                let regex = RegexBuilder::new(pattern)
+                    .case_insensitive(p.ignore_case)
+                    .build()
+                    .map_err(|e| PredicateError::InvalidPattern(e.to_string()))?;
+                target
+                    .and_then(Value::as_str)
+                    .is_some_and(|target| regex.is_match(target))
+            }
+            Predicate::Test(p) => {
+                target.is_some_and(|target| values_equal(target, &p.value, p.ignore_case))
+            }
+            Predicate::Type(p) => has_type(target, p.value),
+            Predicate::Defined(_) => target.is_some(),
+            Predicate::Undefined(_) => target.is_none(),
+            Predicate::And(p) => {
+                for predicate in &p.apply {
+                    if !predicate.evaluate_at(doc, &path)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            Predicate::Or(p) | Predicate::Not(p) => {
+                let mut any = false;
+                for predicate in &p.apply {
+                    if predicate.evaluate_at(doc, &path)? {
+                        any = true;
+                        break;
+                    }
+                }
+                any == matches!(self, Predicate::Or(_))
+            }
+        };
+        Ok(holds)
+    }
+}
+
+/// Tests the target of a string predicate against its value, lowercased if the case is ignored.
+/// Targets which are not strings do not pass.
+fn text(
+    target: Option<&Value>,
+    predicate: &ValuePredicate,
+    op: &'static str,
+    test: fn(&str, &str) -> bool,
+) -> Result<bool, PredicateError> {
+    let value = predicate
+        .value
+        .as_str()
+        .ok_or(PredicateError::InvalidValue(op))?;
+    Ok(match target.and_then(Value::as_str) {
+        Some(target) if predicate.ignore_case => {
+            test(&target.to_lowercase(), &value.to_lowercase())
+        }
+        Some(target) => test(target, value),
+        None => false,
+    })
+}
+
+/// Patch with preconditions: predicates which must all hold for the patch to be applied.
+///
+/// It is serialized as an object with the `preconditions` and `patch` members.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ConditionalPatch {
+    /// Predicates which must hold for the document before the patch is applied.
+    #[serde(default)]
+    pub preconditions: Vec<Predicate>,
+    /// Patch to apply.
+    pub patch: Patch,
+}
+
+/// This type represents all possible errors that can occur when applying a [`ConditionalPatch`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ConditionalPatchError {
+    /// Precondition with the given index does not hold.
+    #[error("precondition '/{0}' does not hold")]
+    PreconditionFailed(usize),
+    /// Precondition with the given index is not valid.
+    #[error("precondition '/{index}' is invalid: {source}")]
+    InvalidPrecondition {
+        /// Index of the precondition.
+        index: usize,
+        /// Evaluation error.
+        #[source]
+        source: PredicateError,
+    },
+    /// Preconditions hold, but the patch failed.
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+}
+
+/// Patch the document in-place with the conditional patch, if all its preconditions hold. If any
+/// of them does not, or the patch fails, the document is left intact.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::predicate::{patch_conditional, ConditionalPatch, ConditionalPatchError};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "version": 3, "title": "Draft" });
+/// let p: ConditionalPatch = from_value(json!({
+///   "preconditions": [{ "op": "less", "path": "/version", "value": 4 }],
+///   "patch": [{ "op": "replace", "path": "/title", "value": "Final" }]
+/// })).unwrap();
+///
+/// patch_conditional(&mut doc, &p).unwrap();
+/// assert_eq!(doc, json!({ "version": 3, "title": "Final" }));
+///
+/// doc["version"] = json!(4);
+/// let err = patch_conditional(&mut doc, &p).unwrap_err();
+/// assert!(matches!(err, ConditionalPatchError::PreconditionFailed(0)));
+/// ```
+pub fn patch_conditional(
+    doc: &mut Value,
+    conditional: &ConditionalPatch,
+) -> Result<(), ConditionalPatchError> {
+    for (index, predicate) in conditional.preconditions.iter().enumerate() {
+        match predicate.evaluate(doc) {
+            Ok(true) => {}
+            Ok(false) => return Err(ConditionalPatchError::PreconditionFailed(index)),
+            Err(source) => {
+                return Err(ConditionalPatchError::InvalidPrecondition { index, source })
+            }
+        }
+    }
+    patch(doc, &conditional.patch)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{from_value, json};
+
+    fn evaluate(predicate: Value, doc: &Value) -> Result<bool, PredicateError> {
+        from_value::<Predicate>(predicate).unwrap().evaluate(doc)
+    }
+
+    #[test]
+    fn first_order() {
+        let doc = json!({
+            "num": 10,
+            "str": "Hello World",
+            "arr": [1, "a"],
+            "date": "2023-04-05",
+            "dt": "2023-04-05T10:20:30.5+02:00",
+            "time": "10:20:30Z",
+            "lang": "en-US",
+            "iri": "https://example.com/a#b",
+            "null": null,
+        });
+        for (predicate, expected) in [
+            (
+                json!({ "op": "contains", "path": "/str", "value": "lo W" }),
+                true,
+            ),
+            (
+                json!({ "op": "contains", "path": "/str", "value": "lo w" }),
+                false,
+            ),
+            (
+                json!({ "op": "contains", "path": "/str", "value": "lo w", "ignore_case": true }),
+                true,
+            ),
+            (
+                json!({ "op": "contains", "path": "/num", "value": "1" }),
+                false,
+            ),
+            (json!({ "op": "defined", "path": "/null" }), true),
+            (json!({ "op": "defined", "path": "/missing" }), false),
+            (json!({ "op": "undefined", "path": "/missing" }), true),
+            (
+                json!({ "op": "ends", "path": "/str", "value": "world", "ignore_case": true }),
+                true,
+            ),
+            (
+                json!({ "op": "starts", "path": "/str", "value": "World" }),
+                false,
+            ),
+            (
+                json!({ "op": "in", "path": "/num", "value": [1, 10] }),
+                true,
+            ),
+            (
+                json!({ "op": "in", "path": "/arr/1", "value": ["A"], "ignore_case": true }),
+                true,
+            ),
+            (
+                json!({ "op": "in", "path": "/missing", "value": [null] }),
+                false,
+            ),
+            (json!({ "op": "less", "path": "/num", "value": 10.5 }), true),
+            (json!({ "op": "less", "path": "/num", "value": 10 }), false),
+            (json!({ "op": "more", "path": "/num", "value": 9 }), true),
+            (json!({ "op": "more", "path": "/str", "value": 9 }), false),
+            (
+                json!({ "op": "matches", "path": "/str", "value": "^hello\\s" }),
+                false,
+            ),
+            (
+                json!({ "op": "matches", "path": "/str", "value": "^hello\\s", "ignore_case": true }),
+                true,
+            ),
+            (
+                json!({ "op": "test", "path": "/arr", "value": [1, "a"] }),
+                true,
+            ),
+            (
+                json!({ "op": "test", "path": "/str", "value": "hello world", "ignore_case": true }),
+                true,
+            ),
+            (
+                json!({ "op": "test", "path": "/missing", "value": null }),
+                false,
+            ),
+            (
+                json!({ "op": "type", "path": "/num", "value": "number" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/null", "value": "null" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/missing", "value": "undefined" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/null", "value": "undefined" }),
+                false,
+            ),
+            (
+                json!({ "op": "type", "path": "/date", "value": "date" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/dt", "value": "date-time" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/date", "value": "date-time" }),
+                false,
+            ),
+            (
+                json!({ "op": "type", "path": "/time", "value": "time" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/lang", "value": "lang" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/lang", "value": "lang-range" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/str", "value": "lang" }),
+                false,
+            ),
+            (
+                json!({ "op": "type", "path": "/iri", "value": "iri" }),
+                true,
+            ),
+            (
+                json!({ "op": "type", "path": "/iri", "value": "absolute-iri" }),
+                false,
+            ),
+        ] {
+            assert_eq!(
+                evaluate(predicate.clone(), &doc),
+                Ok(expected),
+                "{}",
+                predicate
+            );
+        }
+    }
+
+    #[test]
+    fn second_order() {
+        let doc = json!({ "a": { "b": 1, "c": "x" }, "d": [] });
+        let predicate = json!({
+            "op": "or",
+            "apply": [
+                { "op": "defined", "path": "/missing" },
+                {
+                    "op": "and",
+                    "path": "/a",
+                    "apply": [
+                        { "op": "test", "path": "/b", "value": 1 },
+                        { "op": "not", "apply": [{ "op": "type", "path": "/c", "value": "number" }] },
+                    ]
+                },
+            ]
+        });
+        assert_eq!(evaluate(predicate.clone(), &doc), Ok(true));
+
+        let p: Predicate = from_value(predicate.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&p).unwrap()["apply"][1]["path"], "/a");
+        assert_eq!(
+            evaluate(json!({ "op": "and", "apply": [] }), &doc),
+            Ok(true)
+        );
+        assert_eq!(
+            evaluate(json!({ "op": "or", "apply": [] }), &doc),
+            Ok(false)
+        );
+        assert_eq!(
+            evaluate(
+                json!({ "op": "not", "path": "/d", "apply": [{ "op": "defined", "path": "/0" }] }),
+                &doc
+            ),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let doc = json!({ "a": "x" });
+        assert_eq!(
+            evaluate(json!({ "op": "less", "path": "/a", "value": "1" }), &doc),
+            Err(PredicateError::InvalidValue("less"))
+        );
+        assert_eq!(
+            evaluate(json!({ "op": "in", "path": "/a", "value": "x" }), &doc),
+            Err(PredicateError::InvalidValue("in"))
+        );
+        assert_eq!(
+            evaluate(json!({ "op": "defined", "path": "a" }), &doc),
+            Err(PredicateError::InvalidPointer("a".into()))
+        );
+        assert!(matches!(
+            evaluate(json!({ "op": "matches", "path": "/a", "value": "(" }), &doc),
+            Err(PredicateError::InvalidPattern(_))
+        ));
+        assert!(from_value::<Predicate>(json!({ "op": "between", "path": "/a" })).is_err());
+    }
+
+    #[test]
+    fn conditional() {
+        let mut doc = json!({ "a": 1 });
+        let p: ConditionalPatch = from_value(json!({
+            "preconditions": [
+                { "op": "type", "path": "/a", "value": "number" },
+                { "op": "undefined", "path": "/b" },
+            ],
+            "patch": [
+                { "op": "add", "path": "/b", "value": 2 },
+            ]
+        }))
+        .unwrap();
+        patch_conditional(&mut doc, &p).unwrap();
+        assert_eq!(doc, json!({ "a": 1, "b": 2 }));
+
+        let err = patch_conditional(&mut doc, &p).unwrap_err();
+        assert_eq!(err.to_string(), "precondition '/1' does not hold");
+        assert_eq!(doc, json!({ "a": 1, "b": 2 }));
+
+        let p: ConditionalPatch = from_value(json!({
+            "patch": [{ "op": "remove", "path": "/c" }]
+        }))
+        .unwrap();
+        assert!(matches!(
+            patch_conditional(&mut doc, &p),
+            Err(ConditionalPatchError::Patch(_))
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::write_json;
+use crate::{
+    AddOperation, CopyOperation, MoveOperation, OpKind, Patch, PatchOperation, RemoveOperation,
+    ReplaceOperation, TestOperation,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::value::{to_raw_value, RawValue};
+use serde_json::Value;
+
+/// Representation of JSON Patch keeping the values of the operations as JSON text, see
+/// [`RawOperation`].
+///
+/// Values embedded in the patch are only parsed if the patch is converted to a [`Patch`] (for
+/// example, to apply it), so patches that are forwarded, stored or filtered by their pointers
+/// never build the `serde_json::Value` trees of their values. Requires the `raw_value` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch, RawPatch};
+/// use serde_json::json;
+///
+/// let mut raw: RawPatch = serde_json::from_str(r#"[
+///   { "op": "add", "path": "/blob", "value": { "large": ["value", "kept", "as", "text"] } },
+///   { "op": "replace", "path": "/name", "value": "b" }
+/// ]"#).unwrap();
+///
+/// raw.0.retain(|op| op.path != "/blob");
+/// assert_eq!(raw.to_string(), r#"[{"op":"replace","path":"/name","value":"b"}]"#);
+///
+/// let mut doc = json!({ "name": "a" });
+/// patch(&mut doc, &raw.to_patch().unwrap()).unwrap();
+/// assert_eq!(doc, json!({ "name": "b" }));
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RawPatch(pub Vec<RawOperation>);
+
+impl Display for RawPatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+impl core::ops::Deref for RawPatch {
+    type Target = [RawOperation];
+
+    fn deref(&self) -> &[RawOperation] {
+        &self.0
+    }
+}
+
+impl RawPatch {
+    /// Parses the values of the operations, converting the patch into a [`Patch`].
+    pub fn to_patch(&self) -> Result<Patch, serde_json::Error> {
+        self.iter().map(RawOperation::to_operation).collect()
+    }
+
+    /// Converts the patch, serializing the values of the operations.
+    pub fn from_patch(patch: &[PatchOperation]) -> Result<RawPatch, serde_json::Error> {
+        patch.iter().map(RawOperation::from_operation).collect()
+    }
+}
+
+impl From<Vec<RawOperation>> for RawPatch {
+    fn from(ops: Vec<RawOperation>) -> Self {
+        RawPatch(ops)
+    }
+}
+
+impl FromIterator<RawOperation> for RawPatch {
+    fn from_iter<I: IntoIterator<Item = RawOperation>>(iter: I) -> Self {
+        RawPatch(iter.into_iter().collect())
+    }
+}
+
+/// JSON Patch operation keeping its `value` as JSON text, see [`RawPatch`].
+///
+/// Members required by the kind of the operation are checked when it is deserialized:
+/// `from` for `move` and `copy`, and `value` for `add`, `replace` and `test`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(try_from = "RawFields")]
+pub struct RawOperation {
+    /// Kind of the operation.
+    pub op: OpKind,
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
+    /// to move or copy value from, for 'move' and 'copy' operations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// JSON-Pointer value [RFC6901](https://tools.ietf.org/html/rfc6901) that references a location
+    /// within the target document where the operation is performed.
+    pub path: String,
+    /// Value of 'add', 'replace' and 'test' operations, as JSON text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Box<RawValue>>,
+}
+
+impl Display for RawOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_json(f, self)
+    }
+}
+
+/// Members of a [`RawOperation`], before they are checked.
+#[derive(Deserialize)]
+struct RawFields {
+    op: OpKind,
+    #[serde(default)]
+    from: Option<String>,
+    path: String,
+    // Explicit `null` is a value, not a missing member
+    #[serde(default, deserialize_with = "present")]
+    value: Option<Box<RawValue>>,
+}
+
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Box<RawValue>>, D::Error> {
+    Box::<RawValue>::deserialize(deserializer).map(Some)
+}
+
+impl TryFrom<RawFields> for RawOperation {
+    type Error = serde_json::Error;
+
+    fn try_from(fields: RawFields) -> Result<Self, Self::Error> {
+        let op = RawOperation {
+            op: fields.op,
+            from: fields.from,
+            path: fields.path,
+            value: fields.value,
+        };
+        op.check()?;
+        Ok(op)
+    }
+}
+
+impl RawOperation {
+    /// Converts the operation, serializing its value.
+    pub fn from_operation(op: &PatchOperation) -> Result<RawOperation, serde_json::Error> {
+        Ok(RawOperation {
+            op: op.op_kind(),
+            from: op.from().map(Into::into),
+            path: op.path().into(),
+            value: op.value().map(to_raw_value).transpose()?,
+        })
+    }
+
+    /// Parses the value of the operation, converting it into a [`PatchOperation`]. Fails if
+    /// the operation lacks a member required by its kind.
+    pub fn to_operation(&self) -> Result<PatchOperation, serde_json::Error> {
+        self.check()?;
+        let path = self.path.clone();
+        let from = || self.from.clone().unwrap_or_default();
+        let value = || match &self.value {
+            Some(value) => serde_json::from_str::<Value>(value.get()),
+            None => Ok(Value::Null),
+        };
+        Ok(match self.op {
+            OpKind::Add => PatchOperation::Add(AddOperation {
+                path,
+                value: value()?,
+            }),
+            OpKind::Remove => PatchOperation::Remove(RemoveOperation { path }),
+            OpKind::Replace => PatchOperation::Replace(ReplaceOperation {
+                path,
+                value: value()?,
+            }),
+            OpKind::Move => PatchOperation::Move(MoveOperation { from: from(), path }),
+            OpKind::Copy => PatchOperation::Copy(CopyOperation { from: from(), path }),
+            OpKind::Test => PatchOperation::Test(TestOperation {
+                path,
+                value: value()?,
+            }),
+        })
+    }
+
+    fn check(&self) -> Result<(), serde_json::Error> {
+        match self.op {
+            OpKind::Add | OpKind::Replace | OpKind::Test if self.value.is_none() => {
+                Err(serde_json::Error::missing_field("value"))
+            }
+            OpKind::Move | OpKind::Copy if self.from.is_none() => {
+                Err(serde_json::Error::missing_field("from"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RawOperation, RawPatch};
+    use crate::Patch;
+    use serde_json::{from_str, from_value, json};
+
+    #[test]
+    fn round_trip() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a", "value": null },
+            { "op": "remove", "path": "/b" },
+            { "op": "replace", "path": "/c", "value": [1, { "d": 2.5 }] },
+            { "op": "move", "from": "/c", "path": "/e" },
+            { "op": "copy", "from": "/e", "path": "/f" },
+            { "op": "test", "path": "/f", "value": "g" }
+        ]))
+        .unwrap();
+        let text = p.to_string();
+
+        let raw: RawPatch = from_str(&text).unwrap();
+        assert_eq!(raw.to_string(), text);
+        assert_eq!(raw.to_patch().unwrap(), p);
+        assert_eq!(RawPatch::from_patch(&p).unwrap().to_string(), text);
+
+        // Values are kept as they were written
+        let raw: RawOperation =
+            from_str(r#"{ "op": "add", "path": "/a", "value": { "b" : 1 } }"#).unwrap();
+        assert_eq!(raw.value.unwrap().get(), r#"{ "b" : 1 }"#);
+    }
+
+    #[test]
+    fn missing_members() {
+        let err = from_str::<RawOperation>(r#"{ "op": "add", "path": "/a" }"#).unwrap_err();
+        assert!(err.to_string().contains("missing field `value`"), "{err}");
+        let err = from_str::<RawOperation>(r#"{ "op": "move", "path": "/a" }"#).unwrap_err();
+        assert!(err.to_string().contains("missing field `from`"), "{err}");
+        assert!(from_str::<RawOperation>(r#"{ "op": "remove", "path": "/a" }"#).is_ok());
+    }
+}
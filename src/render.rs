@@ -0,0 +1,437 @@
+//! Rendering of patches as human-readable text, in the style of unified diffs.
+//!
+//! Consecutive operations under the same parent location are grouped into hunks, headed by
+//! `@@ <parent pointer> @@` (`(root)` for members of the whole document), and every operation is
+//! rendered as one or two lines, referring to its target by the last reference token only:
+//!
+//! * `+ key: value` for `add` operations, and for `move` and `copy` operations with the source
+//!   in parentheses,
+//! * `- key` for `remove` operations, and `- key` followed by `+ key: value` for `replace`
+//!   operations,
+//! * `  key: value` (a context line) for `test` operations.
+//!
+//! When rendered with the original document, the removed and replaced values are shown, as well
+//! as the values moved or copied. Operations not applicable to the document are marked with a
+//! `! error` line, and the following operations are rendered without values from the document.
+//!
+//! With the `html` feature, [`render_html`] produces the same report as a standalone HTML page,
+//! with the lines highlighted.
+//!
+//! For log lines and error messages, [`PatchOperation::display`] and [`Patch::display`] render
+//! operations on single lines instead, with their full pointers.
+//!
+//! # Example
+//!
+//! ```rust
+//! use json_patch::render::{render, render_with_document};
+//! use json_patch::Patch;
+//! use serde_json::{from_value, json};
+//!
+//! let doc = json!({ "title": "Draft", "tags": ["a"], "author": "Ann" });
+//! let p: Patch = from_value(json!([
+//!   { "op": "replace", "path": "/title", "value": "Final" },
+//!   { "op": "remove", "path": "/author" },
+//!   { "op": "add", "path": "/tags/-", "value": "b" },
+//!   { "op": "copy", "from": "/title", "path": "/tags/0" },
+//! ])).unwrap();
+//!
+//! assert_eq!(render(&p), r#"@@ (root) @@
+//! - title
+//! + title: "Final"
+//! - author
+//! @@ /tags @@
+//! + -: "b"
+//! + 0 (copied from /title)
+//! "#);
+//!
+//! assert_eq!(render_with_document(&p, &doc), r#"@@ (root) @@
+//! - title: "Draft"
+//! + title: "Final"
+//! - author: "Ann"
+//! @@ /tags @@
+//! + -: "b"
+//! + 0: "Final" (copied from /title)
+//! "#);
+//! ```
+use crate::{patch, unescape, Patch, PatchOperation};
+use alloc::{borrow::Cow, format, string::String};
+use core::fmt::{self, Display, Formatter, Write};
+use core::slice;
+use serde_json::Value;
+
+/// Render the patch as text, see the [module documentation](self).
+pub fn render(patch: &[PatchOperation]) -> String {
+    Renderer::new(None, false).render(patch)
+}
+
+/// Render the patch as text, showing the values removed, replaced, moved or copied by the
+/// operations when applied to the document, see the [module documentation](self).
+pub fn render_with_document(patch: &[PatchOperation], doc: &Value) -> String {
+    Renderer::new(Some(doc.clone()), false).render(patch)
+}
+
+/// Render the patch applied to the document as a standalone HTML page, with added, removed and
+/// changed values highlighted.
+///
+/// The page shows the same lines as [`render_with_document`] in a `<pre class="json-patch">`
+/// element, each line in a `<span>` of class `hunk`, `added`, `removed`, `changed-from`,
+/// `changed-to`, `context` or `error`, so the page can be restyled when embedded.
+///
+/// Requires the `html` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::render::render_html;
+/// use json_patch::Patch;
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "title": "<Draft>" });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/title", "value": "Final" },
+/// ])).unwrap();
+///
+/// let html = render_html(&p, &doc);
+/// assert!(html.starts_with("<!DOCTYPE html>"));
+/// assert!(html.contains(r#"<span class="changed-from">- title: &quot;&lt;Draft&gt;&quot;</span>"#));
+/// assert!(html.contains(r#"<span class="changed-to">+ title: &quot;Final&quot;</span>"#));
+/// ```
+#[cfg(feature = "html")]
+pub fn render_html(patch: &[PatchOperation], doc: &Value) -> String {
+    let mut out = String::from(HTML_HEAD);
+    out.push_str(&Renderer::new(Some(doc.clone()), true).render(patch));
+    out.push_str(HTML_TAIL);
+    out
+}
+
+/// Human-readable rendering of an operation on a single line, returned by
+/// [`PatchOperation::display`].
+///
+/// Operations are rendered as `add /path ← value`, `remove /path`, `replace /path ← value`,
+/// `move /from → /path`, `copy /from → /path` and `test /path = value`, with the values as
+/// compact JSON and the whole document as `(document)`.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{Patch, PatchOperation};
+/// use serde_json::json;
+///
+/// let op = PatchOperation::replace("/title", json!("Hello!"));
+/// assert_eq!(op.display().to_string(), "replace /title ← \"Hello!\"");
+///
+/// let p = Patch(vec![
+///     PatchOperation::move_from("/draft", "/posts/-"),
+///     PatchOperation::test("", json!({ "a": [1] })),
+/// ]);
+/// assert_eq!(p.display().to_string(), "move /draft → /posts/-\ntest (document) = {\"a\":[1]}");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayOperation<'a>(&'a PatchOperation);
+
+/// Human-readable rendering of a patch, with every operation on its own line as for
+/// [`DisplayOperation`], returned by [`Patch::display`].
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayPatch<'a>(&'a [PatchOperation]);
+
+impl PatchOperation {
+    /// Returns an object rendering the operation on a single human-readable line, see
+    /// [`DisplayOperation`]. The operation itself is displayed as JSON.
+    pub fn display(&self) -> DisplayOperation<'_> {
+        DisplayOperation(self)
+    }
+}
+
+impl Patch {
+    /// Returns an object rendering the patch as human-readable lines, see [`DisplayPatch`]. The
+    /// patch itself is displayed as JSON.
+    pub fn display(&self) -> DisplayPatch<'_> {
+        DisplayPatch(self)
+    }
+}
+
+/// Pointer of an operation, with the whole document named.
+fn pointer(path: &str) -> &str {
+    if path.is_empty() {
+        "(document)"
+    } else {
+        path
+    }
+}
+
+impl Display for DisplayOperation<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let path = pointer(self.0.path());
+        match self.0 {
+            PatchOperation::Add(op) => write!(f, "add {} ← {}", path, op.value),
+            PatchOperation::Remove(_) => write!(f, "remove {}", path),
+            PatchOperation::Replace(op) => write!(f, "replace {} ← {}", path, op.value),
+            PatchOperation::Move(op) => write!(f, "move {} → {}", pointer(&op.from), path),
+            PatchOperation::Copy(op) => write!(f, "copy {} → {}", pointer(&op.from), path),
+            PatchOperation::Test(op) => write!(f, "test {} = {}", path, op.value),
+        }
+    }
+}
+
+impl Display for DisplayPatch<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (idx, op) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", op.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "html")]
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>JSON Patch</title>
+<style>
+.json-patch { font-family: monospace; }
+.json-patch .hunk { color: #6f42c1; }
+.json-patch .added, .json-patch .changed-to { background: #e6ffed; color: #22863a; }
+.json-patch .removed, .json-patch .changed-from { background: #ffeef0; color: #b31d28; }
+.json-patch .changed-from, .json-patch .changed-to { font-weight: bold; }
+.json-patch .context { color: #586069; }
+.json-patch .error { background: #fff5b1; color: #b31d28; }
+</style>
+</head>
+<body>
+<pre class="json-patch">
+"#;
+
+#[cfg(feature = "html")]
+const HTML_TAIL: &str = "</pre>\n</body>\n</html>\n";
+
+struct Renderer {
+    out: String,
+    /// Document with the operations rendered so far applied, if they all were applicable.
+    doc: Option<Value>,
+    /// Parent location of the current hunk.
+    parent: Option<String>,
+    /// Whether to render the lines as HTML.
+    html: bool,
+}
+
+/// Splits the path into the header of its hunk and the key of the location within its parent,
+/// if the path refers to a location within a parent.
+fn split(path: &str) -> (Cow<'_, str>, Option<Cow<'_, str>>) {
+    match path.rfind('/') {
+        Some(0) => (Cow::Borrowed("(root)"), Some(unescape(&path[1..]))),
+        Some(idx) => (
+            Cow::Borrowed(&path[..idx]),
+            Some(unescape(&path[idx + 1..])),
+        ),
+        None if path.is_empty() => (Cow::Borrowed("(document)"), None),
+        None => (Cow::Borrowed(path), None),
+    }
+}
+
+impl Renderer {
+    fn new(doc: Option<Value>, html: bool) -> Renderer {
+        Renderer {
+            out: String::new(),
+            doc,
+            parent: None,
+            html,
+        }
+    }
+
+    fn render(mut self, patch: &[PatchOperation]) -> String {
+        for op in patch {
+            self.operation(op);
+        }
+        self.out
+    }
+
+    /// Value at the given location of the document, before the current operation.
+    fn current(&self, path: &str) -> Option<Value> {
+        self.doc.as_ref()?.pointer(path).cloned()
+    }
+
+    fn operation(&mut self, op: &PatchOperation) {
+        let (parent, key) = split(op.path());
+        if self.parent.as_deref() != Some(parent.as_ref()) {
+            self.emit("hunk", &format!("@@ {} @@", parent));
+            self.parent = Some(parent.into_owned());
+        }
+        let key = key.as_deref();
+
+        match op {
+            PatchOperation::Add(op) => self.line("added", key, Some(&op.value), None),
+            PatchOperation::Remove(op) => {
+                let old = self.current(&op.path);
+                self.line("removed", key, old.as_ref(), None);
+            }
+            PatchOperation::Replace(op) => {
+                let old = self.current(&op.path);
+                self.line("changed-from", key, old.as_ref(), None);
+                self.line("changed-to", key, Some(&op.value), None);
+            }
+            PatchOperation::Move(op) => {
+                let value = self.current(&op.from);
+                self.line("added", key, value.as_ref(), Some(("moved", &op.from)));
+            }
+            PatchOperation::Copy(op) => {
+                let value = self.current(&op.from);
+                self.line("added", key, value.as_ref(), Some(("copied", &op.from)));
+            }
+            PatchOperation::Test(op) => self.line("context", key, Some(&op.value), None),
+        }
+
+        if let Some(doc) = &mut self.doc {
+            if let Err(err) = patch(doc, slice::from_ref(op)) {
+                self.emit("error", &format!("! {}", err.kind));
+                self.doc = None;
+            }
+        }
+    }
+
+    fn line(
+        &mut self,
+        class: &str,
+        key: Option<&str>,
+        value: Option<&Value>,
+        from: Option<(&str, &str)>,
+    ) {
+        let mut line = String::from(match class {
+            "added" | "changed-to" => "+",
+            "removed" | "changed-from" => "-",
+            _ => " ",
+        });
+        match (key, value) {
+            (Some(key), Some(value)) => write!(line, " {}: {}", key, value),
+            (Some(key), None) => write!(line, " {}", key),
+            (None, Some(value)) => write!(line, " {}", value),
+            (None, None) => Ok(()),
+        }
+        .unwrap();
+        if let Some((verb, from)) = from {
+            write!(line, " ({} from {})", verb, from).unwrap();
+        }
+        self.emit(class, &line);
+    }
+
+    /// Writes the line, highlighted with the class when rendering HTML.
+    fn emit(&mut self, class: &str, line: &str) {
+        if !self.html {
+            self.out.push_str(line);
+        } else {
+            write!(self.out, "<span class=\"{}\">", class).unwrap();
+            for c in line.chars() {
+                match c {
+                    '&' => self.out.push_str("&amp;"),
+                    '<' => self.out.push_str("&lt;"),
+                    '>' => self.out.push_str("&gt;"),
+                    '"' => self.out.push_str("&quot;"),
+                    '\'' => self.out.push_str("&#39;"),
+                    c => self.out.push(c),
+                }
+            }
+            self.out.push_str("</span>");
+        }
+        self.out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, render_with_document};
+    use crate::Patch;
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn display() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a~1b", "value": [1, "x"] },
+            { "op": "remove", "path": "/c/0" },
+            { "op": "replace", "path": "", "value": null },
+            { "op": "copy", "from": "", "path": "/d" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            p.display().to_string(),
+            "add /a~1b ← [1,\"x\"]\nremove /c/0\nreplace (document) ← null\ncopy (document) → /d"
+        );
+        assert_eq!(Patch::default().display().to_string(), "");
+        assert_eq!(p[1].to_string(), r#"{"op":"remove","path":"/c/0"}"#);
+    }
+
+    #[test]
+    fn hunks() {
+        let p: Patch = from_value(json!([
+            { "op": "test", "path": "/a~1b/c", "value": [1, 2] },
+            { "op": "move", "from": "/a~1b/c", "path": "/a~1b/d~0" },
+            { "op": "remove", "path": "/x" },
+            { "op": "add", "path": "/a~1b/e", "value": { "f": null } },
+            { "op": "replace", "path": "", "value": {} },
+            { "op": "remove", "path": "/y" },
+            { "op": "remove", "path": "invalid" },
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            render(&p),
+            r#"@@ /a~1b @@
+  c: [1,2]
++ d~ (moved from /a~1b/c)
+@@ (root) @@
+- x
+@@ /a~1b @@
++ e: {"f":null}
+@@ (document) @@
+-
++ {}
+@@ (root) @@
+- y
+@@ invalid @@
+-
+"#
+        );
+
+        let doc = json!({ "a/b": { "c": [1, 2] }, "x": true, "y": 1 });
+        assert_eq!(
+            render_with_document(&p, &doc),
+            r#"@@ /a~1b @@
+  c: [1,2]
++ d~: [1,2] (moved from /a~1b/c)
+@@ (root) @@
+- x: true
+@@ /a~1b @@
++ e: {"f":null}
+@@ (document) @@
+- {"a/b":{"d~":[1,2],"e":{"f":null}},"y":1}
++ {}
+@@ (root) @@
+- y
+! path does not exist
+@@ invalid @@
+-
+"#
+        );
+    }
+    #[cfg(feature = "html")]
+    #[test]
+    fn html() {
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a", "value": "<b> & 'c'" },
+            { "op": "remove", "path": "/x" },
+        ]))
+        .unwrap();
+
+        let html = super::render_html(&p, &json!({ "x": 1 }));
+        assert!(html.contains(
+            r#"<pre class="json-patch">
+<span class="hunk">@@ (root) @@</span>
+<span class="added">+ a: &quot;&lt;b&gt; &amp; &#39;c&#39;&quot;</span>
+<span class="removed">- x: 1</span>
+</pre>"#
+        ));
+        assert!(html.ends_with("</html>\n"));
+    }
+}
@@ -0,0 +1,173 @@
+use serde::Serialize;
+use serde_json::ser::{CompactFormatter, Formatter, PrettyFormatter};
+use std::io;
+
+/// Options controlling how patches (and other values of this crate) are serialized into JSON
+/// text.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{to_string_with_options, Patch, SerializeOptions};
+/// use serde_json::{from_value, json};
+///
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/café", "value": "☕" }
+/// ])).unwrap();
+///
+/// let options = SerializeOptions {
+///     escape_unicode: true,
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     to_string_with_options(&p, &options).unwrap(),
+///     r#"[{"op":"add","path":"/caf\u00e9","value":"\u2615"}]"#
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerializeOptions {
+    /// Escape all non-ASCII characters in strings (both paths and values) as `\uXXXX` sequences
+    /// (using surrogate pairs for characters outside of the Basic Multilingual Plane), so the
+    /// output is pure ASCII. By default, such characters are emitted as UTF-8.
+    pub escape_unicode: bool,
+    /// Emit indented, multi-line JSON.
+    pub pretty: bool,
+}
+
+/// Formatter escaping non-ASCII characters, delegating everything else to the inner formatter.
+struct AsciiFormatter<F>(F);
+
+macro_rules! delegate {
+    ($($name:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $name<W>(&mut self, writer: &mut W $(, $arg: $ty)*) -> io::Result<()>
+            where
+                W: ?Sized + io::Write,
+            {
+                self.0.$name(writer $(, $arg)*)
+            }
+        )*
+    };
+}
+
+impl<F: Formatter> Formatter for AsciiFormatter<F> {
+    delegate! {
+        begin_array();
+        end_array();
+        begin_array_value(first: bool);
+        end_array_value();
+        begin_object();
+        end_object();
+        begin_object_key(first: bool);
+        end_object_key();
+        begin_object_value();
+        end_object_value();
+    }
+
+    fn write_string_fragment<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        let mut start = 0;
+        for (idx, ch) in fragment.char_indices() {
+            if ch.is_ascii() {
+                continue;
+            }
+            writer.write_all(&fragment.as_bytes()[start..idx])?;
+            let mut buf = [0; 2];
+            for unit in ch.encode_utf16(&mut buf) {
+                write!(writer, "\\u{:04x}", unit)?;
+            }
+            start = idx + ch.len_utf8();
+        }
+        writer.write_all(&fragment.as_bytes()[start..])
+    }
+}
+
+fn serialize<T, W, F>(value: &T, writer: W, formatter: F) -> serde_json::Result<()>
+where
+    T: ?Sized + Serialize,
+    W: io::Write,
+    F: Formatter,
+{
+    let mut ser = serde_json::Serializer::with_formatter(writer, formatter);
+    value.serialize(&mut ser)
+}
+
+/// Serialize the given value (typically, a [`crate::Patch`]) as JSON into the IO stream, using
+/// provided options.
+pub fn to_writer_with_options<T, W>(
+    writer: W,
+    value: &T,
+    options: &SerializeOptions,
+) -> serde_json::Result<()>
+where
+    T: ?Sized + Serialize,
+    W: io::Write,
+{
+    match (options.escape_unicode, options.pretty) {
+        (false, false) => serialize(value, writer, CompactFormatter),
+        (false, true) => serialize(value, writer, PrettyFormatter::new()),
+        (true, false) => serialize(value, writer, AsciiFormatter(CompactFormatter)),
+        (true, true) => serialize(value, writer, AsciiFormatter(PrettyFormatter::new())),
+    }
+}
+
+/// Serialize the given value (typically, a [`crate::Patch`]) as a JSON string, using provided
+/// options.
+pub fn to_string_with_options<T>(
+    value: &T,
+    options: &SerializeOptions,
+) -> serde_json::Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let mut buf = Vec::new();
+    to_writer_with_options(&mut buf, value, options)?;
+    // Both serde_json and our formatter only emit valid UTF-8
+    Ok(String::from_utf8(buf).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_string_with_options, SerializeOptions};
+    use crate::Patch;
+    use serde_json::{from_str, from_value, json};
+
+    #[test]
+    fn escape_unicode_round_trip() {
+        let p: Patch = from_value(json!([
+            { "op": "replace", "path": "/ключ/~1", "value": ["😀", "ascii", "\n\u{7f}"] },
+        ]))
+        .unwrap();
+        let options = SerializeOptions {
+            escape_unicode: true,
+            ..Default::default()
+        };
+        let s = to_string_with_options(&p, &options).unwrap();
+        assert!(s.is_ascii());
+        assert!(s.contains(r#""\ud83d\ude00""#));
+        assert_eq!(from_str::<Patch>(&s).unwrap(), p);
+    }
+
+    #[test]
+    fn pretty_and_utf8() {
+        let p: Patch = from_value(json!([{ "op": "remove", "path": "/é" }])).unwrap();
+        let options = SerializeOptions {
+            pretty: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            to_string_with_options(&p, &options).unwrap(),
+            format!("{:#}", p)
+        );
+        let options = SerializeOptions {
+            pretty: true,
+            escape_unicode: true,
+        };
+        assert_eq!(
+            to_string_with_options(&p, &options).unwrap(),
+            "[\n  {\n    \"op\": \"remove\",\n    \"path\": \"/\\u00e9\"\n  }\n]"
+        );
+    }
+}
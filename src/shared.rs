@@ -0,0 +1,259 @@
+//! JSON documents sharing their unchanged subtrees between versions, for keeping many versions
+//! of large documents alive at once. Requires the `shared` feature.
+//!
+//! Arrays, objects and strings of a [`SharedValue`] are reference-counted, so cloning a document
+//! is cheap, and modifying a clone only copies the arrays and objects on the paths to the
+//! modified locations (copy-on-write), sharing everything else with the original document.
+//! Documents are patched and diffed with the functions of the [`generic`](crate::generic)
+//! module, and comparing versions sharing subtrees skips the shared ones.
+//!
+//! # Example
+//!
+//! ```rust
+//! use json_patch::shared::SharedValue;
+//! use json_patch::{generic, Patch};
+//! use serde_json::{from_value, json};
+//!
+//! let v1 = SharedValue::from(&json!({ "big": { "data": [1, 2, 3] }, "title": "Draft" }));
+//! let p: Patch = from_value(json!([
+//!   { "op": "replace", "path": "/title", "value": "Final" }
+//! ])).unwrap();
+//!
+//! let v2 = v1.patched(&p).unwrap();
+//! assert_eq!(v2.to_json(), json!({ "big": { "data": [1, 2, 3] }, "title": "Final" }));
+//! assert_eq!(v1.to_json()["title"], "Draft");
+//!
+//! // The unchanged subtree is shared between the versions
+//! assert!(v1.member("big").unwrap().ptr_eq(v2.member("big").unwrap()));
+//! assert_eq!(generic::diff(&v1, &v2), p);
+//! ```
+use crate::generic::{self, JsonLike};
+use crate::{PatchError, PatchOperation};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use serde_json::{Map, Number, Value};
+
+/// JSON value with reference-counted arrays, objects and strings, shared between the clones of
+/// the value until they are modified, see the [module documentation](self).
+///
+/// Object members are kept in the order of their keys.
+#[derive(Debug, Clone, Default)]
+pub enum SharedValue {
+    /// JSON `null`.
+    #[default]
+    Null,
+    /// JSON boolean.
+    Bool(bool),
+    /// JSON number.
+    Number(Number),
+    /// JSON string.
+    String(Arc<str>),
+    /// JSON array.
+    Array(Arc<Vec<SharedValue>>),
+    /// JSON object.
+    Object(Arc<BTreeMap<String, SharedValue>>),
+}
+
+impl SharedValue {
+    /// Returns the patched version of the value, sharing the subtrees the patch leaves unchanged
+    /// with this one, which is left intact.
+    pub fn patched(&self, patch: &[PatchOperation]) -> Result<SharedValue, PatchError> {
+        generic::patched(self, patch)
+    }
+
+    /// Converts the value into a `serde_json::Value`.
+    pub fn to_json(&self) -> Value {
+        JsonLike::to_json(self)
+    }
+
+    /// Returns the member of the object with the given key, `None` if the value is not an
+    /// object or has no such member.
+    pub fn member(&self, key: &str) -> Option<&SharedValue> {
+        match self {
+            SharedValue::Object(obj) => obj.get(key),
+            _ => None,
+        }
+    }
+
+    /// Checks if the two values are the same array, object or string, shared between them. Other
+    /// values are never shared.
+    pub fn ptr_eq(&self, other: &SharedValue) -> bool {
+        match (self, other) {
+            (SharedValue::String(left), SharedValue::String(right)) => Arc::ptr_eq(left, right),
+            (SharedValue::Array(left), SharedValue::Array(right)) => Arc::ptr_eq(left, right),
+            (SharedValue::Object(left), SharedValue::Object(right)) => Arc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for SharedValue {
+    fn eq(&self, other: &SharedValue) -> bool {
+        if self.ptr_eq(other) {
+            return true;
+        }
+        match (self, other) {
+            (SharedValue::Null, SharedValue::Null) => true,
+            (SharedValue::Bool(left), SharedValue::Bool(right)) => left == right,
+            (SharedValue::Number(left), SharedValue::Number(right)) => left == right,
+            (SharedValue::String(left), SharedValue::String(right)) => left == right,
+            (SharedValue::Array(left), SharedValue::Array(right)) => left == right,
+            (SharedValue::Object(left), SharedValue::Object(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl From<&Value> for SharedValue {
+    fn from(value: &Value) -> SharedValue {
+        match value {
+            Value::Null => SharedValue::Null,
+            Value::Bool(b) => SharedValue::Bool(*b),
+            Value::Number(n) => SharedValue::Number(n.clone()),
+            Value::String(s) => SharedValue::String(s.as_str().into()),
+            Value::Array(arr) => SharedValue::Array(Arc::new(arr.iter().map(Into::into).collect())),
+            Value::Object(obj) => SharedValue::Object(Arc::new(
+                obj.iter().map(|(k, v)| (k.clone(), v.into())).collect(),
+            )),
+        }
+    }
+}
+
+impl From<Value> for SharedValue {
+    fn from(value: Value) -> SharedValue {
+        SharedValue::from(&value)
+    }
+}
+
+impl From<&SharedValue> for Value {
+    fn from(value: &SharedValue) -> Value {
+        match value {
+            SharedValue::Null => Value::Null,
+            SharedValue::Bool(b) => Value::Bool(*b),
+            SharedValue::Number(n) => Value::Number(n.clone()),
+            SharedValue::String(s) => Value::String(String::from(&**s)),
+            SharedValue::Array(arr) => Value::Array(arr.iter().map(Into::into).collect()),
+            SharedValue::Object(obj) => Value::Object(
+                obj.iter()
+                    .map(|(k, v)| (k.clone(), v.into()))
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+impl JsonLike for SharedValue {
+    fn from_json(value: &Value) -> Self {
+        value.into()
+    }
+
+    fn to_json(&self) -> Value {
+        self.into()
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            SharedValue::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    fn as_array_mut(&mut self) -> Option<&mut [Self]> {
+        match self {
+            SharedValue::Array(arr) => Some(Arc::make_mut(arr).as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn insert_element(&mut self, index: usize, value: Self) {
+        match self {
+            SharedValue::Array(arr) => Arc::make_mut(arr).insert(index, value),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn remove_element(&mut self, index: usize) -> Self {
+        match self {
+            SharedValue::Array(arr) => Arc::make_mut(arr).remove(index),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn is_object(&self) -> bool {
+        matches!(self, SharedValue::Object(_))
+    }
+
+    fn members(&self) -> Option<Vec<(&str, &Self)>> {
+        match self {
+            SharedValue::Object(obj) => Some(obj.iter().map(|(k, v)| (k.as_str(), v)).collect()),
+            _ => None,
+        }
+    }
+
+    fn member(&self, key: &str) -> Option<&Self> {
+        SharedValue::member(self, key)
+    }
+
+    fn member_mut(&mut self, key: &str) -> Option<&mut Self> {
+        match self {
+            // Only copy the object if it has the member
+            SharedValue::Object(obj) if obj.contains_key(key) => Arc::make_mut(obj).get_mut(key),
+            _ => None,
+        }
+    }
+
+    fn insert_member(&mut self, key: String, value: Self) -> Option<Self> {
+        match self {
+            SharedValue::Object(obj) => Arc::make_mut(obj).insert(key, value),
+            _ => None,
+        }
+    }
+
+    fn remove_member(&mut self, key: &str) -> Option<Self> {
+        match self {
+            SharedValue::Object(obj) if obj.contains_key(key) => Arc::make_mut(obj).remove(key),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedValue;
+    use crate::{generic, Patch};
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn structural_sharing() {
+        let v1 = SharedValue::from(json!({
+            "a": { "b": [1, { "c": "x" }], "d": "y" },
+            "e": [true, null],
+        }));
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/a/b/1/f", "value": 2 },
+            { "op": "remove", "path": "/e/1" },
+        ]))
+        .unwrap();
+        let v2 = v1.patched(&p).unwrap();
+        assert_eq!(
+            v2.to_json(),
+            json!({ "a": { "b": [1, { "c": "x", "f": 2 }], "d": "y" }, "e": [true] })
+        );
+        assert_eq!(v1.to_json()["e"], json!([true, null]));
+
+        let (a1, a2) = (v1.member("a").unwrap(), v2.member("a").unwrap());
+        assert!(!a1.ptr_eq(a2));
+        assert!(a1.member("d").unwrap().ptr_eq(a2.member("d").unwrap()));
+        assert_eq!(generic::diff(&v1, &v2), p);
+
+        // Failed patches leave the value intact
+        let p: Patch = from_value(json!([
+            { "op": "remove", "path": "/a/d" },
+            { "op": "remove", "path": "/x" },
+        ]))
+        .unwrap();
+        assert!(v2.patched(&p).is_err());
+        let mut v3 = v2.clone();
+        assert!(generic::patch(&mut v3, &p).is_err());
+        assert_eq!(v3, v2);
+    }
+}
@@ -0,0 +1,227 @@
+//! Patching and diffing documents parsed with `simd-json`, see [`JsonLike`].
+//!
+//! Documents can either be kept as `simd_json::OwnedValue`s, patched with [`patch_simd`] and
+//! diffed with [`diff_simd`], or parsed with `simd-json` straight into `serde_json::Value`s with
+//! [`from_slice_simd`], to be used with the rest of the crate.
+use crate::generic::{self, JsonLike};
+use crate::{Patch, PatchError, PatchOperation};
+use serde_json::Value;
+use simd_json::prelude::*;
+use simd_json::{OwnedValue as Simd, StaticNode};
+
+impl JsonLike for Simd {
+    fn from_json(value: &Value) -> Self {
+        match value {
+            Value::Null => Simd::Static(StaticNode::Null),
+            Value::Bool(b) => Simd::from(*b),
+            Value::Number(n) => {
+                if let Some(n) = n.as_u64() {
+                    Simd::from(n)
+                } else if let Some(n) = n.as_i64() {
+                    Simd::from(n)
+                } else {
+                    Simd::from(n.as_f64().unwrap_or_default())
+                }
+            }
+            Value::String(s) => Simd::from(s.clone()),
+            Value::Array(arr) => Simd::Array(Box::new(arr.iter().map(Simd::from_json).collect())),
+            Value::Object(obj) => Simd::Object(Box::new(
+                obj.iter()
+                    .map(|(k, v)| (k.clone(), Simd::from_json(v)))
+                    .collect(),
+            )),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Simd::Static(node) => {
+                if let Some(b) = node.as_bool() {
+                    Value::Bool(b)
+                } else if let Some(n) = node.as_u64() {
+                    Value::from(n)
+                } else if let Some(n) = node.as_i64() {
+                    Value::from(n)
+                } else if let Some(n) = node.as_f64() {
+                    // Infinities and NaN have no JSON representation
+                    Value::from(n)
+                } else {
+                    Value::Null
+                }
+            }
+            Simd::String(s) => Value::String(s.clone()),
+            Simd::Array(arr) => Value::Array(arr.iter().map(Simd::to_json).collect()),
+            Simd::Object(obj) => {
+                Value::Object(obj.iter().map(|(k, v)| (k.clone(), v.to_json())).collect())
+            }
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Simd::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    fn as_array_mut(&mut self) -> Option<&mut [Self]> {
+        match self {
+            Simd::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    fn insert_element(&mut self, index: usize, value: Self) {
+        match self {
+            Simd::Array(arr) => arr.insert(index, value),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn remove_element(&mut self, index: usize) -> Self {
+        match self {
+            Simd::Array(arr) => arr.remove(index),
+            _ => unreachable!("not an array"),
+        }
+    }
+
+    fn is_object(&self) -> bool {
+        matches!(self, Simd::Object(_))
+    }
+
+    fn members(&self) -> Option<Vec<(&str, &Self)>> {
+        match self {
+            Simd::Object(obj) => Some(obj.iter().map(|(k, v)| (k.as_str(), v)).collect()),
+            _ => None,
+        }
+    }
+
+    fn member(&self, key: &str) -> Option<&Self> {
+        match self {
+            Simd::Object(obj) => obj.get(key),
+            _ => None,
+        }
+    }
+
+    fn member_mut(&mut self, key: &str) -> Option<&mut Self> {
+        match self {
+            Simd::Object(obj) => obj.get_mut(key),
+            _ => None,
+        }
+    }
+
+    fn insert_member(&mut self, key: String, value: Self) -> Option<Self> {
+        match self {
+            Simd::Object(obj) => obj.insert(key, value),
+            _ => None,
+        }
+    }
+
+    fn remove_member(&mut self, key: &str) -> Option<Self> {
+        match self {
+            Simd::Object(obj) => obj.remove(key),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the JSON document with `simd-json` into a `serde_json::Value`. The buffer is used as
+/// scratch space while parsing, so its contents are unspecified afterwards.
+///
+/// Requires the `simd-json` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{from_slice_simd, patch, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut bytes = br#"{ "name": "Andrew", "tags": [] }"#.to_vec();
+/// let mut doc = from_slice_simd(&mut bytes).unwrap();
+///
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/tags/-", "value": "new" },
+/// ])).unwrap();
+/// patch(&mut doc, &p).unwrap();
+/// assert_eq!(doc, json!({ "name": "Andrew", "tags": ["new"] }));
+/// ```
+pub fn from_slice_simd(bytes: &mut [u8]) -> Result<Value, simd_json::Error> {
+    simd_json::serde::from_slice(bytes)
+}
+
+/// Diff two `simd-json` values and generate a JSON Patch (RFC 6902), as [`diff`](crate::diff)
+/// does for JSON documents.
+///
+/// Requires the `simd-json` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{diff_simd, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let left = simd_json::to_owned_value(&mut br#"{ "a": 1, "b": [true] }"#.to_vec()).unwrap();
+/// let right = simd_json::to_owned_value(&mut br#"{ "a": 2, "b": [true] }"#.to_vec()).unwrap();
+///
+/// let p = diff_simd(&left, &right);
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/a", "value": 2 },
+/// ])).unwrap());
+/// ```
+pub fn diff_simd(left: &Simd, right: &Simd) -> Patch {
+    generic::diff(left, right)
+}
+
+/// Patch the `simd-json` value in place, as [`patch`](crate::patch) does for JSON documents. If
+/// any of the operations fails, all previous operations are reverted.
+///
+/// Requires the `simd-json` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_simd, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = simd_json::to_owned_value(&mut br#"{ "readings": [] }"#.to_vec()).unwrap();
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/readings/-", "value": 21.5 },
+/// ])).unwrap();
+///
+/// patch_simd(&mut doc, &p).unwrap();
+/// assert_eq!(doc, simd_json::json!({ "readings": [21.5] }));
+/// ```
+pub fn patch_simd(doc: &mut Simd, patch: &[PatchOperation]) -> Result<(), PatchError> {
+    generic::patch(doc, patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_simd, from_slice_simd, patch_simd};
+    use serde_json::json;
+
+    #[test]
+    fn round_trip() {
+        let left = json!({
+            "id": 1,
+            "big": u64::MAX,
+            "neg": i64::MIN,
+            "values": [1.5, null, "a", { "b": false }],
+        });
+        let right = json!({
+            "id": 2,
+            "big": i64::MIN,
+            "values": [1.5, { "b": true }],
+            "new": { "c": [] },
+        });
+        let text = |value: &serde_json::Value| value.to_string().into_bytes();
+        assert_eq!(from_slice_simd(&mut text(&left)).unwrap(), left);
+
+        let left = simd_json::to_owned_value(&mut text(&left)).unwrap();
+        let right = simd_json::to_owned_value(&mut text(&right)).unwrap();
+        let p = diff_simd(&left, &right);
+        let mut doc = left.clone();
+        patch_simd(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::estimate::serialized_len;
+use crate::{is_prefix, split_last, OpKind, Patch, PatchOperation};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
+use serde::Serialize;
+
+/// Statistics of a patch, as returned by [`Patch::stats`].
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct PatchStats {
+    /// Number of operations of each kind. Kinds not present in the patch are omitted.
+    pub operations: BTreeMap<OpKind, usize>,
+    /// Top-level JSON Pointers (like `/a`, or `""` for the whole document) of the locations
+    /// touched by the `path` and `from` pointers of the operations.
+    pub top_level_paths: BTreeSet<String>,
+    /// Total size in bytes of the compact JSON serialization of the values embedded into the
+    /// `add`, `replace` and `test` operations.
+    pub value_bytes: usize,
+    /// Maximum number of reference tokens in the `path` and `from` pointers.
+    pub max_pointer_depth: usize,
+}
+
+impl PatchStats {
+    fn pointer(&mut self, pointer: &str) {
+        let top_level = match pointer[1.min(pointer.len())..].find('/') {
+            Some(idx) => &pointer[..idx + 1],
+            None => pointer,
+        };
+        if !self.top_level_paths.contains(top_level) {
+            self.top_level_paths.insert(top_level.into());
+        }
+        let depth = pointer.matches('/').count();
+        self.max_pointer_depth = self.max_pointer_depth.max(depth);
+    }
+}
+
+impl Patch {
+    /// Returns the statistics of this patch: the number of operations of each kind, the top-level
+    /// locations touched, the total size of the embedded values and the maximum pointer depth.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{OpKind, Patch};
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "add", "path": "/a/b/c", "value": [1, 2] },
+    ///   { "op": "add", "path": "/a/d", "value": true },
+    ///   { "op": "move", "from": "/x", "path": "/y/0" }
+    /// ])).unwrap();
+    ///
+    /// let stats = p.stats();
+    /// assert_eq!(stats.operations[&OpKind::Add], 2);
+    /// assert_eq!(stats.operations[&OpKind::Move], 1);
+    /// assert_eq!(stats.top_level_paths, ["/a".into(), "/x".into(), "/y".into()].into());
+    /// assert_eq!(stats.value_bytes, 9);
+    /// assert_eq!(stats.max_pointer_depth, 3);
+    /// ```
+    pub fn stats(&self) -> PatchStats {
+        let mut stats = PatchStats::default();
+        for op in self {
+            *stats.operations.entry(op.op_kind()).or_default() += 1;
+            stats.pointer(op.path());
+            if let Some(from) = op.from() {
+                stats.pointer(from);
+            }
+            if let Some(value) = op.value() {
+                stats.value_bytes += serialized_len(value);
+            }
+        }
+        stats
+    }
+}
+
+/// JSON Pointers of the locations read and written by a patch, as returned by
+/// [`Patch::touched_pointers`].
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct TouchedPointers {
+    /// Locations whose values the operations depend on: `from` of `move` and `copy` operations
+    /// and `path` of `test` operations.
+    pub reads: BTreeSet<String>,
+    /// Locations whose values the operations may change: `path` of `add`, `remove`, `replace`,
+    /// `move` and `copy` operations and `from` of `move` operations. Inserting or removing an
+    /// array element shifts the elements after it, so the array itself is written then. Locations
+    /// inside of other written locations are omitted.
+    pub writes: BTreeSet<String>,
+}
+
+impl TouchedPointers {
+    /// Checks if the value at `pointer` may be changed by the patch, that is if the patch writes
+    /// to that location, to one of its ancestors or to one of its descendants.
+    pub fn is_written(&self, pointer: &str) -> bool {
+        self.writes
+            .iter()
+            .any(|write| is_prefix(write, pointer) || is_prefix(pointer, write))
+    }
+}
+
+/// Widens `pointer` to the array an earlier operation shifted elements of, if it points inside
+/// of one, as the element it refers to is not the one at that index before the patch.
+fn widen<'a>(shifted: &[&'a str], pointer: &'a str) -> &'a str {
+    shifted
+        .iter()
+        .find(|array| pointer.len() > array.len() && is_prefix(array, pointer))
+        .copied()
+        .unwrap_or(pointer)
+}
+
+/// Returns the array whose elements are shifted by inserting or removing the value at `pointer`.
+fn shifted_array(pointer: &str) -> Option<&str> {
+    let (parent, last) = split_last(pointer).ok()?;
+    let index = last == "-" || (!last.is_empty() && last.bytes().all(|b| b.is_ascii_digit()));
+    index.then_some(parent)
+}
+
+impl Patch {
+    /// Returns the locations the patch reads and writes (see [`TouchedPointers`]), for example to
+    /// invalidate cached values derived from the document after applying it.
+    ///
+    /// Pointers are collected as they appear in the operations, except that pointers below an
+    /// array whose elements were shifted by an earlier operation are replaced by the pointer to
+    /// the array. Since the array is then written as a whole, the result never misses a location
+    /// of the original document the patch reads or writes, though it may include more of them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::Patch;
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "test", "path": "/user/name", "value": "a" },
+    ///   { "op": "remove", "path": "/items/0" },
+    ///   { "op": "copy", "from": "/items/1/price", "path": "/total" }
+    /// ])).unwrap();
+    ///
+    /// let touched = p.touched_pointers();
+    /// assert_eq!(touched.reads, ["/items".into(), "/user/name".into()].into());
+    /// assert_eq!(touched.writes, ["/items".into(), "/total".into()].into());
+    /// assert!(touched.is_written("/items/3/price"));
+    /// assert!(!touched.is_written("/user"));
+    /// ```
+    pub fn touched_pointers(&self) -> TouchedPointers {
+        let mut touched = TouchedPointers::default();
+        let mut shifted: Vec<&str> = Vec::new();
+        for op in self {
+            let (from, path) = match op {
+                PatchOperation::Move(op) => (Some(op.from.as_str()), Some(op.path.as_str())),
+                PatchOperation::Copy(op) => (Some(op.from.as_str()), Some(op.path.as_str())),
+                PatchOperation::Test(op) => {
+                    touched.reads.insert(widen(&shifted, &op.path).into());
+                    continue;
+                }
+                _ => (None, Some(op.path())),
+            };
+            if let Some(from) = from {
+                let from = widen(&shifted, from);
+                touched.reads.insert(from.into());
+                if let PatchOperation::Move(_) = op {
+                    touched.writes.insert(from.into());
+                    shifted.extend(shifted_array(from));
+                }
+            }
+            if let Some(path) = path {
+                let path = widen(&shifted, path);
+                touched.writes.insert(path.into());
+                if !matches!(op, PatchOperation::Replace(_)) {
+                    shifted.extend(shifted_array(path));
+                }
+            }
+        }
+        for array in &shifted {
+            touched.writes.insert((*array).into());
+        }
+        let writes = touched.writes.clone();
+        touched.writes.retain(|write| {
+            !writes
+                .iter()
+                .any(|other| other.len() < write.len() && is_prefix(other, write))
+        });
+        touched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OpKind, Patch};
+    use alloc::string::String;
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn stats() {
+        let p: Patch = from_value(json!([
+            { "op": "replace", "path": "", "value": {} },
+            { "op": "test", "path": "/a~1b", "value": "xyz" },
+            { "op": "remove", "path": "/a~1b" },
+            { "op": "copy", "from": "/c/d", "path": "/" },
+        ]))
+        .unwrap();
+
+        let stats = p.stats();
+        assert_eq!(
+            stats.operations,
+            [
+                (OpKind::Remove, 1),
+                (OpKind::Replace, 1),
+                (OpKind::Copy, 1),
+                (OpKind::Test, 1)
+            ]
+            .into()
+        );
+        assert_eq!(
+            stats.top_level_paths,
+            ["".into(), "/".into(), "/a~1b".into(), "/c".into()].into()
+        );
+        assert_eq!(stats.value_bytes, 7);
+        assert_eq!(stats.max_pointer_depth, 2);
+
+        assert_eq!(Patch::default().stats(), Default::default());
+    }
+
+    #[test]
+    fn touched_pointers() {
+        let p: Patch = from_value(json!([
+            { "op": "replace", "path": "/a/x", "value": 1 },
+            { "op": "test", "path": "/a/x", "value": 1 },
+            { "op": "move", "from": "/b/2", "path": "/c/k" },
+            { "op": "copy", "from": "/b/3/y", "path": "/d/-" },
+            { "op": "test", "path": "/d/0", "value": 1 },
+            { "op": "add", "path": "/e", "value": [] },
+            { "op": "add", "path": "/e/f", "value": 1 },
+            { "op": "replace", "path": "/g/0", "value": 1 },
+            { "op": "test", "path": "/g/1", "value": 1 },
+        ]))
+        .unwrap();
+        let touched = p.touched_pointers();
+        assert_eq!(
+            touched.reads,
+            ["/a/x", "/b", "/b/2", "/d", "/g/1"]
+                .map(String::from)
+                .into()
+        );
+        assert_eq!(
+            touched.writes,
+            ["/a/x", "/b", "/c/k", "/d", "/e", "/g/0"]
+                .map(String::from)
+                .into()
+        );
+        assert!(touched.is_written("/a"));
+        assert!(touched.is_written("/b/0/z"));
+        assert!(!touched.is_written("/a/y"));
+        assert!(!touched.is_written("/g/1"));
+        assert!(touched.is_written(""));
+
+        let p: Patch = from_value(json!([{ "op": "remove", "path": "" }])).unwrap();
+        assert_eq!(p.touched_pointers().writes, ["".into()].into());
+        assert_eq!(Patch::default().touched_pointers(), Default::default());
+    }
+}
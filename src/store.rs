@@ -0,0 +1,499 @@
+use crate::deep::values_equal;
+use crate::{parse_index, test_failed, translate_error};
+use crate::{PatchError, PatchErrorKind, PatchOperation, Pointer};
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use serde_json::Value;
+use thiserror::Error;
+
+/// JSON document accessed location by location, for applying patches with [`patch_store`] to
+/// documents which are not kept in memory as a whole, like documents living in a database or
+/// loaded lazily.
+///
+/// Arrays are always read and written as a whole when elements are inserted into or removed from
+/// them, so stores only ever deal with object members and whole values:
+///
+/// * [`set`](DocumentStore::set) is called for the root of the document, a member of an existing
+///   object (adding or replacing it) or an existing element of an array (replacing it);
+/// * [`remove`](DocumentStore::remove) is called for an existing member of an object.
+///
+/// The applier reads the values it replaces or removes, to revert the document if the patch
+/// fails, and checks the [`shape`](DocumentStore::shape) of the parents of the locations it adds
+/// values to or removes them from.
+///
+/// `serde_json::Value` implements the trait, storing the document in memory.
+pub trait DocumentStore {
+    /// Error of accessing the document.
+    type Error;
+
+    /// Returns the value at the location, or `None` if there is no such value.
+    fn get(&self, pointer: &Pointer) -> Result<Option<Value>, Self::Error>;
+
+    /// Sets the value at the location.
+    fn set(&mut self, pointer: &Pointer, value: Value) -> Result<(), Self::Error>;
+
+    /// Removes the member of an object at the location.
+    fn remove(&mut self, pointer: &Pointer) -> Result<(), Self::Error>;
+
+    /// Returns the shape of the value at the location, or `None` if there is no such value.
+    ///
+    /// Reads the whole value by default. Stores can provide a cheaper implementation, without
+    /// loading large objects just to add a member to them.
+    fn shape(&self, pointer: &Pointer) -> Result<Option<Shape>, Self::Error> {
+        Ok(self.get(pointer)?.as_ref().map(Shape::of))
+    }
+}
+
+/// Shape of a value of a [`DocumentStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// JSON object.
+    Object,
+    /// JSON array with the given number of elements.
+    Array(usize),
+    /// Any other JSON value.
+    Scalar,
+}
+
+impl Shape {
+    /// Returns the shape of the value.
+    pub fn of(value: &Value) -> Shape {
+        match value {
+            Value::Object(_) => Shape::Object,
+            Value::Array(arr) => Shape::Array(arr.len()),
+            _ => Shape::Scalar,
+        }
+    }
+}
+
+/// This type represents all possible errors that can occur when applying JSON patch to a
+/// [`DocumentStore`] with [`patch_store`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum StoreError<E> {
+    /// Patch cannot be applied to the document.
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+    /// Document cannot be accessed.
+    #[error("operation '/{operation}': cannot access the document: {source}")]
+    Store {
+        /// Index of the operation accessing the document.
+        operation: usize,
+        /// Error of the store.
+        source: E,
+    },
+}
+
+impl DocumentStore for Value {
+    type Error = Infallible;
+
+    fn get(&self, pointer: &Pointer) -> Result<Option<Value>, Infallible> {
+        Ok(self.pointer(pointer.as_str()).cloned())
+    }
+
+    fn set(&mut self, pointer: &Pointer, value: Value) -> Result<(), Infallible> {
+        let (Some(parent), Some(last)) = (pointer.parent(), pointer.last()) else {
+            *self = value;
+            return Ok(());
+        };
+        match self.pointer_mut(parent.as_str()) {
+            Some(Value::Object(obj)) => {
+                obj.insert(last.into_owned(), value);
+            }
+            Some(Value::Array(arr)) => {
+                if let Some(element) = last.parse().ok().and_then(|idx: usize| arr.get_mut(idx)) {
+                    *element = value;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, pointer: &Pointer) -> Result<(), Infallible> {
+        if let (Some(parent), Some(last)) = (pointer.parent(), pointer.last()) {
+            if let Some(Value::Object(obj)) = self.pointer_mut(parent.as_str()) {
+                crate::remove_member(obj, &last);
+            }
+        }
+        Ok(())
+    }
+
+    fn shape(&self, pointer: &Pointer) -> Result<Option<Shape>, Infallible> {
+        Ok(self.pointer(pointer.as_str()).map(Shape::of))
+    }
+}
+
+/// Failure of an operation applied to a store.
+enum Failure<E> {
+    Patch(PatchErrorKind),
+    Store(E),
+}
+
+impl<E> From<PatchErrorKind> for Failure<E> {
+    fn from(kind: PatchErrorKind) -> Self {
+        Failure::Patch(kind)
+    }
+}
+
+/// Change made to the store, to revert it.
+enum Undo {
+    Set(Pointer, Value),
+    Remove(Pointer),
+}
+
+/// Applies operations to the store, recording the changes made to it.
+struct Applier<'a, S: ?Sized> {
+    store: &'a mut S,
+    undo_stack: Vec<Undo>,
+}
+
+impl<S: DocumentStore + ?Sized> Applier<'_, S> {
+    fn get(&self, pointer: &Pointer) -> Result<Option<Value>, Failure<S::Error>> {
+        self.store.get(pointer).map_err(Failure::Store)
+    }
+
+    fn shape(&self, pointer: &Pointer) -> Result<Option<Shape>, Failure<S::Error>> {
+        self.store.shape(pointer).map_err(Failure::Store)
+    }
+
+    /// Sets the value at the location, which had the value `prev`.
+    fn set(
+        &mut self,
+        pointer: &Pointer,
+        value: Value,
+        prev: Option<Value>,
+    ) -> Result<(), Failure<S::Error>> {
+        self.store.set(pointer, value).map_err(Failure::Store)?;
+        self.undo_stack.push(match prev {
+            Some(prev) => Undo::Set(pointer.clone(), prev),
+            None => Undo::Remove(pointer.clone()),
+        });
+        Ok(())
+    }
+
+    /// Returns the array at the location, which the store reported to be an array.
+    fn array(&self, pointer: &Pointer) -> Result<Vec<Value>, Failure<S::Error>> {
+        match self.get(pointer)? {
+            Some(Value::Array(arr)) => Ok(arr),
+            _ => Err(Failure::Patch(PatchErrorKind::PathNotFound)),
+        }
+    }
+
+    fn add(&mut self, path: &Pointer, value: Value) -> Result<(), Failure<S::Error>> {
+        let (Some(parent), Some(last)) = (path.parent(), path.last()) else {
+            let prev = self.get(path)?;
+            return self.set(path, value, prev);
+        };
+        match self.shape(&parent)? {
+            Some(Shape::Object) => {
+                let prev = self.get(path)?;
+                self.set(path, value, prev)
+            }
+            Some(Shape::Array(len)) => {
+                let idx = match &*last {
+                    "-" => len,
+                    _ => parse_index(&last, len + 1)?,
+                };
+                let mut arr = self.array(&parent)?;
+                let prev = arr.clone();
+                arr.insert(idx, value);
+                self.set(&parent, Value::Array(arr), Some(Value::Array(prev)))
+            }
+            Some(Shape::Scalar) => Err(PatchErrorKind::InvalidPointer.into()),
+            None => Err(PatchErrorKind::PathNotFound.into()),
+        }
+    }
+
+    fn remove(&mut self, path: &Pointer) -> Result<Value, Failure<S::Error>> {
+        let (Some(parent), Some(last)) = (path.parent(), path.last()) else {
+            return Err(PatchErrorKind::InvalidPointer.into());
+        };
+        match self.shape(&parent)? {
+            Some(Shape::Object) => {
+                let prev = self.get(path)?.ok_or(PatchErrorKind::PathNotFound)?;
+                self.store.remove(path).map_err(Failure::Store)?;
+                self.undo_stack.push(Undo::Set(path.clone(), prev.clone()));
+                Ok(prev)
+            }
+            Some(Shape::Array(len)) => {
+                let idx = parse_index(&last, len)?;
+                let mut arr = self.array(&parent)?;
+                let prev = arr.clone();
+                let removed = arr.remove(idx);
+                self.set(&parent, Value::Array(arr), Some(Value::Array(prev)))?;
+                Ok(removed)
+            }
+            Some(Shape::Scalar) => Err(PatchErrorKind::InvalidPointer.into()),
+            None => Err(PatchErrorKind::PathNotFound.into()),
+        }
+    }
+
+    fn replace(&mut self, path: &Pointer, value: Value) -> Result<(), Failure<S::Error>> {
+        let prev = self.get(path)?.ok_or(PatchErrorKind::PathNotFound)?;
+        self.set(path, value, Some(prev))
+    }
+
+    fn mov(&mut self, from: &Pointer, path: &Pointer) -> Result<(), Failure<S::Error>> {
+        if from != path && from.is_prefix_of(path) {
+            return Err(PatchErrorKind::CannotMoveInsideItself.into());
+        }
+        let value = self.remove(from).map_err(|err| match err {
+            Failure::Patch(
+                PatchErrorKind::InvalidPointer
+                | PatchErrorKind::PathNotFound
+                | PatchErrorKind::IndexOutOfBounds,
+            ) => Failure::Patch(PatchErrorKind::InvalidFromPointer),
+            err => err,
+        })?;
+        self.add(path, value)
+    }
+
+    fn copy(&mut self, from: &Pointer, path: &Pointer) -> Result<(), Failure<S::Error>> {
+        let value = self.get(from)?.ok_or(PatchErrorKind::InvalidFromPointer)?;
+        self.add(path, value)
+    }
+
+    fn test(&self, path: &Pointer, expected: &Value) -> Result<(), Failure<S::Error>> {
+        match self.get(path)? {
+            Some(actual) if values_equal(&actual, expected) => Ok(()),
+            actual => Err(test_failed(expected, actual.as_ref()).into()),
+        }
+    }
+
+    fn apply(&mut self, op: &PatchOperation) -> Result<(), Failure<S::Error>> {
+        let path = Pointer::parse(op.path()).map_err(|_| PatchErrorKind::InvalidPointer)?;
+        let from = match op.from() {
+            Some(from) => {
+                Some(Pointer::parse(from).map_err(|_| PatchErrorKind::InvalidFromPointer)?)
+            }
+            None => None,
+        };
+        match (op, from) {
+            (PatchOperation::Add(op), _) => self.add(&path, op.value.clone()),
+            (PatchOperation::Remove(_), _) => self.remove(&path).map(drop),
+            (PatchOperation::Replace(op), _) => self.replace(&path, op.value.clone()),
+            (PatchOperation::Move(_), Some(from)) => self.mov(&from, &path),
+            (PatchOperation::Copy(_), Some(from)) => self.copy(&from, &path),
+            (PatchOperation::Test(op), _) => self.test(&path, &op.value),
+            _ => unreachable!("operation without \"from\" path"),
+        }
+    }
+
+    /// Reverts all the changes made to the store.
+    fn revert(&mut self) -> Result<(), S::Error> {
+        while let Some(undo) = self.undo_stack.pop() {
+            match undo {
+                Undo::Set(pointer, value) => self.store.set(&pointer, value)?,
+                Undo::Remove(pointer) => self.store.remove(&pointer)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Patch the document of the store in place, the same way [`patch`](crate::patch) patches a
+/// `serde_json::Value`, accessing it through the [`DocumentStore`] trait. If any of the
+/// operations fails, all the changes made by the previous ones are reverted.
+///
+/// If the store fails to access the document, [`StoreError::Store`] is returned after reverting
+/// the changes. If the store also fails while reverting them, that failure is returned instead,
+/// and the document is left partially patched.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_store, DocumentStore, Patch, Pointer, Shape};
+/// use serde_json::{from_value, json, Value};
+/// use std::collections::BTreeMap;
+///
+/// /// Document stored as one record per top-level member, the records being loaded as needed.
+/// #[derive(Default)]
+/// struct Records(BTreeMap<String, Value>);
+///
+/// impl Records {
+///     /// Splits the pointer into the key of the record and the pointer into the record.
+///     fn locate(pointer: &Pointer) -> Result<(String, Pointer), String> {
+///         let mut tokens = pointer.tokens();
+///         let key = tokens.next().ok_or("cannot access the whole document")?;
+///         Ok((key.into_owned(), Pointer::from_tokens(tokens)))
+///     }
+/// }
+///
+/// impl DocumentStore for Records {
+///     type Error = String;
+///
+///     fn get(&self, pointer: &Pointer) -> Result<Option<Value>, String> {
+///         let (key, pointer) = Records::locate(pointer)?;
+///         Ok(self.0.get(&key).and_then(|record| record.pointer(pointer.as_str())).cloned())
+///     }
+///
+///     fn set(&mut self, pointer: &Pointer, value: Value) -> Result<(), String> {
+///         let (key, pointer) = Records::locate(pointer)?;
+///         let record = self.0.entry(key).or_default();
+///         record.set(&pointer, value).map_err(|e| match e {})
+///     }
+///
+///     fn remove(&mut self, pointer: &Pointer) -> Result<(), String> {
+///         let (key, pointer) = Records::locate(pointer)?;
+///         if pointer.is_root() {
+///             self.0.remove(&key);
+///         } else if let Some(record) = self.0.get_mut(&key) {
+///             record.remove(&pointer).unwrap_or_else(|e| match e {});
+///         }
+///         Ok(())
+///     }
+///
+///     fn shape(&self, pointer: &Pointer) -> Result<Option<Shape>, String> {
+///         // The document is an object, without loading all the records
+///         if pointer.is_root() {
+///             return Ok(Some(Shape::Object));
+///         }
+///         Ok(self.get(pointer)?.as_ref().map(Shape::of))
+///     }
+/// }
+///
+/// let mut records = Records::default();
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/user", "value": { "name": "Andrew", "tags": [] } },
+///   { "op": "add", "path": "/user/tags/-", "value": "new" },
+///   { "op": "copy", "from": "/user/name", "path": "/author" }
+/// ])).unwrap();
+///
+/// patch_store(&mut records, &p).unwrap();
+/// assert_eq!(records.0["user"], json!({ "name": "Andrew", "tags": ["new"] }));
+/// assert_eq!(records.0["author"], "Andrew");
+/// ```
+pub fn patch_store<S>(store: &mut S, patch: &[PatchOperation]) -> Result<(), StoreError<S::Error>>
+where
+    S: DocumentStore + ?Sized,
+{
+    let mut applier = Applier {
+        store,
+        undo_stack: Vec::new(),
+    };
+    for (operation, op) in patch.iter().enumerate() {
+        let Err(failure) = applier.apply(op) else {
+            continue;
+        };
+        applier
+            .revert()
+            .map_err(|source| StoreError::Store { operation, source })?;
+        return Err(match failure {
+            Failure::Patch(kind) => translate_error(kind, operation, op.path()).into(),
+            Failure::Store(source) => StoreError::Store { operation, source },
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{patch_store, DocumentStore, Shape, StoreError};
+    use crate::{patch, Patch, PatchErrorKind, Pointer};
+    use alloc::{format, string::String, vec::Vec};
+    use serde_json::{from_value, json, Value};
+
+    /// Store failing to access the locations under `/locked`, and logging its accesses.
+    struct Faulty(Value, Vec<String>);
+
+    impl DocumentStore for Faulty {
+        type Error = &'static str;
+
+        fn get(&self, pointer: &Pointer) -> Result<Option<Value>, &'static str> {
+            DocumentStore::get(&self.0, pointer).map_err(|e| match e {})
+        }
+
+        fn set(&mut self, pointer: &Pointer, value: Value) -> Result<(), &'static str> {
+            if pointer.as_str().starts_with("/locked") {
+                return Err("locked");
+            }
+            self.1.push(format!("set {pointer}"));
+            self.0.set(pointer, value).map_err(|e| match e {})
+        }
+
+        fn remove(&mut self, pointer: &Pointer) -> Result<(), &'static str> {
+            self.1.push(format!("remove {pointer}"));
+            DocumentStore::remove(&mut self.0, pointer).map_err(|e| match e {})
+        }
+
+        fn shape(&self, pointer: &Pointer) -> Result<Option<Shape>, &'static str> {
+            self.0.shape(pointer).map_err(|e| match e {})
+        }
+    }
+
+    #[test]
+    fn same_as_in_memory() {
+        let doc = json!({ "a": { "b": [1, 2, { "c": 3 }] }, "d": "e", "": [] });
+        for p in [
+            json!([
+                { "op": "add", "path": "/a/b/1", "value": 4 },
+                { "op": "remove", "path": "/a/b/0" },
+                { "op": "replace", "path": "/d", "value": null },
+                { "op": "move", "from": "/a/b/2/c", "path": "/a/b/-" },
+                { "op": "copy", "from": "/a", "path": "/~1" },
+                { "op": "test", "path": "/~1/b", "value": [4, 2, {}, 3] },
+                { "op": "add", "path": "", "value": { "x": 1 } },
+            ]),
+            json!([{ "op": "move", "from": "/a/b/0", "path": "/a/b/0" }]),
+            json!([{ "op": "add", "path": "//-", "value": 1 }]),
+            json!([{ "op": "add", "path": "/a/b/4", "value": 1 }]),
+            json!([{ "op": "add", "path": "/d/x", "value": 1 }]),
+            json!([{ "op": "add", "path": "/x/y", "value": 1 }]),
+            json!([{ "op": "remove", "path": "/a/b/-" }]),
+            json!([{ "op": "remove", "path": "/a/x" }]),
+            json!([{ "op": "remove", "path": "" }]),
+            json!([{ "op": "replace", "path": "/x", "value": 1 }]),
+            json!([{ "op": "move", "from": "/a", "path": "/a/b" }]),
+            json!([{ "op": "move", "from": "/x", "path": "/y" }]),
+            json!([{ "op": "copy", "from": "/x", "path": "/y" }]),
+            json!([{ "op": "test", "path": "/d", "value": "f" }]),
+            json!([{ "op": "test", "path": "/x", "value": "f" }]),
+            json!([{ "op": "add", "path": "x", "value": 1 }]),
+        ] {
+            let p: Patch = from_value(p).unwrap();
+            let mut expected = doc.clone();
+            let expected_result = patch(&mut expected, &p).map_err(|e| e.to_string());
+            let mut store = doc.clone();
+            let result = patch_store(&mut store, &p).map_err(|e| e.to_string());
+            assert_eq!((&store, result), (&expected, expected_result), "{p}");
+        }
+    }
+
+    #[test]
+    fn store_failures() {
+        let mut store = Faulty(json!({ "a": [1], "locked": {} }), Vec::new());
+        let p: Patch = from_value(json!([
+            { "op": "remove", "path": "/a/0" },
+            { "op": "add", "path": "/b", "value": 2 },
+            { "op": "add", "path": "/locked/c", "value": 3 },
+        ]))
+        .unwrap();
+        let err = patch_store(&mut store, &p).unwrap_err();
+        assert!(matches!(
+            err,
+            StoreError::Store {
+                operation: 2,
+                source: "locked"
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "operation '/2': cannot access the document: locked"
+        );
+        assert_eq!(store.0, json!({ "a": [1], "locked": {} }));
+        assert_eq!(store.1, ["set /a", "set /b", "remove /b", "set /a"]);
+
+        let p: Patch = from_value(json!([
+            { "op": "add", "path": "/b", "value": 2 },
+            { "op": "test", "path": "/b", "value": 3 },
+        ]))
+        .unwrap();
+        let err = patch_store(&mut store, &p).unwrap_err();
+        let StoreError::Patch(err) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert!(matches!(err.kind, PatchErrorKind::TestFailed { .. }));
+        assert_eq!(store.0, json!({ "a": [1], "locked": {} }));
+    }
+}
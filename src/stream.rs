@@ -0,0 +1,758 @@
+use crate::{PatchError, PatchErrorKind, PatchOperation};
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::error::Category;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::slice;
+use thiserror::Error;
+
+/// Error of reading or applying a stream of patch operations, see [`PatchReader`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum StreamError {
+    /// Reading the stream failed.
+    #[error("cannot read the stream: {0}")]
+    Io(#[from] io::Error),
+    /// Line of the stream is not a valid patch operation.
+    #[error("invalid operation on line {line}: {source}")]
+    Parse {
+        /// Number of the line, starting from 1.
+        line: usize,
+        /// Deserialization error.
+        #[source]
+        source: serde_json::Error,
+    },
+    /// Operation cannot be applied. [`PatchError::operation`] is the index of the operation in
+    /// the stream.
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+    /// Document is not valid JSON, see [`patch_stream`].
+    #[error("invalid document: {0}")]
+    Document(#[source] serde_json::Error),
+}
+
+/// Reader of patch operations stored one per line (JSON Lines, also known as NDJSON), as written
+/// by [`PatchWriter`]. Blank lines are skipped.
+///
+/// The reader is an iterator over the operations, ending when the end of the underlying reader is
+/// reached. If more lines are appended to the underlying reader afterwards (for example, a log
+/// file being written to), iteration can be resumed. An incomplete last line is kept until the rest
+/// of it is available.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::PatchReader;
+/// use serde_json::json;
+///
+/// let log = br#"{"op":"add","path":"/readings","value":[]}
+/// {"op":"add","path":"/readings/-","value":21.5}
+///
+/// {"op":"add","path":"/readings/-","value":22.0}
+/// "#;
+///
+/// let mut doc = json!({});
+/// let mut reader = PatchReader::new(&log[..]);
+/// assert_eq!(reader.apply(&mut doc).unwrap(), 3);
+/// assert_eq!(doc, json!({ "readings": [21.5, 22.0] }));
+/// ```
+#[derive(Debug)]
+pub struct PatchReader<R> {
+    reader: R,
+    buf: String,
+    line: usize,
+    line_open: bool,
+    operations: usize,
+}
+
+impl<R: BufRead> PatchReader<R> {
+    /// Creates a reader of operations from the given reader.
+    pub fn new(reader: R) -> PatchReader<R> {
+        PatchReader {
+            reader,
+            buf: String::new(),
+            line: 0,
+            line_open: false,
+            operations: 0,
+        }
+    }
+
+    /// Returns the number of operations read so far.
+    pub fn operations(&self) -> usize {
+        self.operations
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the remaining operations and applies them to the document as they are read,
+    /// returning the number of applied operations. Every operation is applied atomically, but
+    /// operations applied before a failure are kept, as are the ones applied by previous calls.
+    pub fn apply(&mut self, doc: &mut Value) -> Result<usize, StreamError> {
+        let mut applied = 0;
+        while let Some(op) = self.next() {
+            let op = op?;
+            if let Err(mut err) = crate::patch(doc, slice::from_ref(&op)) {
+                err.operation = self.operations - 1;
+                return Err(err.into());
+            }
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    fn read_operation(&mut self) -> Result<Option<PatchOperation>, StreamError> {
+        loop {
+            self.reader.read_line(&mut self.buf)?;
+            if self.line_open && self.buf.starts_with('\n') {
+                // End of the last line, consumed before its line end was available
+                self.buf.remove(0);
+            }
+            self.line_open = false;
+            let complete = self.buf.ends_with('\n');
+            let line = self.buf.trim();
+            if line.is_empty() {
+                if !complete {
+                    return Ok(None);
+                }
+                self.line += 1;
+                self.buf.clear();
+                continue;
+            }
+            let result = serde_json::from_str(line);
+            if matches!(&result, Err(err) if err.is_eof()) && !complete {
+                // The rest of the line is not written yet
+                return Ok(None);
+            }
+            self.line += 1;
+            self.line_open = !complete;
+            self.buf.clear();
+            return match result {
+                Ok(op) => {
+                    self.operations += 1;
+                    Ok(Some(op))
+                }
+                Err(source) => Err(StreamError::Parse {
+                    line: self.line,
+                    source,
+                }),
+            };
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PatchReader<R> {
+    type Item = Result<PatchOperation, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_operation().transpose()
+    }
+}
+
+/// Writer of patch operations, one per line (JSON Lines, also known as NDJSON), to be read by
+/// [`PatchReader`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{PatchOperation, PatchWriter};
+/// use serde_json::json;
+///
+/// let mut writer = PatchWriter::new(Vec::new());
+/// writer.write(&PatchOperation::add("/a", json!(1))).unwrap();
+/// writer.write(&PatchOperation::remove("/b")).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(writer.into_inner()).unwrap(),
+///     "{\"op\":\"add\",\"path\":\"/a\",\"value\":1}\n{\"op\":\"remove\",\"path\":\"/b\"}\n"
+/// );
+/// ```
+#[derive(Debug)]
+pub struct PatchWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> PatchWriter<W> {
+    /// Creates a writer of operations into the given writer.
+    pub fn new(writer: W) -> PatchWriter<W> {
+        PatchWriter { writer }
+    }
+
+    /// Writes the operation as a single line.
+    pub fn write(&mut self, op: &PatchOperation) -> io::Result<()> {
+        // Write the line at once, so concurrent readers never see a line without its end
+        let mut line = serde_json::to_vec(op)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)
+    }
+
+    /// Writes all the operations, one per line.
+    pub fn write_all(&mut self, ops: &[PatchOperation]) -> io::Result<()> {
+        for op in ops {
+            self.write(op)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Apply the patch to a JSON document read from `reader`, writing the patched document to
+/// `writer` as it is read, without loading the whole document in memory.
+///
+/// Only the parts of the document the patch works on are loaded: the values that are replaced or
+/// tested, the members of objects that are added, removed, moved or copied, and the arrays whose
+/// elements are (since inserting or removing an element shifts the others). The rest of the
+/// document is written as it is read, in compact form. An operation with an empty `path` or
+/// `from` loads the whole document.
+///
+/// Operations in different parts of the document are applied as those parts are read, so the
+/// patch has the same effect as if it were applied with [`crate::patch`], except that members
+/// of an object the patch adds, removes or replaces are written after the other members.
+/// Unlike [`crate::patch`], the output cannot be reverted: if the patch fails, or the document
+/// cannot be read, the partially written output should be discarded. The error reported is the
+/// one of the first failing part of the document, which might not be the first failing
+/// operation of the patch.
+///
+/// Neither the reader nor the writer are buffered, consider wrapping them in [`io::BufReader`]
+/// and [`io::BufWriter`].
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_stream, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let doc = br#"{ "archive": [1, 2, 3], "meta": { "tags": ["a"], "version": 1 } }"#;
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/meta/version", "value": 2 },
+///   { "op": "add", "path": "/meta/tags/-", "value": "b" }
+/// ])).unwrap();
+///
+/// let mut out = Vec::new();
+/// patch_stream(&doc[..], &mut out, &p).unwrap();
+/// assert_eq!(
+///     String::from_utf8(out).unwrap(),
+///     r#"{"archive":[1,2,3],"meta":{"tags":["a","b"],"version":2}}"#
+/// );
+/// ```
+pub fn patch_stream<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    patch: &[PatchOperation],
+) -> Result<(), StreamError> {
+    let plan = Node::plan(patch);
+    let mut state = State {
+        patch,
+        writer,
+        applied: vec![false; patch.len()],
+        error: None,
+    };
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    let result = Transcode {
+        state: &mut state,
+        node: Some(&plan),
+        depth: 0,
+        separator: false,
+    }
+    .deserialize(&mut de)
+    .and_then(|()| de.end());
+    if let Some(err) = state.error {
+        return Err(err);
+    }
+    if let Err(err) = result {
+        return Err(match err.classify() {
+            Category::Io => StreamError::Io(err.into()),
+            _ => StreamError::Document(err),
+        });
+    }
+    // Operations working on parts missing from the document
+    if let Some(operation) = state.applied.iter().position(|applied| !applied) {
+        return Err(crate::translate_error(
+            PatchErrorKind::PathNotFound,
+            operation,
+            patch[operation].path(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Splits the pointer into its reference tokens, still escaped, or returns `None` if it is not
+/// a valid pointer.
+fn split(pointer: &str) -> Option<Vec<&str>> {
+    match pointer.strip_prefix('/') {
+        Some(rest) => Some(rest.split('/').collect()),
+        None if pointer.is_empty() => Some(Vec::new()),
+        None => None,
+    }
+}
+
+/// Returns the pointer relative to its ancestor `depth` reference tokens up.
+fn relative(pointer: &str, depth: usize) -> &str {
+    let mut rest = pointer;
+    for _ in 0..depth {
+        rest = &rest[1..];
+        rest = &rest[rest.find('/').unwrap_or(rest.len())..];
+    }
+    rest
+}
+
+/// Location in the document some operations of the patch work on, see [`patch_stream`].
+#[derive(Debug, Default)]
+struct Node {
+    /// Operations to apply to the value at this location.
+    ops: Vec<usize>,
+    /// Keys of the object members the operations work on, if the value is an object.
+    keys: BTreeSet<String>,
+    /// If the operations work on the value itself, rather than on some of its members.
+    whole: bool,
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    fn plan(patch: &[PatchOperation]) -> Node {
+        let mut root = Node::default();
+        for (operation, op) in patch.iter().enumerate() {
+            let (Some(path), Some(from)) =
+                (split(op.path()), op.from().map_or(Some(Vec::new()), split))
+            else {
+                root.ops.push(operation);
+                root.whole = true;
+                continue;
+            };
+            let parent = |pointer: &[&str]| pointer.len().saturating_sub(1);
+            // The operation works on the closest common ancestor of the replaced or tested
+            // value, of the parents of the added or removed ones and of the copied one
+            let (anchors, pointers) = match op {
+                PatchOperation::Add(_) | PatchOperation::Remove(_) => {
+                    (vec![parent(&path)], vec![&path])
+                }
+                PatchOperation::Replace(_) | PatchOperation::Test(_) => {
+                    (vec![path.len()], vec![&path])
+                }
+                PatchOperation::Move(_) => (vec![parent(&from), parent(&path)], vec![&from, &path]),
+                PatchOperation::Copy(_) => (vec![from.len(), parent(&path)], vec![&from, &path]),
+            };
+            let depth = pointers
+                .iter()
+                .zip(anchors)
+                .map(|(pointer, anchor)| {
+                    let common = path
+                        .iter()
+                        .zip(pointer.iter())
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    anchor.min(common)
+                })
+                .min()
+                .unwrap_or(0);
+            let mut node = &mut root;
+            for token in &path[..depth] {
+                node = node
+                    .children
+                    .entry(crate::unescape(token).into_owned())
+                    .or_default();
+            }
+            node.ops.push(operation);
+            for pointer in pointers {
+                match pointer.get(depth) {
+                    Some(key) => {
+                        node.keys.insert(crate::unescape(key).into_owned());
+                    }
+                    None => node.whole = true,
+                }
+            }
+        }
+        root
+    }
+
+    /// Collects the operations of the node and of its descendants.
+    fn collect(&self, ops: &mut Vec<usize>) {
+        ops.extend(&self.ops);
+        for child in self.children.values() {
+            child.collect(ops);
+        }
+    }
+}
+
+struct State<'p, W> {
+    patch: &'p [PatchOperation],
+    writer: W,
+    applied: Vec<bool>,
+    error: Option<StreamError>,
+}
+
+impl<W: Write> State<'_, W> {
+    fn fail<E: de::Error>(&mut self, err: StreamError) -> E {
+        self.error = Some(err);
+        E::custom("patch failed")
+    }
+
+    fn write<T: Serialize + ?Sized, E: de::Error>(&mut self, value: &T) -> Result<(), E> {
+        serde_json::to_writer(&mut self.writer, value)
+            .map_err(|err| self.fail(StreamError::Io(err.into())))
+    }
+
+    fn write_raw<E: de::Error>(&mut self, raw: &[u8]) -> Result<(), E> {
+        self.writer
+            .write_all(raw)
+            .map_err(|err| self.fail(StreamError::Io(err)))
+    }
+
+    /// Applies the operations, with pointers relative to the value at the given depth.
+    fn apply<E: de::Error>(
+        &mut self,
+        mut ops: Vec<usize>,
+        depth: usize,
+        value: &mut Value,
+    ) -> Result<(), E> {
+        ops.sort_unstable();
+        let relative: Vec<_> = ops
+            .iter()
+            .map(|&operation| {
+                let mut op = self.patch[operation].clone();
+                match &mut op {
+                    PatchOperation::Add(op) => op.path = relative(&op.path, depth).to_owned(),
+                    PatchOperation::Remove(op) => op.path = relative(&op.path, depth).to_owned(),
+                    PatchOperation::Replace(op) => op.path = relative(&op.path, depth).to_owned(),
+                    PatchOperation::Move(op) => {
+                        op.from = relative(&op.from, depth).to_owned();
+                        op.path = relative(&op.path, depth).to_owned();
+                    }
+                    PatchOperation::Copy(op) => {
+                        op.from = relative(&op.from, depth).to_owned();
+                        op.path = relative(&op.path, depth).to_owned();
+                    }
+                    PatchOperation::Test(op) => op.path = relative(&op.path, depth).to_owned(),
+                }
+                op
+            })
+            .collect();
+        if let Err(mut err) = crate::patch(value, &relative) {
+            err.operation = ops[err.operation];
+            err.path = self.patch[err.operation].path().to_owned();
+            return Err(self.fail(err.into()));
+        }
+        for operation in ops {
+            self.applied[operation] = true;
+        }
+        Ok(())
+    }
+}
+
+/// Transcodes a value from the deserializer to the writer of the state, applying the operations
+/// of the node.
+struct Transcode<'a, 'p, W> {
+    state: &'a mut State<'p, W>,
+    node: Option<&'a Node>,
+    depth: usize,
+    /// If the value is an element of an array following another one.
+    separator: bool,
+}
+
+impl<W: Write> Transcode<'_, '_, W> {
+    /// Writes the value, after applying the operations of the node and its descendants to it.
+    fn apply<E: de::Error>(self, mut value: Value) -> Result<(), E> {
+        if let Some(node) = self.node.filter(|node| !node.ops.is_empty()) {
+            let mut ops = Vec::new();
+            node.collect(&mut ops);
+            self.state.apply(ops, self.depth, &mut value)?;
+        }
+        self.state.write(&value)
+    }
+
+    fn scalar<T: Serialize, E: de::Error>(self, value: T) -> Result<(), E> {
+        if self.node.is_some_and(|node| !node.ops.is_empty()) {
+            let value = serde_json::to_value(value).map_err(E::custom)?;
+            return self.apply(value);
+        }
+        self.state.write(&value)
+    }
+}
+
+impl<'de, W: Write> DeserializeSeed<'de> for Transcode<'_, '_, W> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        if self.separator {
+            self.state.write_raw(b",")?;
+        }
+        if self.node.is_some_and(|node| node.whole) {
+            let value = Value::deserialize(deserializer)?;
+            return self.apply(value);
+        }
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, W: Write> Visitor<'de> for Transcode<'_, '_, W> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<(), E> {
+        self.scalar(v)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<(), E> {
+        self.scalar(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<(), E> {
+        self.scalar(v)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<(), E> {
+        self.scalar(v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<(), E> {
+        self.scalar(v)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        self.scalar(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        if self.node.is_some_and(|node| !node.ops.is_empty()) {
+            let mut arr = Vec::new();
+            while let Some(value) = seq.next_element()? {
+                arr.push(value);
+            }
+            return self.apply(Value::Array(arr));
+        }
+        self.state.write_raw(b"[")?;
+        let indexed = self.node.is_some_and(|node| !node.children.is_empty());
+        let mut index = 0usize;
+        loop {
+            let key = if indexed {
+                index.to_string()
+            } else {
+                String::new()
+            };
+            let element = Transcode {
+                state: &mut *self.state,
+                node: self.node.and_then(|node| node.children.get(&key)),
+                depth: self.depth + 1,
+                separator: index > 0,
+            };
+            if seq.next_element_seed(element)?.is_none() {
+                break;
+            }
+            index += 1;
+        }
+        self.state.write_raw(b"]")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let node = self.node.filter(|node| !node.ops.is_empty());
+        let mut members = Map::new();
+        self.state.write_raw(b"{")?;
+        let mut first = true;
+        while let Some(key) = map.next_key::<String>()? {
+            if node.is_some_and(|node| node.keys.contains(&key)) {
+                members.insert(key, map.next_value()?);
+                continue;
+            }
+            if !first {
+                self.state.write_raw(b",")?;
+            }
+            first = false;
+            self.state.write(&key)?;
+            self.state.write_raw(b":")?;
+            map.next_value_seed(Transcode {
+                state: &mut *self.state,
+                node: self.node.and_then(|node| node.children.get(&key)),
+                depth: self.depth + 1,
+                separator: false,
+            })?;
+        }
+        if let Some(node) = node {
+            let mut ops = node.ops.clone();
+            for key in &node.keys {
+                if let Some(child) = node.children.get(key) {
+                    child.collect(&mut ops);
+                }
+            }
+            let mut value = Value::Object(members);
+            self.state.apply(ops, self.depth, &mut value)?;
+            if let Value::Object(members) = value {
+                for (key, value) in &members {
+                    if !first {
+                        self.state.write_raw(b",")?;
+                    }
+                    first = false;
+                    self.state.write(key)?;
+                    self.state.write_raw(b":")?;
+                    self.state.write(value)?;
+                }
+            }
+        }
+        self.state.write_raw(b"}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{patch_stream, PatchReader, PatchWriter, StreamError};
+    use crate::{Patch, PatchErrorKind, PatchOperation};
+    use serde_json::{from_value, json, Value};
+    use std::io::{BufReader, Read};
+    use std::sync::{Arc, Mutex};
+
+    fn ops() -> Vec<PatchOperation> {
+        vec![
+            PatchOperation::add("/a", json!({ "multi\nline": "☕" })),
+            PatchOperation::copy_from("/a", "/b"),
+            PatchOperation::test("/b", json!({ "multi\nline": "☕" })),
+            PatchOperation::remove("/a"),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut writer = PatchWriter::new(Vec::new());
+        writer.write_all(&ops()).unwrap();
+        let bytes = writer.into_inner();
+        assert_eq!(bytes.iter().filter(|&&b| b == b'\n').count(), 4);
+
+        let read: Vec<_> = PatchReader::new(&bytes[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read, ops());
+    }
+
+    /// Reader being appended to while it is read, yielding at most `chunk` bytes per read.
+    #[derive(Clone, Default)]
+    struct Log(Arc<Mutex<(Vec<u8>, usize)>>);
+
+    impl Read for Log {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let mut log = self.0.lock().unwrap();
+            let (data, pos) = &mut *log;
+            let len = buf.len().min(data.len() - *pos).min(7);
+            buf[..len].copy_from_slice(&data[*pos..*pos + len]);
+            *pos += len;
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn tail() {
+        let log = Log::default();
+        let mut reader = PatchReader::new(BufReader::new(log.clone()));
+        let mut doc = json!({});
+
+        let mut writer = PatchWriter::new(Vec::new());
+        writer.write_all(&ops()[..2]).unwrap();
+        let mut bytes = writer.into_inner();
+        bytes.extend_from_slice(b"\n{\"op\":\"remove\",");
+        log.0.lock().unwrap().0.extend_from_slice(&bytes);
+
+        assert_eq!(reader.apply(&mut doc).unwrap(), 2);
+        assert_eq!(
+            doc,
+            json!({ "a": { "multi\nline": "☕" }, "b": { "multi\nline": "☕" } })
+        );
+        assert!(reader.next().is_none());
+
+        log.0
+            .lock()
+            .unwrap()
+            .0
+            .extend_from_slice(b"\"path\":\"/a\"}\n{\"op\":\"remove\",\"path\":\"/a\"}");
+        // The last line is complete, even without the line end
+        match reader.apply(&mut doc).unwrap_err() {
+            StreamError::Patch(err) => {
+                assert_eq!(err.operation, 3);
+                assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+        assert_eq!(doc, json!({ "b": { "multi\nline": "☕" } }));
+        assert_eq!(reader.operations(), 4);
+
+        log.0.lock().unwrap().0.extend_from_slice(b"\n");
+        assert!(reader.next().is_none());
+        log.0.lock().unwrap().0.extend_from_slice(b"[]\n");
+        match reader.next() {
+            Some(Err(StreamError::Parse { line, .. })) => assert_eq!(line, 6),
+            _ => panic!("expected a parse error"),
+        }
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn streaming_apply() {
+        let doc = json!({
+            "a": { "b": [1, { "c": 2 }, 3], "d~/e": "x", "f": null },
+            "g": [[true], [false]],
+            "h": 1.5,
+        });
+        let patches = [
+            json!([]),
+            json!([{ "op": "add", "path": "/a/x", "value": { "y": [] } }]),
+            json!([{ "op": "add", "path": "/a/b/1/c", "value": 3 }]),
+            json!([{ "op": "add", "path": "/a/b/-", "value": 4 }]),
+            json!([{ "op": "remove", "path": "/a/d~0~1e" }, { "op": "remove", "path": "/g/1/0" }]),
+            json!([{ "op": "replace", "path": "/h", "value": "i" }]),
+            json!([{ "op": "replace", "path": "", "value": [] }]),
+            json!([{ "op": "move", "from": "/a/b/1", "path": "/g/0/0" }]),
+            json!([{ "op": "move", "from": "/a/f", "path": "/a/z" }]),
+            json!([{ "op": "copy", "from": "/a", "path": "/a/b/0" }]),
+            json!([{ "op": "copy", "from": "/g/0", "path": "/g/-" }]),
+            json!([{ "op": "test", "path": "/a/b/1", "value": { "c": 2 } }]),
+            json!([
+                { "op": "add", "path": "/a/b/0", "value": 0 },
+                { "op": "replace", "path": "/g/1/0", "value": 1 },
+                { "op": "remove", "path": "/a/b/2/c" },
+                { "op": "test", "path": "/a/b", "value": [0, 1, {}, 3] },
+                { "op": "copy", "from": "/h", "path": "/a/b/2/h" }
+            ]),
+            json!([{ "op": "test", "path": "/a/f", "value": 1 }]),
+            json!([{ "op": "remove", "path": "/a/b/3" }]),
+            json!([{ "op": "add", "path": "/h/i", "value": 1 }]),
+            json!([{ "op": "replace", "path": "/a/b/1/c/d", "value": 1 }]),
+            json!([{ "op": "add", "path": "/z/y/x", "value": 1 }]),
+            json!([{ "op": "remove", "path": "a" }]),
+        ];
+
+        let input = doc.to_string();
+        for p in patches {
+            let p: Patch = from_value(p).unwrap();
+            let mut expected = doc.clone();
+            let expected_result = crate::patch(&mut expected, &p).map_err(|err| err.to_string());
+
+            let mut out = Vec::new();
+            let result =
+                patch_stream(input.as_bytes(), &mut out, &p).map_err(|err| err.to_string());
+            assert_eq!(result, expected_result, "{p}");
+            if result.is_ok() {
+                let actual: Value = serde_json::from_slice(&out).unwrap();
+                assert_eq!(actual, expected, "{p}");
+            }
+        }
+
+        match patch_stream(&b"{\"a\": [1, 2"[..], Vec::new(), &[]) {
+            Err(StreamError::Document(err)) => assert!(err.is_eof()),
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{apply_patches, is_prefix, resolve_pointer, revert, split_last};
+use crate::{PatchError, PatchOperation, Pointer};
+use alloc::{borrow::Cow, vec, vec::Vec};
+use serde_json::Value;
+
+/// Identifier of a subscription registered with [`Subscriptions::subscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubscriptionId(u64);
+
+/// Registry of subscriptions to the changes of a document under JSON pointer prefixes, telling
+/// which of them are affected by a patch as it is applied.
+///
+/// A subscription is affected by a patch if one of its operations changes the value at the prefix
+/// or nested under it, including:
+///
+/// * operations on an ancestor of the prefix, like replacing or removing the parent object;
+/// * both locations of a `move` operation, the value being removed from one and added to the
+///   other;
+/// * inserting or removing array elements, which shifts the elements after them (the element at
+///   `/items/3` is affected by removing `/items/1`, the one at `/items/0` is not).
+///
+/// Prefixes are locations of the document as it is when each operation is applied, so a
+/// subscription to `/a` is affected by a `move` of `/b` to `/a`. `test` operations never affect
+/// subscriptions.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{Patch, Pointer, Subscriptions};
+/// use serde_json::{from_value, json};
+///
+/// let mut subscriptions = Subscriptions::new();
+/// let name = subscriptions.subscribe(Pointer::parse("/user/name").unwrap());
+/// let email = subscriptions.subscribe(Pointer::parse("/user/email").unwrap());
+/// let second = subscriptions.subscribe(Pointer::parse("/items/1").unwrap());
+///
+/// let mut doc = json!({ "user": { "name": "Andrew" }, "items": [1, 2] });
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/user", "value": { "name": "Maxim" } },
+///   { "op": "add", "path": "/items/-", "value": 3 }
+/// ])).unwrap();
+///
+/// assert_eq!(subscriptions.patch(&mut doc, &p).unwrap(), [name, email]);
+///
+/// let p: Patch = from_value(json!([
+///   { "op": "remove", "path": "/items/0" }
+/// ])).unwrap();
+///
+/// let mut changed = Vec::new();
+/// subscriptions
+///     .patch_notifying(&mut doc, &p, |id, prefix| changed.push((id, prefix.to_string())))
+///     .unwrap();
+/// assert_eq!(changed, [(second, "/items/1".to_string())]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Subscriptions {
+    prefixes: Vec<(SubscriptionId, Pointer)>,
+    next: u64,
+}
+
+/// Location changed by an operation.
+enum Change<'a> {
+    /// Value at the location, and everything nested under it.
+    At(&'a str),
+    /// Array elements starting from an index, inserted or removed elements shifting the rest.
+    Shifted(&'a str, usize),
+}
+
+impl Subscriptions {
+    /// Creates a registry without subscriptions.
+    pub fn new() -> Subscriptions {
+        Subscriptions::default()
+    }
+
+    /// Subscribe to the changes of the value at the prefix or nested under it.
+    pub fn subscribe(&mut self, prefix: Pointer) -> SubscriptionId {
+        let id = SubscriptionId(self.next);
+        self.next += 1;
+        self.prefixes.push((id, prefix));
+        id
+    }
+
+    /// Remove the subscription, returning its prefix, or `None` if there is no such subscription.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> Option<Pointer> {
+        let idx = self.prefixes.iter().position(|(other, _)| *other == id)?;
+        Some(self.prefixes.remove(idx).1)
+    }
+
+    /// Returns the prefix of the subscription, or `None` if there is no such subscription.
+    pub fn prefix(&self, id: SubscriptionId) -> Option<&Pointer> {
+        self.prefixes
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, prefix)| prefix)
+    }
+
+    /// Returns the number of subscriptions.
+    pub fn len(&self) -> usize {
+        self.prefixes.len()
+    }
+
+    /// Returns `true` if there are no subscriptions.
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Patch provided JSON document (given as `serde_json::Value`) in-place, same as
+    /// [`patch`](crate::patch), returning the subscriptions affected by the patch, in the order
+    /// they were registered.
+    ///
+    /// If any of the operations fails, all previous operations are reverted and no subscriptions
+    /// are reported.
+    pub fn patch(
+        &self,
+        doc: &mut Value,
+        patch: &[PatchOperation],
+    ) -> Result<Vec<SubscriptionId>, PatchError> {
+        let affected = self.apply(doc, patch)?;
+        Ok(self
+            .prefixes
+            .iter()
+            .zip(affected)
+            .filter_map(|((id, _), affected)| affected.then_some(*id))
+            .collect())
+    }
+
+    /// Same as [`Subscriptions::patch`], calling `notify` with the identifier and the prefix of
+    /// every affected subscription, once the whole patch is applied.
+    pub fn patch_notifying<F>(
+        &self,
+        doc: &mut Value,
+        patch: &[PatchOperation],
+        mut notify: F,
+    ) -> Result<(), PatchError>
+    where
+        F: FnMut(SubscriptionId, &Pointer),
+    {
+        let affected = self.apply(doc, patch)?;
+        for ((id, prefix), affected) in self.prefixes.iter().zip(affected) {
+            if affected {
+                notify(*id, prefix);
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the patch, returning whether each of the subscriptions is affected.
+    fn apply(&self, doc: &mut Value, patch: &[PatchOperation]) -> Result<Vec<bool>, PatchError> {
+        let mut affected = vec![false; self.prefixes.len()];
+        let mut undo_stack = Vec::with_capacity(patch.len());
+        for (operation, op) in patch.iter().enumerate() {
+            let path = resolve_pointer(doc, op.path()).unwrap_or(Cow::Borrowed(op.path()));
+            let from = op
+                .from()
+                .map(|from| resolve_pointer(doc, from).unwrap_or(Cow::Borrowed(from)));
+            // Removed values are located before applying the operation, added ones after it
+            match op {
+                PatchOperation::Remove(_) | PatchOperation::Replace(_) => {
+                    self.mark(&mut affected, &removed(doc, &path, op))
+                }
+                PatchOperation::Move(_) if from.as_deref() != Some(&*path) => {
+                    let from = from.as_deref().unwrap_or_default();
+                    self.mark(&mut affected, &removed(doc, from, op));
+                }
+                _ => {}
+            }
+            if let Err(mut e) = apply_patches(doc, core::slice::from_ref(op), Some(&mut undo_stack))
+            {
+                revert(doc, undo_stack);
+                e.operation = operation;
+                return Err(e);
+            }
+            match op {
+                PatchOperation::Add(_) | PatchOperation::Copy(_) => {
+                    self.mark(&mut affected, &added(doc, &path))
+                }
+                PatchOperation::Move(_) if from.as_deref() != Some(&*path) => {
+                    self.mark(&mut affected, &added(doc, &path))
+                }
+                _ => {}
+            }
+        }
+        Ok(affected)
+    }
+
+    fn mark(&self, affected: &mut [bool], change: &Change<'_>) {
+        for ((_, prefix), affected) in self.prefixes.iter().zip(affected) {
+            *affected = *affected || is_changed(prefix.as_str(), change);
+        }
+    }
+}
+
+/// Returns the location changed by removing (or replacing) the value at `path` of the document.
+fn removed<'a>(doc: &Value, path: &'a str, op: &PatchOperation) -> Change<'a> {
+    if let PatchOperation::Remove(_) | PatchOperation::Move(_) = op {
+        if let Ok((parent, last)) = split_last(path) {
+            if let (Some(Value::Array(_)), Ok(idx)) = (doc.pointer(parent), last.parse()) {
+                return Change::Shifted(parent, idx);
+            }
+        }
+    }
+    Change::At(path)
+}
+
+/// Returns the location changed by adding the value at `path` of the (patched) document.
+fn added<'a>(doc: &Value, path: &'a str) -> Change<'a> {
+    if let Ok((parent, last)) = split_last(path) {
+        if let Some(Value::Array(arr)) = doc.pointer(parent) {
+            let idx = match last {
+                "-" => arr.len() - 1,
+                _ => last.parse().unwrap_or_default(),
+            };
+            return Change::Shifted(parent, idx);
+        }
+    }
+    Change::At(path)
+}
+
+/// Checks if the value at `prefix` or nested under it is changed.
+fn is_changed(prefix: &str, change: &Change<'_>) -> bool {
+    match change {
+        Change::At(path) => is_prefix(path, prefix) || is_prefix(prefix, path),
+        Change::Shifted(array, start) => {
+            if is_prefix(prefix, array) {
+                return true;
+            }
+            let Some(rest) = prefix
+                .strip_prefix(*array)
+                .and_then(|rest| rest.strip_prefix('/'))
+            else {
+                return false;
+            };
+            let token = rest.split('/').next().unwrap_or_default();
+            token.parse::<usize>().is_ok_and(|idx| idx >= *start)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SubscriptionId, Subscriptions};
+    use crate::{Patch, PatchErrorKind, Pointer};
+    use serde_json::{from_value, json};
+
+    fn subscriptions(prefixes: &[&str]) -> (Subscriptions, Vec<SubscriptionId>) {
+        let mut subscriptions = Subscriptions::new();
+        let ids = prefixes
+            .iter()
+            .map(|prefix| subscriptions.subscribe(Pointer::parse(prefix).unwrap()))
+            .collect();
+        (subscriptions, ids)
+    }
+
+    #[test]
+    fn affected_subscriptions() {
+        let (subscriptions, ids) = subscriptions(&[
+            "", "/a", "/a/b", "/a/c", "/l/0", "/l/1", "/l/2/x", "/m", "/n/k", "/t",
+        ]);
+        for (patch, affected) in [
+            (
+                json!([{ "op": "replace", "path": "/a", "value": 1 }]),
+                vec![0, 1, 2, 3],
+            ),
+            (
+                json!([{ "op": "add", "path": "/a/c", "value": 1 }]),
+                vec![0, 1, 3],
+            ),
+            (json!([{ "op": "remove", "path": "/l/1" }]), vec![0, 5, 6]),
+            (
+                json!([{ "op": "add", "path": "/l/-", "value": 4 }]),
+                vec![0],
+            ),
+            (
+                json!([{ "op": "add", "path": "/l/2", "value": 4 }]),
+                vec![0, 6],
+            ),
+            (
+                json!([{ "op": "replace", "path": "/l/1", "value": 4 }]),
+                vec![0, 5],
+            ),
+            (
+                json!([{ "op": "move", "from": "/a/b", "path": "/m" }]),
+                vec![0, 1, 2, 7],
+            ),
+            (
+                json!([{ "op": "move", "from": "/l/0", "path": "/n/k" }]),
+                vec![0, 4, 5, 6, 8],
+            ),
+            (
+                json!([{ "op": "copy", "from": "/a", "path": "/n" }]),
+                vec![0, 8],
+            ),
+            (
+                json!([{ "op": "move", "from": "/m", "path": "/m" }]),
+                vec![],
+            ),
+            (json!([{ "op": "test", "path": "/t", "value": 1 }]), vec![]),
+        ] {
+            let mut doc = json!({
+                "a": { "b": 1 },
+                "l": [1, 2, { "x": 3 }],
+                "m": 2,
+                "n": {},
+                "t": 1,
+            });
+            let p: Patch = from_value(patch.clone()).unwrap();
+            let affected: Vec<_> = affected.into_iter().map(|idx| ids[idx]).collect();
+            assert_eq!(
+                subscriptions.patch(&mut doc, &p).unwrap(),
+                affected,
+                "{patch}"
+            );
+        }
+    }
+
+    #[test]
+    fn failed_patch() {
+        let (mut subscriptions, ids) = subscriptions(&["/a", "/b"]);
+        let mut doc = json!({ "a": 1 });
+        let p: Patch = from_value(json!([
+            { "op": "remove", "path": "/a" },
+            { "op": "remove", "path": "/b" },
+        ]))
+        .unwrap();
+        let mut notified = 0;
+        let err = subscriptions
+            .patch_notifying(&mut doc, &p, |_, _| notified += 1)
+            .unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+        assert_eq!(doc, json!({ "a": 1 }));
+        assert_eq!(notified, 0);
+
+        assert_eq!(subscriptions.unsubscribe(ids[0]).unwrap().as_str(), "/a");
+        assert!(subscriptions.unsubscribe(ids[0]).is_none());
+        assert_eq!(subscriptions.len(), 1);
+        let p: Patch = from_value(json!([{ "op": "remove", "path": "/a" }])).unwrap();
+        assert!(subscriptions.patch(&mut doc, &p).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{is_prefix, OpKind, Patch, PatchOperation, Pointer, PointerError};
+use alloc::{string::String, vec, vec::Vec};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Problem with an operation of a patch found by [`Patch::check`] or [`Patch::check_json`],
+/// without a target document.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("operation '/{operation}': {kind}")]
+#[non_exhaustive]
+pub struct SyntaxIssue {
+    /// Index of the operation with the problem.
+    pub operation: usize,
+    /// Kind of the problem.
+    pub kind: SyntaxIssueKind,
+}
+
+/// Kind of a [`SyntaxIssue`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum SyntaxIssueKind {
+    /// Patch document is not an array. Reported for the operation `0`.
+    #[error("patch is not an array")]
+    NotAnArray,
+    /// Operation is not an object.
+    #[error("operation is not an object")]
+    NotAnObject,
+    /// Member required by the kind of the operation is missing: `op` and `path` for all the
+    /// operations, `from` for `move` and `copy`, and `value` for `add`, `replace` and `test`.
+    #[error("missing member '{0}'")]
+    MissingMember(&'static str),
+    /// Member of the operation is not a string.
+    #[error("member '{0}' is not a string")]
+    NotAString(&'static str),
+    /// `op` member is not the name of a JSON Patch operation.
+    #[error("unknown operation '{0}'")]
+    UnknownOperation(String),
+    /// `path` is not a valid JSON pointer.
+    #[error("invalid path: {0}")]
+    InvalidPath(PointerError),
+    /// `from` is not a valid JSON pointer.
+    #[error("invalid \"from\" path: {0}")]
+    InvalidFrom(PointerError),
+    /// `move` operation moves the value inside itself, which always fails.
+    #[error("value is moved inside itself")]
+    MoveInsideItself,
+    /// `move` operation moves the value to its own location, which has no effect.
+    #[error("value is moved to its own location")]
+    MoveToItself,
+}
+
+impl Patch {
+    /// Check the patch for problems which can be found without a target document, to lint
+    /// patches as they are received: invalid JSON pointers in `path` and `from`, and `move`
+    /// operations into the moved value itself or to its own location. All the problems are
+    /// reported, in the order of the operations.
+    ///
+    /// Members required by the kinds of the operations are always present in a [`Patch`], see
+    /// [`Patch::check_json`] to check patches before parsing them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Patch, PointerError, SyntaxIssueKind};
+    /// use serde_json::{from_value, json};
+    ///
+    /// let p: Patch = from_value(json!([
+    ///   { "op": "add", "path": "items/-", "value": 1 },
+    ///   { "op": "move", "from": "/a", "path": "/a/b" },
+    ///   { "op": "copy", "from": "/a", "path": "/c" }
+    /// ])).unwrap();
+    ///
+    /// let issues = p.check().unwrap_err();
+    /// assert_eq!(issues.len(), 2);
+    /// assert_eq!(issues[0].kind, SyntaxIssueKind::InvalidPath(PointerError::MissingLeadingSlash));
+    /// assert_eq!((issues[1].operation, &issues[1].kind), (1, &SyntaxIssueKind::MoveInsideItself));
+    /// ```
+    pub fn check(&self) -> Result<(), Vec<SyntaxIssue>> {
+        let mut issues = Vec::new();
+        for (operation, op) in self.iter().enumerate() {
+            check_operation(op, &mut |kind| issues.push(SyntaxIssue { operation, kind }));
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// Check the patch given as a JSON document like [`Patch::check`] does, also checking that
+    /// it is an array of operations with all the members required by their kinds. Returns the
+    /// parsed patch if no problems are found.
+    ///
+    /// Unlike parsing the patch, which stops at the first problem, all the problems are reported.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Patch, SyntaxIssueKind};
+    /// use serde_json::json;
+    ///
+    /// let issues = Patch::check_json(&json!([
+    ///   { "op": "add", "path": "/a" },
+    ///   { "op": "copy", "path": "/b" },
+    ///   { "op": "delete", "path": "/c" },
+    ///   { "op": "test", "path": "/d", "value": null }
+    /// ])).unwrap_err();
+    ///
+    /// let kinds: Vec<_> = issues.into_iter().map(|issue| issue.kind).collect();
+    /// assert_eq!(kinds, [
+    ///     SyntaxIssueKind::MissingMember("value"),
+    ///     SyntaxIssueKind::MissingMember("from"),
+    ///     SyntaxIssueKind::UnknownOperation("delete".into()),
+    /// ]);
+    /// ```
+    pub fn check_json(patch: &Value) -> Result<Patch, Vec<SyntaxIssue>> {
+        let Value::Array(ops) = patch else {
+            return Err(vec![SyntaxIssue {
+                operation: 0,
+                kind: SyntaxIssueKind::NotAnArray,
+            }]);
+        };
+        let mut issues = Vec::new();
+        let mut parsed = Vec::with_capacity(ops.len());
+        for (operation, op) in ops.iter().enumerate() {
+            let mut report = |kind| issues.push(SyntaxIssue { operation, kind });
+            if let Some(op) = check_members(op, &mut report) {
+                check_operation(&op, &mut report);
+                parsed.push(op);
+            }
+        }
+        if issues.is_empty() {
+            Ok(Patch(parsed))
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+/// Check the members of the operation, returning the operation if they are all valid.
+fn check_members(op: &Value, report: &mut impl FnMut(SyntaxIssueKind)) -> Option<PatchOperation> {
+    let Value::Object(members) = op else {
+        report(SyntaxIssueKind::NotAnObject);
+        return None;
+    };
+    let mut valid = true;
+    let kind = match members.get("op") {
+        Some(Value::String(name)) => match serde_json::from_value(Value::String(name.clone())) {
+            Ok(kind) => Some(kind),
+            Err(_) => {
+                report(SyntaxIssueKind::UnknownOperation(name.clone()));
+                None
+            }
+        },
+        Some(_) => {
+            report(SyntaxIssueKind::NotAString("op"));
+            None
+        }
+        None => {
+            report(SyntaxIssueKind::MissingMember("op"));
+            None
+        }
+    };
+    let needs_from = matches!(kind, Some(OpKind::Move | OpKind::Copy));
+    for (name, required) in [("path", true), ("from", needs_from)] {
+        match members.get(name) {
+            Some(Value::String(_)) => {}
+            Some(_) => {
+                report(SyntaxIssueKind::NotAString(name));
+                valid = false;
+            }
+            None if required => {
+                report(SyntaxIssueKind::MissingMember(name));
+                valid = false;
+            }
+            None => {}
+        }
+    }
+    let needs_value = matches!(kind, Some(OpKind::Add | OpKind::Replace | OpKind::Test));
+    if needs_value && !members.contains_key("value") {
+        report(SyntaxIssueKind::MissingMember("value"));
+        valid = false;
+    }
+    if !valid || kind.is_none() {
+        return None;
+    }
+    serde_json::from_value(op.clone()).ok()
+}
+
+/// Check the pointers of a well-formed operation.
+fn check_operation(op: &PatchOperation, report: &mut impl FnMut(SyntaxIssueKind)) {
+    if let Err(e) = Pointer::parse(op.path()) {
+        report(SyntaxIssueKind::InvalidPath(e));
+    }
+    if let Some(from) = op.from() {
+        if let Err(e) = Pointer::parse(from) {
+            report(SyntaxIssueKind::InvalidFrom(e));
+        }
+    }
+    if let PatchOperation::Move(op) = op {
+        if op.from == op.path {
+            report(SyntaxIssueKind::MoveToItself);
+        } else if is_prefix(&op.from, &op.path) {
+            report(SyntaxIssueKind::MoveInsideItself);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyntaxIssue, SyntaxIssueKind};
+    use crate::{Patch, PatchOperation, PointerError};
+    use serde_json::json;
+
+    #[test]
+    fn check_json() {
+        let issues = Patch::check_json(&json!([
+            1,
+            { "op": 1, "path": "/a" },
+            { "path": "/a", "from": false },
+            { "op": "move", "from": "/a~2", "path": "/b" },
+            { "op": "move", "from": "/a", "path": "/a" },
+            { "op": "remove", "path": "/a", "value": 1 },
+        ]))
+        .unwrap_err();
+        let kinds: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.operation, issue.kind.clone()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (0, SyntaxIssueKind::NotAnObject),
+                (1, SyntaxIssueKind::NotAString("op")),
+                (2, SyntaxIssueKind::MissingMember("op")),
+                (2, SyntaxIssueKind::NotAString("from")),
+                (
+                    3,
+                    SyntaxIssueKind::InvalidFrom(PointerError::InvalidEscape(2))
+                ),
+                (4, SyntaxIssueKind::MoveToItself),
+            ]
+        );
+        assert_eq!(
+            issues[4].to_string(),
+            "operation '/3': invalid \"from\" path: invalid escape sequence at position 2"
+        );
+
+        assert_eq!(
+            Patch::check_json(&json!({})).unwrap_err(),
+            [SyntaxIssue {
+                operation: 0,
+                kind: SyntaxIssueKind::NotAnArray
+            }]
+        );
+        assert_eq!(
+            Patch::check_json(&json!([{ "op": "remove", "path": "/a" }])).unwrap(),
+            Patch(vec![PatchOperation::remove("/a")])
+        );
+    }
+}
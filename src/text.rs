@@ -0,0 +1,165 @@
+use crate::{Patch, PatchError};
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use serde_json::Value;
+use thiserror::Error;
+
+/// This type represents all possible errors that can occur when patching or diffing documents
+/// given as JSON text, see [`patch_str`] and [`Patch::from_json_str`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TextError {
+    /// Document is not valid JSON.
+    #[error("invalid document: {0}")]
+    Document(#[source] serde_json::Error),
+    /// Patch is not a valid JSON Patch document.
+    #[error("invalid patch: {0}")]
+    Patch(#[source] serde_json::Error),
+    /// Patch cannot be applied to the document.
+    #[error(transparent)]
+    Apply(#[from] PatchError),
+    /// Left document of [`diff_str`] is not valid JSON.
+    #[error("invalid left document: {0}")]
+    Left(#[source] serde_json::Error),
+    /// Right document of [`diff_str`] is not valid JSON.
+    #[error("invalid right document: {0}")]
+    Right(#[source] serde_json::Error),
+}
+
+/// Patch the JSON document given as text and return the patched document as text, for callers
+/// (like foreign function interfaces and scripting layers) dealing only in JSON text. Patching
+/// is atomic, as with [`patch`](crate::patch).
+///
+/// Errors tell which of the inputs is invalid, with the line and column of the problem.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_str, TextError};
+///
+/// let doc = r#"{ "tags": ["a"] }"#;
+/// let patched = patch_str(doc, r#"[{ "op": "add", "path": "/tags/-", "value": "b" }]"#);
+/// assert_eq!(patched.unwrap(), r#"{"tags":["a","b"]}"#);
+///
+/// let err = patch_str(doc, r#"[{ "op": "add", "path": "/tags/-" }]"#).unwrap_err();
+/// assert!(matches!(err, TextError::Patch(_)));
+/// assert!(err.to_string().starts_with("invalid patch: missing field `value`"));
+/// ```
+pub fn patch_str(doc_json: &str, patch_json: &str) -> Result<String, TextError> {
+    let mut doc: Value = serde_json::from_str(doc_json).map_err(TextError::Document)?;
+    let patch = Patch::from_json_str(patch_json)?;
+    crate::patch(&mut doc, &patch)?;
+    Ok(doc.to_string())
+}
+
+/// Diff two JSON documents given as text and return the JSON Patch (RFC 6902) as text, as
+/// [`diff`](crate::diff) does for parsed documents.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::diff_str;
+///
+/// let p = diff_str(r#"{ "a": 1, "b": 2 }"#, r#"{ "a": 1, "b": 3 }"#).unwrap();
+/// assert_eq!(p, r#"[{"op":"replace","path":"/b","value":3}]"#);
+///
+/// let err = diff_str("{}", "{ \"a\": }").unwrap_err();
+/// assert_eq!(err.to_string(), "invalid right document: expected value at line 1 column 8");
+/// ```
+#[cfg(feature = "diff")]
+pub fn diff_str(left: &str, right: &str) -> Result<String, TextError> {
+    let left: Value = serde_json::from_str(left).map_err(TextError::Left)?;
+    let right: Value = serde_json::from_str(right).map_err(TextError::Right)?;
+    Ok(crate::diff(&left, &right).to_string())
+}
+
+impl Patch {
+    /// Parse the patch from JSON text. Patches are written as JSON text with
+    /// [`to_string`](ToString::to_string) (compact) or [`Patch::to_string_pretty`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use json_patch::{Patch, TextError};
+    ///
+    /// let p = Patch::from_json_str(r#"[{ "op": "remove", "path": "/a" }]"#).unwrap();
+    /// assert_eq!(p.to_string(), r#"[{"op":"remove","path":"/a"}]"#);
+    /// assert_eq!(
+    ///     p.to_string_pretty(),
+    ///     "[\n  {\n    \"op\": \"remove\",\n    \"path\": \"/a\"\n  }\n]"
+    /// );
+    ///
+    /// let err = Patch::from_json_str(r#"[{ "op": "delete", "path": "/a" }]"#).unwrap_err();
+    /// assert!(matches!(err, TextError::Patch(_)));
+    /// ```
+    pub fn from_json_str(json: &str) -> Result<Patch, TextError> {
+        serde_json::from_str(json).map_err(TextError::Patch)
+    }
+
+    /// Returns the patch as indented, multi-line JSON text.
+    pub fn to_string_pretty(&self) -> String {
+        format!("{:#}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{patch_str, TextError};
+    use crate::{Patch, PatchErrorKind, PatchOperation};
+
+    #[test]
+    fn errors() {
+        let err = patch_str("[1, 2", "[]").unwrap_err();
+        assert!(matches!(err, TextError::Document(_)));
+        assert_eq!(
+            err.to_string(),
+            "invalid document: EOF while parsing a list at line 1 column 5"
+        );
+
+        let err = patch_str("[]", r#"{ "op": "remove", "path": "/0" }"#).unwrap_err();
+        assert!(matches!(err, TextError::Patch(_)));
+
+        let err = patch_str("[1]", r#"[{ "op": "remove", "path": "/1" }]"#).unwrap_err();
+        assert!(matches!(
+            err,
+            TextError::Apply(ref e) if matches!(e.kind, PatchErrorKind::IndexOutOfBounds)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Operation '/0' failed at path '/1': array index is out of bounds"
+        );
+
+        assert_eq!(patch_str("[1]", "[]").unwrap(), "[1]");
+    }
+
+    #[test]
+    fn patch_text() {
+        let p = Patch(vec![
+            PatchOperation::add("/a~1b", "c\u{e9}".into()),
+            PatchOperation::move_from("/a~1b", "/d"),
+        ]);
+        assert_eq!(Patch::from_json_str(&p.to_string()).unwrap(), p);
+        assert_eq!(Patch::from_json_str(&p.to_string_pretty()).unwrap(), p);
+        assert!(p.to_string_pretty().contains("\n    \"op\": \"move\",\n"));
+
+        let err = Patch::from_json_str("{}").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid patch: invalid type: map"));
+    }
+
+    #[cfg(feature = "diff")]
+    #[test]
+    fn round_trip() {
+        let left = r#"{ "list": [1, 2, 3], "name": "a" }"#;
+        let right = r#"{ "list": [1, 3], "name": "b", "new": null }"#;
+        let p = super::diff_str(left, right).unwrap();
+        assert_eq!(
+            patch_str(left, &p).unwrap(),
+            r#"{"list":[1,3],"name":"b","new":null}"#
+        );
+        assert!(matches!(super::diff_str("", "{}"), Err(TextError::Left(_))));
+    }
+}
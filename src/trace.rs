@@ -0,0 +1,126 @@
+//! Instrumentation with `tracing`, compiled out unless the `tracing` feature is enabled.
+//!
+//! Fields of spans and events are only evaluated when a subscriber is interested in them, so
+//! expensive fields (like the serialized sizes of documents) cost nothing otherwise.
+
+/// Enter a debug-level span until the end of the enclosing block. The time spent in the span is
+/// the duration of the phase.
+macro_rules! span {
+    ($($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(target: "json_patch", $($args)*).entered();
+    };
+}
+
+/// Emit an event at the given level (`trace`, `debug`, ...).
+macro_rules! event {
+    ($level:ident, $($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!(target: "json_patch", $($args)*);
+    };
+}
+
+/// Emit a debug-level event describing the failure of a patch.
+macro_rules! failure {
+    ($error:expr) => {
+        $crate::trace::event!(
+            debug,
+            operation = $error.operation,
+            path = %$error.path,
+            error = %$error.kind,
+            "patch failed"
+        );
+        #[cfg(not(feature = "tracing"))]
+        let _ = &$error;
+    };
+}
+
+pub(crate) use {event, failure, span};
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::{diff_with_options, patch, DiffOptions, OpOrder, Patch};
+    use serde_json::{from_value, json};
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Subscriber recording the names of the spans and the fields of the events.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Recorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}={value:?}", field.name()));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut lines = self.0.lock().unwrap();
+            lines.push(format!("span {}", span.metadata().name()));
+            Id::from_u64(lines.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut self.clone());
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn spans_and_events() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let left = json!({ "b": 1, "a": [1] });
+            let right = json!({ "b": 2, "a": [] });
+            let options = DiffOptions {
+                op_order: OpOrder::RemovesLast,
+                ..Default::default()
+            };
+            assert_eq!(diff_with_options(&left, &right, &options).len(), 2);
+
+            let p: Patch = from_value(json!([
+                { "op": "add", "path": "/c", "value": true },
+                { "op": "remove", "path": "/d" },
+            ]))
+            .unwrap();
+            patch(&mut json!({}), &p).unwrap_err();
+        });
+
+        let lines = recorder.0.lock().unwrap();
+        for expected in [
+            "span diff",
+            "span traverse",
+            "span reorder",
+            "operations=2",
+            "span patch",
+            "size=2",
+            "operation=1",
+            "path=/d",
+            "error=path does not exist",
+            "span revert",
+        ] {
+            assert!(
+                lines.iter().any(|line| line == expected),
+                "{expected}: {lines:?}"
+            );
+        }
+    }
+}
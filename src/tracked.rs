@@ -0,0 +1,160 @@
+use crate::invert::resolve_appended;
+use crate::{apply_patches, Patch, PatchError, PatchOperation, Pointer, Undo};
+use alloc::{string::ToString, vec::Vec};
+use serde_json::Value;
+
+/// JSON document recording the changes made to it as a JSON Patch, along with the inverse patch
+/// reverting them.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch, Patch, Pointer, TrackedValue};
+/// use serde_json::{from_value, json};
+///
+/// let original = json!({ "name": "Andrew", "tags": [] });
+/// let mut doc = TrackedValue::new(original.clone());
+/// doc.set(&Pointer::from_tokens(["name"]), json!("Maxim")).unwrap();
+/// doc.push(&Pointer::from_tokens(["tags"]), json!("new")).unwrap();
+/// assert_eq!(doc.value(), &json!({ "name": "Maxim", "tags": ["new"] }));
+///
+/// let (changes, inverse) = doc.take_changes();
+/// assert_eq!(changes, from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/name", "value": "Maxim" },
+///   { "op": "add", "path": "/tags/-", "value": "new" }
+/// ])).unwrap());
+///
+/// let mut reverted = doc.into_inner();
+/// patch(&mut reverted, &inverse).unwrap();
+/// assert_eq!(reverted, original);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackedValue {
+    value: Value,
+    changes: Vec<PatchOperation>,
+    undo_stack: Vec<Undo>,
+}
+
+impl TrackedValue {
+    /// Start tracking changes made to the document.
+    pub fn new(value: Value) -> TrackedValue {
+        TrackedValue {
+            value,
+            changes: Vec::new(),
+            undo_stack: Vec::new(),
+        }
+    }
+
+    /// Returns the current state of the document.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Stops tracking changes, returning the current state of the document.
+    pub fn into_inner(self) -> Value {
+        self.value
+    }
+
+    /// Set the value at the given location, replacing the existing value or adding a new one.
+    pub fn set(&mut self, pointer: &Pointer, value: Value) -> Result<(), PatchError> {
+        let path = pointer.to_string();
+        if self.value.pointer(&path).is_some() {
+            self.apply(PatchOperation::replace(path, value))
+        } else {
+            self.apply(PatchOperation::add(path, value))
+        }
+    }
+
+    /// Remove the value at the given location.
+    pub fn remove(&mut self, pointer: &Pointer) -> Result<(), PatchError> {
+        self.apply(PatchOperation::remove(pointer.to_string()))
+    }
+
+    /// Append the value to the end of the array at the given location.
+    pub fn push(&mut self, pointer: &Pointer, value: Value) -> Result<(), PatchError> {
+        self.apply(PatchOperation::add(pointer.join("-").to_string(), value))
+    }
+
+    /// Returns the patch with all the changes made since tracking started (or since the last call
+    /// to [`TrackedValue::take_changes`]).
+    pub fn changes(&self) -> &[PatchOperation] {
+        &self.changes
+    }
+
+    /// Returns the patch reverting all the changes returned by [`TrackedValue::changes`].
+    pub fn inverse(&self) -> Patch {
+        self.undo_stack
+            .iter()
+            .rev()
+            .map(|undo| undo.op.clone())
+            .collect()
+    }
+
+    /// Returns the changes and their inverse, and starts tracking the changes anew.
+    pub fn take_changes(&mut self) -> (Patch, Patch) {
+        let inverse = self.inverse();
+        self.undo_stack.clear();
+        (Patch(core::mem::take(&mut self.changes)), inverse)
+    }
+
+    fn apply(&mut self, op: PatchOperation) -> Result<(), PatchError> {
+        let start = self.undo_stack.len();
+        apply_patches(
+            &mut self.value,
+            core::slice::from_ref(&op),
+            Some(&mut self.undo_stack),
+        )
+        .map_err(|mut e| {
+            e.operation = self.changes.len();
+            e
+        })?;
+        if let Some(resolved) = resolve_appended(&self.value, op.path()) {
+            for undo in &mut self.undo_stack[start..] {
+                if let PatchOperation::Remove(undo) = &mut undo.op {
+                    undo.path.clone_from(&resolved);
+                }
+            }
+        }
+        self.changes.push(op);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrackedValue;
+    use crate::{patch, PatchErrorKind, Pointer};
+    use serde_json::json;
+
+    #[test]
+    fn track_changes() {
+        let original = json!({ "a": { "b": 1 }, "c": [1, 2] });
+        let mut doc = TrackedValue::new(original.clone());
+        let ptr = |path: &str| Pointer::parse(path).unwrap();
+
+        doc.set(&ptr("/a/b"), json!(2)).unwrap();
+        doc.set(&ptr("/a/d"), json!(3)).unwrap();
+        doc.push(&ptr("/c"), json!(3)).unwrap();
+        doc.remove(&ptr("/c/0")).unwrap();
+        doc.push(&ptr("/c"), json!(4)).unwrap();
+        doc.remove(&ptr("/a")).unwrap();
+        assert_eq!(doc.value(), &json!({ "c": [2, 3, 4] }));
+
+        let err = doc.remove(&ptr("/a")).unwrap_err();
+        assert_eq!(err.operation, 6);
+        assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+        assert_eq!(doc.changes().len(), 6);
+
+        let mut replayed = original.clone();
+        patch(&mut replayed, doc.changes()).unwrap();
+        assert_eq!(&replayed, doc.value());
+        patch(&mut replayed, &doc.inverse()).unwrap();
+        assert_eq!(replayed, original);
+
+        let (changes, inverse) = doc.take_changes();
+        assert_eq!(changes.len(), 6);
+        assert_eq!(inverse.len(), 6);
+        assert!(doc.changes().is_empty());
+        assert!(doc.inverse().is_empty());
+    }
+}
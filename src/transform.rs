@@ -0,0 +1,422 @@
+use crate::deep::{clone_value, drop_value};
+use crate::{is_prefix, split_last, Patch, PatchOperation, RemoveOperation};
+use alloc::{format, string::String, vec::Vec};
+use core::slice;
+use serde_json::Value;
+
+fn is_index(token: &str) -> bool {
+    !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Splits the `pointer` into the location of an array element `at` and the rest, if it refers to
+/// an element of the array at `parent` or to something inside of it.
+fn element<'a>(pointer: &'a str, parent: &str) -> Option<(usize, &'a str)> {
+    let rest = pointer.strip_prefix(parent)?.strip_prefix('/')?;
+    let (token, rest) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if is_index(token) {
+        Some((token.parse().ok()?, rest))
+    } else {
+        None
+    }
+}
+
+/// Checks if the `pointer` refers to something strictly inside the value at `path`.
+fn is_inside(pointer: &str, path: &str) -> bool {
+    pointer.len() > path.len() && is_prefix(path, pointer)
+}
+
+/// Rewrites the `pointer` of an operation applied after a value was added at `path`. `first` is
+/// set if the `pointer` is the target location of an element being inserted, which has to go
+/// before the element inserted at the same index.
+fn after_add(pointer: String, path: &str, first: bool) -> Option<String> {
+    match split_last(path) {
+        Ok((parent, last)) if is_index(last) => match element(&pointer, parent) {
+            Some(_) if first && pointer == path => Some(pointer),
+            Some((idx, rest)) if idx >= last.parse().ok()? => {
+                Some(format!("{}/{}{}", parent, idx + 1, rest))
+            }
+            _ => Some(pointer),
+        },
+        // Appending to an array does not affect the locations of existing elements
+        Ok((_, "-")) => Some(pointer),
+        // Values inside the replaced value are gone
+        _ if is_inside(&pointer, path) => None,
+        _ => Some(pointer),
+    }
+}
+
+/// Rewrites the `pointer` of an operation applied after the value at `path` was removed. Returns
+/// `None` if the `pointer` refers to the removed value.
+fn after_remove(pointer: String, path: &str, insert: bool) -> Option<String> {
+    if pointer == path && insert {
+        // New value can still be added at the location of the removed one
+        return Some(pointer);
+    }
+    match split_last(path) {
+        Ok((parent, last)) if is_index(last) => {
+            let removed = last.parse::<usize>().ok()?;
+            match element(&pointer, parent) {
+                Some((idx, _)) if idx == removed => None,
+                Some((idx, rest)) if idx > removed => {
+                    Some(format!("{}/{}{}", parent, idx - 1, rest))
+                }
+                _ => Some(pointer),
+            }
+        }
+        _ if is_prefix(path, &pointer) => None,
+        _ => Some(pointer),
+    }
+}
+
+/// Rewrites the `pointer` of an operation to refer to the same value after the operation `op`
+/// was applied. `insert` is set if the `pointer` is the target location of a value being added,
+/// and `first` if its elements inserted at the same index as the ones of `op` go first.
+fn after(op: &PatchOperation, pointer: String, insert: bool, first: bool) -> Option<String> {
+    let first = insert && first;
+    match op {
+        PatchOperation::Add(op) => after_add(pointer, &op.path, first),
+        PatchOperation::Remove(op) => after_remove(pointer, &op.path, insert),
+        PatchOperation::Replace(op) if is_inside(&pointer, &op.path) => None,
+        PatchOperation::Replace(_) | PatchOperation::Test(_) => Some(pointer),
+        PatchOperation::Move(op) if op.from == op.path => Some(pointer),
+        PatchOperation::Move(op)
+            if is_prefix(&op.from, &pointer) && !(pointer == op.from && insert) =>
+        {
+            if op.path.ends_with("/-") {
+                // Index of the appended element is not known
+                return None;
+            }
+            Some(format!("{}{}", op.path, &pointer[op.from.len()..]))
+        }
+        PatchOperation::Move(op) => {
+            after_add(after_remove(pointer, &op.from, insert)?, &op.path, first)
+        }
+        PatchOperation::Copy(op) => after_add(pointer, &op.path, first),
+    }
+}
+
+/// Location of the value the operation sets as a whole, unless it inserts an array element.
+fn overwritten(op: &PatchOperation) -> Option<&str> {
+    let path = match op {
+        PatchOperation::Replace(op) => return Some(&op.path),
+        PatchOperation::Add(op) => &op.path,
+        PatchOperation::Copy(op) => &op.path,
+        PatchOperation::Move(op) if op.from != op.path => &op.path,
+        _ => return None,
+    };
+    match split_last(path) {
+        Ok((_, last)) if is_index(last) || last == "-" => None,
+        _ => Some(path),
+    }
+}
+
+/// Rewrites the operation to apply after the operation `op` was applied to the same document, or
+/// returns `None` if the value it refers to is gone. `first` is set if the `target` operation
+/// goes first where both operations set the same location, its value being overwritten by `op`.
+fn rebase(op: &PatchOperation, target: &PatchOperation, first: bool) -> Option<PatchOperation> {
+    let after = |pointer: &String, insert: bool| after(op, pointer.clone(), insert, first);
+    if first && overwritten(target).is_some_and(|path| overwritten(op) == Some(path)) {
+        // Only the removal of the moved value is left
+        return match target {
+            PatchOperation::Move(target) => Some(PatchOperation::Remove(RemoveOperation {
+                path: after(&target.from, false)?,
+            })),
+            _ => None,
+        };
+    }
+    let mut target = target.clone();
+    match &mut target {
+        PatchOperation::Add(target) => target.path = after(&target.path, true)?,
+        PatchOperation::Remove(target) => target.path = after(&target.path, false)?,
+        PatchOperation::Replace(target) => target.path = after(&target.path, false)?,
+        PatchOperation::Move(target) => {
+            target.from = after(&target.from, false)?;
+            target.path = after(&target.path, true)?;
+        }
+        PatchOperation::Copy(target) => {
+            target.from = after(&target.from, false)?;
+            target.path = after(&target.path, true)?;
+        }
+        PatchOperation::Test(target) => target.path = after(&target.path, false)?,
+    }
+    Some(target)
+}
+
+/// Transform the patch `a`, so it applies to the document which has already been patched with
+/// `b`, where both `a` and `b` were made against the same document (operational transform).
+///
+/// Array indices in `a` are shifted to account for elements inserted and removed by `b`,
+/// locations moved by `b` are followed, and operations of `a` on values removed or replaced by `b`
+/// are dropped, along with the operations of `a` on the values the dropped ones added. Where both
+/// patches insert array elements at the same index, elements inserted by `b` come first, and
+/// where both set the same object member, the value set by `a` wins. Reference tokens consisting
+/// of digits only are treated as array indices.
+///
+/// Values moved by `b` to the end of an array (with the `-` reference token) cannot be followed
+/// without knowing the length of the array, so the operations of `a` on them are dropped. Use
+/// [`transform_with_document`] to follow them as well.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch, transform, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "items": ["a", "b"] });
+///
+/// // Queued offline patch
+/// let a: Patch = from_value(json!([
+///   { "op": "replace", "path": "/items/1", "value": "B" }
+/// ])).unwrap();
+/// // Patch already applied on the server
+/// let b: Patch = from_value(json!([
+///   { "op": "add", "path": "/items/0", "value": "first" }
+/// ])).unwrap();
+///
+/// patch(&mut doc, &b).unwrap();
+/// patch(&mut doc, &transform(&a, &b)).unwrap();
+/// assert_eq!(doc, json!({ "items": ["first", "a", "B"] }));
+/// ```
+pub fn transform(a: &[PatchOperation], b: &[PatchOperation]) -> Patch {
+    // Operations of `b` rebased onto the operations of `a` transformed so far
+    let mut b = b.to_vec();
+    // Locations of the values added by the dropped operations of `a`, as seen by the following
+    // operations of `a`
+    let mut dropped: Vec<String> = Vec::new();
+    let mut ops = Vec::with_capacity(a.len());
+    for op in a {
+        let created = added(op);
+        // The following operations do not see the values added by the dropped ones
+        let mut op = dropped.iter().rev().try_fold(op.clone(), |op, path| {
+            let removal = PatchOperation::Remove(RemoveOperation { path: path.clone() });
+            rebase(&removal, &op, false)
+        });
+        let mut rebased = Vec::with_capacity(b.len());
+        for other in &b {
+            match op {
+                Some(ref current) => {
+                    rebased.extend(rebase(current, other, true));
+                    op = rebase(other, current, false);
+                }
+                None => rebased.push(other.clone()),
+            }
+        }
+        b = rebased;
+        match op {
+            Some(op) => ops.push(op),
+            None => dropped.extend(created.map(String::from)),
+        }
+    }
+    Patch(ops)
+}
+
+/// Same as [`transform`], but follows the values moved by `b` to the end of an array, resolving
+/// the `-` reference tokens of `b` against `doc`, the document both patches were made against.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch, transform_with_document, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let doc = json!({ "todo": ["a", "b"], "done": [] });
+/// let a: Patch = from_value(json!([
+///   { "op": "replace", "path": "/todo/1", "value": "B" }
+/// ])).unwrap();
+/// let b: Patch = from_value(json!([
+///   { "op": "move", "from": "/todo/1", "path": "/done/-" }
+/// ])).unwrap();
+///
+/// let mut patched = doc.clone();
+/// patch(&mut patched, &b).unwrap();
+/// patch(&mut patched, &transform_with_document(&a, &b, &doc)).unwrap();
+/// assert_eq!(patched, json!({ "todo": ["a"], "done": ["B"] }));
+/// ```
+pub fn transform_with_document(a: &[PatchOperation], b: &[PatchOperation], doc: &Value) -> Patch {
+    transform(a, &resolve_appends(b, doc))
+}
+
+/// Location of the value the operation adds, if any.
+fn added(op: &PatchOperation) -> Option<&str> {
+    match op {
+        PatchOperation::Add(op) => Some(&op.path),
+        PatchOperation::Copy(op) => Some(&op.path),
+        PatchOperation::Move(op) if op.from != op.path => Some(&op.path),
+        _ => None,
+    }
+}
+
+/// Replaces the `-` reference tokens at the end of the locations values are added at with the
+/// indices of the appended elements, applying the patch to a copy of the document. Operations
+/// from the first one failing to apply are left as they are.
+fn resolve_appends(patch: &[PatchOperation], doc: &Value) -> Vec<PatchOperation> {
+    let mut doc = clone_value(doc);
+    let mut ops = patch.to_vec();
+    for op in &mut ops {
+        if crate::patch(&mut doc, slice::from_ref(op)).is_err() {
+            break;
+        }
+        let path = match op {
+            PatchOperation::Add(op) => &mut op.path,
+            PatchOperation::Copy(op) => &mut op.path,
+            PatchOperation::Move(op) => &mut op.path,
+            _ => continue,
+        };
+        let Some(parent) = path.strip_suffix("/-") else {
+            continue;
+        };
+        if let Some(len) = doc.pointer(parent).and_then(Value::as_array).map(Vec::len) {
+            *path = format!("{}/{}", parent, len - 1);
+        }
+    }
+    drop_value(doc);
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{transform, transform_with_document};
+    use crate::{patch, Patch};
+    use serde_json::{from_value, json, Value};
+
+    fn check_transform(doc: Value, a: Value, b: Value, expected: Value) {
+        let a: Patch = from_value(a).unwrap();
+        let b: Patch = from_value(b).unwrap();
+        let mut doc = doc;
+        patch(&mut doc, &b).unwrap();
+        patch(&mut doc, &transform(&a, &b)).unwrap();
+        assert_eq!(doc, expected);
+    }
+
+    #[test]
+    fn shift_indices() {
+        check_transform(
+            json!([1, 2, 3, 4]),
+            json!([
+                { "op": "replace", "path": "/3", "value": 40 },
+                { "op": "add", "path": "/1", "value": 5 },
+                { "op": "remove", "path": "/1" },
+            ]),
+            json!([
+                { "op": "add", "path": "/1", "value": 6 },
+                { "op": "remove", "path": "/0" },
+                { "op": "add", "path": "/-", "value": 7 },
+            ]),
+            json!([6, 2, 3, 40, 7]),
+        );
+    }
+
+    #[test]
+    fn sequential_operations() {
+        check_transform(
+            json!(["p", "q"]),
+            json!([
+                { "op": "add", "path": "/0", "value": "x" },
+                { "op": "replace", "path": "/1", "value": "y" },
+                { "op": "add", "path": "/3", "value": "z" },
+            ]),
+            json!([
+                { "op": "remove", "path": "/0" },
+                { "op": "add", "path": "/1", "value": "r" },
+            ]),
+            json!(["x", "q", "r", "z"]),
+        );
+    }
+
+    #[test]
+    fn drop_removed() {
+        check_transform(
+            json!({ "a": { "b": [1, 2] }, "c": { "d": 1 }, "e": 1 }),
+            json!([
+                { "op": "add", "path": "/a/b/-", "value": 3 },
+                { "op": "remove", "path": "/a/b/1" },
+                { "op": "replace", "path": "/c/d", "value": 2 },
+                { "op": "add", "path": "/e", "value": 2 },
+            ]),
+            json!([
+                { "op": "remove", "path": "/a" },
+                { "op": "replace", "path": "/c", "value": {} },
+                { "op": "remove", "path": "/e" },
+            ]),
+            json!({ "c": {}, "e": 2 }),
+        );
+    }
+
+    #[test]
+    fn follow_moves() {
+        check_transform(
+            json!({ "a": [{ "x": 1 }, { "x": 2 }], "b": [] }),
+            json!([
+                { "op": "replace", "path": "/a/1/x", "value": 20 },
+                { "op": "add", "path": "/a/0/y", "value": 10 },
+            ]),
+            json!([
+                { "op": "move", "from": "/a/0", "path": "/b/0" },
+                { "op": "move", "from": "/a/0", "path": "/a/0" },
+            ]),
+            json!({ "a": [{ "x": 20 }], "b": [{ "x": 1, "y": 10 }] }),
+        );
+    }
+
+    #[test]
+    fn drop_dependent() {
+        // The copied value is not there once its source is removed
+        check_transform(
+            json!({ "b": 1 }),
+            json!([
+                { "op": "copy", "from": "/b", "path": "/n" },
+                { "op": "replace", "path": "/n", "value": 2 },
+                { "op": "add", "path": "/m", "value": 3 },
+            ]),
+            json!([{ "op": "remove", "path": "/b" }]),
+            json!({ "m": 3 }),
+        );
+        // Elements following the dropped ones are shifted back
+        check_transform(
+            json!({ "b": 1, "c": [{}, 2] }),
+            json!([
+                { "op": "copy", "from": "/b", "path": "/c/0" },
+                { "op": "replace", "path": "/c/2", "value": 20 },
+                { "op": "add", "path": "/c/1/x", "value": 1 },
+            ]),
+            json!([{ "op": "remove", "path": "/b" }]),
+            json!({ "c": [{ "x": 1 }, 20] }),
+        );
+    }
+
+    #[test]
+    fn overwrite_same_member() {
+        check_transform(
+            json!({}),
+            json!([
+                { "op": "add", "path": "/n", "value": [1] },
+                { "op": "replace", "path": "/n/0", "value": 2 },
+            ]),
+            json!([{ "op": "add", "path": "/n", "value": [5, 6] }]),
+            json!({ "n": [2] }),
+        );
+        check_transform(
+            json!({ "a": [1], "n": 0 }),
+            json!([
+                { "op": "replace", "path": "/n", "value": [1] },
+                { "op": "add", "path": "/n/-", "value": 2 },
+                { "op": "add", "path": "/a/0", "value": 0 },
+            ]),
+            json!([{ "op": "move", "from": "/a", "path": "/n" }]),
+            json!({ "n": [1, 2] }),
+        );
+    }
+
+    #[test]
+    fn follow_appending_moves() {
+        let doc = json!([["b"], "a"]);
+        let a: Patch =
+            from_value(json!([{ "op": "replace", "path": "/1", "value": "x" }])).unwrap();
+        let b: Patch = from_value(json!([{ "op": "move", "from": "/1", "path": "/0/-" }])).unwrap();
+        let mut patched = doc.clone();
+        patch(&mut patched, &b).unwrap();
+        assert_eq!(transform(&a, &b), Patch(vec![]));
+        patch(&mut patched, &transform_with_document(&a, &b, &doc)).unwrap();
+        assert_eq!(patched, json!([["b", "x"]]));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{apply_patches, PatchError, PatchOperation};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+
+/// This type represents all possible errors that can occur when applying JSON patch to a typed
+/// value with [`apply_to`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TypedPatchError {
+    /// Target value cannot be represented as JSON.
+    #[error("cannot serialize the target: {0}")]
+    Serialize(#[source] serde_json::Error),
+    /// Patch cannot be applied to the JSON representation of the target value.
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+    /// Patched JSON document cannot be deserialized back into the type of the target value.
+    #[error("patched document does not match the target type: {source}")]
+    Deserialize {
+        /// Index of the operation which made the document incompatible with the target type: the
+        /// document matches the type before this operation, but none of the documents produced by
+        /// it and by the following operations do. `None` if the JSON representation of the
+        /// target value does not match its type in the first place.
+        operation: Option<usize>,
+        /// Deserialization error.
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// Patch the typed value in-place by applying the JSON patch to its JSON representation and
+/// deserializing the result back. If the patch fails, the value is left intact.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{apply_to, Patch, TypedPatchError};
+/// use serde::{Deserialize, Serialize};
+/// use serde_json::{from_value, json};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Person {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// let mut person = Person { name: "Andrew".into(), age: 30 };
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/age", "value": 31 }
+/// ])).unwrap();
+/// apply_to(&mut person, &p).unwrap();
+/// assert_eq!(person, Person { name: "Andrew".into(), age: 31 });
+///
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/nickname", "value": "Andy" },
+///   { "op": "replace", "path": "/age", "value": "old" }
+/// ])).unwrap();
+/// let err = apply_to(&mut person, &p).unwrap_err();
+/// assert!(matches!(err, TypedPatchError::Deserialize { operation: Some(1), .. }));
+/// ```
+pub fn apply_to<T>(target: &mut T, patch: &[PatchOperation]) -> Result<(), TypedPatchError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut doc = serde_json::to_value(&*target).map_err(TypedPatchError::Serialize)?;
+    apply_patches(&mut doc, patch, None)?;
+    match T::deserialize(doc) {
+        Ok(value) => {
+            *target = value;
+            Ok(())
+        }
+        Err(source) => Err(TypedPatchError::Deserialize {
+            operation: incompatible_operation::<T>(target, patch),
+            source,
+        }),
+    }
+}
+
+/// Replays the patch to find the operation after which the document stops matching the type.
+fn incompatible_operation<T>(target: &T, patch: &[PatchOperation]) -> Option<usize>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut doc = serde_json::to_value(target).ok()?;
+    let matches = |doc: &Value| T::deserialize(doc).is_ok();
+    let mut operation = matches(&doc).then_some(0)?;
+    for (idx, op) in patch.iter().enumerate() {
+        apply_patches(&mut doc, core::slice::from_ref(op), None).ok()?;
+        if matches(&doc) {
+            operation = idx + 1;
+        }
+    }
+    Some(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_to, TypedPatchError};
+    use crate::{Patch, PatchErrorKind};
+    use alloc::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{from_value, json, Value};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        id: u64,
+        tags: Vec<String>,
+        attrs: BTreeMap<String, u32>,
+    }
+
+    fn record() -> Record {
+        Record {
+            id: 1,
+            tags: vec!["a".into()],
+            attrs: BTreeMap::new(),
+        }
+    }
+
+    fn apply(patch: Value) -> Result<Record, TypedPatchError> {
+        let p: Patch = from_value(patch).unwrap();
+        let mut target = record();
+        let result = apply_to(&mut target, &p);
+        if result.is_err() {
+            assert_eq!(target, record());
+        }
+        result.map(|()| target)
+    }
+
+    #[test]
+    fn typed_apply() {
+        let patched = apply(json!([
+            { "op": "add", "path": "/tags/-", "value": "b" },
+            { "op": "add", "path": "/attrs/x", "value": 2 },
+        ]))
+        .unwrap();
+        assert_eq!(patched.tags, ["a", "b"]);
+        assert_eq!(patched.attrs.get("x"), Some(&2));
+
+        // Intermediate documents do not have to match the type
+        let patched = apply(json!([
+            { "op": "remove", "path": "/id" },
+            { "op": "add", "path": "/id", "value": 5 },
+        ]))
+        .unwrap();
+        assert_eq!(patched.id, 5);
+    }
+
+    #[test]
+    fn typed_apply_failure() {
+        let err = apply(json!([{ "op": "remove", "path": "/attrs/x" }])).unwrap_err();
+        assert!(matches!(
+            err,
+            TypedPatchError::Patch(ref err) if matches!(err.kind, PatchErrorKind::PathNotFound)
+        ));
+
+        let err = apply(json!([
+            { "op": "remove", "path": "/id" },
+            { "op": "add", "path": "/id", "value": 5 },
+            { "op": "add", "path": "/attrs/x", "value": -1 },
+            { "op": "add", "path": "/tags/0", "value": "b" },
+        ]))
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            TypedPatchError::Deserialize {
+                operation: Some(2),
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{apply_patches, invert, Patch, PatchError};
+use alloc::vec::Vec;
+use serde_json::Value;
+
+/// Change applied to the document, along with its inverse.
+#[derive(Debug, Clone, PartialEq)]
+struct Change {
+    forward: Patch,
+    inverse: Patch,
+}
+
+/// Undo/redo manager, recording the patches applied to a document along with their inverse
+/// patches (see [`invert`]).
+///
+/// The manager does not own the document: the same document has to be passed to all of its
+/// methods, and any changes made to the document bypassing the manager might make undoing or
+/// redoing fail.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{Patch, UndoStack};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc = json!({ "name": "Andrew" });
+/// let mut history = UndoStack::new();
+/// let p: Patch = from_value(json!([
+///   { "op": "replace", "path": "/name", "value": "Maxim" }
+/// ])).unwrap();
+/// history.apply(&mut doc, p).unwrap();
+/// assert_eq!(doc, json!({ "name": "Maxim" }));
+///
+/// assert!(history.undo(&mut doc).unwrap());
+/// assert_eq!(doc, json!({ "name": "Andrew" }));
+/// assert!(!history.undo(&mut doc).unwrap());
+///
+/// assert!(history.redo(&mut doc).unwrap());
+/// assert_eq!(doc, json!({ "name": "Maxim" }));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UndoStack {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl UndoStack {
+    /// Create an empty undo stack.
+    pub fn new() -> UndoStack {
+        UndoStack::default()
+    }
+
+    /// Apply the patch to the document and record it, discarding the changes which can be
+    /// redone. If the patch fails, nothing is recorded and the document is left intact.
+    pub fn apply(&mut self, doc: &mut Value, patch: Patch) -> Result<(), PatchError> {
+        let inverse = invert(&patch, doc)?;
+        // Inverting the patch has just applied it to a copy of the document
+        if let Err(e) = apply_patches(doc, &patch, None) {
+            unreachable!("unable to apply inverted patch: {e}")
+        }
+        self.record(patch, inverse);
+        Ok(())
+    }
+
+    /// Record a patch which has already been applied to the document, along with its inverse
+    /// (for example, the changes made to a [`TrackedValue`](crate::TrackedValue)). Discards the
+    /// changes which can be redone.
+    pub fn record(&mut self, forward: Patch, inverse: Patch) {
+        self.redo.clear();
+        self.undo.push(Change { forward, inverse });
+    }
+
+    /// Revert the last recorded change. Returns `false` if there is nothing to undo. If the
+    /// inverse patch fails, the document and the stack are left intact.
+    pub fn undo(&mut self, doc: &mut Value) -> Result<bool, PatchError> {
+        let Some(change) = self.undo.last() else {
+            return Ok(false);
+        };
+        crate::patch(doc, &change.inverse)?;
+        self.redo.extend(self.undo.pop());
+        Ok(true)
+    }
+
+    /// Re-apply the last undone change. Returns `false` if there is nothing to redo. If the patch
+    /// fails, the document and the stack are left intact.
+    pub fn redo(&mut self, doc: &mut Value) -> Result<bool, PatchError> {
+        let Some(change) = self.redo.last() else {
+            return Ok(false);
+        };
+        crate::patch(doc, &change.forward)?;
+        self.undo.extend(self.redo.pop());
+        Ok(true)
+    }
+
+    /// Checks if there is a change to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Checks if there is a change to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forget all the recorded changes.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UndoStack;
+    use crate::{Patch, Pointer, TrackedValue};
+    use serde_json::{from_value, json, Value};
+
+    fn patch(ops: Value) -> Patch {
+        from_value(ops).unwrap()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut doc = json!({ "items": [1] });
+        let mut stack = UndoStack::new();
+        stack
+            .apply(
+                &mut doc,
+                patch(json!([{ "op": "add", "path": "/items/-", "value": 2 }])),
+            )
+            .unwrap();
+        stack
+            .apply(
+                &mut doc,
+                patch(json!([{ "op": "remove", "path": "/items/0" }])),
+            )
+            .unwrap();
+        assert!(stack
+            .apply(&mut doc, patch(json!([{ "op": "remove", "path": "/x" }])))
+            .is_err());
+        assert_eq!(doc, json!({ "items": [2] }));
+
+        assert!(stack.undo(&mut doc).unwrap());
+        assert!(stack.undo(&mut doc).unwrap());
+        assert_eq!(doc, json!({ "items": [1] }));
+        assert!(!stack.can_undo());
+        assert!(stack.redo(&mut doc).unwrap());
+        assert_eq!(doc, json!({ "items": [1, 2] }));
+
+        // Undoing fails if the document was changed behind the stack's back
+        let mut changed = json!({ "items": [] });
+        assert!(stack.undo(&mut changed).is_err());
+        assert!(stack.can_undo());
+
+        // Recording a new change discards the changes to redo
+        assert!(stack.can_redo());
+        let mut tracked = TrackedValue::new(doc);
+        tracked
+            .set(&Pointer::from_tokens(["name"]), json!("list"))
+            .unwrap();
+        let (forward, inverse) = tracked.take_changes();
+        stack.record(forward, inverse);
+        assert!(!stack.can_redo());
+
+        let mut doc = tracked.into_inner();
+        assert!(stack.undo(&mut doc).unwrap());
+        assert_eq!(doc, json!({ "items": [1, 2] }));
+        stack.clear();
+        assert!(!stack.can_undo() && !stack.can_redo());
+    }
+}
@@ -0,0 +1,84 @@
+//! JavaScript bindings, exported with [`wasm_bindgen`], so that the browser diffs and patches
+//! documents exactly the way the Rust code does. Documents and patches are passed as plain
+//! JavaScript values and converted to and from JSON values with [`serde_wasm_bindgen`]; integral
+//! numbers are converted into JSON integers, as if the values were parsed from JSON text.
+//!
+//! Functions throw an `Error` when their arguments are not valid JSON values or patches, or when
+//! the patch cannot be applied.
+//!
+//! Requires the `wasm` feature.
+use crate::PatchOperation;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use serde_wasm_bindgen::Serializer;
+use wasm_bindgen::prelude::*;
+
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, JsError> {
+    serde_wasm_bindgen::from_value(value).map_err(|err| JsError::new(&err.to_string()))
+}
+
+fn to_js<T: Serialize + ?Sized>(value: &T) -> Result<JsValue, JsError> {
+    // Produce plain objects rather than `Map`s, and numbers rather than `BigInt`s
+    value
+        .serialize(&Serializer::json_compatible())
+        .map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// Operation failed by [`validate`].
+#[derive(Serialize)]
+struct ValidationIssue {
+    operation: usize,
+    path: String,
+    message: String,
+}
+
+/// Diff two documents and return the JSON Patch transforming `left` into `right`, as
+/// [`diff`](crate::diff) does.
+#[cfg(feature = "diff")]
+#[wasm_bindgen]
+pub fn diff(left: JsValue, right: JsValue) -> Result<JsValue, JsError> {
+    let left: Value = from_js(left)?;
+    let right: Value = from_js(right)?;
+    to_js(&crate::diff(&left, &right))
+}
+
+/// Apply the JSON Patch to the document and return the patched document, as
+/// [`patch`](crate::patch) does. The document passed in is left intact.
+#[wasm_bindgen]
+pub fn patch(doc: JsValue, patch: JsValue) -> Result<JsValue, JsError> {
+    let mut doc: Value = from_js(doc)?;
+    let patch: Vec<PatchOperation> = from_js(patch)?;
+    crate::patch(&mut doc, &patch)?;
+    to_js(&doc)
+}
+
+/// Apply the JSON Merge Patch to the document and return the patched document, as
+/// [`merge`](crate::merge) does. The document passed in is left intact.
+#[wasm_bindgen(js_name = mergePatch)]
+pub fn merge_patch(doc: JsValue, patch: JsValue) -> Result<JsValue, JsError> {
+    let mut doc: Value = from_js(doc)?;
+    let patch: Value = from_js(patch)?;
+    crate::merge(&mut doc, &patch);
+    to_js(&doc)
+}
+
+/// Check if the JSON Patch applies to the document, as [`validate`](crate::validate) does, and
+/// return the array of the failed operations as `{ operation, path, message }` objects, which is
+/// empty if the patch is valid.
+#[wasm_bindgen]
+pub fn validate(doc: JsValue, patch: JsValue) -> Result<JsValue, JsError> {
+    let doc: Value = from_js(doc)?;
+    let patch: Vec<PatchOperation> = from_js(patch)?;
+    let issues: Vec<_> = crate::validate(&doc, &patch)
+        .err()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|err| ValidationIssue {
+            operation: err.operation,
+            message: err.kind.to_string(),
+            path: err.path,
+        })
+        .collect();
+    to_js(&issues)
+}
@@ -0,0 +1,245 @@
+//! Patching and diffing YAML documents, see [`JsonLike`].
+//!
+//! Mappings are addressed by their string keys, like JSON objects; members with other keys are
+//! never added, removed or compared by the patch operations. Tagged values are treated as opaque
+//! scalars.
+use crate::generic::{self, JsonLike};
+use crate::{Patch, PatchError, PatchOperation};
+use serde_json::Value;
+use serde_yaml::{Number, Value as Yaml};
+
+impl JsonLike for Yaml {
+    fn from_json(value: &Value) -> Self {
+        match value {
+            Value::Null => Yaml::Null,
+            Value::Bool(b) => Yaml::Bool(*b),
+            Value::Number(n) => Yaml::Number(if let Some(n) = n.as_u64() {
+                Number::from(n)
+            } else if let Some(n) = n.as_i64() {
+                Number::from(n)
+            } else {
+                Number::from(n.as_f64().unwrap_or_default())
+            }),
+            Value::String(s) => Yaml::String(s.clone()),
+            Value::Array(arr) => Yaml::Sequence(arr.iter().map(Yaml::from_json).collect()),
+            Value::Object(obj) => Yaml::Mapping(
+                obj.iter()
+                    .map(|(k, v)| (Yaml::String(k.clone()), Yaml::from_json(v)))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Yaml::Null => Value::Null,
+            Yaml::Bool(b) => Value::Bool(*b),
+            Yaml::Number(n) => {
+                if let Some(n) = n.as_u64() {
+                    Value::from(n)
+                } else if let Some(n) = n.as_i64() {
+                    Value::from(n)
+                } else {
+                    // Infinities and NaN have no JSON representation
+                    n.as_f64().map_or(Value::Null, Value::from)
+                }
+            }
+            Yaml::String(s) => Value::String(s.clone()),
+            Yaml::Sequence(seq) => Value::Array(seq.iter().map(Yaml::to_json).collect()),
+            Yaml::Mapping(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| (key_to_string(k), v.to_json()))
+                    .collect(),
+            ),
+            Yaml::Tagged(tagged) => tagged.value.to_json(),
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        self.as_sequence().map(Vec::as_slice)
+    }
+
+    fn as_array_mut(&mut self) -> Option<&mut [Self]> {
+        self.as_sequence_mut().map(Vec::as_mut_slice)
+    }
+
+    fn insert_element(&mut self, index: usize, value: Self) {
+        match self {
+            Yaml::Sequence(seq) => seq.insert(index, value),
+            _ => unreachable!("not a sequence"),
+        }
+    }
+
+    fn remove_element(&mut self, index: usize) -> Self {
+        match self {
+            Yaml::Sequence(seq) => seq.remove(index),
+            _ => unreachable!("not a sequence"),
+        }
+    }
+
+    fn is_object(&self) -> bool {
+        self.is_mapping()
+    }
+
+    fn members(&self) -> Option<Vec<(&str, &Self)>> {
+        let map = self.as_mapping()?;
+        Some(
+            map.iter()
+                .filter_map(|(k, v)| Some((k.as_str()?, v)))
+                .collect(),
+        )
+    }
+
+    fn member(&self, key: &str) -> Option<&Self> {
+        self.as_mapping()?.get(key)
+    }
+
+    fn member_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_mapping_mut()?.get_mut(key)
+    }
+
+    fn insert_member(&mut self, key: String, value: Self) -> Option<Self> {
+        self.as_mapping_mut()?.insert(Yaml::String(key), value)
+    }
+
+    fn remove_member(&mut self, key: &str) -> Option<Self> {
+        // Keep the order of the remaining keys, as written in the document
+        self.as_mapping_mut()?.shift_remove(key)
+    }
+}
+
+/// Representation of a mapping key in JSON, where only string keys are allowed.
+fn key_to_string(key: &Yaml) -> String {
+    match key {
+        Yaml::String(s) => s.clone(),
+        Yaml::Null => "null".to_string(),
+        Yaml::Bool(b) => b.to_string(),
+        Yaml::Number(n) => n.to_string(),
+        key => serde_yaml::to_string(key)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string(),
+    }
+}
+
+/// Diff two YAML documents and generate a JSON Patch (RFC 6902), as [`diff`](crate::diff) does
+/// for JSON documents.
+///
+/// Requires the `yaml` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{diff_yaml, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let left: serde_yaml::Value = serde_yaml::from_str("
+/// kind: Deployment
+/// spec:
+///   replicas: 1
+/// ").unwrap();
+/// let right: serde_yaml::Value = serde_yaml::from_str("
+/// kind: Deployment
+/// spec:
+///   replicas: 3
+/// ").unwrap();
+///
+/// let p = diff_yaml(&left, &right);
+/// assert_eq!(p, from_value::<Patch>(json!([
+///   { "op": "replace", "path": "/spec/replicas", "value": 3 },
+/// ])).unwrap());
+/// ```
+pub fn diff_yaml(left: &Yaml, right: &Yaml) -> Patch {
+    generic::diff(left, right)
+}
+
+/// Patch the YAML document in place, as [`patch`](crate::patch) does for JSON documents. If any
+/// of the operations fails, all previous operations are reverted.
+///
+/// Requires the `yaml` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use json_patch::{patch_yaml, Patch};
+/// use serde_json::{from_value, json};
+///
+/// let mut doc: serde_yaml::Value = serde_yaml::from_str("
+/// metadata:
+///   name: web
+///   labels:
+///     app: web
+/// ").unwrap();
+/// let p: Patch = from_value(json!([
+///   { "op": "add", "path": "/metadata/labels/tier", "value": "frontend" },
+/// ])).unwrap();
+///
+/// patch_yaml(&mut doc, &p).unwrap();
+/// assert_eq!(doc["metadata"]["labels"]["tier"], "frontend");
+/// ```
+pub fn patch_yaml(doc: &mut Yaml, patch: &[PatchOperation]) -> Result<(), PatchError> {
+    generic::patch(doc, patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_yaml, patch_yaml};
+    use serde_yaml::Value as Yaml;
+
+    #[test]
+    fn round_trip() {
+        let left: Yaml = serde_yaml::from_str(
+            "
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: config
+  annotations:
+    a/b: c
+data:
+  keep: 1
+  drop: 2.5
+  list: [a, b, c]
+1: numeric key
+",
+        )
+        .unwrap();
+        let right: Yaml = serde_yaml::from_str(
+            "
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: config
+  annotations: {}
+data:
+  keep: 1
+  list: [a, {x: true}]
+  new: null
+1: numeric key
+",
+        )
+        .unwrap();
+
+        let p = diff_yaml(&left, &right);
+        assert_eq!(p.len(), 5);
+        assert_eq!(p[0].path(), "/data/list/1");
+        assert_eq!(p[4].path(), "/metadata/annotations/a~1b");
+
+        let mut doc = left.clone();
+        patch_yaml(&mut doc, &p).unwrap();
+        assert_eq!(doc, right);
+    }
+
+    #[test]
+    fn failed_patch() {
+        let mut doc: Yaml = serde_yaml::from_str("a: [1, 2]").unwrap();
+        let p: crate::Patch = serde_json::from_value(serde_json::json!([
+            { "op": "remove", "path": "/a/0" },
+            { "op": "test", "path": "/a", "value": [1] },
+        ]))
+        .unwrap();
+        let err = patch_yaml(&mut doc, &p).unwrap_err();
+        assert_eq!(err.operation, 1);
+        assert_eq!(doc, serde_yaml::from_str::<Yaml>("a: [1, 2]").unwrap());
+    }
+}
@@ -1,6 +1,6 @@
 use json_patch::{
-    AddOperation, CopyOperation, MoveOperation, Patch, PatchOperation, RemoveOperation,
-    ReplaceOperation, TestOperation,
+    AddOperation, CopyOperation, MoveOperation, Patch, PatchErrorKind, PatchOperation, Pointer,
+    RemoveOperation, ReplaceOperation, SyntaxIssueKind, TestOperation,
 };
 use serde_json::{from_str, from_value, json, Value};
 
@@ -208,3 +208,346 @@ fn display_patch() {
 ]"#
     );
 }
+
+#[test]
+fn collect_patch() {
+    let patch: Patch = from_value(json!([
+        { "op": "add", "path": "/a", "value": 1 },
+        { "op": "remove", "path": "/b" },
+        { "op": "test", "path": "/c", "value": 2 },
+    ]))
+    .unwrap();
+    assert_eq!(patch.len(), 3);
+    assert!(!patch.is_empty());
+    assert!(Patch::default().is_empty());
+
+    let mut filtered: Patch = patch
+        .clone()
+        .into_iter()
+        .filter(|op| !matches!(op, PatchOperation::Test(_)))
+        .collect();
+    filtered.extend((&patch).into_iter().skip(2).cloned());
+    assert_eq!(filtered, patch);
+
+    let ops: Vec<PatchOperation> = patch.clone().into();
+    assert_eq!(Patch::from(ops), patch);
+}
+
+#[test]
+fn operation_accessors() {
+    let ops = vec![
+        PatchOperation::add("/a", json!(1)),
+        PatchOperation::remove("/b"),
+        PatchOperation::replace("/c", json!(2)),
+        PatchOperation::move_from("/d", "/e"),
+        PatchOperation::copy_from("/f", "/g"),
+        PatchOperation::test("/h", json!(3)),
+    ];
+    let expected: Patch = from_value(json!([
+        { "op": "add", "path": "/a", "value": 1 },
+        { "op": "remove", "path": "/b" },
+        { "op": "replace", "path": "/c", "value": 2 },
+        { "op": "move", "from": "/d", "path": "/e" },
+        { "op": "copy", "from": "/f", "path": "/g" },
+        { "op": "test", "path": "/h", "value": 3 },
+    ]))
+    .unwrap();
+    assert_eq!(Patch(ops.clone()), expected);
+
+    let kinds: Vec<_> = ops.iter().map(|op| op.op_kind().to_string()).collect();
+    assert_eq!(kinds, ["add", "remove", "replace", "move", "copy", "test"]);
+    let paths: Vec<_> = ops.iter().map(PatchOperation::path).collect();
+    assert_eq!(paths, ["/a", "/b", "/c", "/e", "/g", "/h"]);
+    let values: Vec<_> = ops.iter().filter_map(PatchOperation::value).collect();
+    assert_eq!(values, [&json!(1), &json!(2), &json!(3)]);
+    let froms: Vec<_> = ops.iter().filter_map(PatchOperation::from).collect();
+    assert_eq!(froms, ["/d", "/f"]);
+}
+
+#[test]
+fn typed_constructors() {
+    let pointer = |tokens: &[&str]| Pointer::from_tokens(tokens);
+    let ops = vec![
+        PatchOperation::Add(AddOperation::new(pointer(&["a/b"]), json!(1))),
+        PatchOperation::Remove(RemoveOperation::new(pointer(&["b~"]))),
+        PatchOperation::Replace(ReplaceOperation::new(pointer(&[]), json!(2))),
+        PatchOperation::Move(MoveOperation::new(pointer(&["d"]), pointer(&["e"])).unwrap()),
+        PatchOperation::Copy(CopyOperation::new(pointer(&["f"]), pointer(&["f", "g"]))),
+        PatchOperation::Test(TestOperation::new(pointer(&["h", "0"]), json!(3))),
+    ];
+    let expected: Patch = from_value(json!([
+        { "op": "add", "path": "/a~1b", "value": 1 },
+        { "op": "remove", "path": "/b~0" },
+        { "op": "replace", "path": "", "value": 2 },
+        { "op": "move", "from": "/d", "path": "/e" },
+        { "op": "copy", "from": "/f", "path": "/f/g" },
+        { "op": "test", "path": "/h/0", "value": 3 },
+    ]))
+    .unwrap();
+    assert_eq!(Patch(ops), expected);
+
+    assert_eq!(
+        MoveOperation::new(pointer(&["d"]), pointer(&["d"])),
+        Ok(MoveOperation {
+            from: "/d".into(),
+            path: "/d".into()
+        })
+    );
+    assert_eq!(
+        MoveOperation::new(pointer(&[]), pointer(&["d"])),
+        Err(SyntaxIssueKind::MoveInsideItself)
+    );
+    assert!(MoveOperation::new(pointer(&["d"]), pointer(&["dd"])).is_ok());
+}
+
+#[test]
+fn validate_patch() {
+    let doc = json!({ "a": { "b": [1] } });
+    let p: Patch = from_value(json!([
+        { "op": "add", "path": "/a/b/-", "value": 2 },
+        { "op": "move", "from": "/a/b/0", "path": "/x/y" },
+        { "op": "test", "path": "/a/b", "value": [1, 2] },
+        { "op": "add", "path": "/a/b/3", "value": 3 },
+    ]))
+    .unwrap();
+    let errors = json_patch::validate(&doc, &p).unwrap_err();
+    let failed: Vec<_> = errors.iter().map(|err| err.operation).collect();
+    assert_eq!(failed, [1, 3]);
+    assert_eq!(doc, json!({ "a": { "b": [1] } }));
+
+    assert!(json_patch::validate(&doc, &p[..1]).is_ok());
+}
+
+#[test]
+fn patch_unsafe_partial() {
+    let p: Patch = from_value(json!([
+        { "op": "add", "path": "/a", "value": 1 },
+        { "op": "remove", "path": "/b" },
+        { "op": "add", "path": "/c", "value": 2 },
+    ]))
+    .unwrap();
+
+    let mut doc = json!({});
+    let err = json_patch::patch_unsafe(&mut doc, &p).unwrap_err();
+    assert_eq!(err.operation, 1);
+    assert_eq!(doc, json!({ "a": 1 }));
+
+    let mut doc = json!({ "b": 0 });
+    json_patch::patch_unsafe(&mut doc, &p).unwrap();
+    assert_eq!(doc, json!({ "a": 1, "c": 2 }));
+}
+
+#[test]
+fn patch_lenient_skips_failures() {
+    let p: Patch = from_value(json!([
+        { "op": "add", "path": "/a/-", "value": 3 },
+        { "op": "move", "from": "/a/0", "path": "/x/y" },
+        { "op": "test", "path": "/a", "value": [1, 2] },
+        { "op": "copy", "from": "/a/0", "path": "/b" },
+    ]))
+    .unwrap();
+
+    let mut doc = json!({ "a": [1, 2] });
+    let results = json_patch::patch_lenient(&mut doc, &p);
+    let failed: Vec<_> = results
+        .iter()
+        .filter_map(|r| r.as_ref().err())
+        .map(|err| err.operation)
+        .collect();
+    assert_eq!(results.len(), 4);
+    assert_eq!(failed, [1, 2]);
+    assert_eq!(doc, json!({ "a": [1, 2, 3], "b": 1 }));
+}
+
+#[test]
+fn structured_errors() {
+    let doc = json!({ "a": [1, 2], "b": "str" });
+    let fail = |op: Value| {
+        let p: Patch =
+            from_value(json!([{ "op": "test", "path": "/b", "value": "str" }, op])).unwrap();
+        json_patch::patch(&mut doc.clone(), &p).unwrap_err()
+    };
+
+    let err = fail(json!({ "op": "remove", "path": "/c" }));
+    assert_eq!(err.operation, 1);
+    assert_eq!(err.pointer(), Some(Pointer::from_tokens(["c"])));
+    assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+
+    let err = fail(json!({ "op": "replace", "path": "/a/2", "value": 0 }));
+    assert!(matches!(err.kind, PatchErrorKind::PathNotFound));
+    let err = fail(json!({ "op": "add", "path": "/a/3", "value": 0 }));
+    assert!(matches!(err.kind, PatchErrorKind::IndexOutOfBounds));
+    let err = fail(json!({ "op": "add", "path": "/a/01", "value": 0 }));
+    assert!(matches!(err.kind, PatchErrorKind::InvalidPointer));
+    let err = fail(json!({ "op": "test", "path": "/a/0", "value": 2 }));
+    match err.kind {
+        PatchErrorKind::TestFailed { expected, actual } => {
+            assert_eq!(*expected, json!(2));
+            assert_eq!(actual.as_deref(), Some(&json!(1)));
+        }
+        kind => panic!("unexpected error: {kind}"),
+    }
+    let err = fail(json!({ "op": "test", "path": "/a/2", "value": 2 }));
+    match err.kind {
+        PatchErrorKind::TestFailed { expected, actual } => {
+            assert_eq!(*expected, json!(2));
+            assert_eq!(actual, None);
+        }
+        kind => panic!("unexpected error: {kind}"),
+    }
+
+    let err = fail(json!({ "op": "remove", "path": "a" }));
+    assert_eq!(err.pointer(), None);
+    assert!(matches!(err.kind, PatchErrorKind::InvalidPointer));
+}
+
+#[test]
+fn patched_copy() {
+    let doc = json!({ "a": [1, 2] });
+    let p: Patch = from_value(json!([
+        { "op": "remove", "path": "/a/0" },
+        { "op": "add", "path": "/b", "value": 3 },
+    ]))
+    .unwrap();
+    assert_eq!(
+        json_patch::patched(&doc, &p).unwrap(),
+        json!({ "a": [2], "b": 3 })
+    );
+    assert_eq!(doc, json!({ "a": [1, 2] }));
+
+    let err = json_patch::patched(&json!({ "a": [] }), &p).unwrap_err();
+    assert_eq!(err.operation, 0);
+}
+
+#[test]
+fn deep_values() {
+    fn nested(depth: usize) -> Value {
+        let mut value = Value::Null;
+        for _ in 0..depth {
+            value = Value::Array(vec![value]);
+        }
+        value
+    }
+    fn deep_patch(last: &str) -> Patch {
+        Patch(vec![
+            PatchOperation::Add(AddOperation {
+                path: String::from("/a"),
+                value: nested(100_000),
+            }),
+            PatchOperation::Copy(CopyOperation {
+                from: String::from("/a"),
+                path: String::from("/b"),
+            }),
+            PatchOperation::Test(TestOperation {
+                path: String::from("/b"),
+                value: nested(100_000),
+            }),
+            PatchOperation::Replace(ReplaceOperation {
+                path: String::from("/a"),
+                value: nested(100_000),
+            }),
+            PatchOperation::Remove(RemoveOperation {
+                path: String::from(last),
+            }),
+        ])
+    }
+
+    let p = deep_patch("/b");
+    let mut doc = json!({});
+    json_patch::patch(&mut doc, &p).unwrap();
+    assert!(doc.pointer("/b").is_none());
+
+    // Reverting the applied operations must be stack-safe as well.
+    let failing = deep_patch("/c");
+    let mut original = json!({});
+    let err = json_patch::patch(&mut original, &failing).unwrap_err();
+    assert_eq!(err.operation, 4);
+    assert_eq!(original, json!({}));
+
+    // Values are too deep to be dropped recursively.
+    drop_deep(doc);
+    p.into_iter().chain(failing).for_each(drop_deep_operation);
+}
+
+/// Drop the value without recursion.
+fn drop_deep(value: Value) {
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Array(arr) => stack.extend(arr),
+            Value::Object(obj) => stack.extend(obj.into_iter().map(|(_, value)| value)),
+            _ => {}
+        }
+    }
+}
+
+/// Drop the value of the operation without recursion.
+fn drop_deep_operation(op: PatchOperation) {
+    match op {
+        PatchOperation::Add(op) => drop_deep(op.value),
+        PatchOperation::Replace(op) => drop_deep(op.value),
+        PatchOperation::Test(op) => drop_deep(op.value),
+        _ => {}
+    }
+}
+
+#[test]
+fn drop_deep_values() {
+    let mut value = json!({ "leaf": [1, "two", null] });
+    for depth in 0..100_000 {
+        value = if depth % 2 == 0 {
+            Value::Array(vec![value, Value::from(depth)])
+        } else {
+            Value::Object([(String::from("a"), value)].into_iter().collect())
+        };
+    }
+    drop_deep(value);
+    drop_deep_operation(PatchOperation::add("/a", json!({ "b": [[{}]] })));
+    drop_deep_operation(PatchOperation::remove("/a"));
+}
+
+#[cfg(feature = "preserve_order")]
+#[test]
+fn preserve_order() {
+    let mut doc: Value = from_str(r#"{ "c": 1, "a": 2, "d": 3, "b": 4 }"#).unwrap();
+    let p: Patch = from_value(json!([
+        { "op": "remove", "path": "/c" },
+        { "op": "replace", "path": "/d", "value": 5 },
+        { "op": "add", "path": "/0", "value": 6 },
+    ]))
+    .unwrap();
+    json_patch::patch(&mut doc, &p).unwrap();
+    assert_eq!(doc.to_string(), r#"{"a":2,"d":5,"b":4,"0":6}"#);
+
+    json_patch::merge(&mut doc, &json!({ "a": null, "c": 7 }));
+    assert_eq!(doc.to_string(), r#"{"d":5,"b":4,"0":6,"c":7}"#);
+}
+
+#[cfg(feature = "preserve_order")]
+#[test]
+fn preserve_order_reverted() {
+    let original = r#"{"c":1,"a":2,"d":3,"b":4,"e":{"f":5,"g":6}}"#;
+    let patches = [
+        json!([{ "op": "remove", "path": "/a" }]),
+        json!([{ "op": "remove", "path": "/c" }, { "op": "remove", "path": "/b" }]),
+        json!([{ "op": "move", "from": "/a", "path": "/x" }]),
+        json!([{ "op": "move", "from": "/a", "path": "/b" }]),
+        json!([{ "op": "move", "from": "/b", "path": "/a" }]),
+        json!([{ "op": "move", "from": "/d", "path": "/d" }]),
+        json!([{ "op": "move", "from": "/e/f", "path": "/c" }]),
+        json!([{ "op": "move", "from": "/c", "path": "/e/g" }]),
+        json!([{ "op": "add", "path": "/a", "value": 7 }, { "op": "remove", "path": "/c" }]),
+        json!([{ "op": "remove", "path": "/e/f" }, { "op": "copy", "from": "/e/g", "path": "/d" }]),
+    ];
+    for p in patches {
+        let mut p: Patch = from_value(p).unwrap();
+        p.0.push(PatchOperation::remove("/missing"));
+        let mut doc: Value = from_str(original).unwrap();
+        json_patch::patch(&mut doc, &p).unwrap_err();
+        assert_eq!(doc.to_string(), original, "{p}");
+        json_patch::generic::patch(&mut doc, &p).unwrap_err();
+        assert_eq!(doc.to_string(), original, "generic: {p}");
+        p.compile().unwrap().apply(&mut doc).unwrap_err();
+        assert_eq!(doc.to_string(), original, "compiled: {p}");
+    }
+}
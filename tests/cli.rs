@@ -0,0 +1,82 @@
+#![cfg(feature = "cli")]
+
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn file(name: &str, value: &Value) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("json-patch-cli-{}-{}", std::process::id(), name));
+    std::fs::write(&path, value.to_string()).unwrap();
+    path
+}
+
+fn run(args: &[&str], stdin: &Value) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json-patch"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = child.stdin.take().unwrap();
+    input.write_all(stdin.to_string().as_bytes()).unwrap();
+    drop(input);
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> Value {
+    assert!(output.status.success(), "{:?}", output);
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn commands() {
+    let left = json!({ "title": "Goodbye!", "tags": ["a"] });
+    let right = json!({ "title": "Hello!", "tags": ["a", "b"] });
+    let doc = file("doc.json", &left);
+    let doc = doc.to_str().unwrap();
+
+    let patch = stdout(&run(&["diff", doc, "-"], &right));
+    assert_eq!(
+        patch,
+        serde_json::to_value(json_patch::diff(&left, &right)).unwrap()
+    );
+    let patch_file = file("patch.json", &patch);
+    let patch_file = patch_file.to_str().unwrap();
+
+    assert_eq!(stdout(&run(&["apply", doc, "-"], &patch)), right);
+    let output = run(&["--pretty", "apply", doc, patch_file], &Value::Null);
+    assert!(String::from_utf8(output.stdout.clone())
+        .unwrap()
+        .contains("\n  \"tags\""));
+    assert_eq!(stdout(&output), right);
+
+    let inverse = stdout(&run(&["invert", "-", patch_file], &left));
+    let inverse_file = file("inverse.json", &inverse);
+    let reverted = run(&["apply", "-", inverse_file.to_str().unwrap()], &right);
+    assert_eq!(stdout(&reverted), left);
+
+    let output = run(&["validate", doc, "-"], &patch);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let invalid = json!([{ "op": "remove", "path": "/missing" }]);
+    let output = run(&["validate", doc, "-"], &invalid);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Operation '/0' failed at path '/missing': path does not exist\n"
+    );
+
+    let output = run(&["apply", doc, "-"], &invalid);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("error: "));
+
+    let output = run(&["apply", "-", "-"], &left);
+    assert_eq!(output.status.code(), Some(1));
+    let output = run(&["diff", "/nonexistent/file.json", "-"], &left);
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,10 @@
+#[cfg(feature = "schemars")]
+#[test]
+fn schema() {
+    use json_patch::Patch;
+
+    let schema = schemars::schema_for!(Patch);
+    let doc = serde_json::to_string_pretty(&schema).unwrap();
+
+    expectorate::assert_contents("tests/schemars.json", &doc);
+}
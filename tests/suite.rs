@@ -30,6 +30,42 @@ fn revert_tests() {
     );
 }
 
+#[test]
+fn generic_tests() {
+    run_specs("tests/errors.yaml", Errors::ExactMatch, PatchKind::Generic);
+    run_specs(
+        "specs/tests.json",
+        Errors::IgnoreContent,
+        PatchKind::Generic,
+    );
+    run_specs(
+        "specs/spec_tests.json",
+        Errors::IgnoreContent,
+        PatchKind::Generic,
+    );
+    run_specs(
+        "specs/revert_tests.json",
+        Errors::IgnoreContent,
+        PatchKind::Generic,
+    );
+}
+
+#[test]
+fn owned_tests() {
+    run_specs("tests/errors.yaml", Errors::ExactMatch, PatchKind::Owned);
+    run_specs("specs/tests.json", Errors::IgnoreContent, PatchKind::Owned);
+    run_specs(
+        "specs/spec_tests.json",
+        Errors::IgnoreContent,
+        PatchKind::Owned,
+    );
+    run_specs(
+        "specs/revert_tests.json",
+        Errors::IgnoreContent,
+        PatchKind::Owned,
+    );
+}
+
 #[test]
 fn merge_tests() {
     run_specs(
@@ -49,6 +85,8 @@ enum Errors {
 enum PatchKind {
     Patch,
     MergePatch,
+    Generic,
+    Owned,
 }
 
 #[derive(Debug, Deserialize)]
@@ -71,13 +109,17 @@ fn run_patch_test_case(tc: &PatchTestCase, kind: PatchKind) -> Result<Value, Str
 
     // Patch and verify that in case of error document wasn't changed
     let patch: Patch = serde_json::from_value(tc.patch.clone()).map_err(|err| err.to_string())?;
-    json_patch::patch(&mut actual, &patch)
-        .map_err(|e| {
+    let result = match kind {
+        PatchKind::Generic => json_patch::generic::patch(&mut actual, &patch),
+        PatchKind::Owned => json_patch::patch_owned(&mut actual, patch),
+        _ => json_patch::patch(&mut actual, &patch),
+    };
+    result
+        .inspect_err(|_| {
             assert_eq!(
                 tc.doc, actual,
                 "no changes should be made to the original document"
             );
-            e
         })
         .map_err(|err| err.to_string())?;
     Ok(actual)
@@ -96,6 +138,10 @@ fn run_specs(path: &str, errors: Errors, kind: PatchKind) {
         if tc.disabled {
             continue;
         }
+        // Negative array indices are valid with the `negative-indices` feature
+        if cfg!(feature = "negative-indices") && tc.error.is_some() && has_negative_index(&tc) {
+            continue;
+        }
         match run_patch_test_case(&tc, kind) {
             Ok(actual) => {
                 if let Some(error) = tc.error {
@@ -132,3 +178,16 @@ fn run_specs(path: &str, errors: Errors, kind: PatchKind) {
         }
     }
 }
+
+fn has_negative_index(tc: &PatchTestCase) -> bool {
+    let ops = tc.patch.as_array().map(Vec::as_slice).unwrap_or_default();
+    ops.iter()
+        .flat_map(|op| [&op["path"], &op["from"]])
+        .filter_map(Value::as_str)
+        .flat_map(|pointer| pointer.split('/'))
+        .any(|token| {
+            token.len() > 1
+                && token.starts_with('-')
+                && token[1..].bytes().all(|b| b.is_ascii_digit())
+        })
+}
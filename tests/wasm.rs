@@ -0,0 +1,41 @@
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use json_patch::wasm;
+use serde_json::{json, Value};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn to_js(value: &Value) -> JsValue {
+    serde_wasm_bindgen::to_value(value).unwrap()
+}
+
+fn from_js(value: JsValue) -> Value {
+    serde_wasm_bindgen::from_value(value).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn diff_and_patch() {
+    let left = json!({ "a": 1, "b": [1, 2], "c": 1.5 });
+    let right = json!({ "a": 2, "b": [1], "d": null });
+
+    let p = wasm::diff(to_js(&left), to_js(&right)).unwrap();
+    let expected = serde_json::to_value(json_patch::diff(&left, &right)).unwrap();
+    assert_eq!(from_js(p.clone()), expected);
+    let patched = wasm::patch(to_js(&left), p).unwrap();
+    assert_eq!(from_js(patched), right);
+}
+
+#[wasm_bindgen_test]
+fn merge_patch_and_validate() {
+    let doc = json!({ "a": { "b": 1 }, "c": 2 });
+    let merged = wasm::merge_patch(to_js(&doc), to_js(&json!({ "a": { "b": null } }))).unwrap();
+    assert_eq!(from_js(merged), json!({ "a": {}, "c": 2 }));
+
+    let p = json!([{ "op": "remove", "path": "/x" }, { "op": "remove", "path": "/c" }]);
+    let issues = wasm::validate(to_js(&doc), to_js(&p)).unwrap();
+    assert_eq!(
+        from_js(issues),
+        json!([{ "operation": 0, "path": "/x", "message": "path does not exist" }])
+    );
+    assert!(wasm::patch(to_js(&doc), to_js(&p)).is_err());
+}